@@ -20,6 +20,11 @@ var callback = client => {
         }
     });
 
+    client.on('query', key => {
+        client.emit('query-received', client.handshake.query[key]);
+        console.log(['query', key]);
+    });
+
     client.on('binary', data => {
         var bufView = new Uint8Array(data);
         console.log(['binary', 'Yehaa binary payload!']);
@@ -2,23 +2,26 @@ use std::{
     fmt::Debug,
     pin::Pin,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use async_stream::try_stream;
 use bytes::Bytes;
 use futures_util::{stream, Stream, StreamExt};
-use memchr::memchr;
-use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::{runtime::Handle, sync::Mutex, time::Instant};
+use tokio::sync::Mutex;
 
 use crate::{
-    asynchronous::{callback::OptionalCallback, transport::AsyncTransportType},
+    asynchronous::{
+        callback::OptionalCallback,
+        runtime::{self, Handle, Instant},
+        transport::AsyncTransportType,
+    },
     error::Result,
-    packet::{HandshakePacket, Payload},
-    Error, Packet, PacketId,
+    packet::HandshakePacket,
+    Direction, Error, Frame, Packet, PacketId, ProtocolVersion,
 };
 
 #[derive(Clone)]
@@ -31,17 +34,34 @@ pub struct Socket {
     on_error: OptionalCallback<String>,
     on_open: OptionalCallback<()>,
     on_packet: OptionalCallback<Packet>,
+    on_frame: OptionalCallback<Frame>,
+    on_ping: OptionalCallback<()>,
+    on_pong: OptionalCallback<Duration>,
     connected: Arc<AtomicBool>,
-    sid_received: Arc<AtomicBool>,
     last_ping: Arc<Mutex<Instant>>,
+    /// When the client last sent a heartbeat: its pong, or its ping with revision 3.
     last_pong: Arc<Mutex<Instant>>,
+    /// The latest round trip estimate in microseconds.
+    latency: Arc<AtomicU64>,
+    /// The encoded size of the packets written to the transport.
+    bytes_sent: Arc<AtomicU64>,
+    /// The encoded size of the packets read from the transport.
+    bytes_received: Arc<AtomicU64>,
     connection_data: Arc<HandshakePacket>,
     max_ping_timeout: u64,
-    sid_tx: Arc<Mutex<Sender<bool>>>,
-    sid_rx: Arc<Mutex<Receiver<bool>>>,
+    /// Whether heartbeats are only sent by [`Socket::send_heartbeat`], or once they are
+    /// about to be too late.
+    manual_heartbeat: bool,
+    /// The number of pings received from the server.
+    pings: Arc<AtomicU64>,
+    /// The number of the server's ping that wasn't answered yet with a manual heartbeat,
+    /// zero if there is none.
+    ping_pending: Arc<AtomicU64>,
+    protocol_version: ProtocolVersion,
 }
 
 impl Socket {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         transport: AsyncTransportType,
         handshake: HandshakePacket,
@@ -50,56 +70,44 @@ impl Socket {
         on_error: OptionalCallback<String>,
         on_open: OptionalCallback<()>,
         on_packet: OptionalCallback<Packet>,
+        on_frame: OptionalCallback<Frame>,
+        on_ping: OptionalCallback<()>,
+        on_pong: OptionalCallback<Duration>,
+        protocol_version: ProtocolVersion,
+        handle: Handle,
     ) -> Self {
         let max_ping_timeout = handshake.ping_interval + handshake.ping_timeout;
-        let (tx, rx) = tokio::sync::mpsc::channel(1);
-        let sid_tx = Arc::new(Mutex::new(tx));
-        let sid_rx = Arc::new(Mutex::new(rx));
 
         Socket {
-            handle: Handle::current(),
+            handle,
             on_close,
             on_data,
             on_error,
             on_open,
             on_packet,
+            on_frame,
+            on_ping,
+            on_pong,
             transport: Arc::new(Mutex::new(transport.clone())),
             transport_raw: transport,
             connected: Arc::new(AtomicBool::default()),
-            sid_received: Arc::new(AtomicBool::default()),
             last_ping: Arc::new(Mutex::new(Instant::now())),
             last_pong: Arc::new(Mutex::new(Instant::now())),
+            latency: Arc::new(AtomicU64::default()),
+            bytes_sent: Arc::new(AtomicU64::default()),
+            bytes_received: Arc::new(AtomicU64::default()),
             connection_data: Arc::new(handshake),
             max_ping_timeout,
-            sid_tx,
-            sid_rx,
-        }
-    }
-
-    // check for sid after first connect and return sid
-    // if do not check first server's client return sid value then
-    // client's fast emit(after connect().await) sometimes failed.
-    pub async fn check_incoming_sid_packet(&self, data: Bytes) -> Result<()> {
-        //check sid packet
-        if let Some(_pos) = memchr(b's', &data).and_then(|pos| {
-            memchr(b'i', &data[pos + 1..])
-                .and_then(|offset| memchr(b'd', &data[pos + offset + 2..]))
-        }) {
-            self.sid_received.store(true, Ordering::Release);
-            let _ = self.sid_tx.lock().await.send(true).await;
-        } else {
-            self.sid_received.store(false, Ordering::Release);
+            manual_heartbeat: false,
+            pings: Arc::new(AtomicU64::default()),
+            ping_pending: Arc::new(AtomicU64::default()),
+            protocol_version,
         }
-        Ok(())
-    }
-
-    pub async fn wait_connect_incoming_sid(&self) -> Result<()> {
-        self.sid_rx.lock().await.recv().await;
-        Ok(())
     }
 
     /// Opens the connection to a specified server. The first Pong packet is sent
-    /// to the server to trigger the Ping-cycle.
+    /// to the server to trigger the Ping-cycle. With revision 3 of the protocol the
+    /// client sends the pings instead, every `pingInterval`.
     pub async fn connect(&self) -> Result<()> {
         // SAFETY: Has valid handshake due to type
         self.connected.store(true, Ordering::Release);
@@ -112,15 +120,90 @@ impl Socket {
         // set the last ping to now and set the connected state
         *self.last_ping.lock().await = Instant::now();
 
+        if self.protocol_version == ProtocolVersion::V3 {
+            let socket = self.clone();
+            self.handle.spawn(async move { socket.send_pings().await });
+            return Ok(());
+        }
+
         // emit a pong packet to keep trigger the ping cycle on the server
-        self.emit(Packet::new(PacketId::Pong, Bytes::new())).await?;
+        self.emit_heartbeat(PacketId::Pong).await?;
 
         Ok(())
     }
 
+    /// Sends a ping every `pingInterval` until the socket is disconnected. The server
+    /// answers with a pong, which is tracked like the pings of newer servers. With a
+    /// manual heartbeat, a ping is only sent if none was sent by then and half of the
+    /// `pingTimeout` passed as well.
+    async fn send_pings(&self) {
+        let mut period = Duration::from_millis(self.connection_data.ping_interval);
+        if self.manual_heartbeat {
+            period += self.heartbeat_grace();
+        }
+        loop {
+            let since_last_ping = self.last_pong.lock().await.elapsed();
+            if since_last_ping < period {
+                runtime::sleep(period - since_last_ping).await;
+                continue;
+            }
+            if !self.is_connected() || self.emit_heartbeat(PacketId::Ping).await.is_err() {
+                break;
+            }
+            self.call_ping_callback();
+        }
+    }
+
+    /// Sends the heartbeat that is due with a manual heartbeat: a ping with revision 3,
+    /// which is sent right away, or the pong answering the server's ping. Returns
+    /// whether a heartbeat was sent, which it isn't if the server's ping was answered
+    /// already.
+    pub(crate) async fn send_heartbeat(&self) -> Result<bool> {
+        if self.protocol_version == ProtocolVersion::V3 {
+            self.emit_heartbeat(PacketId::Ping).await?;
+            self.call_ping_callback();
+            return Ok(true);
+        }
+        if self.ping_pending.swap(0, Ordering::AcqRel) == 0 {
+            return Ok(false);
+        }
+        self.emit_heartbeat(PacketId::Pong).await?;
+        self.call_pong_callback();
+        Ok(true)
+    }
+
+    /// How long a manual heartbeat may be late, half of the `pingTimeout` of the server
+    /// so the heartbeat still arrives in time.
+    fn heartbeat_grace(&self) -> Duration {
+        Duration::from_millis(self.connection_data.ping_timeout / 2)
+    }
+
+    /// Waits for a manual heartbeat answering the server's ping with the given number,
+    /// answering it once it's about to be too late.
+    async fn answer_ping_late(&self, ping: u64) {
+        runtime::sleep(self.heartbeat_grace()).await;
+        if self.is_connected()
+            && self
+                .ping_pending
+                .compare_exchange(ping, 0, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            && self.emit_heartbeat(PacketId::Pong).await.is_ok()
+        {
+            self.call_pong_callback();
+        }
+    }
+
+    /// Sends a heartbeat packet and remembers when it left, so the latency can be
+    /// estimated once the server's next heartbeat arrives.
+    async fn emit_heartbeat(&self, packet_id: PacketId) -> Result<()> {
+        *self.last_pong.lock().await = Instant::now();
+        self.emit(Packet::new(packet_id, Bytes::new())).await
+    }
+
     /// A helper method that distributes
     pub(super) async fn handle_incoming_packet(&self, packet: Packet) -> Result<()> {
         // check for the appropriate action or callback
+        self.observe(Direction::Inbound, &packet);
         self.handle_packet(packet.clone());
         match packet.packet_id {
             PacketId::MessageBinary => {
@@ -128,10 +211,6 @@ impl Socket {
             }
             PacketId::Message => {
                 self.handle_data(packet.data.clone());
-
-                if !self.is_exsit_sid() {
-                    let _ = self.check_incoming_sid_packet(packet.data.clone()).await;
-                }
             }
             PacketId::Close => {
                 self.handle_close();
@@ -141,7 +220,21 @@ impl Socket {
             }
             PacketId::Ping => {
                 self.pinged().await;
-                self.emit(Packet::new(PacketId::Pong, Bytes::new())).await?;
+                self.call_ping_callback();
+                if self.manual_heartbeat {
+                    let ping = self.pings.fetch_add(1, Ordering::AcqRel) + 1;
+                    self.ping_pending.store(ping, Ordering::Release);
+                    let socket = self.clone();
+                    self.handle
+                        .spawn(async move { socket.answer_ping_late(ping).await });
+                } else {
+                    self.emit_heartbeat(PacketId::Pong).await?;
+                    self.call_pong_callback();
+                }
+            }
+            PacketId::Pong if self.protocol_version == ProtocolVersion::V3 => {
+                self.pinged().await;
+                self.call_pong_callback();
             }
             PacketId::Pong | PacketId::Open => {
                 // this will never happen as the pong and open
@@ -154,9 +247,13 @@ impl Socket {
     }
 
     /// Helper method that parses bytes and returns an iterator over the elements.
-    fn parse_payload(bytes: Bytes) -> impl Stream<Item = Result<Packet>> {
+    fn parse_payload(
+        bytes: Bytes,
+        protocol_version: ProtocolVersion,
+        polling: bool,
+    ) -> impl Stream<Item = Result<Packet>> {
         try_stream! {
-            let payload = Payload::try_from(bytes);
+            let payload = protocol_version.decode(bytes, polling);
 
             for elem in payload?.into_iter() {
                 yield elem;
@@ -168,12 +265,14 @@ impl Socket {
     /// underlying transport types.
     fn stream(
         mut transport: AsyncTransportType,
+        protocol_version: ProtocolVersion,
     ) -> Pin<Box<impl Stream<Item = Result<Packet>> + 'static + Send>> {
+        let polling = transport.is_polling();
         // map the byte stream of the underlying transport
         // to a packet stream
         Box::pin(try_stream! {
             for await payload in transport.as_pin_box() {
-                for await packet in Self::parse_payload(payload?) {
+                for await packet in Self::parse_payload(payload?, protocol_version, polling) {
                     yield packet?;
                 }
             }
@@ -190,33 +289,66 @@ impl Socket {
             .await?;
 
         self.connected.store(false, Ordering::Release);
-        self.sid_received.store(false, Ordering::Release);
 
-        Ok(())
+        self.transport.lock().await.close().await
     }
 
     /// Sends a packet to the server.
     pub async fn emit(&self, packet: Packet) -> Result<()> {
+        self.emit_batch(vec![packet]).await
+    }
+
+    /// Sends packets to the server in the given order. Polling transports send them
+    /// as a single payload instead of making a request per packet, split into several
+    /// if they would exceed the `maxPayload` of the server. Websockets flush the
+    /// connection once after the last of them.
+    pub async fn emit_batch(&self, packets: Vec<Packet>) -> Result<()> {
         if !self.connected.load(Ordering::Acquire) {
             let error = Error::IllegalActionBeforeOpen();
             self.call_error_callback(format!("{}", error));
             return Err(error);
         }
 
-        let is_binary = packet.packet_id == PacketId::MessageBinary;
-
-        // send a post request with the encoded payload as body
-        // if this is a binary attachment, then send the raw bytes
-        let data: Bytes = if is_binary {
-            packet.data
+        let lock = self.transport.lock().await;
+        let result = if lock.is_polling() {
+            let max_payload = self.connection_data.max_payload;
+            let mut result = Ok(());
+            for batch in self.protocol_version.split_payload(packets, max_payload) {
+                let frames: Vec<_> = batch
+                    .iter()
+                    .map(|packet| (packet.packet_id, self.protocol_version.frame_size(packet)))
+                    .collect();
+                result = match self.protocol_version.encode_payload(batch) {
+                    Ok(data) => lock.as_transport().emit(data, false).await,
+                    Err(error) => Err(error),
+                };
+                if result.is_err() {
+                    break;
+                }
+                for (packet_id, size) in frames {
+                    self.observe_frame(Direction::Outbound, packet_id, size);
+                }
+            }
+            result
         } else {
-            packet.into()
+            let frames: Vec<_> = packets
+                .iter()
+                .map(|packet| (packet.packet_id, self.protocol_version.frame_size(packet)))
+                .collect();
+            let messages = packets
+                .into_iter()
+                .map(|packet| self.protocol_version.encode(packet, false))
+                .collect();
+            let result = lock.emit_many(messages).await;
+            if result.is_ok() {
+                for (packet_id, size) in frames {
+                    self.observe_frame(Direction::Outbound, packet_id, size);
+                }
+            }
+            result
         };
 
-        let lock = self.transport.lock().await;
-        let fut = lock.as_transport().emit(data, is_binary);
-
-        if let Err(error) = fut.await {
+        if let Err(error) = result {
             self.call_error_callback(error.to_string());
             return Err(error);
         }
@@ -224,6 +356,34 @@ impl Socket {
         Ok(())
     }
 
+    /// Counts a packet going over the transport and passes it to the `on_frame` callback.
+    fn observe(&self, direction: Direction, packet: &Packet) {
+        self.observe_frame(
+            direction,
+            packet.packet_id,
+            self.protocol_version.frame_size(packet),
+        );
+    }
+
+    fn observe_frame(&self, direction: Direction, packet_id: PacketId, size: usize) {
+        let bytes = match direction {
+            Direction::Inbound => &self.bytes_received,
+            Direction::Outbound => &self.bytes_sent,
+        };
+        bytes.fetch_add(size as u64, Ordering::Relaxed);
+
+        if let Some(on_frame) = self.on_frame.as_ref() {
+            let on_frame = on_frame.clone();
+            let frame = Frame {
+                direction,
+                packet_id,
+                size,
+                transport: self.transport_raw.name(),
+            };
+            self.handle.spawn(async move { on_frame(frame).await });
+        }
+    }
+
     /// Calls the error callback with a given message.
     #[inline]
     fn call_error_callback(&self, text: String) {
@@ -238,12 +398,72 @@ impl Socket {
         self.connected.load(Ordering::Acquire)
     }
 
-    pub(crate) fn is_exsit_sid(&self) -> bool {
-        self.sid_received.load(Ordering::Acquire)
+    pub(crate) fn handshake(&self) -> &HandshakePacket {
+        &self.connection_data
     }
 
+    /// Records a heartbeat from the server and updates the latency estimate. With
+    /// revision 3 the server's pong answers our ping, so the elapsed time is the round
+    /// trip. Newer servers send their next ping `pingInterval` after receiving our pong,
+    /// so the round trip is whatever exceeds the interval.
     pub(crate) async fn pinged(&self) {
-        *self.last_ping.lock().await = Instant::now();
+        let now = Instant::now();
+        *self.last_ping.lock().await = now;
+
+        let mut round_trip = now.saturating_duration_since(*self.last_pong.lock().await);
+        if self.protocol_version != ProtocolVersion::V3 {
+            round_trip = round_trip
+                .saturating_sub(Duration::from_millis(self.connection_data.ping_interval));
+        }
+        self.latency
+            .store(round_trip.as_micros() as u64, Ordering::Release);
+    }
+
+    /// Overrides the `pingTimeout` of the handshake, see
+    /// [`super::ClientBuilder::ping_timeout`].
+    pub(crate) fn set_ping_timeout(&mut self, timeout: Duration) {
+        self.max_ping_timeout = self.connection_data.ping_interval + timeout.as_millis() as u64;
+    }
+
+    /// Leaves the heartbeat to [`Socket::send_heartbeat`], see
+    /// [`super::ClientBuilder::manual_heartbeat`].
+    pub(crate) fn set_manual_heartbeat(&mut self) {
+        self.manual_heartbeat = true;
+    }
+
+    pub(crate) fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency.load(Ordering::Acquire))
+    }
+
+    pub(crate) async fn since_heartbeat(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self.last_ping.lock().await)
+    }
+
+    pub(crate) fn heartbeat_timeout(&self) -> Duration {
+        Duration::from_millis(self.max_ping_timeout)
+    }
+
+    fn call_ping_callback(&self) {
+        if let Some(on_ping) = self.on_ping.as_ref() {
+            let on_ping = on_ping.clone();
+            self.handle.spawn(async move { on_ping(()).await });
+        }
+    }
+
+    fn call_pong_callback(&self) {
+        if let Some(on_pong) = self.on_pong.as_ref() {
+            let on_pong = on_pong.clone();
+            let latency = self.latency();
+            self.handle.spawn(async move { on_pong(latency).await });
+        }
     }
 
     /// Returns the time in milliseconds that is left until a new ping must be received.
@@ -291,10 +511,10 @@ impl Socket {
         &'a self,
     ) -> Pin<Box<dyn Stream<Item = Result<Packet>> + Send + 'a>> {
         stream::unfold(
-            Self::stream(self.transport_raw.clone()),
+            Self::stream(self.transport_raw.clone(), self.protocol_version),
             |mut stream| async {
                 // Wait for the next payload or until we should have received the next ping.
-                match tokio::time::timeout(
+                match runtime::timeout(
                     std::time::Duration::from_millis(self.time_to_next_ping().await),
                     stream.next(),
                 )
@@ -327,9 +547,13 @@ impl Debug for Socket {
             .field("on_error", &self.on_error)
             .field("on_open", &self.on_open)
             .field("on_packet", &self.on_packet)
+            .field("on_frame", &self.on_frame)
+            .field("on_ping", &self.on_ping)
+            .field("on_pong", &self.on_pong)
             .field("connected", &self.connected)
             .field("last_ping", &self.last_ping)
             .field("last_pong", &self.last_pong)
+            .field("latency", &self.latency)
             .field("connection_data", &self.connection_data)
             .finish()
     }
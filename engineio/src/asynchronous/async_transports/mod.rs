@@ -1,8 +1,25 @@
+#[cfg(not(target_arch = "wasm32"))]
 mod polling;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
 mod websocket;
+#[cfg(not(target_arch = "wasm32"))]
 mod websocket_general;
+#[cfg(not(target_arch = "wasm32"))]
 mod websocket_secure;
+#[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+mod webtransport;
+#[cfg(not(target_arch = "wasm32"))]
+mod ws_backend;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::polling::PollingTransport;
+#[cfg(feature = "wasm")]
+pub use self::wasm::{WasmPollingTransport, WasmWebsocketTransport};
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::websocket::WebsocketTransport;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::websocket_secure::WebsocketSecureTransport;
+#[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+pub use self::webtransport::WebtransportTransport;
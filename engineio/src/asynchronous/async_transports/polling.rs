@@ -5,8 +5,8 @@ use base64::{engine::general_purpose, Engine as _};
 use bytes::{BufMut, Bytes, BytesMut};
 use futures_util::{Stream, StreamExt};
 use http::HeaderMap;
-use native_tls::TlsConnector;
-use reqwest::{Client, ClientBuilder, Response};
+#[cfg(feature = "http-reqwest")]
+use reqwest::{header::CONTENT_ENCODING, Client, ClientBuilder, Response};
 use std::fmt::Debug;
 use std::time::SystemTime;
 use std::{pin::Pin, sync::Arc};
@@ -14,15 +14,36 @@ use tokio::sync::RwLock;
 use url::Url;
 
 use crate::asynchronous::generator::StreamGenerator;
-use crate::{asynchronous::transport::AsyncTransport, error::Result, Error};
+#[cfg(not(feature = "http-reqwest"))]
+use crate::hyper_client::HyperClient;
+#[cfg(unix)]
+use crate::unix::{self, UnixClient};
+use crate::{
+    asynchronous::transport::AsyncTransport, compression::HttpCompression, error::Result,
+    tcp::TcpOptions, tls::TlsConnector, Error,
+};
+
+/// The http client that sends the requests of a polling transport. A `reqwest` client
+/// comes with the headers sent with every request, for clients built without them as
+/// default headers.
+#[derive(Clone, Debug)]
+enum HttpClient {
+    #[cfg(feature = "http-reqwest")]
+    Reqwest(Client, HeaderMap),
+    #[cfg(not(feature = "http-reqwest"))]
+    Hyper(HyperClient),
+    #[cfg(unix)]
+    Unix(UnixClient),
+}
 
 /// An asynchronous polling type. Makes use of the nonblocking reqwest types and
-/// methods.
+/// methods, or of a minimal hyper based client without the `http-reqwest` feature.
 #[derive(Clone)]
 pub struct PollingTransport {
-    client: Client,
+    client: HttpClient,
     base_url: Arc<RwLock<Url>>,
     generator: StreamGenerator<Bytes>,
+    compression: HttpCompression,
 }
 
 impl PollingTransport {
@@ -31,20 +52,111 @@ impl PollingTransport {
         tls_config: Option<TlsConnector>,
         opening_headers: Option<HeaderMap>,
     ) -> Self {
-        let client = match (tls_config, opening_headers) {
-            (Some(config), Some(map)) => ClientBuilder::new()
-                .use_preconfigured_tls(config)
-                .default_headers(map)
-                .build()
-                .unwrap(),
-            (Some(config), None) => ClientBuilder::new()
-                .use_preconfigured_tls(config)
-                .build()
-                .unwrap(),
-            (None, Some(map)) => ClientBuilder::new().default_headers(map).build().unwrap(),
-            (None, None) => Client::new(),
+        #[cfg(unix)]
+        if unix::socket_path(&base_url).is_some() {
+            let client = UnixClient::new(opening_headers);
+            return Self::with_client(base_url, HttpClient::Unix(client));
+        }
+
+        #[cfg(feature = "http-reqwest")]
+        let client = HttpClient::Reqwest(
+            match (tls_config, opening_headers) {
+                (Some(config), Some(map)) => config
+                    .configure(ClientBuilder::new())
+                    .default_headers(map)
+                    .build()
+                    .unwrap(),
+                (Some(config), None) => config.configure(ClientBuilder::new()).build().unwrap(),
+                (None, Some(map)) => ClientBuilder::new().default_headers(map).build().unwrap(),
+                (None, None) => Client::new(),
+            },
+            HeaderMap::new(),
+        );
+        #[cfg(not(feature = "http-reqwest"))]
+        let client = HttpClient::Hyper(HyperClient::new(
+            &base_url,
+            tls_config,
+            opening_headers,
+            None,
+            TcpOptions::default(),
+        ));
+
+        Self::with_client(base_url, client)
+    }
+
+    /// Creates an instance of `PollingTransport` that sends all requests via the given
+    /// proxy and compresses them as configured, over tcp connections with the given options.
+    /// Without a proxy, the proxies configured in the environment are used. Unix domain
+    /// sockets are always reached directly and uncompressed.
+    pub(crate) fn with_proxy(
+        base_url: Url,
+        tls_config: Option<TlsConnector>,
+        opening_headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        compression: HttpCompression,
+        tcp: TcpOptions,
+    ) -> Result<Self> {
+        #[cfg(unix)]
+        if unix::socket_path(&base_url).is_some() {
+            return Ok(Self::new(base_url, tls_config, opening_headers));
+        }
+
+        #[cfg(feature = "http-reqwest")]
+        let client = {
+            let mut builder = tcp.configure(compression.configure(ClientBuilder::new()));
+            if let Some(proxy) = proxy {
+                builder = builder.proxy(crate::proxy::reqwest_proxy(&proxy)?);
+            }
+            if let Some(config) = tls_config {
+                builder = config.configure(builder);
+            }
+            if let Some(map) = opening_headers {
+                builder = builder.default_headers(map);
+            }
+            HttpClient::Reqwest(builder.build()?, HeaderMap::new())
         };
+        #[cfg(not(feature = "http-reqwest"))]
+        let client = {
+            if let Some(proxy) = &proxy {
+                crate::proxy::validate(proxy)?;
+            }
+            HttpClient::Hyper(HyperClient::new(
+                &base_url,
+                tls_config,
+                opening_headers,
+                proxy,
+                tcp,
+            ))
+        };
+
+        let mut transport = Self::with_client(base_url, client);
+        transport.compression = compression;
+        Ok(transport)
+    }
 
+    /// Creates an instance of `PollingTransport` that sends all requests with the given,
+    /// preconfigured client, e.g. one sharing its connection pool with the rest of an
+    /// application. Its proxy, tls and timeout settings are used as they are, the opening
+    /// headers are added to every request. Unix domain sockets are reached without it.
+    #[cfg(feature = "http-reqwest")]
+    pub(crate) fn with_reqwest_client(
+        base_url: Url,
+        client: Client,
+        opening_headers: Option<HeaderMap>,
+        compression: HttpCompression,
+    ) -> Self {
+        #[cfg(unix)]
+        if unix::socket_path(&base_url).is_some() {
+            return Self::new(base_url, None, opening_headers);
+        }
+
+        let headers = opening_headers.unwrap_or_default();
+        let mut transport = Self::with_client(base_url, HttpClient::Reqwest(client, headers));
+        transport.compression = compression;
+        transport
+    }
+
+    fn with_client(base_url: Url, client: HttpClient) -> Self {
         let mut url = base_url;
         url.query_pairs_mut().append_pair("transport", "polling");
 
@@ -52,6 +164,7 @@ impl PollingTransport {
             client: client.clone(),
             base_url: Arc::new(RwLock::new(url.clone())),
             generator: StreamGenerator::new(Self::stream(url, client)),
+            compression: HttpCompression::default(),
         }
     }
 
@@ -62,25 +175,44 @@ impl PollingTransport {
         Ok(url)
     }
 
-    fn send_request(url: Url, client: Client) -> impl Stream<Item = Result<Response>> {
+    #[cfg(feature = "http-reqwest")]
+    fn send_request(
+        url: Url,
+        client: Client,
+        headers: HeaderMap,
+    ) -> impl Stream<Item = Result<Response>> {
         try_stream! {
             let address = Self::address(url);
 
             yield client
                 .get(address?)
+                .headers(headers)
                 .send().await?
         }
     }
 
     fn stream(
         url: Url,
-        client: Client,
+        client: HttpClient,
     ) -> Pin<Box<dyn Stream<Item = Result<Bytes>> + 'static + Send>> {
         Box::pin(try_stream! {
             loop {
-                for await elem in Self::send_request(url.clone(), client.clone()) {
-                    for await bytes in elem?.bytes_stream() {
-                        yield bytes?;
+                match &client {
+                    #[cfg(feature = "http-reqwest")]
+                    HttpClient::Reqwest(client, headers) => {
+                        for await elem in Self::send_request(url.clone(), client.clone(), headers.clone()) {
+                            for await bytes in elem?.bytes_stream() {
+                                yield bytes?;
+                            }
+                        }
+                    }
+                    #[cfg(not(feature = "http-reqwest"))]
+                    HttpClient::Hyper(client) => {
+                        yield client.get(&Self::address(url.clone())?).await?.into_body();
+                    }
+                    #[cfg(unix)]
+                    HttpClient::Unix(client) => {
+                        yield client.get(&Self::address(url.clone())?).await?.into_body();
                     }
                 }
             }
@@ -115,14 +247,31 @@ impl AsyncTransport for PollingTransport {
             data
         };
 
-        let status = self
-            .client
-            .post(self.address().await?)
-            .body(data_to_send)
-            .send()
-            .await?
-            .status()
-            .as_u16();
+        let address = self.address().await?;
+        let status = match &self.client {
+            #[cfg(feature = "http-reqwest")]
+            HttpClient::Reqwest(client, headers) => {
+                let (body, encoding) = self.compression.compress(data_to_send)?;
+                let mut request = client.post(address).headers(headers.clone()).body(body);
+                if let Some(encoding) = encoding {
+                    request = request.header(CONTENT_ENCODING, encoding);
+                }
+                request.send().await?.status().as_u16()
+            }
+            #[cfg(not(feature = "http-reqwest"))]
+            HttpClient::Hyper(client) => {
+                let (body, encoding) = self.compression.compress(data_to_send)?;
+                client
+                    .post(&address, body, encoding)
+                    .await?
+                    .status()
+                    .as_u16()
+            }
+            #[cfg(unix)]
+            HttpClient::Unix(client) => {
+                client.post(&address, data_to_send).await?.status().as_u16()
+            }
+        };
 
         if status != 200 {
             let error = Error::IncompleteHttp(status);
@@ -192,4 +341,49 @@ mod test {
         assert_ne!(transport.base_url().await?.to_string(), url);
         Ok(())
     }
+    #[cfg(feature = "http-reqwest")]
+    #[tokio::test]
+    async fn polling_transport_reqwest_client() -> Result<()> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}/engine.io/", listener.local_addr()?))?;
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                stream.read_line(&mut head).await.unwrap();
+            }
+            stream
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n4hello")
+                .await
+                .unwrap();
+            head.to_lowercase()
+        });
+
+        let client = ClientBuilder::new()
+            .no_proxy()
+            .user_agent("shared-client")
+            .build()?;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-token", "secret".parse().unwrap());
+        let mut transport = PollingTransport::with_reqwest_client(
+            url,
+            client,
+            Some(headers),
+            HttpCompression::default(),
+        );
+
+        assert_eq!(
+            transport.next().await.unwrap()?,
+            Bytes::from_static(b"4hello")
+        );
+        let head = server.await.unwrap();
+        assert!(head.starts_with("get /engine.io/?transport=polling&t="));
+        assert!(head.contains("user-agent: shared-client\r\n"));
+        assert!(head.contains("x-token: secret\r\n"));
+        Ok(())
+    }
 }
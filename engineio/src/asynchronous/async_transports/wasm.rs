@@ -0,0 +1,394 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use adler32::adler32;
+use async_stream::try_stream;
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{ready, FutureExt, Stream, StreamExt};
+use http::HeaderMap;
+use js_sys::{ArrayBuffer, Promise, Uint8Array};
+use send_wrapper::SendWrapper;
+use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
+use url::Url;
+use wasm_bindgen::{closure::Closure, prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{BinaryType, Event, MessageEvent, Request, RequestInit, Response, WebSocket};
+
+use crate::asynchronous::generator::StreamGenerator;
+use crate::asynchronous::runtime::SystemTime;
+use crate::asynchronous::transport::AsyncTransport;
+use crate::error::Result;
+use crate::{Error, Packet, PacketId};
+
+#[wasm_bindgen]
+extern "C" {
+    /// The global `fetch`, which windows as well as workers provide.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &Request) -> Promise;
+}
+
+fn js_error(err: JsValue) -> Error {
+    Error::IncompleteResponseFromJs(err.as_string().unwrap_or_else(|| format!("{:?}", err)))
+}
+
+/// Sends a request with the `fetch` api of the javascript host and returns the status code
+/// and body of the response. The future holds javascript values and is therefore not `Send`,
+/// callers wrap it in a `SendWrapper` as wasm targets are single threaded.
+async fn fetch(
+    method: &str,
+    url: &Url,
+    headers: &HeaderMap,
+    body: Option<Bytes>,
+) -> Result<(u16, Bytes)> {
+    let mut init = RequestInit::new();
+    init.method(method);
+    if let Some(body) = body {
+        let body: JsValue = Uint8Array::from(&body[..]).into();
+        init.body(Some(&body));
+    }
+
+    let request = Request::new_with_str_and_init(url.as_str(), &init).map_err(js_error)?;
+    for (name, value) in headers {
+        request
+            .headers()
+            .set(name.as_str(), &String::from_utf8_lossy(value.as_bytes()))
+            .map_err(js_error)?;
+    }
+
+    let response: Response = JsFuture::from(fetch_with_request(&request))
+        .await
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)?;
+    let body = JsFuture::from(response.array_buffer().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    Ok((
+        response.status(),
+        Bytes::from(Uint8Array::new(&body).to_vec()),
+    ))
+}
+
+/// An asynchronous polling transport for wasm targets. Sends its requests with the `fetch`
+/// api of the javascript host, so cookies and tls are handled by the host as well.
+#[derive(Clone)]
+pub struct WasmPollingTransport {
+    headers: Arc<HeaderMap>,
+    base_url: Arc<RwLock<Url>>,
+    generator: StreamGenerator<Bytes>,
+}
+
+impl WasmPollingTransport {
+    /// Creates a new instance over an URL and headers that are sent with every request.
+    /// Headers that are forbidden by the host, e.g. `cookie` in browsers, are not sent.
+    pub fn new(base_url: Url, opening_headers: Option<HeaderMap>) -> Self {
+        let mut url = base_url;
+        url.query_pairs_mut().append_pair("transport", "polling");
+        let headers = Arc::new(opening_headers.unwrap_or_default());
+
+        WasmPollingTransport {
+            headers: headers.clone(),
+            base_url: Arc::new(RwLock::new(url.clone())),
+            generator: StreamGenerator::new(Self::stream(url, headers)),
+        }
+    }
+
+    fn address(mut url: Url) -> Url {
+        let reader = format!("{:#?}", SystemTime::now());
+        let hash = adler32(reader.as_bytes()).unwrap();
+        url.query_pairs_mut().append_pair("t", &hash.to_string());
+        url
+    }
+
+    fn stream(
+        url: Url,
+        headers: Arc<HeaderMap>,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes>> + 'static + Send>> {
+        Box::pin(try_stream! {
+            loop {
+                let address = Self::address(url.clone());
+                let (status, body) =
+                    SendWrapper::new(fetch("GET", &address, &headers, None)).await?;
+                if status != 200 {
+                    Err(Error::IncompleteHttp(status))?;
+                }
+                yield body;
+            }
+        })
+    }
+}
+
+impl Stream for WasmPollingTransport {
+    type Item = Result<Bytes>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.generator.poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for WasmPollingTransport {
+    async fn emit(&self, data: Bytes, is_binary_att: bool) -> Result<()> {
+        let data_to_send = if is_binary_att {
+            // the binary attachment gets `base64` encoded
+            let mut packet_bytes = BytesMut::with_capacity(data.len() + 1);
+            packet_bytes.put_u8(b'b');
+
+            let encoded_data = general_purpose::STANDARD.encode(data);
+            packet_bytes.put(encoded_data.as_bytes());
+
+            packet_bytes.freeze()
+        } else {
+            data
+        };
+
+        let address = self.address().await?;
+        let (status, _) =
+            SendWrapper::new(fetch("POST", &address, &self.headers, Some(data_to_send))).await?;
+
+        if status != 200 {
+            return Err(Error::IncompleteHttp(status));
+        }
+
+        Ok(())
+    }
+
+    async fn base_url(&self) -> Result<Url> {
+        Ok(self.base_url.read().await.clone())
+    }
+
+    async fn set_base_url(&self, base_url: Url) -> Result<()> {
+        let mut url = base_url;
+        if !url
+            .query_pairs()
+            .any(|(k, v)| k == "transport" && v == "polling")
+        {
+            url.query_pairs_mut().append_pair("transport", "polling");
+        }
+        *self.base_url.write().await = url;
+        Ok(())
+    }
+}
+
+impl Debug for WasmPollingTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPollingTransport")
+            .field("base_url", &self.base_url)
+            .finish()
+    }
+}
+
+/// A websocket of the javascript host together with its event handlers. Detaches the
+/// handlers and closes the websocket once the last clone of the transport is dropped.
+struct WebsocketHandle {
+    socket: WebSocket,
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut(Event)>,
+    _on_close: Closure<dyn FnMut(Event)>,
+}
+
+impl Drop for WebsocketHandle {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onerror(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+/// An asynchronous websocket transport for wasm targets, using the `WebSocket` api of the
+/// javascript host for plain ("ws://") as well as secure ("wss://") connections.
+#[derive(Clone)]
+pub struct WasmWebsocketTransport {
+    socket: Arc<SendWrapper<WebsocketHandle>>,
+    receiver: Arc<Mutex<mpsc::UnboundedReceiver<Result<Bytes>>>>,
+    base_url: Arc<RwLock<Url>>,
+}
+
+impl WasmWebsocketTransport {
+    /// Creates a new instance over an URL and waits for the websocket to open. Browsers do
+    /// not allow to set headers on websocket requests.
+    pub async fn new(base_url: Url) -> Result<Self> {
+        SendWrapper::new(Self::connect(base_url)).await
+    }
+
+    async fn connect(base_url: Url) -> Result<Self> {
+        let mut url = base_url;
+        url.query_pairs_mut().append_pair("transport", "websocket");
+        match url.scheme() {
+            "http" | "ws" => url.set_scheme("ws").unwrap(),
+            "https" | "wss" => url.set_scheme("wss").unwrap(),
+            scheme => return Err(Error::InvalidUrlScheme(scheme.to_owned())),
+        }
+
+        let socket = WebSocket::new(url.as_str()).map_err(js_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        // the handlers share the senders, closing the websocket drops them and ends the stream
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = Rc::new(RefCell::new(Some(tx)));
+        let (open_tx, open_rx) = oneshot::channel();
+        let opened = Rc::new(RefCell::new(Some(open_tx)));
+
+        let on_open = {
+            let opened = opened.clone();
+            Closure::<dyn FnMut(Event)>::new(move |_| {
+                if let Some(opened) = opened.borrow_mut().take() {
+                    let _ = opened.send(Ok(()));
+                }
+            })
+        };
+        let on_message = {
+            let sender = sender.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let data = event.data();
+                let message = if let Some(text) = data.as_string() {
+                    Bytes::from(text)
+                } else if let Some(buffer) = data.dyn_ref::<ArrayBuffer>() {
+                    let data = Uint8Array::new(buffer).to_vec();
+                    let mut msg = BytesMut::with_capacity(data.len() + 1);
+                    msg.put_u8(PacketId::Message as u8);
+                    msg.put(data.as_ref());
+                    msg.freeze()
+                } else {
+                    // ignore messages other than text and binary
+                    return;
+                };
+                if let Some(sender) = sender.borrow().as_ref() {
+                    let _ = sender.send(Ok(message));
+                }
+            })
+        };
+        let on_error = {
+            let sender = sender.clone();
+            let opened = opened.clone();
+            Closure::<dyn FnMut(Event)>::new(move |_| {
+                let error = Error::IncompleteResponseFromJs("websocket error".to_owned());
+                if let Some(opened) = opened.borrow_mut().take() {
+                    let _ = opened.send(Err(error));
+                } else if let Some(sender) = sender.borrow().as_ref() {
+                    let _ = sender.send(Err(error));
+                }
+            })
+        };
+        let on_close = Closure::<dyn FnMut(Event)>::new(move |_| {
+            opened.borrow_mut().take();
+            sender.borrow_mut().take();
+        });
+
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        let handle = WebsocketHandle {
+            socket,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_error: on_error,
+            _on_close: on_close,
+        };
+
+        open_rx.await.map_err(|_| {
+            Error::IncompleteResponseFromJs("websocket closed before opening".to_owned())
+        })??;
+
+        Ok(WasmWebsocketTransport {
+            socket: Arc::new(SendWrapper::new(handle)),
+            receiver: Arc::new(Mutex::new(rx)),
+            base_url: Arc::new(RwLock::new(url)),
+        })
+    }
+}
+
+impl Stream for WasmWebsocketTransport {
+    type Item = Result<Bytes>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let mut lock = ready!(Box::pin(self.receiver.lock()).poll_unpin(cx));
+        lock.poll_recv(cx)
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for WasmWebsocketTransport {
+    async fn emit(&self, data: Bytes, is_binary_att: bool) -> Result<()> {
+        let socket = &self.socket.socket;
+        if is_binary_att {
+            socket.send_with_u8_array(&data)
+        } else {
+            socket.send_with_str(std::str::from_utf8(&data)?)
+        }
+        .map_err(js_error)
+    }
+
+    async fn base_url(&self) -> Result<Url> {
+        Ok(self.base_url.read().await.clone())
+    }
+
+    async fn set_base_url(&self, base_url: Url) -> Result<()> {
+        let mut url = base_url;
+        if !url
+            .query_pairs()
+            .any(|(k, v)| k == "transport" && v == "websocket")
+        {
+            url.query_pairs_mut().append_pair("transport", "websocket");
+        }
+        match url.scheme() {
+            "https" | "wss" => url.set_scheme("wss").unwrap(),
+            _ => url.set_scheme("ws").unwrap(),
+        }
+        *self.base_url.write().await = url;
+        Ok(())
+    }
+
+    /// Sends probe packet to ensure connection is valid, then sends upgrade
+    /// request
+    async fn upgrade(&self) -> Result<()> {
+        self.emit(
+            Packet::new(PacketId::Ping, Bytes::from("probe")).into(),
+            false,
+        )
+        .await?;
+
+        let msg = self
+            .receiver
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or(Error::IllegalWebsocketUpgrade())??;
+        if msg != Bytes::from(Packet::new(PacketId::Pong, Bytes::from("probe"))) {
+            return Err(Error::InvalidPacket());
+        }
+
+        self.emit(Packet::new(PacketId::Upgrade, Bytes::new()).into(), false)
+            .await
+    }
+}
+
+impl Debug for WasmWebsocketTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmWebsocketTransport")
+            .field(
+                "base_url",
+                &self
+                    .base_url
+                    .try_read()
+                    .map_or("Currently not available".to_owned(), |url| url.to_string()),
+            )
+            .finish()
+    }
+}
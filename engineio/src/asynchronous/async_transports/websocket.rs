@@ -4,17 +4,20 @@ use std::sync::Arc;
 
 use crate::asynchronous::transport::AsyncTransport;
 use crate::error::Result;
+use crate::tcp::TcpOptions;
+use crate::Error;
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures_util::stream::StreamExt;
 use futures_util::Stream;
 use http::HeaderMap;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::RwLock;
-use tokio_tungstenite::connect_async;
-use tungstenite::client::IntoClientRequest;
+use tungstenite::protocol::WebSocketConfig;
 use url::Url;
 
 use super::websocket_general::AsyncWebsocketGeneralTransport;
+use super::ws_backend;
 
 /// An asynchronous websocket transport type.
 /// This type only allows for plain websocket
@@ -28,35 +31,88 @@ pub struct WebsocketTransport {
 impl WebsocketTransport {
     /// Creates a new instance over a request that might hold additional headers and an URL.
     pub async fn new(base_url: Url, headers: Option<HeaderMap>) -> Result<Self> {
-        let mut url = base_url;
-        url.query_pairs_mut().append_pair("transport", "websocket");
-        url.set_scheme("ws").unwrap();
+        Self::with_proxy(base_url, headers, None, None, TcpOptions::default()).await
+    }
 
-        let mut req = url.clone().into_client_request()?;
-        if let Some(map) = headers {
-            // SAFETY: this unwrap never panics as the underlying request is just initialized and in proper state
-            req.headers_mut().extend(map);
+    /// Creates a new instance that connects through a tunnel opened by the given proxy,
+    /// with the given limits and buffer sizes of the websocket and options of the tcp
+    /// connection.
+    pub(crate) async fn with_proxy(
+        base_url: Url,
+        headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        config: Option<WebSocketConfig>,
+        tcp: TcpOptions,
+    ) -> Result<Self> {
+        #[cfg(unix)]
+        if crate::unix::socket_path(&base_url).is_some() {
+            // proxies are never used for unix domain sockets
+            let stream = crate::unix::connect(&base_url).await?;
+            return Self::with_stream(base_url, headers, stream, config).await;
         }
 
-        let (ws_stream, _) = connect_async(req).await?;
-        let (sen, rec) = ws_stream.split();
+        let mut url = base_url;
+        url.query_pairs_mut().append_pair("transport", "websocket");
+        url.set_scheme("ws").unwrap();
 
-        let inner = AsyncWebsocketGeneralTransport::new(sen, rec).await;
+        let stream = match proxy {
+            Some(proxy) => crate::proxy::tunnel(&proxy, &url, &tcp).await?,
+            None => tcp.connect_url(&url).await?,
+        };
+        let inner = ws_backend::handshake(&url, headers, stream, config).await?;
         Ok(WebsocketTransport {
             inner,
             base_url: Arc::new(RwLock::new(url)),
         })
     }
 
-    /// Sends probe packet to ensure connection is valid, then sends upgrade
-    /// request
-    pub(crate) async fn upgrade(&self) -> Result<()> {
-        self.inner.upgrade().await
+    /// Creates a new instance that performs the websocket handshake over an already connected
+    /// stream. The stream is used as is, so for `https` and `wss` urls it has to be a tls
+    /// stream set up by the caller.
+    pub(crate) async fn with_stream<S>(
+        base_url: Url,
+        headers: Option<HeaderMap>,
+        stream: S,
+        config: Option<WebSocketConfig>,
+    ) -> Result<Self>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let mut url = base_url;
+        url.query_pairs_mut().append_pair("transport", "websocket");
+
+        let request_url = match url.scheme() {
+            "http" | "ws" => {
+                url.set_scheme("ws").unwrap();
+                url.clone()
+            }
+            "https" | "wss" => {
+                url.set_scheme("wss").unwrap();
+                url.clone()
+            }
+            #[cfg(unix)]
+            "http+unix" | "ws+unix" => {
+                url.set_scheme("ws+unix").unwrap();
+                crate::unix::local_url(&url, "ws")
+            }
+            scheme => return Err(Error::InvalidUrlScheme(scheme.to_owned())),
+        };
+
+        let inner = ws_backend::handshake(&request_url, headers, stream, config).await?;
+        Ok(WebsocketTransport {
+            inner,
+            base_url: Arc::new(RwLock::new(url)),
+        })
     }
 
     pub(crate) async fn poll_next(&self) -> Result<Option<Bytes>> {
         self.inner.poll_next().await
     }
+
+    /// Sends the messages, flushing the connection once after the last of them.
+    pub(crate) async fn emit_many(&self, messages: Vec<(Bytes, bool)>) -> Result<()> {
+        self.inner.emit_many(messages).await
+    }
 }
 
 #[async_trait]
@@ -77,10 +133,24 @@ impl AsyncTransport for WebsocketTransport {
         {
             url.query_pairs_mut().append_pair("transport", "websocket");
         }
-        url.set_scheme("ws").unwrap();
+        match url.scheme() {
+            "http+unix" | "ws+unix" => url.set_scheme("ws+unix"),
+            _ => url.set_scheme("ws"),
+        }
+        .unwrap();
         *self.base_url.write().await = url;
         Ok(())
     }
+
+    /// Sends probe packet to ensure connection is valid, then sends upgrade
+    /// request
+    async fn upgrade(&self) -> Result<()> {
+        self.inner.upgrade().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
 }
 
 impl Stream for WebsocketTransport {
@@ -132,7 +202,7 @@ mod test {
         url.set_scheme("ws").unwrap();
         assert_eq!(transport.base_url().await?.to_string(), url.to_string());
         transport
-            .set_base_url(reqwest::Url::parse("https://127.0.0.1")?)
+            .set_base_url(Url::parse("https://127.0.0.1")?)
             .await?;
         assert_eq!(
             transport.base_url().await?.to_string(),
@@ -141,9 +211,7 @@ mod test {
         assert_ne!(transport.base_url().await?.to_string(), url.to_string());
 
         transport
-            .set_base_url(reqwest::Url::parse(
-                "http://127.0.0.1/?transport=websocket",
-            )?)
+            .set_base_url(Url::parse("http://127.0.0.1/?transport=websocket")?)
             .await?;
         assert_eq!(
             transport.base_url().await?.to_string(),
@@ -1,18 +1,22 @@
-use std::{borrow::Cow, str::from_utf8, sync::Arc, task::Poll};
+use std::{pin::Pin, str::from_utf8, sync::Arc, task::Poll};
 
 use crate::{error::Result, Error, Packet, PacketId};
 use bytes::{BufMut, Bytes, BytesMut};
-use futures_util::{
-    ready,
-    stream::{SplitSink, SplitStream},
-    FutureExt, SinkExt, Stream, StreamExt,
-};
-use tokio::{net::TcpStream, sync::Mutex};
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
-use tungstenite::Message;
-
-type AsyncWebsocketSender = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
-type AsyncWebsocketReceiver = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+use futures_util::{ready, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use tokio::sync::Mutex;
+
+/// A data message of a websocket, independent of the backend the connection runs on. Control
+/// frames are answered by the backends on their own and never show up as frames.
+#[derive(Debug)]
+pub(crate) enum Frame {
+    Text(Bytes),
+    Binary(Bytes),
+}
+
+// boxed, so that connections over tcp and unix domain sockets as well as the different
+// backends share one transport type
+pub(crate) type AsyncWebsocketSender = Pin<Box<dyn Sink<Frame, Error = Error> + Send>>;
+pub(crate) type AsyncWebsocketReceiver = Pin<Box<dyn Stream<Item = Result<Frame>> + Send>>;
 
 /// A general purpose asynchronous websocket transport type. Holds
 /// the sender and receiver stream of a websocket connection
@@ -25,10 +29,7 @@ pub(crate) struct AsyncWebsocketGeneralTransport {
 }
 
 impl AsyncWebsocketGeneralTransport {
-    pub(crate) async fn new(
-        sender: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-        receiver: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    ) -> Self {
+    pub(crate) fn new(sender: AsyncWebsocketSender, receiver: AsyncWebsocketReceiver) -> Self {
         AsyncWebsocketGeneralTransport {
             sender: Arc::new(Mutex::new(sender)),
             receiver: Arc::new(Mutex::new(receiver)),
@@ -37,65 +38,95 @@ impl AsyncWebsocketGeneralTransport {
 
     /// Sends probe packet to ensure connection is valid, then sends upgrade
     /// request
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "upgrade", skip_all))]
     pub(crate) async fn upgrade(&self) -> Result<()> {
         let mut receiver = self.receiver.lock().await;
         let mut sender = self.sender.lock().await;
 
         sender
-            .send(Message::text(Cow::Borrowed(from_utf8(&Bytes::from(
-                Packet::new(PacketId::Ping, Bytes::from("probe")),
-            ))?)))
+            .send(Frame::Text(Bytes::from(Packet::new(
+                PacketId::Ping,
+                Bytes::from("probe"),
+            ))))
             .await?;
 
-        let msg = receiver
+        let data = match receiver
             .next()
             .await
-            .ok_or(Error::IllegalWebsocketUpgrade())??;
+            .ok_or(Error::IllegalWebsocketUpgrade())??
+        {
+            Frame::Text(data) | Frame::Binary(data) => data,
+        };
 
-        if msg.into_data() != Bytes::from(Packet::new(PacketId::Pong, Bytes::from("probe"))) {
+        if data != Bytes::from(Packet::new(PacketId::Pong, Bytes::from("probe"))) {
             return Err(Error::InvalidPacket());
         }
 
         sender
-            .send(Message::text(Cow::Borrowed(from_utf8(&Bytes::from(
-                Packet::new(PacketId::Upgrade, Bytes::from("")),
-            ))?)))
+            .send(Frame::Text(Bytes::from(Packet::new(
+                PacketId::Upgrade,
+                Bytes::from(""),
+            ))))
             .await?;
 
         Ok(())
     }
 
+    /// Sends a close frame, starting the closing handshake of the websocket.
+    pub(crate) async fn close(&self) -> Result<()> {
+        self.sender.lock().await.close().await?;
+        Ok(())
+    }
+
     pub(crate) async fn emit(&self, data: Bytes, is_binary_att: bool) -> Result<()> {
         let mut sender = self.sender.lock().await;
 
-        let message = if is_binary_att {
-            Message::binary(Cow::Borrowed(data.as_ref()))
-        } else {
-            Message::text(Cow::Borrowed(std::str::from_utf8(data.as_ref())?))
-        };
+        sender.send(Self::frame(data, is_binary_att)?).await?;
+
+        Ok(())
+    }
 
-        sender.send(message).await?;
+    /// Sends the messages as frames of their own, but flushes the connection only once
+    /// after the last one.
+    pub(crate) async fn emit_many(&self, messages: Vec<(Bytes, bool)>) -> Result<()> {
+        let mut sender = self.sender.lock().await;
+
+        for (data, is_binary_att) in messages {
+            sender.feed(Self::frame(data, is_binary_att)?).await?;
+        }
+        sender.flush().await?;
 
         Ok(())
     }
 
+    fn frame(data: Bytes, is_binary_att: bool) -> Result<Frame> {
+        if is_binary_att {
+            Ok(Frame::Binary(data))
+        } else {
+            from_utf8(data.as_ref())?;
+            Ok(Frame::Text(data))
+        }
+    }
+
     pub(crate) async fn poll_next(&self) -> Result<Option<Bytes>> {
-        loop {
-            let mut receiver = self.receiver.lock().await;
-            let next = receiver.next().await;
-            match next {
-                Some(Ok(Message::Text(str))) => return Ok(Some(Bytes::from(str))),
-                Some(Ok(Message::Binary(data))) => {
-                    let mut msg = BytesMut::with_capacity(data.len() + 1);
-                    msg.put_u8(PacketId::Message as u8);
-                    msg.put(data.as_ref());
-
-                    return Ok(Some(msg.freeze()));
-                }
-                // ignore packets other than text and binary
-                Some(Ok(_)) => (),
-                Some(Err(err)) => return Err(err.into()),
-                None => return Ok(None),
+        let mut receiver = self.receiver.lock().await;
+        receiver
+            .next()
+            .await
+            .transpose()
+            .map(|frame| frame.map(Self::packet))
+    }
+
+    /// Returns the packet carried by the frame. Binary frames hold the payload of a message
+    /// packet, without its id.
+    fn packet(frame: Frame) -> Bytes {
+        match frame {
+            Frame::Text(data) => data,
+            Frame::Binary(data) => {
+                let mut msg = BytesMut::with_capacity(data.len() + 1);
+                msg.put_u8(PacketId::Message as u8);
+                msg.put(data.as_ref());
+                msg.freeze()
             }
         }
     }
@@ -108,24 +139,8 @@ impl Stream for AsyncWebsocketGeneralTransport {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        loop {
-            let mut lock = ready!(Box::pin(self.receiver.lock()).poll_unpin(cx));
-            let next = ready!(lock.poll_next_unpin(cx));
-
-            match next {
-                Some(Ok(Message::Text(str))) => return Poll::Ready(Some(Ok(Bytes::from(str)))),
-                Some(Ok(Message::Binary(data))) => {
-                    let mut msg = BytesMut::with_capacity(data.len() + 1);
-                    msg.put_u8(PacketId::Message as u8);
-                    msg.put(data.as_ref());
-
-                    return Poll::Ready(Some(Ok(msg.freeze())));
-                }
-                // ignore packets other than text and binary
-                Some(Ok(_)) => (),
-                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
-                None => return Poll::Ready(None),
-            }
-        }
+        let mut lock = ready!(Box::pin(self.receiver.lock()).poll_unpin(cx));
+        let next = ready!(lock.poll_next_unpin(cx));
+        Poll::Ready(next.map(|frame| frame.map(Self::packet)))
     }
 }
@@ -4,19 +4,19 @@ use std::sync::Arc;
 
 use crate::asynchronous::transport::AsyncTransport;
 use crate::error::Result;
+use crate::tcp::TcpOptions;
+use crate::tls::TlsConnector;
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures_util::Stream;
 use futures_util::StreamExt;
 use http::HeaderMap;
-use native_tls::TlsConnector;
 use tokio::sync::RwLock;
-use tokio_tungstenite::connect_async_tls_with_config;
-use tokio_tungstenite::Connector;
-use tungstenite::client::IntoClientRequest;
+use tungstenite::protocol::WebSocketConfig;
 use url::Url;
 
 use super::websocket_general::AsyncWebsocketGeneralTransport;
+use super::ws_backend;
 
 /// An asynchronous websocket transport type.
 /// This type only allows for secure websocket
@@ -29,39 +29,30 @@ pub struct WebsocketSecureTransport {
 
 impl WebsocketSecureTransport {
     /// Creates a new instance over a request that might hold additional headers, a possible
-    /// Tls connector and an URL.
+    /// Tls connector, a possible proxy, the limits and buffer sizes of the websocket, the
+    /// options of the tcp connection and an URL. When connecting through a proxy, the tls session is established end to end with
+    /// the server, inside of the tunnel.
     pub(crate) async fn new(
         base_url: Url,
         tls_config: Option<TlsConnector>,
         headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        config: Option<WebSocketConfig>,
+        tcp: TcpOptions,
     ) -> Result<Self> {
         let mut url = base_url;
         url.query_pairs_mut().append_pair("transport", "websocket");
         url.set_scheme("wss").unwrap();
 
-        let mut req = url.clone().into_client_request()?;
-        if let Some(map) = headers {
-            // SAFETY: this unwrap never panics as the underlying request is just initialized and in proper state
-            req.headers_mut().extend(map);
-        }
-
-        // `disable_nagle` Sets the value of the TCP_NODELAY option on this socket.
-        //
-        // If set to `true`, this option disables the Nagle algorithm.
-        // This means that segments are always sent as soon as possible, even if there is only a small amount of data.
-        // When `false`, data is buffered until there is a sufficient amount to send out, thereby avoiding the frequent sending of small packets.
+        // `TCP_NODELAY` stays off unless the tcp options enable it, so small frames may be
+        // buffered until there is a sufficient amount to send out.
         //
         // See the docs: https://docs.rs/tokio/latest/tokio/net/struct.TcpStream.html#method.set_nodelay
-        let (ws_stream, _) = connect_async_tls_with_config(
-            req,
-            None,
-            /*disable_nagle=*/ false,
-            tls_config.map(Connector::NativeTls),
-        )
-        .await?;
-
-        let (sen, rec) = ws_stream.split();
-        let inner = AsyncWebsocketGeneralTransport::new(sen, rec).await;
+        let stream = match proxy {
+            Some(proxy) => crate::proxy::tunnel(&proxy, &url, &tcp).await?,
+            None => tcp.connect_url(&url).await?,
+        };
+        let inner = ws_backend::handshake_tls(&url, headers, stream, config, tls_config).await?;
 
         Ok(WebsocketSecureTransport {
             inner,
@@ -69,15 +60,14 @@ impl WebsocketSecureTransport {
         })
     }
 
-    /// Sends probe packet to ensure connection is valid, then sends upgrade
-    /// request
-    pub(crate) async fn upgrade(&self) -> Result<()> {
-        self.inner.upgrade().await
-    }
-
     pub(crate) async fn poll_next(&self) -> Result<Option<Bytes>> {
         self.inner.poll_next().await
     }
+
+    /// Sends the messages, flushing the connection once after the last of them.
+    pub(crate) async fn emit_many(&self, messages: Vec<(Bytes, bool)>) -> Result<()> {
+        self.inner.emit_many(messages).await
+    }
 }
 
 impl Stream for WebsocketSecureTransport {
@@ -113,6 +103,16 @@ impl AsyncTransport for WebsocketSecureTransport {
         *self.base_url.write().await = url;
         Ok(())
     }
+
+    /// Sends probe packet to ensure connection is valid, then sends upgrade
+    /// request
+    async fn upgrade(&self) -> Result<()> {
+        self.inner.upgrade().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
 }
 
 impl Debug for WebsocketSecureTransport {
@@ -143,6 +143,9 @@ mod test {
             Url::from_str(&url[..])?,
             Some(crate::test::tls_connector()?),
             None,
+            None,
+            None,
+            TcpOptions::default(),
         )
         .await
     }
@@ -158,7 +161,7 @@ mod test {
         url.set_scheme("wss").unwrap();
         assert_eq!(transport.base_url().await?.to_string(), url.to_string());
         transport
-            .set_base_url(reqwest::Url::parse("https://127.0.0.1")?)
+            .set_base_url(Url::parse("https://127.0.0.1")?)
             .await?;
         assert_eq!(
             transport.base_url().await?.to_string(),
@@ -167,9 +170,7 @@ mod test {
         assert_ne!(transport.base_url().await?.to_string(), url.to_string());
 
         transport
-            .set_base_url(reqwest::Url::parse(
-                "http://127.0.0.1/?transport=websocket",
-            )?)
+            .set_base_url(Url::parse("http://127.0.0.1/?transport=websocket")?)
             .await?;
         assert_eq!(
             transport.base_url().await?.to_string(),
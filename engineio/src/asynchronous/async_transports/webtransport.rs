@@ -0,0 +1,422 @@
+use std::fmt::Debug;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::asynchronous::transport::AsyncTransport;
+use crate::error::Result;
+use crate::{Error, Packet, PacketId};
+use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{ready, FutureExt, Stream, StreamExt};
+use h3::client::{RequestStream, SendRequest};
+use http::{HeaderMap, HeaderValue, Method, Request};
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::rustls;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{Mutex, RwLock};
+use url::{Host, Url};
+
+type AsyncWebtransportSender = Pin<Box<dyn AsyncWrite + Send>>;
+type AsyncWebtransportReceiver = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
+/// Stream type that binds a bidirectional stream to a WebTransport session.
+const WEBTRANSPORT_STREAM: u64 = 0x41;
+
+/// The http/3 state that has to outlive the engine.io stream, dropping the request stream
+/// would close the WebTransport session.
+struct Session {
+    _send_request: SendRequest<h3_quinn::OpenStreams, Bytes>,
+    _request: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+}
+
+/// An asynchronous WebTransport transport type. Opens a WebTransport session over http/3
+/// and exchanges the engine.io packets as length prefixed frames on one bidirectional
+/// stream of it. This type only allows for secure connections ("https://").
+#[derive(Clone)]
+pub struct WebtransportTransport {
+    sender: Arc<Mutex<AsyncWebtransportSender>>,
+    receiver: Arc<Mutex<AsyncWebtransportReceiver>>,
+    base_url: Arc<RwLock<Url>>,
+    _session: Option<Arc<Mutex<Session>>>,
+}
+
+impl WebtransportTransport {
+    /// Creates a new instance over a request that might hold additional headers and an URL.
+    /// Server certificates are verified against the webpki roots, unless a rustls
+    /// configuration is given. The `h3` alpn protocol is always set on it.
+    pub(crate) async fn new(
+        base_url: Url,
+        tls_config: Option<rustls::ClientConfig>,
+        headers: Option<HeaderMap>,
+    ) -> Result<Self> {
+        let mut url = base_url;
+        url.query_pairs_mut()
+            .append_pair("transport", "webtransport");
+        match url.scheme() {
+            "https" | "wss" => url.set_scheme("https").unwrap(),
+            scheme => return Err(Error::InvalidUrlScheme(scheme.to_owned())),
+        }
+
+        let host = match url.host() {
+            Some(Host::Domain(domain)) => domain.to_owned(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            None => return Err(Error::InvalidUrl(url::ParseError::EmptyHost)),
+        };
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addr = tokio::net::lookup_host((host.as_str(), port))
+            .await?
+            .next()
+            .ok_or_else(|| Error::WebtransportError(format!("could not resolve {}", host)))?;
+
+        let local: SocketAddr = if addr.is_ipv6() {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        };
+        let endpoint = quinn::Endpoint::client(local)?;
+        let connection = endpoint
+            .connect_with(client_config(tls_config)?, addr, &host)
+            .map_err(webtransport_error)?
+            .await
+            .map_err(webtransport_error)?;
+
+        let (mut driver, mut send_request) = h3::client::builder()
+            .enable_extended_connect(true)
+            .enable_datagram(true)
+            .build(h3_quinn::Connection::new(connection.clone()))
+            .await
+            .map_err(webtransport_error)?;
+        tokio::spawn(async move { driver.wait_idle().await });
+
+        let mut req = Request::builder()
+            .method(Method::CONNECT)
+            .uri(url.as_str())
+            .extension(h3::ext::Protocol::WEB_TRANSPORT)
+            .body(())?;
+        if let Some(map) = headers {
+            req.headers_mut().extend(map);
+        }
+        req.headers_mut().insert(
+            "sec-webtransport-http3-draft02",
+            HeaderValue::from_static("1"),
+        );
+
+        let mut request = send_request
+            .send_request(req)
+            .await
+            .map_err(webtransport_error)?;
+        let response = request.recv_response().await.map_err(webtransport_error)?;
+        if !response.status().is_success() {
+            return Err(Error::IncompleteHttp(response.status().as_u16()));
+        }
+
+        // bind the stream to the session, which is identified by the id of the CONNECT stream
+        let (mut send, recv) = connection.open_bi().await.map_err(webtransport_error)?;
+        let mut header = BytesMut::new();
+        put_varint(&mut header, WEBTRANSPORT_STREAM);
+        put_varint(&mut header, request.id().into_inner());
+        AsyncWriteExt::write_all(&mut send, &header).await?;
+
+        let mut transport = Self::with_streams(url, send, recv).await?;
+        transport._session = Some(Arc::new(Mutex::new(Session {
+            _send_request: send_request,
+            _request: request,
+        })));
+        Ok(transport)
+    }
+
+    /// Creates a new instance over the two halves of an already opened stream and sends the
+    /// opening packet, which carries the sid if the url holds one.
+    pub(crate) async fn with_streams<W, R>(url: Url, send: W, recv: R) -> Result<Self>
+    where
+        W: AsyncWrite + Send + 'static,
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let receiver = futures_util::stream::unfold(recv, |mut recv| async move {
+            match read_frame(&mut recv).await {
+                Ok(Some(packet)) => Some((Ok(packet), recv)),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), recv)),
+            }
+        });
+
+        let transport = WebtransportTransport {
+            sender: Arc::new(Mutex::new(Box::pin(send))),
+            receiver: Arc::new(Mutex::new(Box::pin(receiver))),
+            base_url: Arc::new(RwLock::new(url.clone())),
+            _session: None,
+        };
+
+        let data = match url.query_pairs().find(|(key, _)| key == "sid") {
+            Some((_, sid)) => Bytes::from(serde_json::json!({ "sid": sid }).to_string()),
+            None => Bytes::new(),
+        };
+        transport
+            .emit(Packet::new(PacketId::Open, data).into(), false)
+            .await?;
+
+        Ok(transport)
+    }
+}
+
+/// Uses the given rustls configuration, or one trusting the webpki roots, for quic.
+fn client_config(tls_config: Option<rustls::ClientConfig>) -> Result<quinn::ClientConfig> {
+    let mut tls_config = match tls_config {
+        Some(tls_config) => tls_config,
+        None => {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            rustls::ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(|err| Error::InvalidTlsConfig(err.to_string()))?
+            .with_root_certificates(roots)
+            .with_no_client_auth()
+        }
+    };
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+
+    let crypto = QuicClientConfig::try_from(tls_config)
+        .map_err(|err| Error::InvalidTlsConfig(err.to_string()))?;
+    Ok(quinn::ClientConfig::new(Arc::new(crypto)))
+}
+
+fn webtransport_error<E: std::fmt::Display>(err: E) -> Error {
+    Error::WebtransportError(err.to_string())
+}
+
+/// Appends a quic variable length integer.
+fn put_varint(buf: &mut BytesMut, value: u64) {
+    if value < 1 << 6 {
+        buf.put_u8(value as u8);
+    } else if value < 1 << 14 {
+        buf.put_u16(0x4000 | value as u16);
+    } else if value < 1 << 30 {
+        buf.put_u32(0x8000_0000 | value as u32);
+    } else {
+        buf.put_u64(0xc000_0000_0000_0000 | value);
+    }
+}
+
+/// Encodes a packet as frame: a header with the length of the payload, whose highest bit
+/// marks binary payloads, followed by the payload itself.
+fn encode_frame(data: &[u8], is_binary: bool) -> Bytes {
+    let mut frame = BytesMut::with_capacity(data.len() + 9);
+    let len = data.len();
+    let marker = if is_binary { 0x80 } else { 0 };
+    if len < 126 {
+        frame.put_u8(marker | len as u8);
+    } else if len < 1 << 16 {
+        frame.put_u8(marker | 126);
+        frame.put_u16(len as u16);
+    } else {
+        frame.put_u8(marker | 127);
+        frame.put_u64(len as u64);
+    }
+    frame.put(data);
+    frame.freeze()
+}
+
+/// Reads the next frame. Binary payloads are raw message data, so they are turned into a
+/// message packet like it is done for binary websocket messages.
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Bytes>> {
+    let header = match reader.read_u8().await {
+        Ok(header) => header,
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let is_binary = header & 0x80 != 0;
+    let len = match header & 0x7f {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    let len = usize::try_from(len).map_err(|_| Error::InvalidPacket())?;
+
+    let mut frame = BytesMut::with_capacity(len + 1);
+    if is_binary {
+        frame.put_u8(PacketId::Message as u8);
+    }
+    let start = frame.len();
+    frame.resize(start + len, 0);
+    reader.read_exact(&mut frame[start..]).await?;
+    Ok(Some(frame.freeze()))
+}
+
+impl Stream for WebtransportTransport {
+    type Item = Result<Bytes>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let mut lock = ready!(Box::pin(self.receiver.lock()).poll_unpin(cx));
+        lock.poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for WebtransportTransport {
+    async fn emit(&self, data: Bytes, is_binary_att: bool) -> Result<()> {
+        let frame = encode_frame(&data, is_binary_att);
+        let mut sender = self.sender.lock().await;
+        sender.write_all(&frame).await?;
+        sender.flush().await?;
+        Ok(())
+    }
+
+    async fn base_url(&self) -> Result<Url> {
+        Ok(self.base_url.read().await.clone())
+    }
+
+    async fn set_base_url(&self, base_url: Url) -> Result<()> {
+        let mut url = base_url;
+        if !url
+            .query_pairs()
+            .any(|(k, v)| k == "transport" && v == "webtransport")
+        {
+            url.query_pairs_mut()
+                .append_pair("transport", "webtransport");
+        }
+        url.set_scheme("https").unwrap();
+        *self.base_url.write().await = url;
+        Ok(())
+    }
+
+    /// Sends probe packet to ensure connection is valid, then sends upgrade
+    /// request
+    async fn upgrade(&self) -> Result<()> {
+        self.emit(
+            Packet::new(PacketId::Ping, Bytes::from("probe")).into(),
+            false,
+        )
+        .await?;
+
+        let msg = self
+            .receiver
+            .lock()
+            .await
+            .next()
+            .await
+            .ok_or(Error::IllegalWebsocketUpgrade())??;
+        if msg != Bytes::from(Packet::new(PacketId::Pong, Bytes::from("probe"))) {
+            return Err(Error::InvalidPacket());
+        }
+
+        self.emit(Packet::new(PacketId::Upgrade, Bytes::new()).into(), false)
+            .await
+    }
+}
+
+impl Debug for WebtransportTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncWebtransportTransport")
+            .field(
+                "base_url",
+                &self
+                    .base_url
+                    .try_read()
+                    .map_or("Currently not available".to_owned(), |url| url.to_string()),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::DuplexStream;
+
+    #[test]
+    fn webtransport_frames() {
+        assert_eq!(encode_frame(b"4hello", false)[..], b"\x064hello"[..]);
+        assert_eq!(encode_frame(&[1, 2], true)[..], [0x82, 1, 2]);
+        let frame = encode_frame(&[0; 300], false);
+        assert_eq!(frame[..3], [126, 1, 44]);
+        assert_eq!(frame.len(), 303);
+        let frame = encode_frame(&vec![0; 70000], true);
+        assert_eq!(frame[..9], [0xff, 0, 0, 0, 0, 0, 1, 0x11, 0x70]);
+
+        let mut varint = BytesMut::new();
+        put_varint(&mut varint, WEBTRANSPORT_STREAM);
+        put_varint(&mut varint, 4);
+        put_varint(&mut varint, 494878333);
+        assert_eq!(varint[..], [0x40, 0x41, 4, 0x9d, 0x7f, 0x3e, 0x7d]);
+    }
+
+    async fn new(url: &str) -> Result<(WebtransportTransport, DuplexStream)> {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (recv, send) = tokio::io::split(client);
+        let transport = WebtransportTransport::with_streams(Url::parse(url)?, send, recv).await?;
+        Ok((transport, server))
+    }
+
+    #[tokio::test]
+    async fn webtransport_transport() -> Result<()> {
+        let (mut transport, mut server) =
+            new("https://localhost/engine.io/?EIO=4&transport=webtransport").await?;
+
+        assert_eq!(read_frame(&mut server).await?, Some(Bytes::from("0")));
+
+        server
+            .write_all(&encode_frame(b"0{\"sid\":\"abc\"}", false))
+            .await?;
+        server.write_all(&encode_frame(&[1, 2, 3], true)).await?;
+        let long = "4".repeat(200);
+        server
+            .write_all(&encode_frame(long.as_bytes(), false))
+            .await?;
+        assert_eq!(
+            transport.next().await.unwrap()?,
+            Bytes::from("0{\"sid\":\"abc\"}")
+        );
+        assert_eq!(
+            transport.next().await.unwrap()?,
+            Bytes::from_static(&[PacketId::Message as u8, 1, 2, 3])
+        );
+        assert_eq!(transport.next().await.unwrap()?, Bytes::from(long));
+
+        transport.emit(Bytes::from("4hello"), false).await?;
+        transport.emit(Bytes::from_static(&[4, 5]), true).await?;
+        assert_eq!(read_frame(&mut server).await?, Some(Bytes::from("4hello")));
+        assert_eq!(
+            read_frame(&mut server).await?,
+            Some(Bytes::from_static(&[PacketId::Message as u8, 4, 5]))
+        );
+
+        drop(server);
+        assert!(transport.next().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn webtransport_upgrade() -> Result<()> {
+        let (transport, mut server) =
+            new("https://localhost/engine.io/?EIO=4&sid=abc&transport=webtransport").await?;
+
+        assert_eq!(
+            read_frame(&mut server).await?,
+            Some(Bytes::from("0{\"sid\":\"abc\"}"))
+        );
+        server.write_all(&encode_frame(b"3probe", false)).await?;
+        transport.upgrade().await?;
+        assert_eq!(read_frame(&mut server).await?, Some(Bytes::from("2probe")));
+        assert_eq!(read_frame(&mut server).await?, Some(Bytes::from("5")));
+
+        transport
+            .set_base_url(Url::parse("http://127.0.0.1/?sid=abc")?)
+            .await?;
+        assert_eq!(
+            transport.base_url().await?.to_string(),
+            "https://127.0.0.1/?sid=abc&transport=webtransport"
+        );
+        assert_eq!(
+            format!("{:?}", transport),
+            "AsyncWebtransportTransport { base_url: \"https://127.0.0.1/?sid=abc&transport=webtransport\" }"
+        );
+        Ok(())
+    }
+}
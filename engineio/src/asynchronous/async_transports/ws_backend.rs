@@ -0,0 +1,209 @@
+//! The websocket implementation the websocket transports run on. By default that's
+//! `tungstenite`, with the `tokio-websockets` feature the connections run on
+//! `tokio-websockets` instead, which has less overhead per message. Both perform the
+//! handshake over a stream the transports connected before, and hand the connection to
+//! [`AsyncWebsocketGeneralTransport`] as a sink and stream of [`Frame`]s.
+
+use futures_util::{future, SinkExt, StreamExt};
+use http::HeaderMap;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tungstenite::protocol::WebSocketConfig;
+use url::Url;
+
+use super::websocket_general::{AsyncWebsocketGeneralTransport, Frame};
+use crate::{error::Result, tls::TlsConnector, Error};
+
+#[cfg(not(feature = "tokio-websockets"))]
+use {
+    tokio_tungstenite::{client_async_tls_with_config, client_async_with_config, Connector},
+    tungstenite::{client::IntoClientRequest, handshake::client::Request, Message},
+};
+
+#[cfg(feature = "tokio-websockets")]
+use {
+    crate::TlsConfig,
+    tokio_websockets::{ClientBuilder, Config, Limits, Message, Payload},
+};
+
+/// Performs the websocket handshake for the url over an already connected stream, which is
+/// used as it is.
+#[cfg(not(feature = "tokio-websockets"))]
+pub(crate) async fn handshake<S>(
+    url: &Url,
+    headers: Option<HeaderMap>,
+    stream: S,
+    config: Option<WebSocketConfig>,
+) -> Result<AsyncWebsocketGeneralTransport>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (ws_stream, _) = client_async_with_config(request(url, headers)?, stream, config).await?;
+    Ok(transport(ws_stream))
+}
+
+/// Performs the websocket handshake for a `wss` url over a tcp connection, establishing a
+/// tls session with the server first. Without a connector, the default tls config is used.
+#[cfg(not(feature = "tokio-websockets"))]
+pub(crate) async fn handshake_tls<S>(
+    url: &Url,
+    headers: Option<HeaderMap>,
+    stream: S,
+    config: Option<WebSocketConfig>,
+    connector: Option<TlsConnector>,
+) -> Result<AsyncWebsocketGeneralTransport>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let connector = connector.map(Connector::from);
+    let (ws_stream, _) =
+        client_async_tls_with_config(request(url, headers)?, stream, config, connector).await?;
+    Ok(transport(ws_stream))
+}
+
+#[cfg(not(feature = "tokio-websockets"))]
+fn request(url: &Url, headers: Option<HeaderMap>) -> Result<Request> {
+    let mut request = url.as_str().into_client_request()?;
+    if let Some(map) = headers {
+        request.headers_mut().extend(map);
+    }
+    Ok(request)
+}
+
+#[cfg(not(feature = "tokio-websockets"))]
+fn transport<S>(ws_stream: tokio_tungstenite::WebSocketStream<S>) -> AsyncWebsocketGeneralTransport
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (sender, receiver) = ws_stream.split();
+    let sender = sender.sink_map_err(Error::from).with(|frame| {
+        future::ready(Ok::<_, Error>(match frame {
+            // emit checked that the text is valid utf-8
+            Frame::Text(data) => Message::Text(String::from_utf8_lossy(&data).into_owned()),
+            Frame::Binary(data) => Message::Binary(data.to_vec()),
+        }))
+    });
+    let receiver = receiver.filter_map(|message| {
+        future::ready(match message {
+            Ok(Message::Text(text)) => Some(Ok(Frame::Text(text.into()))),
+            Ok(Message::Binary(data)) => Some(Ok(Frame::Binary(data.into()))),
+            Ok(_) => None,
+            Err(err) => Some(Err(err.into())),
+        })
+    });
+    AsyncWebsocketGeneralTransport::new(Box::pin(sender), Box::pin(receiver))
+}
+
+/// Performs the websocket handshake for the url over an already connected stream, which is
+/// used as it is. Of the config, `max_message_size` and `write_buffer_size` apply.
+#[cfg(feature = "tokio-websockets")]
+pub(crate) async fn handshake<S>(
+    url: &Url,
+    headers: Option<HeaderMap>,
+    stream: S,
+    config: Option<WebSocketConfig>,
+) -> Result<AsyncWebsocketGeneralTransport>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut builder = ClientBuilder::new()
+        .uri(url.as_str())
+        .map_err(http::Error::from)?;
+    for (name, value) in headers.iter().flatten() {
+        builder = builder.add_header(name.clone(), value.clone());
+    }
+    if let Some(config) = config {
+        builder = builder
+            .limits(Limits::default().max_payload_len(config.max_message_size))
+            .config(Config::default().flush_threshold(config.write_buffer_size));
+    }
+
+    let (ws_stream, _) = builder.connect_on(stream).await?;
+    let (sender, receiver) = ws_stream.split();
+    let sender = sender.sink_map_err(Error::from).with(|frame| {
+        future::ready(Ok::<_, Error>(match frame {
+            Frame::Text(data) => Message::text(Payload::from(data)),
+            Frame::Binary(data) => Message::binary(data),
+        }))
+    });
+    let receiver = receiver.filter_map(|message| {
+        future::ready(match message {
+            Ok(message) if message.is_text() => {
+                Some(Ok(Frame::Text(message.into_payload().into())))
+            }
+            Ok(message) if message.is_binary() => {
+                Some(Ok(Frame::Binary(message.into_payload().into())))
+            }
+            Ok(_) => None,
+            Err(err) => Some(Err(err.into())),
+        })
+    });
+    Ok(AsyncWebsocketGeneralTransport::new(
+        Box::pin(sender),
+        Box::pin(receiver),
+    ))
+}
+
+/// Performs the websocket handshake for a `wss` url over a tcp connection, establishing a
+/// tls session with the server first. Without a connector, the default tls config is used.
+#[cfg(feature = "tokio-websockets")]
+pub(crate) async fn handshake_tls<S>(
+    url: &Url,
+    headers: Option<HeaderMap>,
+    stream: S,
+    config: Option<WebSocketConfig>,
+    connector: Option<TlsConnector>,
+) -> Result<AsyncWebsocketGeneralTransport>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::InvalidUrlScheme(url.to_string()))?;
+    let connector = match connector {
+        Some(connector) => connector,
+        None => TlsConfig::new().build()?,
+    };
+    let stream = connector.connect(host, stream).await?;
+    handshake(url, headers, stream, config).await
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use tungstenite::Message as ServerMessage;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn exchanges_text_and_binary_frames() -> Result<()> {
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server.send(ServerMessage::Ping(vec![1])).await.unwrap();
+            // echo the messages back
+            while let Some(Ok(message)) = server.next().await {
+                if message.is_text() || message.is_binary() {
+                    server.send(message).await.unwrap();
+                }
+            }
+        });
+
+        let url = Url::parse("ws://localhost/engine.io/?EIO=4&transport=websocket")?;
+        let transport = handshake(&url, None, stream, None).await?;
+        transport.emit(Bytes::from_static(b"4hello"), false).await?;
+        transport.emit(Bytes::from_static(&[1, 2, 3]), true).await?;
+
+        assert_eq!(
+            transport.poll_next().await?,
+            Some(Bytes::from_static(b"4hello"))
+        );
+        assert_eq!(
+            transport.poll_next().await?,
+            Some(Bytes::from_static(&[4, 1, 2, 3]))
+        );
+        server.abort();
+        Ok(())
+    }
+}
@@ -1,8 +1,8 @@
 use bytes::Bytes;
 use futures_util::future::BoxFuture;
-use std::{fmt::Debug, ops::Deref, sync::Arc};
+use std::{fmt::Debug, ops::Deref, sync::Arc, time::Duration};
 
-use crate::Packet;
+use crate::{asynchronous::transport::TransportUpgrade, Frame, Packet};
 
 /// Internal type, provides a way to store futures and return them in a boxed manner.
 pub(crate) type DynAsyncCallback<I> = dyn 'static + Send + Sync + Fn(I) -> BoxFuture<'static, ()>;
@@ -70,6 +70,34 @@ impl Debug for OptionalCallback<Packet> {
     }
 }
 
+#[cfg_attr(tarpaulin, ignore)]
+impl Debug for OptionalCallback<Frame> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_fmt(format_args!(
+            "Callback({:?})",
+            if self.inner.is_some() {
+                "Fn(Frame)"
+            } else {
+                "None"
+            }
+        ))
+    }
+}
+
+#[cfg_attr(tarpaulin, ignore)]
+impl Debug for OptionalCallback<Duration> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_fmt(format_args!(
+            "Callback({:?})",
+            if self.inner.is_some() {
+                "Fn(Duration)"
+            } else {
+                "None"
+            }
+        ))
+    }
+}
+
 #[cfg_attr(tarpaulin, ignore)]
 impl Debug for OptionalCallback<Bytes> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
@@ -84,6 +112,20 @@ impl Debug for OptionalCallback<Bytes> {
     }
 }
 
+#[cfg_attr(tarpaulin, ignore)]
+impl Debug for OptionalCallback<TransportUpgrade> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_fmt(format_args!(
+            "Callback({:?})",
+            if self.inner.is_some() {
+                "Fn(TransportUpgrade)"
+            } else {
+                "None"
+            }
+        ))
+    }
+}
+
 impl<I> Deref for OptionalCallback<I> {
     type Target = Option<Arc<DynAsyncCallback<I>>>;
     fn deref(&self) -> &<Self as std::ops::Deref>::Target {
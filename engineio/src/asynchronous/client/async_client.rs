@@ -1,8 +1,12 @@
-use std::{fmt::Debug, pin::Pin};
+use std::{fmt::Debug, pin::Pin, time::Duration};
 
 use crate::{
-    asynchronous::{async_socket::Socket as InnerSocket, generator::StreamGenerator},
+    asynchronous::{
+        async_socket::Socket as InnerSocket, generator::StreamGenerator,
+        transport::TransportUpgrade,
+    },
     error::Result,
+    packet::HandshakePacket,
     Packet,
 };
 use async_stream::try_stream;
@@ -12,6 +16,36 @@ use futures_util::{Stream, StreamExt};
 /// server. This client provides means for connecting, disconnecting and sending
 /// packets to the server.
 ///
+/// The client is a [`Stream`] of the packets the server sends, which has to be polled
+/// for the client to answer the heartbeat of the server and call its callbacks. The
+/// stream ends once the connection is closed.
+///
+/// # Example
+/// ```rust
+/// use bytes::Bytes;
+/// use futures_util::StreamExt;
+/// use rust_engineio::{asynchronous::ClientBuilder, Packet, PacketId};
+/// use url::Url;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let url = Url::parse("http://localhost:4201").unwrap();
+///     let mut client = ClientBuilder::new(url).build().await.expect("Connection failed");
+///     client.connect().await.expect("Connection failed");
+///
+///     let packet = Packet::new(PacketId::Message, Bytes::from_static(b"Hello World"));
+///     client.emit(packet).await.expect("Server unreachable");
+///
+///     while let Some(Ok(packet)) = client.next().await {
+///         if packet.packet_id == PacketId::Message {
+///             println!("Received {:?}", packet.data);
+///             break;
+///         }
+///     }
+///     client.disconnect().await.expect("Disconnect failed");
+/// }
+/// ```
+///
 /// ## Note:
 /// There is no need to put this Client behind an `Arc`, as the type uses `Arc`
 /// internally and provides a shared state beyond all cloned instances.
@@ -19,16 +53,19 @@ use futures_util::{Stream, StreamExt};
 pub struct Client {
     pub(super) socket: InnerSocket,
     generator: StreamGenerator<Packet>,
+    upgrade: Option<TransportUpgrade>,
 }
 
 impl Client {
-    pub(super) fn new(socket: InnerSocket) -> Self {
+    pub(super) fn new(socket: InnerSocket, upgrade: Option<TransportUpgrade>) -> Self {
         Client {
             socket: socket.clone(),
             generator: StreamGenerator::new(Self::stream(socket)),
+            upgrade,
         }
     }
 
+    /// Closes the connection, like [`Client::disconnect`].
     pub async fn close(&self) -> Result<()> {
         self.socket.disconnect().await
     }
@@ -39,11 +76,6 @@ impl Client {
         self.socket.connect().await
     }
 
-    //[KDJ]
-    pub async fn wait_connect_incoming_sid(&self) -> Result<()> {
-        self.socket.wait_connect_incoming_sid().await
-    }
-
     /// Disconnects the connection.
     pub async fn disconnect(&self) -> Result<()> {
         self.socket.disconnect().await
@@ -54,6 +86,12 @@ impl Client {
         self.socket.emit(packet).await
     }
 
+    /// Sends packets to the server in the given order. Polling transports send them
+    /// in a single request instead of one per packet.
+    pub async fn emit_batch(&self, packets: Vec<Packet>) -> Result<()> {
+        self.socket.emit_batch(packets).await
+    }
+
     /// Static method that returns a generator for each element of the stream.
     fn stream(
         socket: InnerSocket,
@@ -72,6 +110,59 @@ impl Client {
     pub fn is_connected(&self) -> bool {
         self.socket.is_connected()
     }
+
+    /// Returns the latest estimate of the round trip time to the server, derived from
+    /// the engine.io heartbeat. It is zero until the first heartbeat round completed.
+    pub fn latency(&self) -> Duration {
+        self.socket.latency()
+    }
+
+    /// Returns the time since the latest heartbeat of the server, or since connecting
+    /// if there was none yet. It is reset by every ping of the server, or its pong with
+    /// revision 3 of the protocol.
+    pub async fn since_heartbeat(&self) -> Duration {
+        self.socket.since_heartbeat().await
+    }
+
+    /// Returns how long the client waits for the next heartbeat of the server before it
+    /// considers the connection lost: the `pingInterval` plus the `pingTimeout`, or the
+    /// timeout set with [`ClientBuilder::ping_timeout`](super::ClientBuilder::ping_timeout).
+    pub fn heartbeat_timeout(&self) -> Duration {
+        self.socket.heartbeat_timeout()
+    }
+
+    /// Returns the encoded size of all packets written to the server, as passed to the
+    /// `on_frame` callback.
+    pub fn bytes_sent(&self) -> u64 {
+        self.socket.bytes_sent()
+    }
+
+    /// Returns the encoded size of all packets read from the server, as passed to the
+    /// `on_frame` callback.
+    pub fn bytes_received(&self) -> u64 {
+        self.socket.bytes_received()
+    }
+
+    /// Sends the heartbeat that is due if the client was built with
+    /// [`ClientBuilder::manual_heartbeat`](super::ClientBuilder::manual_heartbeat): the
+    /// pong answering the latest ping of the server, or a ping with revision 3 of the
+    /// protocol. Returns whether a heartbeat was sent, which it isn't if the server's
+    /// ping was answered already.
+    pub async fn send_heartbeat(&self) -> Result<bool> {
+        self.socket.send_heartbeat().await
+    }
+
+    /// Returns the values the server sent in its `OPEN` packet, such as the ping interval
+    /// and timeout, the maximum payload size and the offered transport upgrades.
+    pub fn handshake_info(&self) -> &HandshakePacket {
+        self.socket.handshake()
+    }
+
+    /// Returns the upgrade of the connection if the handshake was performed with the
+    /// polling transport and the connection then upgraded, e.g. to a websocket.
+    pub fn transport_upgrade(&self) -> Option<TransportUpgrade> {
+        self.upgrade
+    }
 }
 
 impl Stream for Client {
@@ -89,6 +180,7 @@ impl Debug for Client {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Client")
             .field("socket", &self.socket)
+            .field("upgrade", &self.upgrade)
             .finish()
     }
 }
@@ -100,7 +192,6 @@ mod test {
     use crate::{asynchronous::ClientBuilder, header::HeaderMap, packet::PacketId, Error};
     use bytes::Bytes;
     use futures_util::StreamExt;
-    use native_tls::TlsConnector;
     use url::Url;
 
     /// The purpose of this test is to check whether the Client is properly cloneable or not.
@@ -168,7 +259,7 @@ mod test {
         Ok(())
     }
 
-    use reqwest::header::HOST;
+    use http::header::HOST;
 
     use crate::packet::Packet;
 
@@ -270,6 +361,26 @@ mod test {
         test_connection(socket).await
     }
 
+    #[tokio::test]
+    async fn test_transport_upgrade() -> Result<()> {
+        let url = crate::test::engine_io_server()?;
+        let socket = builder(url).build().await?;
+        assert_eq!(
+            socket.transport_upgrade(),
+            Some(TransportUpgrade {
+                from: "polling",
+                to: "websocket"
+            })
+        );
+        socket.close().await?;
+
+        // the server only offers polling
+        let url = crate::test::engine_io_polling_server()?;
+        let socket = builder(url).build().await?;
+        assert_eq!(socket.transport_upgrade(), None);
+        socket.close().await
+    }
+
     #[tokio::test]
     async fn test_connection_fallback() -> Result<()> {
         let url = crate::test::engine_io_server()?;
@@ -290,6 +401,17 @@ mod test {
         test_connection(socket).await
     }
 
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn test_connection_dynamic_secure_rustls() -> Result<()> {
+        let url = crate::test::engine_io_server_secure()?;
+        let socket = builder(url)
+            .tls_config(crate::test::rustls_tls_connector()?)
+            .build()
+            .await?;
+        test_connection(socket).await
+    }
+
     #[tokio::test]
     async fn test_connection_polling() -> Result<()> {
         let url = crate::test::engine_io_server()?;
@@ -297,6 +419,35 @@ mod test {
         test_connection(socket).await
     }
 
+    #[tokio::test]
+    async fn test_emit_batch_polling() -> Result<()> {
+        let url = crate::test::engine_io_server()?;
+        let mut socket = builder(url).build_polling().await?;
+        socket.connect().await?;
+
+        assert_eq!(
+            socket.next().await.unwrap()?,
+            Packet::new(PacketId::Message, "hello client")
+        );
+
+        // too large to share a request with the others, but within the maxPayload itself
+        let max_payload = socket.handshake_info().max_payload.unwrap_or(1000) as usize;
+        let large = "x".repeat(max_payload - 2);
+        socket
+            .emit_batch(vec![
+                Packet::new(PacketId::Message, "hi"),
+                Packet::new(PacketId::Message, large),
+                Packet::new(PacketId::Message, "respond"),
+            ])
+            .await?;
+        assert_eq!(
+            socket.next().await.unwrap()?,
+            Packet::new(PacketId::Message, "Roger Roger")
+        );
+
+        socket.close().await
+    }
+
     #[tokio::test]
     async fn test_connection_wss() -> Result<()> {
         let url = crate::test::engine_io_polling_server()?;
@@ -361,6 +512,362 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_connection_stream() -> Result<()> {
+        use futures_util::SinkExt;
+        use tungstenite::Message;
+
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server
+                .send(Message::text(
+                    r#"0{"sid":"stream","upgrades":[],"pingInterval":25000,"pingTimeout":20000,"maxPayload":100000}"#,
+                ))
+                .await
+                .unwrap();
+            while let Some(message) = server.next().await {
+                if message.unwrap().into_text().unwrap() == "4hello" {
+                    server.send(Message::text("4world")).await.unwrap();
+                    break;
+                }
+            }
+        });
+
+        let mut socket = builder(Url::parse("http://localhost/")?)
+            .build_websocket_with_stream(stream)
+            .await?;
+        socket.connect().await?;
+        socket.emit(Packet::new(PacketId::Message, "hello")).await?;
+
+        assert_eq!(
+            socket.next().await.unwrap()?,
+            Packet::new(PacketId::Message, "world")
+        );
+        server.await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_protocol_v3() -> Result<()> {
+        use crate::ProtocolVersion;
+        use futures_util::SinkExt;
+        use tungstenite::Message;
+
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server
+                .send(Message::text(
+                    r#"0{"sid":"v3","upgrades":[],"pingInterval":50,"pingTimeout":1000}"#,
+                ))
+                .await
+                .unwrap();
+            // the client starts pinging instead of answering the server's pings
+            let ping = server.next().await.unwrap().unwrap();
+            assert_eq!(ping, Message::text("2"));
+            server.send(Message::text("3")).await.unwrap();
+            // binary frames start with the packet id
+            server
+                .send(Message::binary(vec![4u8, 1, 2, 3]))
+                .await
+                .unwrap();
+            while let Some(message) = server.next().await {
+                if let Message::Binary(data) = message.unwrap() {
+                    return data;
+                }
+            }
+            unreachable!("the client sends a binary message");
+        });
+
+        let mut socket = builder(Url::parse("http://localhost/")?)
+            .protocol_version(ProtocolVersion::V3)
+            .build_websocket_with_stream(stream)
+            .await?;
+        socket.connect().await?;
+
+        loop {
+            let packet = socket.next().await.unwrap()?;
+            if packet.packet_id == PacketId::Message {
+                assert_eq!(packet.data, Bytes::from_static(&[1, 2, 3]));
+                break;
+            }
+            assert_eq!(packet.packet_id, PacketId::Pong);
+        }
+        socket
+            .emit(Packet::new(PacketId::MessageBinary, vec![9u8]))
+            .await?;
+
+        assert_eq!(server.await.unwrap(), vec![4u8, 9]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_info() -> Result<()> {
+        use futures_util::SinkExt;
+        use tungstenite::Message;
+
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server
+                .send(Message::text(
+                    r#"0{"sid":"info","upgrades":[],"pingInterval":300,"pingTimeout":200,"maxPayload":1000}"#,
+                ))
+                .await
+                .unwrap();
+            server
+        });
+
+        let socket = builder(Url::parse("http://localhost/")?)
+            .build_websocket_with_stream(stream)
+            .await?;
+        let handshake = socket.handshake_info();
+        assert_eq!(handshake.sid, "info");
+        assert!(handshake.upgrades.is_empty());
+        assert_eq!(handshake.ping_interval, 300);
+        assert_eq!(handshake.ping_timeout, 200);
+        assert_eq!(handshake.max_payload, Some(1000));
+
+        drop(server.await.unwrap());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_latency() -> Result<()> {
+        use futures_util::SinkExt;
+        use tokio::sync::mpsc;
+        use tungstenite::Message;
+
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server
+                .send(Message::text(
+                    r#"0{"sid":"rtt","upgrades":[],"pingInterval":50,"pingTimeout":1000}"#,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(server.next().await.unwrap().unwrap(), Message::text("3"));
+            // answer the pong late, as if the connection was slow
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            server.send(Message::text("2")).await.unwrap();
+            assert_eq!(server.next().await.unwrap().unwrap(), Message::text("3"));
+            server
+        });
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut socket = builder(Url::parse("http://localhost/")?)
+            .on_pong(move |latency| {
+                let tx = tx.clone();
+                Box::pin(async move {
+                    tx.send(latency).await.unwrap();
+                })
+            })
+            .build_websocket_with_stream(stream)
+            .await?;
+        assert_eq!(socket.latency(), Duration::ZERO);
+        socket.connect().await?;
+
+        let packet = socket.next().await.unwrap()?;
+        assert_eq!(packet.packet_id, PacketId::Ping);
+        let latency = socket.latency();
+        assert!(latency >= Duration::from_millis(100));
+        assert!(latency < Duration::from_secs(1));
+        assert_eq!(rx.recv().await, Some(latency));
+
+        drop(server.await.unwrap());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_manual_heartbeat() -> Result<()> {
+        use futures_util::SinkExt;
+        use tokio::sync::mpsc;
+        use tungstenite::Message;
+
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let (pinged, mut pings) = mpsc::unbounded_channel();
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server
+                .send(Message::text(
+                    r#"0{"sid":"manual","upgrades":[],"pingInterval":50,"pingTimeout":400}"#,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(server.next().await.unwrap().unwrap(), Message::text("3"));
+            for _ in 0..2 {
+                server.send(Message::text("2")).await.unwrap();
+                let started = tokio::time::Instant::now();
+                assert_eq!(server.next().await.unwrap().unwrap(), Message::text("3"));
+                pinged.send(started.elapsed()).unwrap();
+            }
+            server
+        });
+
+        let mut socket = builder(Url::parse("http://localhost/")?)
+            .manual_heartbeat(true)
+            .build_websocket_with_stream(stream)
+            .await?;
+        socket.connect().await?;
+
+        // the first ping is answered by hand
+        assert_eq!(socket.next().await.unwrap()?.packet_id, PacketId::Ping);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(socket.send_heartbeat().await?);
+        assert!(!socket.send_heartbeat().await?);
+        assert!(pings.recv().await.unwrap() >= Duration::from_millis(100));
+
+        // the second one once half of the ping timeout passed
+        assert_eq!(socket.next().await.unwrap()?.packet_id, PacketId::Ping);
+        assert!(pings.recv().await.unwrap() >= Duration::from_millis(200));
+
+        drop(server.await.unwrap());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frames() -> Result<()> {
+        use crate::{Direction, Frame};
+        use futures_util::SinkExt;
+        use tokio::sync::mpsc;
+        use tungstenite::Message;
+
+        let (stream, server_stream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut server = tokio_tungstenite::accept_async(server_stream)
+                .await
+                .unwrap();
+            server
+                .send(Message::text(
+                    r#"0{"sid":"frames","upgrades":[],"pingInterval":25000,"pingTimeout":20000}"#,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(server.next().await.unwrap().unwrap(), Message::text("3"));
+            server.send(Message::text("4hello")).await.unwrap();
+            assert_eq!(
+                server.next().await.unwrap().unwrap(),
+                Message::binary(vec![1, 2, 3])
+            );
+            server
+        });
+
+        let (observed, mut frames) = mpsc::unbounded_channel();
+        let mut socket = builder(Url::parse("http://localhost/")?)
+            .on_frame(move |frame| {
+                let observed = observed.clone();
+                Box::pin(async move { observed.send(frame).unwrap() })
+            })
+            .build_websocket_with_stream(stream)
+            .await?;
+        socket.connect().await?;
+        assert_eq!(socket.next().await.unwrap()?.packet_id, PacketId::Message);
+        socket
+            .emit(Packet::new(PacketId::MessageBinary, vec![1, 2, 3]))
+            .await?;
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(frames.recv().await.unwrap());
+        }
+        let frame = |direction, packet_id, size| Frame {
+            direction,
+            packet_id,
+            size,
+            transport: "websocket",
+        };
+        // the callbacks are spawned, so the frames may arrive in any order
+        assert!(received.contains(&frame(Direction::Outbound, PacketId::Pong, 1)));
+        assert!(received.contains(&frame(Direction::Inbound, PacketId::Message, 6)));
+        assert!(received.contains(&frame(Direction::Outbound, PacketId::MessageBinary, 3)));
+        assert_eq!(socket.bytes_sent(), 4);
+        assert_eq!(socket.bytes_received(), 6);
+
+        drop(server.await.unwrap());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_custom_transport() -> Result<()> {
+        use crate::asynchronous::transport::AsyncTransport;
+        use async_trait::async_trait;
+        use futures_util::Stream;
+        use std::collections::VecDeque;
+        use std::pin::Pin;
+        use std::sync::{Arc, Mutex};
+        use std::task::{Context, Poll};
+
+        #[derive(Clone, Default)]
+        struct MemoryTransport {
+            incoming: Arc<Mutex<VecDeque<Bytes>>>,
+            outgoing: Arc<Mutex<Vec<Bytes>>>,
+        }
+
+        impl Stream for MemoryTransport {
+            type Item = Result<Bytes>;
+
+            fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+                // the test only reads the payloads queued upfront
+                match self.incoming.lock().unwrap().pop_front() {
+                    Some(payload) => Poll::Ready(Some(Ok(payload))),
+                    None => Poll::Pending,
+                }
+            }
+        }
+
+        #[async_trait]
+        impl AsyncTransport for MemoryTransport {
+            async fn emit(&self, data: Bytes, _is_binary_att: bool) -> Result<()> {
+                self.outgoing.lock()?.push(data);
+                Ok(())
+            }
+
+            async fn base_url(&self) -> Result<Url> {
+                Ok(Url::parse("memory://engine.io/")?)
+            }
+
+            async fn set_base_url(&self, _base_url: Url) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let transport = MemoryTransport::default();
+        transport.incoming.lock()?.extend([
+            Bytes::from_static(
+                br#"0{"sid":"memory","upgrades":[],"pingInterval":25000,"pingTimeout":20000,"maxPayload":100000}"#,
+            ),
+            Bytes::from_static(b"4hello"),
+        ]);
+
+        let mut socket = builder(Url::parse("http://localhost/")?)
+            .build_with_transport(transport.clone())
+            .await?;
+        socket.connect().await?;
+        socket.emit(Packet::new(PacketId::Message, "world")).await?;
+
+        assert_eq!(
+            socket.next().await.unwrap()?,
+            Packet::new(PacketId::Message, "hello")
+        );
+        assert_eq!(
+            transport.outgoing.lock()?.last(),
+            Some(&Bytes::from_static(b"4world"))
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_open_invariants() -> Result<()> {
         let url = crate::test::engine_io_server()?;
@@ -387,9 +894,10 @@ mod test {
             std::env::var("ENGINE_IO_SECURE_HOST").unwrap_or_else(|_| "localhost".to_owned());
         headers.insert(HOST, host);
 
+        #[cfg(feature = "native-tls")]
         let _ = builder(url.clone())
             .tls_config(
-                TlsConnector::builder()
+                native_tls::TlsConnector::builder()
                     .danger_accept_invalid_certs(true)
                     .build()
                     .unwrap(),
@@ -7,9 +7,11 @@ mod callback;
 #[cfg(feature = "async")]
 pub mod client;
 mod generator;
+mod runtime;
 
 #[cfg(feature = "async")]
 pub use client::Client;
 
 #[cfg(feature = "async")]
 pub use client::ClientBuilder;
+pub use transport::TransportUpgrade;
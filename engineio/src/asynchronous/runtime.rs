@@ -0,0 +1,85 @@
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(target_arch = "wasm32")]
+use futures_util::future::{select, Either};
+#[cfg(target_arch = "wasm32")]
+use send_wrapper::SendWrapper;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::SystemTime;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::{Instant, SystemTime};
+
+/// Spawns the callbacks of a socket. On native targets they run on the tokio runtime the
+/// socket was created in, or the one set with the builder, on wasm targets on the event
+/// loop of the javascript host.
+#[derive(Clone, Debug)]
+pub(crate) struct Handle {
+    #[cfg(not(target_arch = "wasm32"))]
+    inner: tokio::runtime::Handle,
+}
+
+impl Handle {
+    pub(crate) fn current() -> Self {
+        Handle {
+            #[cfg(not(target_arch = "wasm32"))]
+            inner: tokio::runtime::Handle::current(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new(inner: tokio::runtime::Handle) -> Self {
+        Handle { inner }
+    }
+
+    pub(crate) fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.inner.spawn(future);
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(future);
+    }
+}
+
+/// Waits until the duration has elapsed.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    {
+        let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+        SendWrapper::new(gloo_timers::future::TimeoutFuture::new(millis)).await;
+    }
+}
+
+/// The future passed to [`timeout`] did not complete in time.
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Waits for the future to complete, but at most for the given duration.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> std::result::Result<F::Output, Elapsed> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        tokio::time::timeout(duration, future)
+            .await
+            .map_err(|_| Elapsed)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+        let timer = SendWrapper::new(gloo_timers::future::TimeoutFuture::new(millis));
+        futures_util::pin_mut!(future);
+        match select(future, timer).await {
+            Either::Left((output, _)) => Ok(output),
+            Either::Right(_) => Err(Elapsed),
+        }
+    }
+}
@@ -3,11 +3,22 @@ use adler32::adler32;
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures_util::Stream;
-use std::{pin::Pin, time::SystemTime};
+use std::pin::Pin;
 use url::Url;
 
+use super::runtime::SystemTime;
+
+#[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+use super::async_transports::WebtransportTransport;
+#[cfg(not(target_arch = "wasm32"))]
 use super::async_transports::{PollingTransport, WebsocketSecureTransport, WebsocketTransport};
+#[cfg(feature = "wasm")]
+use super::async_transports::{WasmPollingTransport, WasmWebsocketTransport};
 
+/// An asynchronous transport carries engine.io payloads between the client and the server,
+/// receiving them as a stream of bytes. Besides the built-in polling and websocket
+/// transports, custom transports (e.g. in-memory or over QUIC) can implement this trait and
+/// be passed to [`ClientBuilder::build_with_transport`](crate::asynchronous::ClientBuilder::build_with_transport).
 #[async_trait]
 pub trait AsyncTransport: Stream<Item = Result<Bytes>> + Unpin {
     /// Sends a packet to the server. This optionally handles sending of a
@@ -21,6 +32,18 @@ pub trait AsyncTransport: Stream<Item = Result<Bytes>> + Unpin {
     /// Used to update the base path, like when adding the sid.
     async fn set_base_url(&self, base_url: Url) -> Result<()>;
 
+    /// Upgrades a connection whose handshake was performed by another transport to this
+    /// one, e.g. by sending the websocket probe. Does nothing by default.
+    async fn upgrade(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Closes the connection once the `Close` packet was sent, e.g. with the closing
+    /// handshake of a websocket. Does nothing by default.
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Full query address
     async fn address(&self) -> Result<Url>
     where
@@ -36,44 +59,214 @@ pub trait AsyncTransport: Stream<Item = Result<Bytes>> + Unpin {
 
 #[derive(Debug, Clone)]
 pub enum AsyncTransportType {
+    #[cfg(not(target_arch = "wasm32"))]
     Polling(PollingTransport),
+    #[cfg(not(target_arch = "wasm32"))]
     Websocket(WebsocketTransport),
+    #[cfg(not(target_arch = "wasm32"))]
     WebsocketSecure(WebsocketSecureTransport),
+    #[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+    Webtransport(WebtransportTransport),
+    #[cfg(feature = "wasm")]
+    WasmPolling(WasmPollingTransport),
+    #[cfg(feature = "wasm")]
+    WasmWebsocket(WasmWebsocketTransport),
+    Custom(Box<dyn DynTransport>),
+}
+
+/// Object-safe counterpart of a cloneable [`AsyncTransport`], implemented for every such
+/// transport so that custom ones can be stored next to the built-in transports.
+pub trait DynTransport: AsyncTransport + Send + Sync {
+    fn clone_box(&self) -> Box<dyn DynTransport>;
+
+    fn as_transport(&self) -> &(dyn AsyncTransport + Send);
+
+    fn as_transport_mut(&mut self) -> &mut (dyn AsyncTransport + Send);
+}
+
+impl<T> DynTransport for T
+where
+    T: AsyncTransport + Clone + Send + Sync + 'static,
+{
+    fn clone_box(&self) -> Box<dyn DynTransport> {
+        Box::new(self.clone())
+    }
+
+    fn as_transport(&self) -> &(dyn AsyncTransport + Send) {
+        self
+    }
+
+    fn as_transport_mut(&mut self) -> &mut (dyn AsyncTransport + Send) {
+        self
+    }
+}
+
+impl Clone for Box<dyn DynTransport> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl std::fmt::Debug for dyn DynTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CustomTransport")
+    }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl From<PollingTransport> for AsyncTransportType {
     fn from(transport: PollingTransport) -> Self {
         AsyncTransportType::Polling(transport)
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl From<WebsocketTransport> for AsyncTransportType {
     fn from(transport: WebsocketTransport) -> Self {
         AsyncTransportType::Websocket(transport)
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl From<WebsocketSecureTransport> for AsyncTransportType {
     fn from(transport: WebsocketSecureTransport) -> Self {
         AsyncTransportType::WebsocketSecure(transport)
     }
 }
 
+#[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+impl From<WebtransportTransport> for AsyncTransportType {
+    fn from(transport: WebtransportTransport) -> Self {
+        AsyncTransportType::Webtransport(transport)
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl From<WasmPollingTransport> for AsyncTransportType {
+    fn from(transport: WasmPollingTransport) -> Self {
+        AsyncTransportType::WasmPolling(transport)
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl From<WasmWebsocketTransport> for AsyncTransportType {
+    fn from(transport: WasmWebsocketTransport) -> Self {
+        AsyncTransportType::WasmWebsocket(transport)
+    }
+}
+
 #[cfg(feature = "async")]
 impl AsyncTransportType {
     pub fn as_transport(&self) -> &(dyn AsyncTransport + Send) {
         match self {
+            #[cfg(not(target_arch = "wasm32"))]
             AsyncTransportType::Polling(transport) => transport,
+            #[cfg(not(target_arch = "wasm32"))]
             AsyncTransportType::Websocket(transport) => transport,
+            #[cfg(not(target_arch = "wasm32"))]
             AsyncTransportType::WebsocketSecure(transport) => transport,
+            #[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+            AsyncTransportType::Webtransport(transport) => transport,
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmPolling(transport) => transport,
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmWebsocket(transport) => transport,
+            AsyncTransportType::Custom(transport) => transport.as_transport(),
         }
     }
 
     pub fn as_pin_box(&mut self) -> Pin<Box<&mut (dyn AsyncTransport + Send)>> {
         match self {
+            #[cfg(not(target_arch = "wasm32"))]
             AsyncTransportType::Polling(transport) => Box::pin(transport),
+            #[cfg(not(target_arch = "wasm32"))]
             AsyncTransportType::Websocket(transport) => Box::pin(transport),
+            #[cfg(not(target_arch = "wasm32"))]
             AsyncTransportType::WebsocketSecure(transport) => Box::pin(transport),
+            #[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+            AsyncTransportType::Webtransport(transport) => Box::pin(transport),
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmPolling(transport) => Box::pin(transport),
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmWebsocket(transport) => Box::pin(transport),
+            AsyncTransportType::Custom(transport) => Box::pin(transport.as_transport_mut()),
+        }
+    }
+
+    /// Closes the connection, see [`AsyncTransport::close`].
+    pub(crate) async fn close(&self) -> Result<()> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::Polling(transport) => transport.close().await,
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::Websocket(transport) => transport.close().await,
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::WebsocketSecure(transport) => transport.close().await,
+            #[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+            AsyncTransportType::Webtransport(transport) => transport.close().await,
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmPolling(transport) => transport.close().await,
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmWebsocket(transport) => transport.close().await,
+            AsyncTransportType::Custom(transport) => transport.close().await,
+        }
+    }
+
+    /// Sends the messages, each as `(data, is_binary_att)`, in the given order. Websockets
+    /// flush the connection once after the last of them, other transports emit them one
+    /// by one.
+    pub(crate) async fn emit_many(&self, messages: Vec<(Bytes, bool)>) -> Result<()> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::Websocket(transport) => transport.emit_many(messages).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::WebsocketSecure(transport) => transport.emit_many(messages).await,
+            _ => {
+                for (data, is_binary_att) in messages {
+                    self.as_transport().emit(data, is_binary_att).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the transport sends payloads of several packets. Custom transports are
+    /// expected to send single packets like websockets.
+    pub(crate) fn is_polling(&self) -> bool {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::Polling(_) => true,
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmPolling(_) => true,
+            _ => false,
+        }
+    }
+    /// The name of the transport as used by `engine.io`, e.g. `websocket`.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::Polling(_) => "polling",
+            #[cfg(not(target_arch = "wasm32"))]
+            AsyncTransportType::Websocket(_) | AsyncTransportType::WebsocketSecure(_) => {
+                "websocket"
+            }
+            #[cfg(all(feature = "webtransport", not(target_arch = "wasm32")))]
+            AsyncTransportType::Webtransport(_) => "webtransport",
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmPolling(_) => "polling",
+            #[cfg(feature = "wasm")]
+            AsyncTransportType::WasmWebsocket(_) => "websocket",
+            AsyncTransportType::Custom(_) => "custom",
         }
     }
 }
+
+/// An upgrade of the connection from the transport of the handshake to another one,
+/// e.g. from `polling` to `websocket`, see [`Client::transport_upgrade`](crate::asynchronous::Client::transport_upgrade).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransportUpgrade {
+    /// The transport the handshake was performed with.
+    pub from: &'static str,
+    /// The transport the connection was upgraded to.
+    pub to: &'static str,
+}
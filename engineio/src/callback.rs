@@ -3,6 +3,7 @@ use bytes::Bytes;
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) type DynCallback<I> = dyn Fn(I) + 'static + Sync + Send;
 
@@ -71,6 +72,20 @@ impl Debug for OptionalCallback<Packet> {
     }
 }
 
+#[cfg_attr(tarpaulin, ignore)]
+impl Debug for OptionalCallback<Duration> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.write_fmt(format_args!(
+            "Callback({:?})",
+            if self.inner.is_some() {
+                "Fn(Duration)"
+            } else {
+                "None"
+            }
+        ))
+    }
+}
+
 #[cfg_attr(tarpaulin, ignore)]
 impl Debug for OptionalCallback<Bytes> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
@@ -0,0 +1,170 @@
+use bytes::Bytes;
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "zstd"))]
+use std::io::Write;
+
+use crate::error::Result;
+
+/// Bodies shorter than this are sent uncompressed, as the coding overhead outweighs the
+/// savings for the short packets (pings, small events) that make up most of the traffic.
+const MIN_COMPRESSED_LEN: usize = 1024;
+
+/// A content coding used to compress the bodies of polling requests, see
+/// [`crate::ClientBuilder::request_compression`]. Each coding is available with the
+/// feature of the same name.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "deflate")]
+    Deflate,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// The value of the `Content-Encoding` header for this coding.
+    fn content_encoding(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => "deflate",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "gzip", feature = "deflate", feature = "zstd")),
+        allow(unused_variables)
+    )]
+    fn encode(self, body: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => {
+                // `deflate` as a content coding is the zlib format
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(Vec::new(), 0)?;
+                encoder.write_all(body)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+/// How the polling transports compress their http traffic.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct HttpCompression {
+    /// Whether compressed responses are accepted, which only has an effect with one of the
+    /// compression features enabled.
+    pub(crate) accept: bool,
+    /// The coding request bodies are compressed with.
+    pub(crate) request: Option<Compression>,
+}
+
+impl Default for HttpCompression {
+    fn default() -> Self {
+        HttpCompression {
+            accept: true,
+            request: None,
+        }
+    }
+}
+
+impl HttpCompression {
+    #[cfg(feature = "http-reqwest")]
+    pub(crate) fn configure(self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        #[cfg(feature = "gzip")]
+        let builder = builder.gzip(self.accept);
+        #[cfg(feature = "deflate")]
+        let builder = builder.deflate(self.accept);
+        #[cfg(feature = "zstd")]
+        let builder = builder.zstd(self.accept);
+        builder
+    }
+
+    #[cfg(feature = "http-reqwest")]
+    pub(crate) fn configure_blocking(
+        self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> reqwest::blocking::ClientBuilder {
+        #[cfg(feature = "gzip")]
+        let builder = builder.gzip(self.accept);
+        #[cfg(feature = "deflate")]
+        let builder = builder.deflate(self.accept);
+        #[cfg(feature = "zstd")]
+        let builder = builder.zstd(self.accept);
+        builder
+    }
+
+    /// Compresses a request body, returning it along with its `Content-Encoding` if it was
+    /// compressed.
+    pub(crate) fn compress(&self, body: Bytes) -> Result<(Bytes, Option<&'static str>)> {
+        match self.request {
+            Some(compression) if body.len() >= MIN_COMPRESSED_LEN => Ok((
+                Bytes::from(compression.encode(&body)?),
+                Some(compression.content_encoding()),
+            )),
+            _ => Ok((body, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compress() -> Result<()> {
+        let long = Bytes::from("4".repeat(4 * MIN_COMPRESSED_LEN));
+
+        let compression = HttpCompression::default();
+        assert_eq!(compression.compress(long.clone())?, (long.clone(), None));
+
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Read;
+
+            let short = Bytes::from_static(b"4hello");
+            let compression = HttpCompression {
+                accept: true,
+                request: Some(Compression::Gzip),
+            };
+            assert_eq!(compression.compress(short.clone())?, (short, None));
+
+            let (body, encoding) = compression.compress(long.clone())?;
+            assert_eq!(encoding, Some("gzip"));
+            assert!(body.len() < long.len());
+
+            let mut decoded = Vec::new();
+            flate2::read::GzDecoder::new(&body[..]).read_to_end(&mut decoded)?;
+            assert_eq!(decoded, long);
+        }
+        #[cfg(feature = "zstd")]
+        {
+            let compression = HttpCompression {
+                accept: true,
+                request: Some(Compression::Zstd),
+            };
+            let (body, encoding) = compression.compress(long.clone())?;
+            assert_eq!(encoding, Some("zstd"));
+            assert_eq!(zstd::decode_all(&body[..])?, long);
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,11 @@
 use base64::DecodeError;
+#[cfg(all(feature = "http-reqwest", not(target_arch = "wasm32")))]
 use reqwest::Error as ReqwestError;
 use serde_json::Error as JsonError;
 use std::io::Error as IoError;
 use std::str::Utf8Error;
 use thiserror::Error;
+#[cfg(not(target_arch = "wasm32"))]
 use tungstenite::Error as TungsteniteError;
 use url::ParseError as UrlParseError;
 
@@ -28,10 +30,20 @@ pub enum Error {
     InvalidUrl(#[from] UrlParseError),
     #[error("Invalid Url Scheme: {0}")]
     InvalidUrlScheme(String),
+    #[cfg(all(feature = "http-reqwest", not(target_arch = "wasm32")))]
     #[error("Error during connection via http: {0}")]
     IncompleteResponseFromReqwest(#[from] ReqwestError),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Error during connection via http: {0}")]
+    IncompleteResponseFromHyper(#[from] hyper::Error),
+    #[cfg(not(target_arch = "wasm32"))]
     #[error("Error with websocket connection: {0}")]
     WebsocketError(#[from] TungsteniteError),
+    #[cfg(all(feature = "tokio-websockets", not(target_arch = "wasm32")))]
+    #[error("Error with websocket connection: {0}")]
+    TokioWebsocketError(#[from] tokio_websockets::Error),
+    #[error("Error with webtransport connection: {0}")]
+    WebtransportError(String),
     #[error("Network request returned with status code: {0}")]
     IncompleteHttp(u16),
     #[error("Got illegal handshake response: {0}")]
@@ -49,15 +61,95 @@ pub enum Error {
     #[error("Server did not allow upgrading to websockets")]
     IllegalWebsocketUpgrade(),
     #[error("Invalid header name")]
-    InvalidHeaderNameFromReqwest(#[from] reqwest::header::InvalidHeaderName),
+    InvalidHeaderNameFromReqwest(#[from] http::header::InvalidHeaderName),
     #[error("Invalid header value")]
-    InvalidHeaderValueFromReqwest(#[from] reqwest::header::InvalidHeaderValue),
+    InvalidHeaderValueFromReqwest(#[from] http::header::InvalidHeaderValue),
     #[error("The server did not send a PING packet in time")]
     PingTimeout(),
+    #[error("Invalid proxy: {0}")]
+    InvalidProxy(String),
+    #[error("Proxy did not open a tunnel: {0}")]
+    IllegalProxyTunnel(String),
+    #[error("Invalid tls configuration: {0}")]
+    InvalidTlsConfig(String),
+    #[error("Error in the javascript host: {0}")]
+    IncompleteResponseFromJs(String),
+}
+
+/// What an [`Error`] is caused by, telling apart errors that may go away when the
+/// action is retried from those that need a change on either side first.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The connection failed, was lost or timed out, or the server is unavailable.
+    Network,
+    /// The server sent something that doesn't follow the protocol.
+    Protocol,
+    /// The server refused the credentials of the client.
+    Auth,
+    /// The client is misconfigured or was used in a way that can't succeed.
+    User,
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// Returns what caused the error, see [`ErrorKind`].
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            #[cfg(all(feature = "http-reqwest", not(target_arch = "wasm32")))]
+            Error::IncompleteResponseFromReqwest(err) => err
+                .status()
+                .map_or(ErrorKind::Network, |status| status_kind(status.as_u16())),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::WebsocketError(TungsteniteError::Http(response)) => {
+                status_kind(response.status().as_u16())
+            }
+            Error::IncompleteHttp(status) => status_kind(*status),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::IncompleteResponseFromHyper(_) | Error::WebsocketError(_) => ErrorKind::Network,
+            #[cfg(all(feature = "tokio-websockets", not(target_arch = "wasm32")))]
+            Error::TokioWebsocketError(_) => ErrorKind::Network,
+            Error::WebtransportError(_)
+            | Error::IllegalActionBeforeOpen()
+            | Error::IncompleteIo(_)
+            | Error::PingTimeout()
+            | Error::IllegalProxyTunnel(_)
+            | Error::IncompleteResponseFromJs(_) => ErrorKind::Network,
+            Error::InvalidPacketId(_)
+            | Error::IncompletePacket()
+            | Error::InvalidPacket()
+            | Error::InvalidUtf8(_)
+            | Error::InvalidBase64(_)
+            | Error::InvalidHandshake(_)
+            | Error::InvalidJson(_)
+            | Error::IllegalWebsocketUpgrade() => ErrorKind::Protocol,
+            Error::InvalidUrl(_)
+            | Error::InvalidUrlScheme(_)
+            | Error::InvalidHttpConfiguration(_)
+            | Error::InvalidPoisonedLock()
+            | Error::InvalidHeaderNameFromReqwest(_)
+            | Error::InvalidHeaderValueFromReqwest(_)
+            | Error::InvalidProxy(_)
+            | Error::InvalidTlsConfig(_) => ErrorKind::User,
+        }
+    }
+
+    /// Whether retrying later may succeed, which is the case for network errors.
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Network
+    }
+}
+
+/// Classifies the status of an http response the handshake failed with.
+fn status_kind(status: u16) -> ErrorKind {
+    match status {
+        401 | 403 | 407 => ErrorKind::Auth,
+        408 | 429 | 500..=599 => ErrorKind::Network,
+        _ => ErrorKind::Protocol,
+    }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(_: std::sync::PoisonError<T>) -> Self {
         Self::InvalidPoisonedLock()
@@ -88,4 +180,19 @@ mod tests {
             std::io::Error::new(std::io::ErrorKind::Other, Error::IllegalWebsocketUpgrade());
         assert!(matches!(_io_error, _error));
     }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Error::PingTimeout().kind(), ErrorKind::Network);
+        assert!(Error::IncompleteIo(std::io::ErrorKind::ConnectionRefused.into()).is_retryable());
+        assert_eq!(Error::IncompleteHttp(503).kind(), ErrorKind::Network);
+        assert_eq!(Error::IncompleteHttp(401).kind(), ErrorKind::Auth);
+        assert_eq!(Error::IncompleteHttp(400).kind(), ErrorKind::Protocol);
+        assert_eq!(Error::InvalidPacket().kind(), ErrorKind::Protocol);
+        assert_eq!(
+            Error::InvalidUrlScheme("ftp".to_owned()).kind(),
+            ErrorKind::User
+        );
+        assert!(!Error::InvalidTlsConfig(String::new()).is_retryable());
+    }
 }
@@ -0,0 +1,176 @@
+use bytes::Bytes;
+#[cfg(not(feature = "http-reqwest"))]
+use http::{
+    header::{CONTENT_ENCODING, HOST},
+    HeaderMap, HeaderValue, Method,
+};
+use http::{Request, Response};
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use url::Url;
+
+use crate::error::Result;
+#[cfg(not(feature = "http-reqwest"))]
+use crate::{error::Error, tcp::TcpOptions, tls::TlsConnector, TlsConfig};
+
+/// Sends a single http/1.1 request over an already connected stream and collects the
+/// response.
+pub(crate) async fn send<S>(stream: S, request: Request<Full<Bytes>>) -> Result<Response<Bytes>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let (parts, body) = sender.send_request(request).await?.into_parts();
+    let body = body.collect().await?.to_bytes();
+    Ok(Response::from_parts(parts, body))
+}
+
+/// Returns the path and query of the url, the target of a request sent to the server
+/// itself rather than to a proxy.
+pub(crate) fn origin_form(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    }
+}
+
+/// A minimal http client for the polling transports, used instead of `reqwest` when the
+/// `http-reqwest` feature is disabled. It speaks http/1.1 only and opens a new connection
+/// for every request, through a tunnel if a proxy is set. As it doesn't advertise any
+/// encoding, responses are never compressed.
+#[cfg(not(feature = "http-reqwest"))]
+#[derive(Clone, Debug)]
+pub(crate) struct HyperClient {
+    tls_config: Option<TlsConnector>,
+    headers: HeaderMap,
+    proxy: Option<Url>,
+    tcp: TcpOptions,
+}
+
+#[cfg(not(feature = "http-reqwest"))]
+impl HyperClient {
+    /// Creates a client for requests to the given url. Without a proxy, the proxies
+    /// configured in the environment are used.
+    pub(crate) fn new(
+        url: &Url,
+        tls_config: Option<TlsConnector>,
+        headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        tcp: TcpOptions,
+    ) -> Self {
+        HyperClient {
+            tls_config,
+            headers: headers.unwrap_or_default(),
+            proxy: proxy.or_else(|| crate::proxy::from_env(url)),
+            tcp,
+        }
+    }
+
+    pub(crate) async fn get(&self, url: &Url) -> Result<Response<Bytes>> {
+        self.send(Method::GET, url, Bytes::new(), None).await
+    }
+
+    pub(crate) async fn post(
+        &self,
+        url: &Url,
+        body: Bytes,
+        content_encoding: Option<&'static str>,
+    ) -> Result<Response<Bytes>> {
+        self.send(Method::POST, url, body, content_encoding).await
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        body: Bytes,
+        content_encoding: Option<&'static str>,
+    ) -> Result<Response<Bytes>> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| Error::InvalidUrlScheme(url.to_string()))?;
+        let authority = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_owned(),
+        };
+
+        let mut request = Request::builder()
+            .method(method)
+            .uri(origin_form(url))
+            .body(Full::new(body))?;
+        request.headers_mut().extend(self.headers.clone());
+        request
+            .headers_mut()
+            .insert(HOST, HeaderValue::from_str(&authority)?);
+        if let Some(encoding) = content_encoding {
+            request
+                .headers_mut()
+                .insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+
+        let stream = match &self.proxy {
+            Some(proxy) => crate::proxy::tunnel(proxy, url, &self.tcp).await?,
+            None => self.tcp.connect_url(url).await?,
+        };
+        if !matches!(url.scheme(), "https" | "wss") {
+            return send(stream, request).await;
+        }
+
+        let connector = match &self.tls_config {
+            Some(connector) => connector.clone(),
+            None => TlsConfig::new().build()?,
+        };
+        send(connector.connect(host, stream).await?, request).await
+    }
+}
+
+#[cfg(all(test, not(feature = "http-reqwest")))]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn hyper_client() -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let port = listener.local_addr()?.port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 1024];
+            let len = stream.read(&mut request).await.unwrap();
+            request.truncate(len);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-test", HeaderValue::from_static("1"));
+        let url = Url::parse(&format!("http://127.0.0.1:{}/engine.io/?EIO=4", port))?;
+        let client = HyperClient::new(&url, None, Some(headers), None, TcpOptions::default());
+        let response = client
+            .post(&url, Bytes::from_static(b"4hello"), Some("gzip"))
+            .await?;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), &Bytes::from_static(b"ok"));
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /engine.io/?EIO=4 HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("host: 127.0.0.1:{}\r\n", port)));
+        assert!(request.contains("x-test: 1\r\n"));
+        assert!(request.contains("content-encoding: gzip\r\n"));
+        assert!(request.ends_with("\r\n\r\n4hello"));
+        Ok(())
+    }
+}
@@ -35,8 +35,42 @@
 //! * `build_polling`: enforces a `polling` transport.
 //! * `build_websocket_with_upgrade`: Build socket with a polling transport then upgrade to websocket transport (if possible).
 //! * `build_websocket`: Build socket with only a websocket transport, crashes when websockets are not allowed.
+//! * `build_websocket_with_stream`: Build socket with only a websocket transport over an already connected stream.
+//! * `build_webtransport`: Build socket with only a WebTransport transport over http/3, available in the async version with the `webtransport` feature.
+//! * `build_with_transport`: Build socket with a custom implementation of [`transport::Transport`] (or [`asynchronous::transport::AsyncTransport`]).
 //!
 //!
+//! ## Standalone use
+//!
+//! The crate is the transport layer of `rust_socketio`, but it's also a supported client
+//! for plain engine.io servers, which exchange raw engine.io packets without the framing
+//! of socket.io. The [`Client`] and [`ClientBuilder`] of both versions, the
+//! [`Packet`]s they exchange, the [`Error`] they fail with and the transport traits
+//! follow semantic versioning. The async [`asynchronous::Client`] is a stream of the
+//! packets the server sends:
+//!
+//! ``` rust
+//! use futures_util::StreamExt;
+//! use rust_engineio::{asynchronous::ClientBuilder, PacketId};
+//! use url::Url;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let url = Url::parse("http://localhost:4201").unwrap();
+//!     let mut client = ClientBuilder::new(url).build().await.expect("Connection failed");
+//!     client.connect().await.expect("Connection failed");
+//!
+//!     // the stream has to be polled for the client to answer the heartbeat
+//!     while let Some(Ok(packet)) = client.next().await {
+//!         if packet.packet_id == PacketId::Message {
+//!             println!("Received {:?}", packet.data);
+//!             break;
+//!         }
+//!     }
+//!     client.disconnect().await.expect("Disconnect failed");
+//! }
+//! ```
+//!
 //! ## Current features
 //!
 //! This implementation now supports all of the features of the engine.io protocol mentioned [here](https://github.com/socketio/engine.io-protocol).
@@ -49,7 +83,24 @@
 //! * on_packet
 //!
 //! It is also possible to pass in custom tls configurations via the `TlsConnector` as well
-//! as custom headers for the opening request.
+//! as custom headers for the opening request. Legacy `engine.io` 3.x servers are supported by
+//! passing `ProtocolVersion::V3` to `ClientBuilder::protocol_version`.
+//!
+//! Secure connections use `native-tls` by default. To build without OpenSSL, disable the default
+//! features and enable `rustls` instead:
+//! ```toml
+//! [dependencies]
+//! rust-engineio = { version = "0.6.0", default-features = false, features = ["async", "rustls"] }
+//! ```
+//!
+//! The polling transports send their requests with `reqwest`, which the default `http-reqwest`
+//! feature pulls in. Without it, a minimal http/1.1 client built on `hyper` is used instead,
+//! shrinking the dependency tree for applications that mostly connect via websockets. It doesn't
+//! decompress responses, and `asynchronous::ClientBuilder::http_client` isn't available then.
+//!
+//! The websocket transports run on `tungstenite`. The `tokio-websockets` feature switches them to
+//! `tokio-websockets`, which has less overhead per message for high-throughput consumers. Of the
+//! [`WebSocketConfig`], only `max_message_size` and `write_buffer_size` apply to it.
 //!
 //! ## Async version
 //!
@@ -64,6 +115,16 @@
 //! rust-engineio = { version = "0.3.1", features = ["async"] }
 //! ```
 //!
+//! ## WebAssembly
+//!
+//! The async version also runs on `wasm32` targets with the `wasm` feature, using the `WebSocket` and
+//! `fetch` apis of the javascript host instead of `tokio` and `reqwest`. The sync version, the tls and
+//! proxy options and `build_websocket_with_stream` are not available there.
+//! ```toml
+//! [dependencies]
+//! rust-engineio = { version = "0.6.0", default-features = false, features = ["wasm"] }
+//! ```
+//!
 #![allow(clippy::rc_buffer)]
 #![warn(clippy::complexity)]
 #![warn(clippy::style)]
@@ -71,6 +132,7 @@
 #![warn(clippy::correctness)]
 /// A small macro that spawns a scoped thread. Used for calling the callback
 /// functions.
+#[cfg(not(target_arch = "wasm32"))]
 macro_rules! spawn_scoped {
     ($e:expr) => {
         std::thread::scope(|s| {
@@ -79,15 +141,43 @@ macro_rules! spawn_scoped {
     };
 }
 
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    not(any(feature = "native-tls", feature = "rustls"))
+))]
+compile_error!("either the `native-tls` or the `rustls` feature has to be enabled");
+
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("the `wasm` feature has to be enabled for wasm targets");
+
 pub mod asynchronous;
+#[cfg(not(target_arch = "wasm32"))]
 mod callback;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod client;
+/// Compression of the http traffic of polling transports
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compression;
 /// Generic header map
 pub mod header;
+#[cfg(not(target_arch = "wasm32"))]
+mod hyper_client;
 pub mod packet;
+#[cfg(not(target_arch = "wasm32"))]
+mod proxy;
+#[cfg(not(target_arch = "wasm32"))]
 pub(self) mod socket;
+#[cfg(not(target_arch = "wasm32"))]
+mod tcp;
+/// Tls backends used for secure connections
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tls;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod transport;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod transports;
+#[cfg(unix)]
+mod unix;
 
 pub const ENGINE_IO_VERSION: i32 = 4;
 
@@ -95,31 +185,64 @@ pub const ENGINE_IO_VERSION: i32 = 4;
 /// crate. Handles all kinds of errors.
 pub mod error;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub use client::{Client, ClientBuilder};
-pub use error::Error;
-pub use packet::{Packet, PacketId};
+#[cfg(not(target_arch = "wasm32"))]
+pub use compression::Compression;
+pub use error::{Error, ErrorKind};
+pub use packet::{Direction, Frame, Packet, PacketId, ProtocolVersion};
+/// The `reqwest` version the polling transport is built on, for clients passed to
+/// [`asynchronous::ClientBuilder::http_client`].
+#[cfg(all(feature = "http-reqwest", not(target_arch = "wasm32")))]
+pub use reqwest;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tcp::TcpOptions;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tls::{CertificatePin, TlsConfig, TlsConnector};
+#[cfg(not(target_arch = "wasm32"))]
+pub use tungstenite::protocol::WebSocketConfig;
 
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
-    use native_tls::TlsConnector;
     const CERT_PATH: &str = "../ci/cert/ca.crt";
-    use native_tls::Certificate;
     use std::fs::File;
     use std::io::Read;
 
-    pub(crate) fn tls_connector() -> error::Result<TlsConnector> {
+    fn ca_certificate() -> error::Result<Vec<u8>> {
         let cert_path = std::env::var("CA_CERT_PATH").unwrap_or_else(|_| CERT_PATH.to_owned());
         let mut cert_file = File::open(cert_path)?;
         let mut buf = vec![];
         cert_file.read_to_end(&mut buf)?;
-        let cert: Certificate = Certificate::from_pem(&buf[..]).unwrap();
-        Ok(TlsConnector::builder()
+        Ok(buf)
+    }
+
+    /// Returns a connector trusting the ca of the test servers, for the `native-tls` backend
+    /// if it's enabled and for `rustls` otherwise.
+    #[cfg(feature = "native-tls")]
+    pub(crate) fn tls_connector() -> error::Result<TlsConnector> {
+        let cert = native_tls::Certificate::from_pem(&ca_certificate()?).unwrap();
+        let connector = native_tls::TlsConnector::builder()
             // ONLY USE FOR TESTING!
             .danger_accept_invalid_hostnames(true)
             .add_root_certificate(cert)
             .build()
-            .unwrap())
+            .unwrap();
+        Ok(connector.into())
+    }
+
+    #[cfg(not(feature = "native-tls"))]
+    pub(crate) fn tls_connector() -> error::Result<TlsConnector> {
+        rustls_tls_connector()
+    }
+
+    /// Returns a `rustls` connector trusting the ca of the test servers, whose certificates
+    /// are valid for `localhost`.
+    #[cfg(feature = "rustls")]
+    pub(crate) fn rustls_tls_connector() -> error::Result<TlsConnector> {
+        TlsConfig::new()
+            .root_certificates_pem(ca_certificate()?)
+            .build_rustls()
     }
     /// The `engine.io` server for testing runs on port 4201
     const SERVER_URL: &str = "http://localhost:4201";
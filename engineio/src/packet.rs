@@ -6,8 +6,155 @@ use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::fmt::{Display, Formatter, Result as FmtResult, Write};
 use std::ops::Index;
+use url::Url;
 
 use crate::error::{Error, Result};
+
+/// The revision of the `engine.io` protocol spoken with the server.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ProtocolVersion {
+    /// Revision 3, used by `engine.io` 3.x (`socket.io` 2.x) servers. Polling payloads are
+    /// prefixed with their length and the client sends the pings.
+    V3,
+    /// Revision 4, used by `engine.io` 4.x and later (`socket.io` 3.x and later) servers.
+    #[default]
+    V4,
+}
+
+impl ProtocolVersion {
+    /// Sets the `EIO` query parameter of the url to this revision. With revision 3 the server
+    /// is also asked to base64 encode binary data, so polling payloads are always text.
+    pub(crate) fn apply_to(self, url: &mut Url) {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| key != "EIO" && key != "b64")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+
+        let mut query = url.query_pairs_mut();
+        query.clear().extend_pairs(pairs);
+        match self {
+            ProtocolVersion::V3 => query.append_pair("EIO", "3").append_pair("b64", "1"),
+            ProtocolVersion::V4 => query.append_pair("EIO", "4"),
+        };
+    }
+
+    /// Encodes a packet to be sent over a transport. Returns the data and whether it has to
+    /// be sent as binary. `polling` transports send payloads, the others single packets.
+    pub(crate) fn encode(self, packet: Packet, polling: bool) -> (Bytes, bool) {
+        let is_binary = packet.packet_id == PacketId::MessageBinary;
+        match self {
+            ProtocolVersion::V3 if polling => (Payload(vec![packet]).encode_v3(), false),
+            // binary frames start with the packet id in revision 3
+            ProtocolVersion::V3 if is_binary => {
+                let mut data = BytesMut::with_capacity(packet.data.len() + 1);
+                data.put_u8(PacketId::Message.into());
+                data.put(packet.data);
+                (data.freeze(), true)
+            }
+            // binary attachments are sent as raw bytes
+            _ if is_binary => (packet.data, true),
+            _ => (packet.into(), false),
+        }
+    }
+
+    /// The size of a packet as encoded for a websocket message, see [`Frame::size`].
+    pub(crate) fn frame_size(self, packet: &Packet) -> usize {
+        match self {
+            ProtocolVersion::V4 if packet.packet_id == PacketId::MessageBinary => packet.data.len(),
+            _ => packet.data.len() + 1,
+        }
+    }
+
+    /// Encodes packets into a single payload for polling transports, which is sent as
+    /// text.
+    pub(crate) fn encode_payload(self, packets: Vec<Packet>) -> Result<Bytes> {
+        match self {
+            ProtocolVersion::V3 => Ok(Payload(packets).encode_v3()),
+            ProtocolVersion::V4 => Bytes::try_from(Payload(packets)),
+        }
+    }
+
+    /// Splits packets into batches whose payloads, see [`ProtocolVersion::encode_payload`],
+    /// are at most `max_payload` bytes long, keeping their order. A packet that is longer on
+    /// its own makes up a batch by itself.
+    pub(crate) fn split_payload(
+        self,
+        packets: Vec<Packet>,
+        max_payload: Option<u64>,
+    ) -> Vec<Vec<Packet>> {
+        let Some(max_payload) = max_payload else {
+            return vec![packets];
+        };
+        let max_payload = usize::try_from(max_payload).unwrap_or(usize::MAX);
+        // revision 4 separates the packets by a byte, revision 3 prefixes their length
+        let separator = usize::from(self == ProtocolVersion::V4);
+
+        let mut batches = Vec::new();
+        let mut batch = Vec::new();
+        let mut size = 0;
+        for packet in packets {
+            let packet_size = self.payload_size(&packet);
+            if batch.is_empty() {
+                size = packet_size;
+            } else if size + separator + packet_size <= max_payload {
+                size += separator + packet_size;
+            } else {
+                batches.push(std::mem::take(&mut batch));
+                size = packet_size;
+            }
+            batch.push(packet);
+        }
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+        batches
+    }
+
+    /// The size of a packet within a payload, see [`ProtocolVersion::encode_payload`].
+    fn payload_size(self, packet: &Packet) -> usize {
+        match self {
+            ProtocolVersion::V3 => Payload(vec![packet.clone()]).encode_v3().len(),
+            // binary data is base64 encoded
+            ProtocolVersion::V4 if packet.packet_id == PacketId::MessageBinary => {
+                1 + packet.data.len().div_ceil(3) * 4
+            }
+            ProtocolVersion::V4 => 1 + packet.data.len(),
+        }
+    }
+
+    /// Decodes the data received over a transport, see [`ProtocolVersion::encode`].
+    pub(crate) fn decode(self, data: Bytes, polling: bool) -> Result<Payload> {
+        match self {
+            ProtocolVersion::V4 => Payload::try_from(data),
+            ProtocolVersion::V3 if polling => Payload::decode_v3(data),
+            // the transports prefix binary frames with the message id, in revision 3 the
+            // frames already start with it
+            ProtocolVersion::V3 if data.first() == Some(&u8::from(PacketId::Message)) => {
+                if data.len() < 2 {
+                    return Err(Error::IncompletePacket());
+                }
+                Ok(Payload(vec![Packet::new(
+                    PacketId::Message,
+                    data.slice(2..),
+                )]))
+            }
+            ProtocolVersion::V3 => Ok(Payload(vec![Packet::try_from(data)?])),
+        }
+    }
+
+    /// Decodes the open packet the server answers the handshake with. Polling transports of
+    /// revision 3 prefix it with its length, while the packet itself starts with its id `0`.
+    pub(crate) fn decode_handshake(self, data: Bytes) -> Result<Packet> {
+        if self == ProtocolVersion::V3 && data.first() != Some(&b'0') {
+            return Payload::decode_v3(data)?
+                .into_iter()
+                .next()
+                .ok_or(Error::IncompletePacket());
+        }
+        Packet::try_from(data)
+    }
+}
 /// Enumeration of the `engine.io` `Packet` types.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum PacketId {
@@ -77,6 +224,31 @@ pub struct Packet {
     pub data: Bytes,
 }
 
+/// Whether a packet was read from or written to the server, as passed to the observers
+/// of packets and frames.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    /// The packet was read from the server.
+    Inbound,
+    /// The packet is written to the server.
+    Outbound,
+}
+
+/// An engine.io packet going over the transport of an async client, as passed to the
+/// `on_frame` callback of the
+/// [`ClientBuilder`](crate::asynchronous::ClientBuilder::on_frame).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Frame {
+    pub direction: Direction,
+    pub packet_id: PacketId,
+    /// The encoded size of the packet in bytes, as sent in a websocket message. Polling
+    /// transports add a separator per packet.
+    pub size: usize,
+    /// The name of the transport, e.g. `polling` or `websocket`.
+    pub transport: &'static str,
+}
+
 /// Data which gets exchanged in a handshake as defined by the server.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct HandshakePacket {
@@ -86,6 +258,14 @@ pub struct HandshakePacket {
     pub ping_interval: u64,
     #[serde(rename = "pingTimeout")]
     pub ping_timeout: u64,
+    /// The largest payload in bytes the server accepts. Servers speaking revision 3 of
+    /// the protocol don't announce it.
+    #[serde(
+        rename = "maxPayload",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_payload: Option<u64>,
 }
 
 impl TryFrom<Packet> for HandshakePacket {
@@ -141,6 +321,33 @@ impl TryFrom<Bytes> for Packet {
     }
 }
 
+impl Packet {
+    /// Decodes a single `Packet` of revision 3 of the protocol, in which base64 encoded
+    /// messages carry the packet id after the `b`, e.g. `b4aGVsbG8=`.
+    pub(crate) fn decode_v3(bytes: Bytes) -> Result<Self> {
+        if bytes.first() == Some(&b'b') {
+            let data = bytes.get(2..).ok_or(Error::IncompletePacket())?;
+            return Ok(Packet::new(
+                PacketId::MessageBinary,
+                general_purpose::STANDARD.decode(data)?,
+            ));
+        }
+        Packet::try_from(bytes)
+    }
+
+    /// Encodes a `Packet` for revision 3 of the protocol.
+    pub(crate) fn encode_v3(self) -> Bytes {
+        if self.packet_id != PacketId::MessageBinary {
+            return self.into();
+        }
+        let mut result = BytesMut::with_capacity(self.data.len() * 4 / 3 + 6);
+        result.put_u8(PacketId::MessageBinary.to_string_byte());
+        result.put_u8(PacketId::Message.to_string_byte());
+        result.extend(general_purpose::STANDARD.encode(self.data).into_bytes());
+        result.freeze()
+    }
+}
+
 impl From<Packet> for Bytes {
     /// Encodes a `Packet` into an `u8` byte stream.
     fn from(packet: Packet) -> Self {
@@ -166,6 +373,53 @@ impl Payload {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    /// Decodes a payload of revision 3 of the protocol, in which every packet is prefixed with
+    /// its length in utf-16 code units (the length of the javascript string), e.g.
+    /// `6:4hello2:4€`.
+    pub(crate) fn decode_v3(payload: Bytes) -> Result<Self> {
+        let text = std::str::from_utf8(&payload)?;
+        let mut packets = Vec::new();
+        let mut start = 0;
+        while start < text.len() {
+            let separator = text[start..].find(':').ok_or(Error::InvalidPacket())? + start;
+            let length: usize = text[start..separator]
+                .parse()
+                .map_err(|_| Error::InvalidPacket())?;
+
+            let mut end = separator + 1;
+            let mut units = 0;
+            for c in text[end..].chars() {
+                if units >= length {
+                    break;
+                }
+                units += c.len_utf16();
+                end += c.len_utf8();
+            }
+            if units != length {
+                return Err(Error::IncompletePacket());
+            }
+
+            packets.push(Packet::decode_v3(payload.slice(separator + 1..end))?);
+            start = end;
+        }
+        Ok(Self(packets))
+    }
+
+    /// Encodes a payload for revision 3 of the protocol, see [`Payload::decode_v3`].
+    pub(crate) fn encode_v3(self) -> Bytes {
+        let mut buf = BytesMut::new();
+        for packet in self {
+            let packet = packet.encode_v3();
+            let length: usize = String::from_utf8_lossy(&packet)
+                .chars()
+                .map(char::len_utf16)
+                .sum();
+            buf.extend(format!("{}:", length).into_bytes());
+            buf.extend(packet);
+        }
+        buf.freeze()
+    }
 }
 
 impl TryFrom<Bytes> for Payload {
@@ -233,6 +487,43 @@ impl Index<usize> for Payload {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_split_payload() {
+        let packets: Vec<_> = ["a", "bb", "ccc", "dddddddddd"]
+            .into_iter()
+            .map(|data| Packet::new(PacketId::Message, data))
+            .collect();
+        let sizes = |batches: Vec<Vec<Packet>>| -> Vec<usize> {
+            batches
+                .into_iter()
+                .map(|batch| {
+                    let payload = ProtocolVersion::V4.encode_payload(batch).unwrap();
+                    payload.len()
+                })
+                .collect()
+        };
+
+        let batches = ProtocolVersion::V4.split_payload(packets.clone(), None);
+        assert_eq!(batches, vec![packets.clone()]);
+
+        // "4a\x1e4bb" is 6 bytes long, "4ccc" doesn't fit after it
+        let batches = ProtocolVersion::V4.split_payload(packets.clone(), Some(8));
+        assert_eq!(batches.concat(), packets);
+        assert_eq!(sizes(batches), vec![6, 4, 11]);
+
+        let binary = Packet::new(PacketId::MessageBinary, vec![0u8; 4]);
+        let size = ProtocolVersion::V4
+            .encode_payload(vec![binary.clone()])
+            .unwrap()
+            .len();
+        assert_eq!(ProtocolVersion::V4.payload_size(&binary), size);
+        let size = ProtocolVersion::V3
+            .encode_payload(vec![binary.clone()])
+            .unwrap()
+            .len();
+        assert_eq!(ProtocolVersion::V3.payload_size(&binary), size);
+    }
+
     #[test]
     fn test_packet_error() {
         let err = Packet::try_from(BytesMut::with_capacity(10).freeze());
@@ -295,6 +586,58 @@ mod tests {
         assert_eq!(Bytes::try_from(packets).unwrap(), data);
     }
 
+    #[test]
+    fn test_payload_v3() -> Result<()> {
+        // the length counts utf-16 code units, '€' is a single one but three bytes long
+        let data = Bytes::from("6:4hello2:4€10:b4SGVsbG8=");
+        let packets = Payload::decode_v3(data.clone())?;
+
+        assert!(packets.len() == 3);
+        assert_eq!(packets[0].packet_id, PacketId::Message);
+        assert_eq!(packets[0].data, Bytes::from_static(b"hello"));
+        assert_eq!(packets[1].packet_id, PacketId::Message);
+        assert_eq!(packets[1].data, Bytes::from("€"));
+        assert_eq!(packets[2].packet_id, PacketId::MessageBinary);
+        assert_eq!(packets[2].data, Bytes::from_static(b"Hello"));
+
+        assert_eq!(packets.encode_v3(), data);
+        assert_eq!(
+            ProtocolVersion::V3.encode(Packet::new(PacketId::MessageBinary, "Hello"), true),
+            (Bytes::from("10:b4SGVsbG8="), false)
+        );
+
+        assert_eq!(
+            ProtocolVersion::V3.encode_payload(vec![
+                Packet::new(PacketId::Message, "hello"),
+                Packet::new(PacketId::MessageBinary, "Hello"),
+            ])?,
+            Bytes::from("6:4hello10:b4SGVsbG8=")
+        );
+        assert_eq!(
+            ProtocolVersion::V4.encode_payload(vec![
+                Packet::new(PacketId::Message, "hello"),
+                Packet::new(PacketId::MessageBinary, "Hello"),
+            ])?,
+            Bytes::from("4hello\x1ebSGVsbG8=")
+        );
+
+        assert!(Payload::decode_v3(Bytes::from_static(b"7:4hello")).is_err());
+        assert!(Payload::decode_v3(Bytes::from_static(b"4hello")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_protocol_version_query() -> Result<()> {
+        let mut url = Url::parse("http://localhost/engine.io/?EIO=4&foo=bar")?;
+
+        ProtocolVersion::V3.apply_to(&mut url);
+        assert_eq!(url.query(), Some("foo=bar&EIO=3&b64=1"));
+
+        ProtocolVersion::V4.apply_to(&mut url);
+        assert_eq!(url.query(), Some("foo=bar&EIO=4"));
+        Ok(())
+    }
+
     #[test]
     fn test_packet_id_conversion_and_incompl_packet() -> Result<()> {
         let sut = Packet::try_from(Bytes::from_static(b"4"));
@@ -349,6 +692,7 @@ mod tests {
             ping_timeout: 1000,
             sid: "Test".to_owned(),
             upgrades: vec!["websocket".to_owned(), "test".to_owned()],
+            max_payload: Some(1000000),
         };
         let encoded: String = serde_json::to_string(&packet).unwrap();
 
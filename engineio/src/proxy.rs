@@ -0,0 +1,359 @@
+use base64::{engine::general_purpose, Engine as _};
+use percent_encoding::percent_decode_str;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+};
+use tokio_socks::{tcp::Socks5Stream, TargetAddr};
+use url::Url;
+
+use crate::{
+    error::{Error, Result},
+    tcp::TcpOptions,
+};
+
+/// Upper bound for the response head a proxy may send back to a `CONNECT` request.
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+/// Checks that the proxy url can be used by all transports. Supported are http proxies,
+/// which the websocket transports tunnel through via `CONNECT`, and SOCKS5 proxies. With
+/// `socks5h` the proxy resolves the host name of the server, with `socks5` it's resolved
+/// locally.
+pub(crate) fn validate(proxy: &Url) -> Result<()> {
+    if !matches!(proxy.scheme(), "http" | "socks5" | "socks5h") {
+        return Err(Error::InvalidProxy(format!(
+            "unsupported scheme {}",
+            proxy.scheme()
+        )));
+    }
+    if proxy.host_str().is_none() {
+        return Err(Error::InvalidProxy("missing host".to_owned()));
+    }
+    Ok(())
+}
+
+/// Converts the proxy url into a proxy for the reqwest based polling transports.
+#[cfg(feature = "http-reqwest")]
+pub(crate) fn reqwest_proxy(proxy: &Url) -> Result<reqwest::Proxy> {
+    validate(proxy)?;
+    Ok(reqwest::Proxy::all(proxy.as_str())?)
+}
+
+/// Returns the proxy configured in the environment for the given url. `HTTPS_PROXY` is
+/// used for secure urls and `HTTP_PROXY` for plain ones, both fall back to `ALL_PROXY`.
+/// Hosts listed in `NO_PROXY` are reached directly.
+pub(crate) fn from_env(target: &Url) -> Option<Url> {
+    from_vars(target, |name| {
+        std::env::var(name)
+            .or_else(|_| std::env::var(name.to_lowercase()))
+            .ok()
+            .filter(|value| !value.is_empty())
+    })
+}
+
+fn from_vars<F: Fn(&str) -> Option<String>>(target: &Url, var: F) -> Option<Url> {
+    // unix domain sockets are always reached directly
+    if matches!(target.scheme(), "http+unix" | "ws+unix") {
+        return None;
+    }
+    let host = target.host_str()?;
+    if let Some(no_proxy) = var("NO_PROXY") {
+        if bypasses(&no_proxy, host) {
+            return None;
+        }
+    }
+
+    let proxy = match target.scheme() {
+        "https" | "wss" => var("HTTPS_PROXY"),
+        _ => var("HTTP_PROXY"),
+    }
+    .or_else(|| var("ALL_PROXY"))?;
+
+    // like curl, treat a proxy without a scheme as a http proxy
+    if proxy.contains("://") {
+        Url::parse(&proxy).ok()
+    } else {
+        Url::parse(&format!("http://{}", proxy)).ok()
+    }
+}
+
+fn bypasses(no_proxy: &str, host: &str) -> bool {
+    no_proxy
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            let domain = entry.trim_start_matches('.');
+            entry == "*"
+                || host.eq_ignore_ascii_case(domain)
+                || (host.len() > domain.len()
+                    && host
+                        .to_ascii_lowercase()
+                        .ends_with(&domain.to_ascii_lowercase())
+                    && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+        })
+}
+
+/// Opens a tcp connection to the proxy and asks it to tunnel to the host of `target`. The
+/// returned stream is connected to `target` once this resolves, so it can be used for both
+/// plain and tls secured websocket handshakes. The connection to the proxy uses the given
+/// tcp options.
+pub(crate) async fn tunnel(proxy: &Url, target: &Url, tcp: &TcpOptions) -> Result<TcpStream> {
+    validate(proxy)?;
+    let target_host = target
+        .host_str()
+        .ok_or_else(|| Error::InvalidUrlScheme(target.to_string()))?;
+    let target_port = target
+        .port_or_known_default()
+        .ok_or_else(|| Error::InvalidUrlScheme(target.scheme().to_owned()))?;
+
+    match proxy.scheme() {
+        "http" => http_tunnel(proxy, target_host, target_port, tcp).await,
+        _ => socks5_tunnel(proxy, target_host, target_port, tcp).await,
+    }
+}
+
+/// Returns the percent decoded user info of the proxy url, if any.
+fn credentials(proxy: &Url) -> Option<(String, String)> {
+    if proxy.username().is_empty() {
+        return None;
+    }
+    Some((
+        percent_decode_str(proxy.username())
+            .decode_utf8_lossy()
+            .into_owned(),
+        percent_decode_str(proxy.password().unwrap_or_default())
+            .decode_utf8_lossy()
+            .into_owned(),
+    ))
+}
+
+async fn http_tunnel(
+    proxy: &Url,
+    target_host: &str,
+    target_port: u16,
+    tcp: &TcpOptions,
+) -> Result<TcpStream> {
+    let proxy_host = proxy.host_str().expect("validated proxy has a host");
+    let proxy_port = proxy.port_or_known_default().unwrap_or(80);
+
+    let authority = format!("{}:{}", target_host, target_port);
+    let mut request = format!(
+        "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n",
+        authority = authority
+    );
+    if let Some((username, password)) = credentials(proxy) {
+        request.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            general_purpose::STANDARD.encode(format!("{}:{}", username, password))
+        ));
+    }
+    request.push_str("\r\n");
+
+    let mut stream = tcp.connect(proxy_host, proxy_port).await?;
+    stream.write_all(request.as_bytes()).await?;
+
+    // read byte wise, so that nothing past the response head is consumed from the tunnel
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(Error::IllegalProxyTunnel(
+                "response head too large".to_owned(),
+            ));
+        }
+        head.push(stream.read_u8().await?);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(stream),
+        _ => Err(Error::IllegalProxyTunnel(status_line.to_owned())),
+    }
+}
+
+async fn socks5_tunnel(
+    proxy: &Url,
+    target_host: &str,
+    target_port: u16,
+    tcp: &TcpOptions,
+) -> Result<TcpStream> {
+    let proxy_host = proxy.host_str().expect("validated proxy has a host");
+    let proxy_port = proxy.port().unwrap_or(1080);
+
+    let target = if proxy.scheme() == "socks5h" {
+        TargetAddr::Domain(target_host.into(), target_port)
+    } else {
+        let addr = lookup_host((
+            target_host.trim_matches(|c| c == '[' || c == ']'),
+            target_port,
+        ))
+        .await?
+        .next()
+        .ok_or_else(|| Error::IllegalProxyTunnel(format!("unknown host {}", target_host)))?;
+        TargetAddr::Ip(addr)
+    };
+
+    let socket = tcp.connect(proxy_host, proxy_port).await?;
+    let stream = match credentials(proxy) {
+        Some((username, password)) => {
+            Socks5Stream::connect_with_password_and_socket(socket, target, &username, &password)
+                .await
+        }
+        None => Socks5Stream::connect_with_socket(socket, target).await,
+    }
+    .map_err(|err| Error::IllegalProxyTunnel(err.to_string()))?;
+
+    Ok(stream.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use tokio::{io::AsyncBufReadExt, net::TcpListener};
+
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn proxy_from_vars() {
+        let plain = Url::parse("ws://example.com/engine.io/").unwrap();
+        let secure = Url::parse("https://example.com/engine.io/").unwrap();
+
+        let env = vars(&[
+            ("HTTP_PROXY", "http://plain:3128"),
+            ("HTTPS_PROXY", "secure:3129"),
+        ]);
+        assert_eq!(
+            from_vars(&plain, &env).map(String::from),
+            Some("http://plain:3128/".to_owned())
+        );
+        assert_eq!(
+            from_vars(&secure, &env).map(String::from),
+            Some("http://secure:3129/".to_owned())
+        );
+
+        let env = vars(&[("ALL_PROXY", "http://all:3128")]);
+        assert_eq!(
+            from_vars(&secure, &env).map(String::from),
+            Some("http://all:3128/".to_owned())
+        );
+        let unix = Url::parse("http+unix://%2Frun%2Fengine.sock/").unwrap();
+        assert_eq!(from_vars(&unix, &env), None);
+
+        assert_eq!(from_vars(&plain, vars(&[])), None);
+    }
+
+    #[test]
+    fn proxy_no_proxy() {
+        let env = |no_proxy| vars(&[("ALL_PROXY", "http://all:3128"), ("NO_PROXY", no_proxy)]);
+        let url = |host| Url::parse(&format!("http://{}/", host)).unwrap();
+
+        assert!(from_vars(&url("example.com"), env("example.com")).is_none());
+        assert!(from_vars(&url("api.example.com"), env("localhost, .example.com")).is_none());
+        assert!(from_vars(&url("example.com"), env("*")).is_none());
+        assert!(from_vars(&url("badexample.com"), env("example.com")).is_some());
+        assert!(from_vars(&url("example.org"), env("example.com")).is_some());
+    }
+
+    #[test]
+    fn proxy_validate() {
+        assert!(validate(&Url::parse("http://proxy:3128").unwrap()).is_ok());
+        assert!(validate(&Url::parse("socks5h://proxy:1080").unwrap()).is_ok());
+        assert!(matches!(
+            validate(&Url::parse("https://proxy:3128").unwrap()),
+            Err(Error::InvalidProxy(_))
+        ));
+    }
+
+    /// Accepts a single `CONNECT` request, answers it with `status_line` and returns the
+    /// request head.
+    async fn fake_proxy(status_line: &'static str) -> (Url, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "http://user:p%40ss@{}",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio::io::BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                stream.read_line(&mut head).await.unwrap();
+            }
+            stream
+                .get_mut()
+                .write_all(format!("{}\r\n\r\n", status_line).as_bytes())
+                .await
+                .unwrap();
+            head
+        });
+
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn proxy_tunnel() -> Result<()> {
+        let target = Url::parse("wss://example.com/engine.io/").unwrap();
+
+        let (proxy, handle) = fake_proxy("HTTP/1.1 200 Connection established").await;
+        tunnel(&proxy, &target, &TcpOptions::default()).await?;
+        let head = handle.await.unwrap();
+        assert!(head.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(head.contains(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            general_purpose::STANDARD.encode("user:p@ss")
+        )));
+
+        let (proxy, _) = fake_proxy("HTTP/1.1 407 Proxy Authentication Required").await;
+        assert!(matches!(
+            tunnel(&proxy, &target, &TcpOptions::default()).await,
+            Err(Error::IllegalProxyTunnel(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn proxy_socks5_tunnel() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = Url::parse(&format!("socks5h://{}", listener.local_addr().unwrap())).unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // greeting offering no authentication
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [5, 1, 0]);
+            stream.write_all(&[5, 0]).await.unwrap();
+
+            // connect request with a domain name target
+            let mut request = [0u8; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [5, 1, 0, 3]);
+            let mut target = vec![0u8; request[4] as usize + 2];
+            stream.read_exact(&mut target).await.unwrap();
+            stream
+                .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 80])
+                .await
+                .unwrap();
+            target
+        });
+
+        let target = Url::parse("ws://example.com/engine.io/").unwrap();
+        tunnel(&proxy, &target, &TcpOptions::default()).await?;
+        assert_eq!(handle.await.unwrap(), b"example.com\x00\x50");
+
+        Ok(())
+    }
+}
@@ -2,14 +2,16 @@ use crate::callback::OptionalCallback;
 use crate::transport::TransportType;
 
 use crate::error::{Error, Result};
-use crate::packet::{HandshakePacket, Packet, PacketId, Payload};
+use crate::packet::{HandshakePacket, Packet, PacketId, ProtocolVersion};
 use bytes::Bytes;
-use std::convert::TryFrom;
 use std::sync::RwLock;
 use std::time::Duration;
 use std::{fmt::Debug, sync::atomic::Ordering};
 use std::{
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc, Mutex,
+    },
     time::Instant,
 };
 
@@ -28,16 +30,23 @@ pub struct Socket {
     on_error: OptionalCallback<String>,
     on_open: OptionalCallback<()>,
     on_packet: OptionalCallback<Packet>,
+    on_ping: OptionalCallback<()>,
+    on_pong: OptionalCallback<Duration>,
     connected: Arc<AtomicBool>,
     last_ping: Arc<Mutex<Instant>>,
+    /// When the client last sent a heartbeat: its pong, or its ping with revision 3.
     last_pong: Arc<Mutex<Instant>>,
+    /// The latest round trip estimate in microseconds.
+    latency: Arc<AtomicU64>,
     connection_data: Arc<HandshakePacket>,
     /// Since we get packets in payloads it's possible to have a state where only some of the packets have been consumed.
     remaining_packets: Arc<RwLock<Option<crate::packet::IntoIter>>>,
     max_ping_timeout: u64,
+    protocol_version: ProtocolVersion,
 }
 
 impl Socket {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         transport: TransportType,
         handshake: HandshakePacket,
@@ -46,6 +55,9 @@ impl Socket {
         on_error: OptionalCallback<String>,
         on_open: OptionalCallback<()>,
         on_packet: OptionalCallback<Packet>,
+        on_ping: OptionalCallback<()>,
+        on_pong: OptionalCallback<Duration>,
+        protocol_version: ProtocolVersion,
     ) -> Self {
         let max_ping_timeout = handshake.ping_interval + handshake.ping_timeout;
 
@@ -55,18 +67,23 @@ impl Socket {
             on_error,
             on_open,
             on_packet,
+            on_ping,
+            on_pong,
             transport: Arc::new(transport),
             connected: Arc::new(AtomicBool::default()),
             last_ping: Arc::new(Mutex::new(Instant::now())),
             last_pong: Arc::new(Mutex::new(Instant::now())),
+            latency: Arc::new(AtomicU64::default()),
             connection_data: Arc::new(handshake),
             remaining_packets: Arc::new(RwLock::new(None)),
             max_ping_timeout,
+            protocol_version,
         }
     }
 
     /// Opens the connection to a specified server. The first Pong packet is sent
-    /// to the server to trigger the Ping-cycle.
+    /// to the server to trigger the Ping-cycle. With revision 3 of the protocol the
+    /// client sends the pings instead, every `pingInterval`.
     pub fn connect(&self) -> Result<()> {
         // SAFETY: Has valid handshake due to type
         self.connected.store(true, Ordering::Release);
@@ -78,12 +95,40 @@ impl Socket {
         // set the last ping to now and set the connected state
         *self.last_ping.lock()? = Instant::now();
 
+        if self.protocol_version == ProtocolVersion::V3 {
+            let socket = self.clone();
+            std::thread::spawn(move || socket.send_pings());
+            return Ok(());
+        }
+
         // emit a pong packet to keep trigger the ping cycle on the server
-        self.emit(Packet::new(PacketId::Pong, Bytes::new()))?;
+        self.emit_heartbeat(PacketId::Pong)?;
 
         Ok(())
     }
 
+    /// Sends a ping every `pingInterval` until the socket is disconnected. The server
+    /// answers with a pong, which is tracked like the pings of newer servers.
+    fn send_pings(&self) {
+        let interval = Duration::from_millis(self.connection_data.ping_interval);
+        loop {
+            std::thread::sleep(interval);
+            if !self.connected.load(Ordering::Acquire)
+                || self.emit_heartbeat(PacketId::Ping).is_err()
+            {
+                break;
+            }
+            self.call_ping_callback();
+        }
+    }
+
+    /// Sends a heartbeat packet and remembers when it left, so the latency can be
+    /// estimated once the server's next heartbeat arrives.
+    pub(crate) fn emit_heartbeat(&self, packet_id: PacketId) -> Result<()> {
+        *self.last_pong.lock()? = Instant::now();
+        self.emit(Packet::new(packet_id, Bytes::new()))
+    }
+
     pub fn disconnect(&self) -> Result<()> {
         if let Some(on_close) = self.on_close.as_ref() {
             spawn_scoped!(on_close(()));
@@ -105,15 +150,9 @@ impl Socket {
             return Err(error);
         }
 
-        let is_binary = packet.packet_id == PacketId::MessageBinary;
-
-        // send a post request with the encoded payload as body
-        // if this is a binary attachment, then send the raw bytes
-        let data: Bytes = if is_binary {
-            packet.data
-        } else {
-            packet.into()
-        };
+        let (data, is_binary) = self
+            .protocol_version
+            .encode(packet, self.transport.is_polling());
 
         if let Err(error) = self.transport.as_transport().emit(data, is_binary) {
             self.call_error_callback(error.to_string());
@@ -148,7 +187,9 @@ impl Socket {
                     continue;
                 }
 
-                let payload = Payload::try_from(data)?;
+                let payload = self
+                    .protocol_version
+                    .decode(data, self.transport.is_polling())?;
                 let mut iter = payload.into_iter();
 
                 if let Some(packet) = iter.next() {
@@ -174,11 +215,48 @@ impl Socket {
         Ok(self.connected.load(Ordering::Acquire))
     }
 
+    pub(crate) fn handshake(&self) -> &HandshakePacket {
+        &self.connection_data
+    }
+
+    pub(crate) fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Records a heartbeat from the server and updates the latency estimate. With
+    /// revision 3 the server's pong answers our ping, so the elapsed time is the round
+    /// trip. Newer servers send their next ping `pingInterval` after receiving our pong,
+    /// so the round trip is whatever exceeds the interval.
     pub(crate) fn pinged(&self) -> Result<()> {
-        *self.last_ping.lock()? = Instant::now();
+        let now = Instant::now();
+        *self.last_ping.lock()? = now;
+
+        let mut round_trip = now.saturating_duration_since(*self.last_pong.lock()?);
+        if self.protocol_version != ProtocolVersion::V3 {
+            round_trip = round_trip
+                .saturating_sub(Duration::from_millis(self.connection_data.ping_interval));
+        }
+        self.latency
+            .store(round_trip.as_micros() as u64, Ordering::Release);
         Ok(())
     }
 
+    pub(crate) fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency.load(Ordering::Acquire))
+    }
+
+    pub(crate) fn call_ping_callback(&self) {
+        if let Some(on_ping) = self.on_ping.as_ref() {
+            spawn_scoped!(on_ping(()));
+        }
+    }
+
+    pub(crate) fn call_pong_callback(&self) {
+        if let Some(on_pong) = self.on_pong.as_ref() {
+            spawn_scoped!(on_pong(self.latency()));
+        }
+    }
+
     /// Returns the time in milliseconds that is left until a new ping must be received.
     /// This is used to detect whether we have been disconnected from the server.
     /// See https://socket.io/docs/v4/how-it-works/#disconnection-detection
@@ -221,16 +299,19 @@ impl Socket {
 impl Debug for Socket {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.write_fmt(format_args!(
-            "EngineSocket(transport: {:?}, on_error: {:?}, on_open: {:?}, on_close: {:?}, on_packet: {:?}, on_data: {:?}, connected: {:?}, last_ping: {:?}, last_pong: {:?}, connection_data: {:?})",
+            "EngineSocket(transport: {:?}, on_error: {:?}, on_open: {:?}, on_close: {:?}, on_packet: {:?}, on_data: {:?}, on_ping: {:?}, on_pong: {:?}, connected: {:?}, last_ping: {:?}, last_pong: {:?}, latency: {:?}, connection_data: {:?})",
             self.transport,
             self.on_error,
             self.on_open,
             self.on_close,
             self.on_packet,
             self.on_data,
+            self.on_ping,
+            self.on_pong,
             self.connected,
             self.last_ping,
             self.last_pong,
+            self.latency,
             self.connection_data,
         ))
     }
@@ -0,0 +1,202 @@
+use std::{net::IpAddr, time::Duration};
+
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{lookup_host, TcpSocket, TcpStream};
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// Options for the tcp connections the transports open to the server or to a proxy. Unix
+/// domain sockets and webtransport, which runs over udp, don't use them.
+///
+/// # Example
+/// ```rust
+/// use std::time::Duration;
+/// use rust_engineio::{ClientBuilder, TcpOptions};
+/// use url::Url;
+///
+/// let url = Url::parse("http://localhost:4201").unwrap();
+/// let builder = ClientBuilder::new(url).tcp_options(
+///     TcpOptions::new()
+///         .nodelay(true)
+///         .keepalive(Duration::from_secs(30))
+///         .keepalive_interval(Duration::from_secs(5)),
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TcpOptions {
+    nodelay: Option<bool>,
+    keepalive: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    local_address: Option<IpAddr>,
+}
+
+impl TcpOptions {
+    /// Options that keep the defaults of the transports.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `TCP_NODELAY`, so that small packets are sent right away instead of being
+    /// buffered. If not set, the polling transports enable it and the websocket transports
+    /// don't.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    /// Enables `SO_KEEPALIVE`, probing the connection once it was idle for the given time.
+    pub fn keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Sets the time between two keepalive probes, if keepalive is enabled. Only the
+    /// websocket transports apply it, the polling transports keep the default of the system.
+    pub fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Binds the connections to the given local address, e.g. to pick the interface of a
+    /// host with several ones.
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    #[cfg(feature = "http-reqwest")]
+    pub(crate) fn configure(self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        let builder = match self.nodelay {
+            Some(nodelay) => builder.tcp_nodelay(nodelay),
+            None => builder,
+        };
+        builder
+            .tcp_keepalive(self.keepalive)
+            .local_address(self.local_address)
+    }
+
+    #[cfg(feature = "http-reqwest")]
+    pub(crate) fn configure_blocking(
+        self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> reqwest::blocking::ClientBuilder {
+        let builder = match self.nodelay {
+            Some(nodelay) => builder.tcp_nodelay(nodelay),
+            None => builder,
+        };
+        builder
+            .tcp_keepalive(self.keepalive)
+            .local_address(self.local_address)
+    }
+
+    /// Opens a tcp connection to the host and port of the url.
+    pub(crate) async fn connect_url(&self, url: &Url) -> Result<TcpStream> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| Error::InvalidUrlScheme(url.to_string()))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| Error::InvalidUrlScheme(url.scheme().to_owned()))?;
+        self.connect(host, port).await
+    }
+
+    /// Opens a tcp connection to the host, trying each of its addresses until one accepts.
+    pub(crate) async fn connect(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let host = host.trim_matches(|c| c == '[' || c == ']');
+        let mut last_error = None;
+        for addr in lookup_host((host, port)).await? {
+            // skip the addresses that can't be reached from the local address
+            if matches!(self.local_address, Some(local) if local.is_ipv4() != addr.is_ipv4()) {
+                continue;
+            }
+            let socket = if addr.is_ipv4() {
+                TcpSocket::new_v4()?
+            } else {
+                TcpSocket::new_v6()?
+            };
+            if let Some(local) = self.local_address {
+                socket.bind((local, 0).into())?;
+            }
+            match socket.connect(addr).await {
+                Ok(stream) => return self.apply(stream),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(match last_error {
+            Some(err) => err.into(),
+            None => Error::IncompleteIo(std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                format!("no usable address for {}", host),
+            )),
+        })
+    }
+
+    fn apply(&self, stream: TcpStream) -> Result<TcpStream> {
+        if let Some(nodelay) = self.nodelay {
+            stream.set_nodelay(nodelay)?;
+        }
+        if let Some(idle) = self.keepalive {
+            let keepalive = TcpKeepalive::new().with_time(idle);
+            #[cfg(any(
+                target_os = "android",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "fuchsia",
+                target_os = "illumos",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "windows",
+            ))]
+            let keepalive = match self.keepalive_interval {
+                Some(interval) => keepalive.with_interval(interval),
+                None => keepalive,
+            };
+            SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+        }
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn applies_the_socket_options() -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let port = listener.local_addr()?.port();
+        let options = TcpOptions::new()
+            .nodelay(true)
+            .keepalive(Duration::from_secs(30))
+            .keepalive_interval(Duration::from_secs(5))
+            .local_address(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let stream = options.connect("localhost", port).await?;
+        let (accepted, peer) = listener.accept().await?;
+        assert_eq!(peer, stream.local_addr()?);
+        assert_eq!(peer.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+        drop(accepted);
+
+        assert!(stream.nodelay()?);
+        let socket = SockRef::from(&stream);
+        assert!(socket.keepalive()?);
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(socket.keepalive_time()?, Duration::from_secs(30));
+            assert_eq!(socket.keepalive_interval()?, Duration::from_secs(5));
+        }
+
+        let defaults = TcpOptions::new().connect("127.0.0.1", port).await?;
+        assert!(!SockRef::from(&defaults).keepalive()?);
+        Ok(())
+    }
+}
@@ -5,6 +5,10 @@ use bytes::Bytes;
 use std::time::{Duration, SystemTime};
 use url::Url;
 
+/// A transport carries engine.io payloads between the client and the server. Besides the
+/// built-in polling and websocket transports, custom transports (e.g. in-memory or over a
+/// serial link) can implement this trait and be passed to
+/// [`ClientBuilder::build_with_transport`](crate::ClientBuilder::build_with_transport).
 pub trait Transport {
     /// Sends a packet to the server. This optionally handles sending of a
     /// socketio binary attachment via the boolean attribute `is_binary_att`.
@@ -22,6 +26,12 @@ pub trait Transport {
     /// Used to update the base path, like when adding the sid.
     fn set_base_url(&self, base_url: Url) -> Result<()>;
 
+    /// Upgrades a connection whose handshake was performed by another transport to this
+    /// one, e.g. by sending the websocket probe. Does nothing by default.
+    fn upgrade(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Full query address
     fn address(&self) -> Result<Url> {
         let reader = format!("{:#?}", SystemTime::now());
@@ -37,6 +47,7 @@ pub enum TransportType {
     Polling(PollingTransport),
     WebsocketSecure(WebsocketSecureTransport),
     Websocket(WebsocketTransport),
+    Custom(Box<dyn Transport + Send + Sync>),
 }
 
 impl From<PollingTransport> for TransportType {
@@ -63,8 +74,15 @@ impl TransportType {
             TransportType::Polling(transport) => transport,
             TransportType::Websocket(transport) => transport,
             TransportType::WebsocketSecure(transport) => transport,
+            TransportType::Custom(transport) => transport.as_ref(),
         }
     }
+
+    /// Whether the transport sends payloads of several packets. Custom transports are
+    /// expected to send single packets like websockets.
+    pub(crate) fn is_polling(&self) -> bool {
+        matches!(self, TransportType::Polling(_))
+    }
 }
 
 impl std::fmt::Debug for dyn Transport {
@@ -72,3 +90,9 @@ impl std::fmt::Debug for dyn Transport {
         f.write_fmt(format_args!("Transport(base_url: {:?})", self.base_url(),))
     }
 }
+
+impl std::fmt::Debug for dyn Transport + Send + Sync {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_fmt(format_args!("Transport(base_url: {:?})", self.base_url(),))
+    }
+}
@@ -1,20 +1,44 @@
+use crate::compression::HttpCompression;
 use crate::error::{Error, Result};
+#[cfg(not(feature = "http-reqwest"))]
+use crate::hyper_client::HyperClient;
+use crate::tcp::TcpOptions;
+use crate::tls::TlsConnector;
 use crate::transport::Transport;
+#[cfg(unix)]
+use crate::unix::{self, UnixClient};
 use base64::{engine::general_purpose, Engine as _};
 use bytes::{BufMut, Bytes, BytesMut};
-use native_tls::TlsConnector;
+use http::HeaderMap;
+#[cfg(feature = "http-reqwest")]
 use reqwest::{
     blocking::{Client, ClientBuilder},
-    header::HeaderMap,
+    header::CONTENT_ENCODING,
 };
+use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
+#[cfg(any(unix, not(feature = "http-reqwest")))]
+use tokio::runtime::Runtime;
 use url::Url;
 
-#[derive(Debug, Clone)]
+/// The http client that sends the requests of a polling transport. The hyper and unix
+/// domain socket clients are asynchronous and therefore driven by their own runtime.
+#[derive(Debug)]
+enum HttpClient {
+    #[cfg(feature = "http-reqwest")]
+    Reqwest(Client),
+    #[cfg(not(feature = "http-reqwest"))]
+    Hyper(Runtime, HyperClient),
+    #[cfg(unix)]
+    Unix(Runtime, UnixClient),
+}
+
+#[derive(Clone)]
 pub struct PollingTransport {
-    client: Arc<Client>,
+    client: Arc<HttpClient>,
     base_url: Arc<RwLock<Url>>,
+    compression: HttpCompression,
 }
 
 impl PollingTransport {
@@ -24,26 +48,107 @@ impl PollingTransport {
         tls_config: Option<TlsConnector>,
         opening_headers: Option<HeaderMap>,
     ) -> Self {
-        let client = match (tls_config, opening_headers) {
-            (Some(config), Some(map)) => ClientBuilder::new()
-                .use_preconfigured_tls(config)
+        #[cfg(unix)]
+        if unix::socket_path(&base_url).is_some() {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let client = UnixClient::new(opening_headers);
+            return Self::with_client(base_url, HttpClient::Unix(runtime, client));
+        }
+
+        #[cfg(feature = "http-reqwest")]
+        let client = HttpClient::Reqwest(match (tls_config, opening_headers) {
+            (Some(config), Some(map)) => config
+                .configure_blocking(ClientBuilder::new())
                 .default_headers(map)
                 .build()
                 .unwrap(),
-            (Some(config), None) => ClientBuilder::new()
-                .use_preconfigured_tls(config)
+            (Some(config), None) => config
+                .configure_blocking(ClientBuilder::new())
                 .build()
                 .unwrap(),
             (None, Some(map)) => ClientBuilder::new().default_headers(map).build().unwrap(),
             (None, None) => Client::new(),
+        });
+        #[cfg(not(feature = "http-reqwest"))]
+        let client = HttpClient::Hyper(
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap(),
+            HyperClient::new(
+                &base_url,
+                tls_config,
+                opening_headers,
+                None,
+                TcpOptions::default(),
+            ),
+        );
+
+        Self::with_client(base_url, client)
+    }
+
+    /// Creates an instance of `PollingTransport` that sends all requests via the given
+    /// proxy and compresses them as configured, over tcp connections with the given options.
+    /// Without a proxy, the proxies configured in the environment are used. Unix domain
+    /// sockets are always reached directly and uncompressed.
+    pub(crate) fn with_proxy(
+        base_url: Url,
+        tls_config: Option<TlsConnector>,
+        opening_headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        compression: HttpCompression,
+        tcp: TcpOptions,
+    ) -> Result<Self> {
+        #[cfg(unix)]
+        if unix::socket_path(&base_url).is_some() {
+            return Ok(Self::new(base_url, tls_config, opening_headers));
+        }
+
+        #[cfg(feature = "http-reqwest")]
+        let client = {
+            let mut builder =
+                tcp.configure_blocking(compression.configure_blocking(ClientBuilder::new()));
+            if let Some(proxy) = proxy {
+                builder = builder.proxy(crate::proxy::reqwest_proxy(&proxy)?);
+            }
+            if let Some(config) = tls_config {
+                builder = config.configure_blocking(builder);
+            }
+            if let Some(map) = opening_headers {
+                builder = builder.default_headers(map);
+            }
+            HttpClient::Reqwest(builder.build()?)
+        };
+        #[cfg(not(feature = "http-reqwest"))]
+        let client = {
+            if let Some(proxy) = &proxy {
+                crate::proxy::validate(proxy)?;
+            }
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            HttpClient::Hyper(
+                runtime,
+                HyperClient::new(&base_url, tls_config, opening_headers, proxy, tcp),
+            )
         };
 
+        let mut transport = Self::with_client(base_url, client);
+        transport.compression = compression;
+        Ok(transport)
+    }
+
+    fn with_client(base_url: Url, client: HttpClient) -> Self {
         let mut url = base_url;
         url.query_pairs_mut().append_pair("transport", "polling");
 
         PollingTransport {
             client: Arc::new(client),
             base_url: Arc::new(RwLock::new(url)),
+            compression: HttpCompression::default(),
         }
     }
 }
@@ -62,13 +167,31 @@ impl Transport for PollingTransport {
         } else {
             data
         };
-        let status = self
-            .client
-            .post(self.address()?)
-            .body(data_to_send)
-            .send()?
-            .status()
-            .as_u16();
+        let address = self.address()?;
+        let status = match self.client.as_ref() {
+            #[cfg(feature = "http-reqwest")]
+            HttpClient::Reqwest(client) => {
+                let (body, encoding) = self.compression.compress(data_to_send)?;
+                let mut request = client.post(address).body(body);
+                if let Some(encoding) = encoding {
+                    request = request.header(CONTENT_ENCODING, encoding);
+                }
+                request.send()?.status().as_u16()
+            }
+            #[cfg(not(feature = "http-reqwest"))]
+            HttpClient::Hyper(runtime, client) => {
+                let (body, encoding) = self.compression.compress(data_to_send)?;
+                runtime
+                    .block_on(client.post(&address, body, encoding))?
+                    .status()
+                    .as_u16()
+            }
+            #[cfg(unix)]
+            HttpClient::Unix(runtime, client) => runtime
+                .block_on(client.post(&address, data_to_send))?
+                .status()
+                .as_u16(),
+        };
 
         if status != 200 {
             let error = Error::IncompleteHttp(status);
@@ -79,12 +202,27 @@ impl Transport for PollingTransport {
     }
 
     fn poll(&self, timeout: Duration) -> Result<Bytes> {
-        Ok(self
-            .client
-            .get(self.address()?)
-            .timeout(timeout)
-            .send()?
-            .bytes()?)
+        let address = self.address()?;
+        match self.client.as_ref() {
+            #[cfg(feature = "http-reqwest")]
+            HttpClient::Reqwest(client) => {
+                Ok(client.get(address).timeout(timeout).send()?.bytes()?)
+            }
+            #[cfg(not(feature = "http-reqwest"))]
+            HttpClient::Hyper(runtime, client) => runtime.block_on(async {
+                match tokio::time::timeout(timeout, client.get(&address)).await {
+                    Ok(response) => Ok(response?.into_body()),
+                    Err(_) => Err(Error::PingTimeout()),
+                }
+            }),
+            #[cfg(unix)]
+            HttpClient::Unix(runtime, client) => runtime.block_on(async {
+                match tokio::time::timeout(timeout, client.get(&address)).await {
+                    Ok(response) => Ok(response?.into_body()),
+                    Err(_) => Err(Error::PingTimeout()),
+                }
+            }),
+        }
     }
 
     fn base_url(&self) -> Result<Url> {
@@ -104,6 +242,15 @@ impl Transport for PollingTransport {
     }
 }
 
+impl Debug for PollingTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollingTransport")
+            .field("client", &self.client)
+            .field("base_url", &self.base_url)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -3,13 +3,15 @@ use crate::{
         async_transports::WebsocketTransport as AsyncWebsocketTransport, transport::AsyncTransport,
     },
     error::Result,
+    tcp::TcpOptions,
     transport::Transport,
     Error,
 };
 use bytes::Bytes;
 use http::HeaderMap;
-use std::{sync::Arc, time::Duration};
+use std::{net::TcpStream, sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
+use tungstenite::protocol::WebSocketConfig;
 use url::Url;
 
 #[derive(Clone)]
@@ -21,11 +23,26 @@ pub struct WebsocketTransport {
 impl WebsocketTransport {
     /// Creates an instance of `WebsocketTransport`.
     pub fn new(base_url: Url, headers: Option<HeaderMap>) -> Result<Self> {
+        Self::with_proxy(base_url, headers, None, None, TcpOptions::default())
+    }
+
+    /// Creates an instance of `WebsocketTransport` that connects through the given proxy,
+    /// with the given limits and buffer sizes of the websocket and options of the tcp
+    /// connection.
+    pub(crate) fn with_proxy(
+        base_url: Url,
+        headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        config: Option<WebSocketConfig>,
+        tcp: TcpOptions,
+    ) -> Result<Self> {
         let runtime = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()?;
 
-        let inner = runtime.block_on(AsyncWebsocketTransport::new(base_url, headers))?;
+        let inner = runtime.block_on(AsyncWebsocketTransport::with_proxy(
+            base_url, headers, proxy, config, tcp,
+        ))?;
 
         Ok(WebsocketTransport {
             runtime: Arc::new(runtime),
@@ -33,10 +50,33 @@ impl WebsocketTransport {
         })
     }
 
-    /// Sends probe packet to ensure connection is valid, then sends upgrade
-    /// request
-    pub(crate) fn upgrade(&self) -> Result<()> {
-        self.runtime.block_on(async { self.inner.upgrade().await })
+    /// Creates an instance of `WebsocketTransport` that performs the websocket handshake over
+    /// an already connected tcp stream.
+    pub(crate) fn with_stream(
+        base_url: Url,
+        headers: Option<HeaderMap>,
+        stream: TcpStream,
+        config: Option<WebSocketConfig>,
+    ) -> Result<Self> {
+        if matches!(base_url.scheme(), "https" | "wss") {
+            return Err(Error::InvalidUrlScheme(base_url.scheme().to_owned()));
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let inner = runtime.block_on(async {
+            // the stream has to be registered with the runtime that drives it
+            stream.set_nonblocking(true)?;
+            let stream = tokio::net::TcpStream::from_std(stream)?;
+            AsyncWebsocketTransport::with_stream(base_url, headers, stream, config).await
+        })?;
+
+        Ok(WebsocketTransport {
+            runtime: Arc::new(runtime),
+            inner: Arc::new(inner),
+        })
     }
 }
 
@@ -67,6 +107,12 @@ impl Transport for WebsocketTransport {
         self.runtime
             .block_on(async { self.inner.set_base_url(url).await })
     }
+
+    /// Sends probe packet to ensure connection is valid, then sends upgrade
+    /// request
+    fn upgrade(&self) -> Result<()> {
+        self.runtime.block_on(async { self.inner.upgrade().await })
+    }
 }
 
 impl std::fmt::Debug for WebsocketTransport {
@@ -103,16 +149,14 @@ mod test {
             .append_pair("transport", "websocket");
         url.set_scheme("ws").unwrap();
         assert_eq!(transport.base_url()?.to_string(), url.to_string());
-        transport.set_base_url(reqwest::Url::parse("https://127.0.0.1")?)?;
+        transport.set_base_url(Url::parse("https://127.0.0.1")?)?;
         assert_eq!(
             transport.base_url()?.to_string(),
             "ws://127.0.0.1/?transport=websocket"
         );
         assert_ne!(transport.base_url()?.to_string(), url.to_string());
 
-        transport.set_base_url(reqwest::Url::parse(
-            "http://127.0.0.1/?transport=websocket",
-        )?)?;
+        transport.set_base_url(Url::parse("http://127.0.0.1/?transport=websocket")?)?;
         assert_eq!(
             transport.base_url()?.to_string(),
             "ws://127.0.0.1/?transport=websocket"
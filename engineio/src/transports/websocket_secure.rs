@@ -1,17 +1,19 @@
+use crate::tls::TlsConnector;
 use crate::{
     asynchronous::{
         async_transports::WebsocketSecureTransport as AsyncWebsocketSecureTransport,
         transport::AsyncTransport,
     },
     error::Result,
+    tcp::TcpOptions,
     transport::Transport,
     Error,
 };
 use bytes::Bytes;
 use http::HeaderMap;
-use native_tls::TlsConnector;
 use std::{sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
+use tungstenite::protocol::WebSocketConfig;
 use url::Url;
 
 #[derive(Clone)]
@@ -26,13 +28,34 @@ impl WebsocketSecureTransport {
         base_url: Url,
         tls_config: Option<TlsConnector>,
         headers: Option<HeaderMap>,
+    ) -> Result<Self> {
+        Self::with_proxy(
+            base_url,
+            tls_config,
+            headers,
+            None,
+            None,
+            TcpOptions::default(),
+        )
+    }
+
+    /// Creates an instance of `WebsocketSecureTransport` that connects through the given
+    /// proxy, with the given limits and buffer sizes of the websocket and options of the tcp
+    /// connection.
+    pub(crate) fn with_proxy(
+        base_url: Url,
+        tls_config: Option<TlsConnector>,
+        headers: Option<HeaderMap>,
+        proxy: Option<Url>,
+        config: Option<WebSocketConfig>,
+        tcp: TcpOptions,
     ) -> Result<Self> {
         let runtime = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()?;
 
         let inner = runtime.block_on(AsyncWebsocketSecureTransport::new(
-            base_url, tls_config, headers,
+            base_url, tls_config, headers, proxy, config, tcp,
         ))?;
 
         Ok(WebsocketSecureTransport {
@@ -40,12 +63,6 @@ impl WebsocketSecureTransport {
             inner: Arc::new(inner),
         })
     }
-
-    /// Sends probe packet to ensure connection is valid, then sends upgrade
-    /// request
-    pub(crate) fn upgrade(&self) -> Result<()> {
-        self.runtime.block_on(async { self.inner.upgrade().await })
-    }
 }
 
 impl Transport for WebsocketSecureTransport {
@@ -75,6 +92,12 @@ impl Transport for WebsocketSecureTransport {
         self.runtime
             .block_on(async { self.inner.set_base_url(url).await })
     }
+
+    /// Sends probe packet to ensure connection is valid, then sends upgrade
+    /// request
+    fn upgrade(&self) -> Result<()> {
+        self.runtime.block_on(async { self.inner.upgrade().await })
+    }
 }
 
 impl std::fmt::Debug for WebsocketSecureTransport {
@@ -112,16 +135,14 @@ mod test {
             .append_pair("transport", "websocket");
         url.set_scheme("wss").unwrap();
         assert_eq!(transport.base_url()?.to_string(), url.to_string());
-        transport.set_base_url(reqwest::Url::parse("https://127.0.0.1")?)?;
+        transport.set_base_url(Url::parse("https://127.0.0.1")?)?;
         assert_eq!(
             transport.base_url()?.to_string(),
             "wss://127.0.0.1/?transport=websocket"
         );
         assert_ne!(transport.base_url()?.to_string(), url.to_string());
 
-        transport.set_base_url(reqwest::Url::parse(
-            "http://127.0.0.1/?transport=websocket",
-        )?)?;
+        transport.set_base_url(Url::parse("http://127.0.0.1/?transport=websocket")?)?;
         assert_eq!(
             transport.base_url()?.to_string(),
             "wss://127.0.0.1/?transport=websocket"
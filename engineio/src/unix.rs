@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use bytes::Bytes;
+use http::{header::HOST, HeaderMap, HeaderValue, Method, Request, Response};
+use http_body_util::Full;
+use percent_encoding::percent_decode_str;
+use tokio::net::UnixStream;
+use url::Url;
+
+use crate::{
+    error::{Error, Result},
+    hyper_client,
+};
+
+/// Returns the path of the unix domain socket a `http+unix` or `ws+unix` url points to. The
+/// path is given as the percent encoded host, e.g. `http+unix://%2Frun%2Fapp.sock/`.
+pub(crate) fn socket_path(url: &Url) -> Option<PathBuf> {
+    if !matches!(url.scheme(), "http+unix" | "ws+unix") {
+        return None;
+    }
+    let host = url.host_str()?;
+    let path = percent_decode_str(host).decode_utf8().ok()?;
+    Some(PathBuf::from(path.into_owned()))
+}
+
+/// Connects to the unix domain socket the url points to.
+pub(crate) async fn connect(url: &Url) -> Result<UnixStream> {
+    let path = socket_path(url).ok_or_else(|| Error::InvalidUrlScheme(url.scheme().to_owned()))?;
+    Ok(UnixStream::connect(path).await?)
+}
+
+/// Rewrites the url to one on `localhost` with the given scheme, as neither http nor
+/// websocket requests accept the socket path as host.
+pub(crate) fn local_url(url: &Url, scheme: &str) -> Url {
+    let mut local = Url::parse(&format!("{}://localhost", scheme)).unwrap();
+    local.set_path(url.path());
+    local.set_query(url.query());
+    local
+}
+
+/// A minimal http client for the polling transports, opening a new connection to the unix
+/// domain socket for every request.
+#[derive(Clone, Debug)]
+pub(crate) struct UnixClient {
+    headers: HeaderMap,
+}
+
+impl UnixClient {
+    pub(crate) fn new(headers: Option<HeaderMap>) -> Self {
+        UnixClient {
+            headers: headers.unwrap_or_default(),
+        }
+    }
+
+    pub(crate) async fn get(&self, url: &Url) -> Result<Response<Bytes>> {
+        self.send(Method::GET, url, Bytes::new()).await
+    }
+
+    pub(crate) async fn post(&self, url: &Url, body: Bytes) -> Result<Response<Bytes>> {
+        self.send(Method::POST, url, body).await
+    }
+
+    async fn send(&self, method: Method, url: &Url, body: Bytes) -> Result<Response<Bytes>> {
+        let stream = connect(url).await?;
+        let mut request = Request::builder()
+            .method(method)
+            .uri(hyper_client::origin_form(url))
+            .body(Full::new(body))?;
+        request.headers_mut().extend(self.headers.clone());
+        request
+            .headers_mut()
+            .insert(HOST, HeaderValue::from_static("localhost"));
+
+        hyper_client::send(stream, request).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    #[test]
+    fn unix_urls() {
+        let url = Url::parse("http+unix://%2Ftmp%2Fengine.sock/engine.io/?EIO=4").unwrap();
+        assert_eq!(socket_path(&url), Some(PathBuf::from("/tmp/engine.sock")));
+        assert_eq!(
+            local_url(&url, "ws").as_str(),
+            "ws://localhost/engine.io/?EIO=4"
+        );
+        assert_eq!(
+            socket_path(&Url::parse("ws+unix://%2Ftmp%2Fengine.sock").unwrap()),
+            Some(PathBuf::from("/tmp/engine.sock"))
+        );
+        assert_eq!(socket_path(&Url::parse("http://localhost/").unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn unix_client() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("engineio-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 1024];
+            let len = stream.read(&mut request).await.unwrap();
+            request.truncate(len);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-test", HeaderValue::from_static("1"));
+        let url = Url::parse(&format!(
+            "http+unix://{}/engine.io/?EIO=4",
+            percent_encoding::utf8_percent_encode(
+                path.to_str().unwrap(),
+                percent_encoding::NON_ALPHANUMERIC
+            )
+        ))?;
+        let response = UnixClient::new(Some(headers)).get(&url).await?;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), &Bytes::from_static(b"hello"));
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("GET /engine.io/?EIO=4 HTTP/1.1\r\n"));
+        assert!(request.contains("host: localhost\r\n"));
+        assert!(request.contains("x-test: 1\r\n"));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
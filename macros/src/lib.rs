@@ -0,0 +1,274 @@
+//! Procedural macros of `rust_socketio`, re-exported by it with the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Error, FnArg, ImplItem, ImplItemFn, ItemImpl, LitStr,
+    Result, Type,
+};
+
+/// Routes events to the methods of a type. Every method of the `impl` block marked
+/// with `#[on("event")]` handles that event, and a `register` method is added that
+/// registers all of them with a client builder, on a shared instance of the type.
+///
+/// The parameters of a handler, after `&self`, receive the arguments of the event in
+/// order, deserialized with `serde`. A parameter of type `Payload` receives the whole
+/// payload instead, and one of type `Client` (or `RawClient`) the client. Events
+/// whose arguments don't deserialize are skipped. Handlers are either all `async`,
+/// for the async client, or none of them, for the blocking one.
+///
+/// See `rust_socketio::handlers` for an example.
+#[proc_macro_attribute]
+pub fn handlers(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(Span::call_site(), "`handlers` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let item = parse_macro_input!(item as ItemImpl);
+    expand(item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// A method marked with `#[on(...)]`.
+struct Handler {
+    event: LitStr,
+    method: Ident,
+    is_async: bool,
+    params: Vec<Param>,
+}
+
+/// What a parameter of a handler receives.
+enum Param {
+    Arg(Box<Type>),
+    Payload,
+    Client,
+}
+
+fn expand(mut item: ItemImpl) -> Result<TokenStream2> {
+    let mut handlers = Vec::new();
+    for impl_item in &mut item.items {
+        if let ImplItem::Fn(method) = impl_item {
+            if let Some(handler) = take_handler(method)? {
+                handlers.push(handler);
+            }
+        }
+    }
+
+    let Some(first) = handlers.first() else {
+        return Err(Error::new(
+            item.self_ty.span(),
+            "no method is marked with `#[on(\"event\")]`",
+        ));
+    };
+    let is_async = first.is_async;
+    if let Some(handler) = handlers.iter().find(|handler| handler.is_async != is_async) {
+        return Err(Error::new(
+            handler.method.span(),
+            "handlers are either all `async` or none of them",
+        ));
+    }
+
+    let (builder, client) = if is_async {
+        (
+            quote!(::rust_socketio::asynchronous::ClientBuilder),
+            quote!(::rust_socketio::asynchronous::Client),
+        )
+    } else {
+        (
+            quote!(::rust_socketio::ClientBuilder),
+            quote!(::rust_socketio::RawClient),
+        )
+    };
+    let registrations = handlers.iter().map(|handler| register(handler, &client));
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    let predicates = where_clause.map(|where_clause| &where_clause.predicates);
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty
+        where
+            Self: ::std::marker::Send + ::std::marker::Sync + 'static,
+            #predicates
+        {
+            /// Registers the handlers of the events with the builder.
+            pub fn register(self, builder: #builder) -> #builder {
+                let handlers = ::std::sync::Arc::new(self);
+                builder #(#registrations)*
+            }
+        }
+    })
+}
+
+/// Removes the `#[on(...)]` attribute of the method, returning it as a handler.
+fn take_handler(method: &mut ImplItemFn) -> Result<Option<Handler>> {
+    let Some(index) = method
+        .attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("on"))
+    else {
+        return Ok(None);
+    };
+    let event: LitStr = method.attrs.remove(index).parse_args()?;
+
+    let signature = &method.sig;
+    let mut inputs = signature.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => {
+            return Err(Error::new(
+                signature.span(),
+                "a handler takes `&self`, the instance is shared by all events",
+            ))
+        }
+    }
+
+    let params = inputs
+        .map(|input| match input {
+            FnArg::Typed(input) => match last_segment(&input.ty).as_deref() {
+                Some("Payload") => Param::Payload,
+                Some("Client" | "RawClient") => Param::Client,
+                _ => Param::Arg(input.ty.clone()),
+            },
+            FnArg::Receiver(_) => unreachable!("only the first input is a receiver"),
+        })
+        .collect();
+
+    Ok(Some(Handler {
+        event,
+        method: signature.ident.clone(),
+        is_async: signature.asyncness.is_some(),
+        params,
+    }))
+}
+
+/// The name of the type, without its path or generics.
+fn last_segment(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// The `.on(...)` call registering the handler.
+fn register(handler: &Handler, client: &TokenStream2) -> TokenStream2 {
+    let Handler {
+        event,
+        method,
+        is_async,
+        params,
+    } = handler;
+
+    let mut index = 0usize;
+    let mut bindings = Vec::new();
+    let mut args = Vec::new();
+    for (position, param) in params.iter().enumerate() {
+        match param {
+            Param::Arg(ty) => {
+                let name = format_ident!("__arg{}", position);
+                bindings.push(quote! {
+                    let #name: #ty = match __payload.arg(#index) {
+                        ::std::result::Result::Ok(arg) => arg,
+                        ::std::result::Result::Err(err) => {
+                            ::rust_socketio::__private::skip_handler(#event, &err);
+                            return;
+                        }
+                    };
+                });
+                args.push(quote!(#name));
+                index += 1;
+            }
+            Param::Payload => args.push(quote!(::std::clone::Clone::clone(&__payload))),
+            Param::Client => args.push(quote!(::std::clone::Clone::clone(&__client))),
+        }
+    }
+
+    if *is_async {
+        quote! {
+            .on(#event, {
+                let handlers = ::std::sync::Arc::clone(&handlers);
+                move |__payload: ::rust_socketio::Payload, __client: #client|
+                    -> ::std::pin::Pin<::std::boxed::Box<
+                        dyn ::std::future::Future<Output = ()> + ::std::marker::Send + 'static,
+                    >> {
+                    let handlers = ::std::sync::Arc::clone(&handlers);
+                    ::std::boxed::Box::pin(async move {
+                        #(#bindings)*
+                        handlers.#method(#(#args),*).await;
+                    })
+                }
+            })
+        }
+    } else {
+        quote! {
+            .on(#event, {
+                let handlers = ::std::sync::Arc::clone(&handlers);
+                move |__payload: ::rust_socketio::Payload, __client: #client| {
+                    #(#bindings)*
+                    handlers.#method(#(#args),*);
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn strips_the_attributes() {
+        let expanded = expand(parse_quote! {
+            impl Chat {
+                #[on("message")]
+                async fn message(&self, text: String, client: Client) {}
+
+                fn helper(&self) {}
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(!expanded.contains("# [on"));
+        assert!(expanded.contains("fn helper"));
+        assert!(expanded.contains(":: rust_socketio :: asynchronous :: ClientBuilder"));
+        assert!(expanded.contains(". on (\"message\""));
+    }
+
+    #[test]
+    fn rejects_invalid_handlers() {
+        let error = |item: ItemImpl| expand(item).unwrap_err().to_string();
+        assert!(error(parse_quote! {
+            impl Chat {
+                fn helper(&self) {}
+            }
+        })
+        .contains("no method"));
+        assert!(error(parse_quote! {
+            impl Chat {
+                #[on("message")]
+                fn message(text: String) {}
+            }
+        })
+        .contains("`&self`"));
+        assert!(error(parse_quote! {
+            impl Chat {
+                #[on("message")]
+                async fn message(&self) {}
+                #[on("typing")]
+                fn typing(&self) {}
+            }
+        })
+        .contains("all `async`"));
+    }
+}
@@ -1,7 +1,8 @@
 use std::time::Duration;
 
 use crate::asynchronous::client::callback::Callback;
-use tokio::time::Instant;
+use crate::asynchronous::runtime::Instant;
+use crate::Event;
 
 use super::callback::DynAsyncCallback;
 
@@ -12,6 +13,7 @@ use super::callback::DynAsyncCallback;
 #[derive(Debug)]
 pub(crate) struct Ack {
     pub id: i32,
+    pub event: Event,
     pub timeout: Duration,
     pub time_started: Instant,
     pub callback: Callback<DynAsyncCallback>,
@@ -1,12 +1,25 @@
-use futures_util::future::BoxFuture;
+use futures_util::{future::BoxFuture, FutureExt};
+use rust_engineio::asynchronous::TransportUpgrade;
 use std::{
+    collections::HashMap,
     fmt::Debug,
-    ops::{Deref, DerefMut},
+    ops::{ControlFlow, Deref, DerefMut},
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
 };
+use tokio::sync::Semaphore;
+use url::Url;
 
-use crate::{Event, Payload};
+use crate::{
+    asynchronous::runtime::{self, Instant},
+    error::Result,
+    stats, ClientError, DisconnectReason, Error, Event, HealthReport, Payload, RawEvent, Severity,
+};
 
 use super::client::{Client, ReconnectSettings};
+use crate::asynchronous::encryption::Encryption;
+#[cfg(feature = "json-schema")]
+use crate::asynchronous::schema::Schemas;
 
 /// Internal type, provides a way to store futures and return them in a boxed manner.
 pub(crate) type DynAsyncCallback =
@@ -16,9 +29,44 @@ pub(crate) type DynAsyncAnyCallback = Box<
     dyn for<'a> FnMut(Event, Payload, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync,
 >;
 
+pub(crate) type DynAsyncRawCallback =
+    Box<dyn for<'a> FnMut(RawEvent, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync>;
+
+pub(crate) type DynAsyncDisconnectCallback = Box<
+    dyn for<'a> FnMut(DisconnectReason, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync,
+>;
+
+pub(crate) type DynAsyncConnectErrorCallback =
+    Box<dyn for<'a> FnMut(Error, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync>;
+
+pub(crate) type DynAsyncAckTimeoutCallback =
+    Box<dyn for<'a> FnMut(Event, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync>;
+
+pub(crate) type DynAsyncPingTimeoutCallback =
+    Box<dyn for<'a> FnMut(Client) -> BoxFuture<'static, ()> + 'static + Send + Sync>;
+
+pub(crate) type DynAsyncErrorExtCallback =
+    Box<dyn for<'a> FnMut(ClientError, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync>;
+
+pub(crate) type DynAsyncHealthCallback =
+    Box<dyn for<'a> FnMut(HealthReport, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync>;
+
+pub(crate) type DynAsyncTransportUpgradeCallback = Box<
+    dyn for<'a> FnMut(TransportUpgrade, Client) -> BoxFuture<'static, ()> + 'static + Send + Sync,
+>;
+
 pub(crate) type DynAsyncReconnectSettingsCallback =
     Box<dyn for<'a> FnMut() -> BoxFuture<'static, ReconnectSettings> + 'static + Send + Sync>;
 
+/// Runs before the callbacks of an event, see
+/// [`crate::asynchronous::ClientBuilder::use_middleware`].
+pub(crate) type Middleware =
+    Box<dyn Fn(&mut Event, &mut Payload) -> ControlFlow<()> + 'static + Send + Sync>;
+
+/// Called without locking the builder, so it is a `Fn` rather than a `FnMut`.
+pub(crate) type DynAsyncEndpointResolver =
+    Box<dyn Fn() -> BoxFuture<'static, Vec<Url>> + 'static + Send + Sync>;
+
 pub(crate) struct Callback<T> {
     inner: T,
 }
@@ -81,6 +129,223 @@ impl Callback<DynAsyncAnyCallback> {
     }
 }
 
+impl Deref for Callback<DynAsyncRawCallback> {
+    type Target =
+        dyn for<'a> FnMut(RawEvent, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncRawCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncRawCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(RawEvent, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncDisconnectCallback> {
+    type Target = dyn for<'a> FnMut(DisconnectReason, Client) -> BoxFuture<'static, ()>
+        + 'static
+        + Sync
+        + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncDisconnectCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncDisconnectCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(DisconnectReason, Client) -> BoxFuture<'static, ()>
+            + 'static
+            + Sync
+            + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncConnectErrorCallback> {
+    type Target =
+        dyn for<'a> FnMut(Error, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncConnectErrorCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncConnectErrorCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(Error, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncAckTimeoutCallback> {
+    type Target =
+        dyn for<'a> FnMut(Event, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncAckTimeoutCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncAckTimeoutCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(Event, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncPingTimeoutCallback> {
+    type Target = dyn for<'a> FnMut(Client) -> BoxFuture<'static, ()> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncPingTimeoutCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncPingTimeoutCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(Client) -> BoxFuture<'static, ()> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncErrorExtCallback> {
+    type Target =
+        dyn for<'a> FnMut(ClientError, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncErrorExtCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncErrorExtCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(ClientError, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncHealthCallback> {
+    type Target =
+        dyn for<'a> FnMut(HealthReport, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncHealthCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncHealthCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(HealthReport, Client) -> BoxFuture<'static, ()> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+impl Deref for Callback<DynAsyncTransportUpgradeCallback> {
+    type Target = dyn for<'a> FnMut(TransportUpgrade, Client) -> BoxFuture<'static, ()>
+        + 'static
+        + Sync
+        + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<DynAsyncTransportUpgradeCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<DynAsyncTransportUpgradeCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: for<'a> FnMut(TransportUpgrade, Client) -> BoxFuture<'static, ()>
+            + 'static
+            + Sync
+            + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
 impl Deref for Callback<DynAsyncReconnectSettingsCallback> {
     type Target =
         dyn for<'a> FnMut() -> BoxFuture<'static, ReconnectSettings> + 'static + Sync + Send;
@@ -106,3 +371,497 @@ impl Callback<DynAsyncReconnectSettingsCallback> {
         }
     }
 }
+
+impl Deref for Callback<DynAsyncEndpointResolver> {
+    type Target = dyn Fn() -> BoxFuture<'static, Vec<Url>> + 'static + Sync + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl Callback<DynAsyncEndpointResolver> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: Fn() -> BoxFuture<'static, Vec<Url>> + 'static + Sync + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+/// The event callbacks of a client, taken from its builder once the client is created.
+/// They are looked up without locking; only the callback that is called gets locked,
+/// and only for as long as it takes to create its future.
+pub(crate) struct EventCallbacks {
+    on: HashMap<Event, Mutex<Callback<DynAsyncCallback>>>,
+    on_any: Option<Mutex<Callback<DynAsyncAnyCallback>>>,
+    // called like `on_any`, deserializing the events themselves
+    on_typed: Vec<Mutex<Callback<DynAsyncAnyCallback>>>,
+    // called with the text of the events, before it is parsed for the other callbacks
+    on_raw: Vec<Mutex<Callback<DynAsyncRawCallback>>>,
+    middlewares: Vec<Middleware>,
+    encryption: Option<Arc<Encryption>>,
+    #[cfg(feature = "json-schema")]
+    schemas: Schemas,
+    dispatcher: Dispatcher,
+}
+
+impl EventCallbacks {
+    pub(crate) fn new(
+        on: HashMap<Event, Callback<DynAsyncCallback>>,
+        on_any: Option<Callback<DynAsyncAnyCallback>>,
+        on_typed: Vec<Callback<DynAsyncAnyCallback>>,
+        on_raw: Vec<Callback<DynAsyncRawCallback>>,
+        middlewares: Vec<Middleware>,
+        encryption: Option<Arc<Encryption>>,
+        concurrency: HandlerConcurrency,
+    ) -> Self {
+        EventCallbacks {
+            on: on
+                .into_iter()
+                .map(|(event, callback)| (event, Mutex::new(callback)))
+                .collect(),
+            on_any: on_any.map(Mutex::new),
+            on_typed: on_typed.into_iter().map(Mutex::new).collect(),
+            on_raw: on_raw.into_iter().map(Mutex::new).collect(),
+            middlewares,
+            encryption,
+            #[cfg(feature = "json-schema")]
+            schemas: Schemas::default(),
+            dispatcher: Dispatcher::new(concurrency),
+        }
+    }
+
+    /// Checks the payloads of incoming events against these schemas.
+    #[cfg(feature = "json-schema")]
+    pub(crate) fn with_schemas(mut self, schemas: Schemas) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    /// Whether there are `on_raw` callbacks.
+    pub(crate) fn has_raw(&self) -> bool {
+        !self.on_raw.is_empty()
+    }
+
+    /// Whether any callback besides the `on_raw` ones takes the event, so its payload
+    /// has to be parsed.
+    pub(crate) fn takes_payload(&self, event: &Event) -> bool {
+        self.on.contains_key(event) || self.on_any.is_some() || !self.on_typed.is_empty()
+    }
+
+    /// Calls the `on_raw` callbacks with a message or custom event.
+    pub(crate) async fn call_raw(&self, raw: RawEvent, client: &Client) -> Result<()> {
+        let event = raw.event().clone();
+        if !matches!(event, Event::Message | Event::Custom(_)) {
+            return Ok(());
+        }
+        for callback in &self.on_raw {
+            let future = callback.lock()?(raw.clone(), client.clone());
+            self.dispatcher
+                .run(catch_panic(future, &event, client))
+                .await;
+        }
+        Ok(())
+    }
+
+    /// Decrypts messages and custom events and runs the middlewares on them, and calls
+    /// the callbacks of the events they let through. Fails without calling them if the
+    /// payload doesn't decrypt or doesn't match the schema of the event.
+    pub(crate) async fn call(
+        &self,
+        event: &Event,
+        mut payload: Payload,
+        client: &Client,
+    ) -> Result<()> {
+        if !matches!(event, Event::Message | Event::Custom(_)) {
+            return self.dispatch(event, payload, client).await;
+        }
+
+        if let Some(encryption) = &self.encryption {
+            payload = encryption.open(event, payload)?;
+        }
+        let mut changed = None;
+        if !self.middlewares.is_empty() {
+            let mut event = event.clone();
+            for middleware in &self.middlewares {
+                if middleware(&mut event, &mut payload).is_break() {
+                    return Ok(());
+                }
+            }
+            changed = Some(event);
+        }
+        let event = changed.as_ref().unwrap_or(event);
+        #[cfg(feature = "json-schema")]
+        self.schemas.check(event, &payload)?;
+        self.dispatch(event, payload, client).await
+    }
+
+    /// Calls the callback of the event, and the `on_any` and `on_typed` callbacks for
+    /// messages and custom events.
+    async fn dispatch(&self, event: &Event, payload: Payload, client: &Client) -> Result<()> {
+        let (on_any, on_typed) = match event {
+            Event::Message | Event::Custom(_) => (self.on_any.as_ref(), &self.on_typed[..]),
+            _ => (None, &[][..]),
+        };
+        let typed_payload = (!on_typed.is_empty()).then(|| payload.clone());
+
+        // the payload is only cloned if both callbacks take it, and each lock is released
+        // before the future of its callback is run
+        match (self.on.get(event), on_any) {
+            (Some(on), Some(on_any)) => {
+                let future = on.lock()?(payload.clone(), client.clone());
+                self.dispatcher
+                    .run(catch_panic(future, event, client))
+                    .await;
+                let future = on_any.lock()?(event.clone(), payload, client.clone());
+                self.dispatcher
+                    .run(catch_panic(future, event, client))
+                    .await;
+            }
+            (Some(on), None) => {
+                let future = on.lock()?(payload, client.clone());
+                self.dispatcher
+                    .run(catch_panic(future, event, client))
+                    .await;
+            }
+            (None, Some(on_any)) => {
+                let future = on_any.lock()?(event.clone(), payload, client.clone());
+                self.dispatcher
+                    .run(catch_panic(future, event, client))
+                    .await;
+            }
+            (None, None) => (),
+        }
+
+        if let Some(payload) = typed_payload {
+            for callback in on_typed {
+                let future = callback.lock()?(event.clone(), payload.clone(), client.clone());
+                self.dispatcher
+                    .run(catch_panic(future, event, client))
+                    .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Catches a panic in the future of an event callback, so it doesn't end the task reading
+/// from the connection, and reports it to the `on_error_ext` callback as
+/// [`Error::CallbackPanicked`].
+fn catch_panic(
+    future: BoxFuture<'static, ()>,
+    event: &Event,
+    client: &Client,
+) -> BoxFuture<'static, ()> {
+    let (event, client) = (event.clone(), client.clone());
+    async move {
+        if AssertUnwindSafe(future).catch_unwind().await.is_err() {
+            let err = Error::CallbackPanicked(event.to_string());
+            client.handle_error(Severity::Error, &err).await;
+        }
+    }
+    .boxed()
+}
+
+/// Decides how the futures of event callbacks are run, see
+/// [`crate::asynchronous::ClientBuilder::handler_concurrency`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum HandlerConcurrency {
+    /// Awaits each callback before the next event is handled, so events are handled in
+    /// the order they were received.
+    #[default]
+    Serial,
+    /// Spawns each callback on the runtime, so a slow callback doesn't hold up others.
+    Spawn,
+    /// Spawns each callback on the runtime, but runs at most the given number of them at
+    /// once. Further events are read once one of the running callbacks finished.
+    Limited(usize),
+}
+
+/// Runs the futures of callbacks as configured by a [`HandlerConcurrency`].
+struct Dispatcher {
+    concurrency: HandlerConcurrency,
+    /// Limits the running callbacks for [`HandlerConcurrency::Limited`].
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl Dispatcher {
+    fn new(concurrency: HandlerConcurrency) -> Self {
+        let permits = match concurrency {
+            HandlerConcurrency::Limited(limit) => Some(Arc::new(Semaphore::new(limit.max(1)))),
+            _ => None,
+        };
+        Dispatcher {
+            concurrency,
+            permits,
+        }
+    }
+
+    async fn run(&self, future: BoxFuture<'static, ()>) {
+        let future = async move {
+            let started = Instant::now();
+            future.await;
+            stats::record_handler_duration(started.elapsed());
+        };
+        if self.concurrency == HandlerConcurrency::Serial {
+            return future.await;
+        }
+
+        // the semaphore is never closed, so a permit is always granted eventually
+        let permit = match &self.permits {
+            Some(permits) => permits.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        runtime::spawn(async move {
+            future.await;
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{sync::mpsc, time::timeout};
+
+    use super::*;
+    use crate::asynchronous::{test_util::MockServer, ClientBuilder};
+
+    /// Runs callbacks that each take a while and returns how many ran at once at most.
+    async fn max_running(concurrency: HandlerConcurrency) -> usize {
+        let dispatcher = Dispatcher::new(concurrency);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..4 {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let finished = finished.clone();
+            let future = async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                runtime::sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                finished.fetch_add(1, Ordering::SeqCst);
+            };
+            dispatcher.run(future.boxed()).await;
+        }
+        while finished.load(Ordering::SeqCst) < 4 {
+            runtime::sleep(Duration::from_millis(10)).await;
+        }
+        max_running.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn handler_concurrency() {
+        assert_eq!(max_running(HandlerConcurrency::Serial).await, 1);
+        assert_eq!(max_running(HandlerConcurrency::Spawn).await, 4);
+        assert_eq!(max_running(HandlerConcurrency::Limited(2)).await, 2);
+        assert_eq!(max_running(HandlerConcurrency::Limited(0)).await, 1);
+    }
+
+    #[tokio::test]
+    async fn reports_panicking_callbacks() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let greeted = tx.clone();
+
+        let _client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on("boom", |_, _| async { panic!("boom") }.boxed())
+            .on("greet", move |_, _| {
+                let greeted = greeted.clone();
+                async move { greeted.send("greet".to_owned()).unwrap() }.boxed()
+            })
+            .on_error_ext(move |err, _| {
+                let tx = tx.clone();
+                async move {
+                    assert_eq!(err.severity, crate::Severity::Error);
+                    assert_eq!(err.kind, crate::ErrorKind::User);
+                    tx.send(err.message).unwrap()
+                }
+                .boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // the panic doesn't stop the client from reading the next event
+        server.emit("boom", json!(1))?;
+        server.emit("greet", json!(2))?;
+        for expected in ["A callback for boom panicked", "greet"] {
+            assert_eq!(
+                timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+                Some(expected.to_owned())
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dispatches_typed_events() -> Result<()> {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum Incoming {
+            ChatMessage { text: String },
+            Typing(String),
+            Moved(String, i32),
+        }
+
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on_typed(move |incoming: Incoming, _| {
+                let tx = tx.clone();
+                async move { tx.send(incoming).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        server.emit("chat_message", json!({"text": "hi"}))?;
+        // unknown events and events with other data are skipped
+        server.emit("unknown", json!(1))?;
+        server.emit("typing", json!(1))?;
+        server.emit("typing", json!("ferris"))?;
+        server.emit("moved", vec![json!("ferris"), json!(3)])?;
+
+        for expected in [
+            Incoming::ChatMessage {
+                text: "hi".to_owned(),
+            },
+            Incoming::Typing("ferris".to_owned()),
+            Incoming::Moved("ferris".to_owned(), 3),
+        ] {
+            assert_eq!(
+                timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+                Some(expected)
+            );
+        }
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn calls_raw_callbacks() -> Result<()> {
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        enum Incoming<'a> {
+            ChatMessage { text: &'a str },
+            Moved(&'a str, i32),
+        }
+
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (typed, mut typed_rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on_raw(move |event, _| {
+                let tx = tx.clone();
+                async move {
+                    let text = match event.deserialize::<Incoming>() {
+                        Ok(incoming) => format!("{incoming:?}"),
+                        Err(_) => event.as_str().to_owned(),
+                    };
+                    tx.send(text).unwrap()
+                }
+                .boxed()
+            })
+            .on("moved", move |payload, _| {
+                let typed = typed.clone();
+                async move { typed.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        server.emit("chat_message", json!({"text": "hi"}))?;
+        server.emit("moved", vec![json!("ferris"), json!(3)])?;
+        server.emit("unknown", json!(1))?;
+
+        for expected in [
+            r#"ChatMessage { text: "hi" }"#,
+            r#"Moved("ferris", 3)"#,
+            r#"["unknown",1]"#,
+        ] {
+            assert_eq!(
+                timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+                Some(expected.to_owned())
+            );
+        }
+        // the other callbacks still get the events they take
+        assert_eq!(
+            timeout(Duration::from_secs(1), typed_rx.recv())
+                .await
+                .unwrap(),
+            Some(Payload::from(vec![json!("ferris"), json!(3)]))
+        );
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn runs_middlewares_in_order() -> Result<()> {
+        use std::ops::ControlFlow;
+
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .use_middleware(|event, _| match event.as_str() {
+                "spam" => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            })
+            .use_middleware(|event, payload| {
+                if event.as_str() == "chat" {
+                    *event = Event::from("chat_message");
+                    *payload = Payload::from(json!("rewritten"));
+                }
+                ControlFlow::Continue(())
+            })
+            .on("chat_message", {
+                let tx = tx.clone();
+                move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send(payload).unwrap() }.boxed()
+                }
+            })
+            .on("spam", move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        server.emit("spam", json!(1))?;
+        server.emit("chat", json!("original"))?;
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(json!("rewritten")))
+        );
+        assert!(timeout(Duration::from_millis(100), rx.recv())
+            .await
+            .is_err());
+
+        client.disconnect().await?;
+        Ok(())
+    }
+}
@@ -1,37 +1,64 @@
-use std::{ops::DerefMut, pin::Pin, sync::Arc};
+use std::{
+    ops::DerefMut,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
-use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
-use futures_util::{future::BoxFuture, stream, Stream, StreamExt};
+use async_stream::stream;
+#[cfg(test)]
+use futures_util::stream;
+#[cfg(any(test, not(target_arch = "wasm32")))]
+use futures_util::StreamExt;
+use futures_util::{future::BoxFuture, FutureExt, Stream};
 use log::trace;
 use rand::{thread_rng, Rng};
 use serde_json::Value;
+#[cfg(test)]
+use std::pin::Pin;
 use tokio::{
-    sync::RwLock,
-    time::{sleep, Duration, Instant},
+    io::{AsyncRead, AsyncReadExt},
+    sync::{broadcast, oneshot, watch, RwLock},
 };
 
 use super::{
     ack::Ack,
-    builder::ClientBuilder,
-    callback::{Callback, DynAsyncCallback},
+    builder::{normalize_namespace, ClientBuilder},
+    callback::{
+        Callback, DynAsyncAckTimeoutCallback, DynAsyncCallback, DynAsyncDisconnectCallback,
+        DynAsyncErrorExtCallback, DynAsyncHealthCallback, DynAsyncPingTimeoutCallback,
+        EventCallbacks,
+    },
+    manager::Manager,
+    sink::ClientSink,
 };
+use crate::asynchronous::encryption::Encryption;
+#[cfg(feature = "outbox")]
+use crate::asynchronous::outbox::Outbox;
+#[cfg(feature = "json-schema")]
+use crate::asynchronous::schema::Schemas;
+use crate::asynchronous::Rpc;
 use crate::{
-    asynchronous::socket::Socket as InnerSocket,
-    error::{Error, Result},
+    asynchronous::{
+        runtime::{self, Instant, Runtime},
+        socket::Socket as InnerSocket,
+    },
+    error::{ClientError, Error, ErrorContext, Result, Severity},
+    json,
     packet::{Packet, PacketId},
-    Event, Payload,
+    stats::{self, Counters},
+    DisconnectReason, Event, EventStats, HealthCheck, HealthReport, Payload, ProtocolVersion,
+    RawEvent, Stats,
 };
 
-#[derive(Default)]
-enum DisconnectReason {
-    /// There is no known reason for the disconnect; likely a network error
-    #[default]
-    Unknown,
-    /// The user disconnected manually
-    Manual,
-    /// The server disconnected
-    Server,
-}
+// how far a stream of `Client::packet_stream` may fall behind
+const PACKET_STREAM_CAPACITY: usize = 1024;
+
+// the largest chunk of `Client::emit_binary_stream`, so acks and small events don't
+// wait long for a chunk to be written
+const MAX_STREAM_CHUNK: usize = 256 * 1024;
 
 /// Settings that can be updated before reconnecting to a server
 #[derive(Default)]
@@ -60,6 +87,65 @@ impl ReconnectSettings {
     }
 }
 
+/// Decides what happens once the last [`Client`] handed out for a namespace (by
+/// [`ClientBuilder::connect`] or [`Client::connect_namespace`]) and all its clones are
+/// dropped, see [`ClientBuilder::drop_behavior`]. The clients passed to callbacks don't
+/// count, as they are clones of the client the background task holds.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DropBehavior {
+    /// Keeps the connection and its background task running, so callbacks are still
+    /// called.
+    #[default]
+    Detach,
+    /// Disconnects the namespace like [`Client::disconnect`], from a spawned task.
+    Disconnect,
+    /// Leaves the namespace without telling the server and, if no other namespace uses
+    /// the connection, stops the background task and closes the connection right away,
+    /// dropping queued events.
+    Abort,
+}
+
+/// The state of the connection of a [`Client`] to its namespace, see [`Client::state`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConnectionState {
+    /// The `Connect` packet was sent and the server didn't answer yet.
+    Connecting,
+    /// The server accepted the namespace.
+    Connected,
+    /// The connection was lost and is being re-established, `attempt` counts the
+    /// attempts from 1.
+    Reconnecting { attempt: u32 },
+    /// The namespace is disconnected and won't be connected again. A namespace
+    /// refused by the server is disconnected with [`DisconnectReason::IoServerDisconnect`].
+    Disconnected { reason: DisconnectReason },
+}
+
+/// Shared by a client handed out to the user and its clones, applies the
+/// [`DropBehavior`] once the last of them is dropped.
+struct ClientHandle {
+    // a clone without a handle, so it doesn't keep itself alive
+    client: Client,
+    detached: AtomicBool,
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        if self.detached.load(Ordering::Acquire) {
+            return;
+        }
+
+        let client = self.client.clone();
+        let runtime = &self.client.runtime;
+        match client.drop_behavior {
+            DropBehavior::Detach => (),
+            DropBehavior::Disconnect => runtime.try_spawn(async move {
+                let _ = client.disconnect().await;
+            }),
+            DropBehavior::Abort => runtime.try_spawn(async move { client.abort().await }),
+        }
+    }
+}
+
 /// A socket which handles communication with the server. It's initialized with
 /// a specific address as well as an optional namespace to connect to. If `None`
 /// is given the client will connect to the default namespace `"/"`.
@@ -73,7 +159,44 @@ pub struct Client {
     // Data send in the opening packet (commonly used as for auth)
     auth: Option<serde_json::Value>,
     builder: Arc<RwLock<ClientBuilder>>,
-    disconnect_reason: Arc<RwLock<DisconnectReason>>,
+    // why the namespace got disconnected, `None` while it is connected
+    disconnect_reason: Arc<RwLock<Option<DisconnectReason>>>,
+    // kept apart from the builder, so a client can be disconnected from within
+    // one of its event callbacks
+    on_disconnect: Arc<RwLock<Option<Callback<DynAsyncDisconnectCallback>>>>,
+    // kept apart as well, so the callback can emit again
+    on_ack_timeout: Arc<RwLock<Option<Callback<DynAsyncAckTimeoutCallback>>>>,
+    ack_timeout: Duration,
+    on_ping_timeout: Arc<RwLock<Option<Callback<DynAsyncPingTimeoutCallback>>>>,
+    on_error_ext: Arc<RwLock<Option<Callback<DynAsyncErrorExtCallback>>>>,
+    // the thresholds of the watchdog, and its callback if it was configured
+    health_check: Option<HealthCheck>,
+    on_health: Arc<RwLock<Option<Callback<DynAsyncHealthCallback>>>>,
+    // taken from the builder as well, so events are dispatched without locking it
+    callbacks: Arc<EventCallbacks>,
+    // whether the namespace is connected, set optimistically when the
+    // `Connect` packet is sent and updated once the server answers
+    connected: Arc<AtomicBool>,
+    // session id of the namespace, received with the server's `Connect` packet
+    sid: Arc<Mutex<Option<String>>>,
+    // The manager that drives the shared engine.io connection, set as soon as
+    // the client is polled.
+    manager: Option<Manager>,
+    drop_behavior: DropBehavior,
+    // the runtime the client enters for its timers and background tasks
+    runtime: Runtime,
+    // only set for the clients handed out to the user and their clones
+    handle: Option<Arc<ClientHandle>>,
+    state: Arc<watch::Sender<ConnectionState>>,
+    // the packets of the namespace, for the streams of `packet_stream`
+    packets: broadcast::Sender<Packet>,
+    // shared with the sockets the client connects over
+    counters: Arc<Counters>,
+    // seals the arguments of the emitted events
+    encryption: Option<Arc<Encryption>>,
+    // keeps the events emitted while disconnected
+    #[cfg(feature = "outbox")]
+    outbox: Option<Arc<Outbox>>,
 }
 
 impl Client {
@@ -82,13 +205,69 @@ impl Client {
     /// `"/"` is taken.
     /// ```
     pub(crate) fn new(socket: InnerSocket, builder: ClientBuilder) -> Result<Self> {
+        Self::new_shared(Arc::new(RwLock::new(socket)), builder)
+    }
+
+    /// Creates a client for the namespace of the given builder on top of an
+    /// already existing (and possibly shared) socket.
+    pub(crate) fn new_shared(
+        socket: Arc<RwLock<InnerSocket>>,
+        mut builder: ClientBuilder,
+    ) -> Result<Self> {
+        let on_disconnect = builder.on_disconnect.take();
+        let on_ack_timeout = builder.on_ack_timeout.take();
+        let ack_timeout = builder.ack_timeout;
+        let on_ping_timeout = builder.on_ping_timeout.take();
+        let on_error_ext = builder.on_error_ext.take();
+        let (health_check, on_health) = builder.health_check.take().unzip();
+        #[cfg(feature = "outbox")]
+        let outbox = match &builder.outbox {
+            Some(path) => Some(Arc::new(Outbox::open(path)?)),
+            None => None,
+        };
+        let callbacks = EventCallbacks::new(
+            std::mem::take(&mut builder.on),
+            builder.on_any.take(),
+            std::mem::take(&mut builder.on_typed),
+            std::mem::take(&mut builder.on_raw),
+            std::mem::take(&mut builder.middlewares),
+            builder.encryption.clone(),
+            builder.handler_concurrency,
+        );
+        #[cfg(feature = "json-schema")]
+        let callbacks = callbacks.with_schemas(Schemas::compile(&builder.schemas)?);
+        let drop_behavior = builder.drop_behavior;
+        let runtime = builder.runtime.clone();
+        let counters = builder.counters.clone();
+        let encryption = builder.encryption.clone();
+
         Ok(Client {
-            socket: Arc::new(RwLock::new(socket)),
+            socket,
             nsp: builder.namespace.to_owned(),
             outstanding_acks: Arc::new(RwLock::new(Vec::new())),
             auth: builder.auth.clone(),
             builder: Arc::new(RwLock::new(builder)),
-            disconnect_reason: Arc::new(RwLock::new(DisconnectReason::default())),
+            disconnect_reason: Arc::new(RwLock::new(None)),
+            on_disconnect: Arc::new(RwLock::new(on_disconnect)),
+            on_ack_timeout: Arc::new(RwLock::new(on_ack_timeout)),
+            ack_timeout,
+            on_ping_timeout: Arc::new(RwLock::new(on_ping_timeout)),
+            on_error_ext: Arc::new(RwLock::new(on_error_ext)),
+            health_check,
+            on_health: Arc::new(RwLock::new(on_health)),
+            callbacks: Arc::new(callbacks),
+            connected: Arc::new(AtomicBool::default()),
+            sid: Arc::new(Mutex::new(None)),
+            manager: None,
+            drop_behavior,
+            runtime,
+            handle: None,
+            state: Arc::new(watch::channel(ConnectionState::Connecting).0),
+            packets: broadcast::channel(PACKET_STREAM_CAPACITY).0,
+            counters,
+            encryption,
+            #[cfg(feature = "outbox")]
+            outbox,
         })
     }
 
@@ -98,8 +277,36 @@ impl Client {
         // Connect the underlying socket
         self.socket.read().await.connect().await?;
 
+        self.notify_transport_upgrade().await;
+        self.send_connect_packet().await
+    }
+
+    /// Calls the `on_transport_upgrade` callback of this client (if any) if the
+    /// connection it uses was upgraded from the transport of the handshake.
+    pub(crate) async fn notify_transport_upgrade(&self) {
+        let Some(upgrade) = self.socket.read().await.transport_upgrade() else {
+            return;
+        };
+        let mut builder = self.builder.write().await;
+        if let Some(callback) = builder.on_transport_upgrade.as_mut() {
+            callback(upgrade, self.clone()).await;
+        }
+    }
+
+    /// Sends the socket.io `Connect` packet for the namespace of this client.
+    pub(crate) async fn send_connect_packet(&self) -> Result<()> {
+        let mut auth = self.auth.as_ref().map(|data| data.to_string());
+        if self.builder.read().await.protocol_version == ProtocolVersion::V2 {
+            // socket.io 2.x servers connect the default namespace on their own
+            if self.nsp == "/" {
+                self.connected.store(true, Ordering::Release);
+                self.set_state(ConnectionState::Connected);
+                return Ok(());
+            }
+            auth = None;
+        }
+
         // construct the opening packet
-        let auth = self.auth.as_ref().map(|data| data.to_string());
         let open_packet = Packet::new(
             PacketId::Connect,
             self.nsp.clone(),
@@ -110,113 +317,371 @@ impl Client {
             None,
         );
 
+        self.connected.store(true, Ordering::Release);
+        self.set_state(ConnectionState::Connecting);
         self.socket.read().await.send(open_packet).await?;
 
         Ok(())
     }
 
-    pub(crate) async fn wait_connect_incoming_sid(&self) -> Result<()> {
-        self.socket.read().await.wait_connect_incoming_sid().await?;
+    /// Calls the `on_reconnect` callback of this client (if any) and applies
+    /// the returned settings. Returns the address the connection should be
+    /// re-established with, if it was changed.
+    pub(crate) async fn apply_reconnect_settings(&mut self) -> Option<String> {
+        let mut builder = self.builder.write().await;
 
-        Ok(())
+        let config = builder.on_reconnect.as_mut()?;
+        let reconnect_settings = config().await;
+
+        if let Some(auth) = reconnect_settings.auth {
+            self.auth = Some(auth);
+        }
+
+        reconnect_settings.address
     }
 
-    pub(crate) async fn reconnect(&mut self) -> Result<()> {
-        let mut builder = self.builder.write().await;
+    /// Drives the stream using a thread so messages are processed
+    pub(crate) async fn poll_stream(&mut self) -> Result<()> {
+        let manager = Manager::from_client(self);
+        self.manager = Some(manager.clone());
 
-        if let Some(config) = builder.on_reconnect.as_mut() {
-            let reconnect_settings = config().await;
-            if let Some(address) = reconnect_settings.address {
-                builder.address = address;
-            }
+        manager.register(self.clone()).await;
+        manager.poll_stream();
 
-            if let Some(auth) = reconnect_settings.auth {
-                self.auth = Some(auth);
+        // a refused namespace isn't an error of connecting, it's reported to the
+        // `on_connect_error` callback instead
+        self.next_state(|state| state != ConnectionState::Connecting)
+            .await;
+        Ok(())
+    }
+
+    /// Waits until the server acknowledged the namespace with its `Connect` packet, so
+    /// events emitted afterwards are received by the namespace. [`ClientBuilder::connect`]
+    /// returns once the server answered, which may also be a refusal. Waits through
+    /// reconnects, a namespace that is refused or disconnected for good fails with
+    /// [`Error::IllegalNamespaceNotConnected`] and one that isn't connected within
+    /// `timeout` with [`Error::ConnectTimeout`].
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .namespace("/admin")
+    ///         .connect()
+    ///         .await;
+    ///
+    ///     if let Ok(socket) = socket {
+    ///         if socket.wait_until_connected(Duration::from_secs(5)).await.is_ok() {
+    ///             println!("Connected with sid {:?}", socket.sid());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub async fn wait_until_connected(&self, timeout: Duration) -> Result<()> {
+        let settled = self.next_state(|state| {
+            matches!(
+                state,
+                ConnectionState::Connected | ConnectionState::Disconnected { .. }
+            )
+        });
+        match self.runtime.timeout(timeout, settled).await {
+            Some(ConnectionState::Connected) => Ok(()),
+            Some(_) => Err(Error::IllegalNamespaceNotConnected(self.nsp.clone())),
+            None => Err(Error::ConnectTimeout()),
+        }
+    }
+
+    /// Waits for the first state, starting with the current one, that is `done`.
+    /// Returns the latest state if the client is dropped before.
+    async fn next_state(&self, done: impl Fn(ConnectionState) -> bool) -> ConnectionState {
+        let mut states = self.state.subscribe();
+        loop {
+            let state = *states.borrow_and_update();
+            if done(state) || states.changed().await.is_err() {
+                return state;
             }
         }
+    }
 
-        let socket = builder.inner_create().await?;
+    /// Returns the session id the server assigned to the namespace of this
+    /// client, or `None` if the namespace is not connected (yet). The id
+    /// changes whenever the client reconnects.
+    pub fn sid(&self) -> Option<String> {
+        self.sid.lock().ok()?.clone()
+    }
 
-        // New inner socket that can be connected
-        let mut client_socket = self.socket.write().await;
-        *client_socket = socket;
+    /// Returns a snapshot of the traffic of the connection this client uses, counted
+    /// over all its reconnects. Namespaces sharing a connection share their stats.
+    pub fn stats(&self) -> Stats {
+        self.counters.snapshot()
+    }
 
-        // Now that we have replaced `self.socket`, we drop the write lock
-        // because the `connect` method we call below will need to use it
-        drop(client_socket);
+    /// Returns the traffic of the connection this client uses per namespace and event
+    /// name, the events with the most bytes first. Like [`Client::stats`], it's
+    /// counted over all reconnects and shared by the namespaces of the connection.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect().await {
+    ///         for stats in socket.stats_by_event().iter().take(3) {
+    ///             println!("{} on {}: {} bytes", stats.event, stats.namespace, stats.bytes());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn stats_by_event(&self) -> Vec<EventStats> {
+        self.counters.events()
+    }
 
-        self.connect().await?;
+    /// Returns the number of acks emitted with [`Client::emit_with_ack`] that the server
+    /// didn't answer yet and that didn't time out.
+    pub async fn pending_acks(&self) -> usize {
+        self.outstanding_acks.read().await.len()
+    }
 
-        Ok(())
+    /// Returns how long the oldest ack the server didn't answer yet is waiting, or
+    /// `None` if there is no pending ack.
+    pub async fn oldest_pending_ack(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.outstanding_acks
+            .read()
+            .await
+            .iter()
+            .map(|ack| now.saturating_duration_since(ack.time_started))
+            .max()
     }
 
-    /// Drives the stream using a thread so messages are processed
-    pub(crate) async fn poll_stream(&mut self) -> Result<()> {
-        let builder = self.builder.read().await;
-        let reconnect_delay_min = builder.reconnect_delay_min;
-        let reconnect_delay_max = builder.reconnect_delay_max;
-        let max_reconnect_attempts = builder.max_reconnect_attempts;
-        let reconnect = builder.reconnect;
-        let reconnect_on_disconnect = builder.reconnect_on_disconnect;
-        drop(builder);
+    /// Returns the number of packets waiting in the queue of
+    /// [`ClientBuilder::outgoing_queue`], which is shared by the namespaces of the
+    /// connection. Without a queue it's always zero.
+    pub async fn outgoing_queue_len(&self) -> usize {
+        self.socket.read().await.queued()
+    }
 
-        let mut client_clone = self.clone();
+    /// Probes the liveness of the client like the watchdog of
+    /// [`ClientBuilder::health_check`] does, judged by its thresholds or, without a
+    /// watchdog, by the default ones of [`HealthCheck`].
+    pub async fn health(&self) -> HealthReport {
+        let check = self
+            .health_check
+            .unwrap_or_else(|| HealthCheck::new(Duration::ZERO));
+        let (since_heartbeat, heartbeat) = {
+            let socket = self.socket.read().await;
+            let heartbeat = (socket.ping_interval(), socket.heartbeat_timeout());
+            (socket.since_heartbeat().await, heartbeat)
+        };
+        let oldest_ack = self.oldest_pending_ack().await;
+        let queued = self.outgoing_queue_len().await;
 
-        tokio::runtime::Handle::current().spawn(async move {
+        check.report(self.state(), since_heartbeat, heartbeat, oldest_ack, queued)
+    }
+
+    /// Spawns the watchdog of [`ClientBuilder::health_check`], if there is one.
+    pub(crate) fn watch_health(&self) {
+        let Some(check) = self.health_check else {
+            return;
+        };
+        // without a handle, so the watchdog doesn't keep the client from being dropped
+        let client = Client {
+            handle: None,
+            ..self.clone()
+        };
+        self.runtime.spawn(async move {
             loop {
-                let mut stream = client_clone.as_stream().await;
-                // Consume the stream until it returns None and the stream is closed.
-                while let Some(item) = stream.next().await {
-                    if let Err(e) = item {
-                        trace!("Network error occurred: {}", e);
-                    }
+                runtime::sleep(check.interval()).await;
+                let report = client.health().await;
+                if let Some(callback) = client.on_health.write().await.as_mut() {
+                    callback(report, client.clone()).await;
                 }
-
-                // Drop the stream so we can once again use `socket_clone` as mutable
-                drop(stream);
-
-                let should_reconnect = match *(client_clone.disconnect_reason.read().await) {
-                    DisconnectReason::Unknown => reconnect,
-                    DisconnectReason::Manual => false,
-                    DisconnectReason::Server => reconnect_on_disconnect,
-                };
-
-                if should_reconnect {
-                    let mut reconnect_attempts = 0;
-                    let mut backoff = ExponentialBackoffBuilder::new()
-                        .with_initial_interval(Duration::from_millis(reconnect_delay_min))
-                        .with_max_interval(Duration::from_millis(reconnect_delay_max))
-                        .build();
-
-                    loop {
-                        if let Some(max_reconnect_attempts) = max_reconnect_attempts {
-                            reconnect_attempts += 1;
-                            if reconnect_attempts > max_reconnect_attempts {
-                                trace!("Max reconnect attempts reached without success");
-                                break;
-                            }
-                        }
-                        match client_clone.reconnect().await {
-                            Ok(_) => {
-                                trace!("Reconnected after {reconnect_attempts} attempts");
-                                break;
-                            }
-                            Err(e) => {
-                                trace!("Failed to reconnect: {e:?}");
-                                if let Some(delay) = backoff.next_backoff() {
-                                    let delay_ms = delay.as_millis();
-                                    trace!("Waiting for {delay_ms}ms before reconnecting");
-                                    sleep(delay).await;
-                                }
-                            }
-                        }
-                    }
-                } else {
+                if matches!(report.state, ConnectionState::Disconnected { .. }) {
                     break;
                 }
             }
         });
-        self.wait_connect_incoming_sid().await?;
+    }
+
+    /// Returns the current state of the connection to the namespace.
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Returns a stream of the states of the connection to the namespace, starting
+    /// with the current one. States that change faster than the stream is polled are
+    /// skipped, only the latest one is yielded. The stream ends once the client and
+    /// all its clones are dropped.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::{ClientBuilder, ConnectionState};
+    /// use futures_util::StreamExt;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .connect()
+    ///         .await;
+    ///
+    ///     if let Ok(socket) = socket {
+    ///         tokio::spawn(socket.state_stream().for_each(|state| async move {
+    ///             if let ConnectionState::Reconnecting { attempt } = state {
+    ///                 println!("Reconnecting, attempt {attempt}");
+    ///             }
+    ///         }));
+    ///     }
+    /// }
+    /// ```
+    pub fn state_stream(&self) -> impl Stream<Item = ConnectionState> + Send + 'static {
+        let mut states = self.state.subscribe();
+        stream! {
+            loop {
+                let state = *states.borrow_and_update();
+                yield state;
+                if states.changed().await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Returns a stream of the decoded packets the client receives for its namespace,
+    /// events, acks, connects and disconnects alike, for dispatching them without the
+    /// callbacks. The callbacks are still called for every packet. A stream that falls
+    /// more than 1024 packets behind skips the oldest ones, yielding an
+    /// [`Error::IncompletePacketStream`] with how many it skipped. The stream ends
+    /// once the client and all its clones are dropped.
+    /// # Example
+    /// ```
+    /// use futures_util::StreamExt;
+    /// use rust_socketio::{asynchronous::ClientBuilder, PacketId};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .connect()
+    ///         .await;
+    ///
+    ///     if let Ok(socket) = socket {
+    ///         let mut packets = socket.packet_stream();
+    ///         tokio::spawn(async move {
+    ///             while let Some(Ok(packet)) = packets.next().await {
+    ///                 if packet.packet_type == PacketId::Event {
+    ///                     println!("Event: {:?}", packet.data);
+    ///                 }
+    ///             }
+    ///         });
+    ///     }
+    /// }
+    /// ```
+    pub fn packet_stream(&self) -> impl Stream<Item = Result<Packet>> + Send + Unpin + 'static {
+        let mut packets = self.packets.subscribe();
+        Box::pin(stream! {
+            loop {
+                match packets.recv().await {
+                    Ok(packet) => yield Ok(packet),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        yield Err(Error::IncompletePacketStream(skipped))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    pub(crate) fn set_state(&self, state: ConnectionState) {
+        self.state.send_replace(state);
+    }
+
+    /// Returns the namespace this client is connected to.
+    pub fn nsp(&self) -> &str {
+        &self.nsp
+    }
+
+    pub(crate) fn socket(&self) -> Arc<RwLock<InnerSocket>> {
+        self.socket.clone()
+    }
+
+    pub(crate) fn builder(&self) -> Arc<RwLock<ClientBuilder>> {
+        self.builder.clone()
+    }
+
+    pub(crate) fn runtime(&self) -> Runtime {
+        self.runtime.clone()
+    }
+
+    pub(crate) fn set_manager(&mut self, manager: Manager) {
+        self.manager = Some(manager);
+    }
+
+    /// Returns this client with a handle, which applies the [`DropBehavior`] once it
+    /// and all its clones are dropped. Only called for the clients handed out to the
+    /// user, after the background task got its own clone.
+    pub(crate) fn with_handle(mut self) -> Self {
+        self.handle = Some(Arc::new(ClientHandle {
+            client: self.clone(),
+            detached: AtomicBool::default(),
+        }));
+        self
+    }
+
+    /// Whether the `on_reconnect` callback may change the address or auth data before
+    /// each reconnect.
+    pub(crate) async fn reconfigures_reconnect(&self) -> bool {
+        self.builder.read().await.on_reconnect.is_some()
+    }
+
+    pub(crate) async fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        *self.disconnect_reason.read().await
+    }
+
+    /// Marks the namespace as disconnected and calls the `on_disconnect`
+    /// callback. Only the first reason is recorded until the namespace is
+    /// connected again.
+    pub(crate) async fn handle_disconnect(&self, reason: DisconnectReason) -> Result<()> {
+        let mut disconnect_reason = self.disconnect_reason.write().await;
+        if disconnect_reason.is_some() {
+            return Ok(());
+        }
+        *disconnect_reason = Some(reason);
+        drop(disconnect_reason);
+
+        self.connected.store(false, Ordering::Release);
+        *self.sid.lock()? = None;
+        self.set_state(ConnectionState::Disconnected { reason });
+
+        if let Some(callback) = self.on_disconnect.write().await.as_mut() {
+            callback(reason, self.clone()).await;
+        }
+
+        Ok(())
+    }
+
+    /// Calls the `on_ping_timeout` callback of this client (if any), as the server's
+    /// ping is overdue.
+    pub(crate) async fn handle_ping_timeout(&self) {
+        if let Some(callback) = self.on_ping_timeout.write().await.as_mut() {
+            callback(self.clone()).await;
+        }
+    }
+
+    /// Calls the `on_error_ext` callback of this client (if any) with an error of the
+    /// task reading from the connection.
+    pub(crate) async fn handle_error(&self, severity: Severity, err: &Error) {
+        if let Some(callback) = self.on_error_ext.write().await.as_mut() {
+            callback(ClientError::new(severity, err), self.clone()).await;
+        }
+    }
+
+    /// Fails with [`Error::IllegalActionBeforeOpen`] if the namespace of this
+    /// client is not connected.
+    fn check_connected(&self) -> Result<()> {
+        if !self.connected.load(Ordering::Acquire) {
+            return Err(Error::IllegalActionBeforeOpen());
+        }
         Ok(())
     }
 
@@ -258,13 +723,291 @@ impl Client {
         E: Into<Event>,
         D: Into<Payload>,
     {
+        #[cfg(feature = "outbox")]
+        if let Some(outbox) = &self.outbox {
+            let packet =
+                Packet::new_from_payload(self.seal(data.into())?, event.into(), &self.nsp, None)?;
+            return self.emit_or_keep(outbox, packet).await;
+        }
+        self.emit_now(event, data).await
+    }
+
+    /// Sends several events, encoding all of them first and writing them to the
+    /// transport at once: websockets flush the connection only after the last frame and
+    /// polling sends a single request. This saves syscalls and requests when publishing
+    /// batches of events.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rust_socketio::{asynchronous::ClientBuilder, Payload};
+    /// use serde_json::json;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .connect()
+    ///         .await
+    ///         .expect("connection failed");
+    ///
+    ///     let ticks = (0..100).map(|tick| ("tick", Payload::from(json!({ "tick": tick }))));
+    ///     socket.emit_many(ticks).await.expect("Server unreachable");
+    /// }
+    /// ```
+    pub async fn emit_many<I, E, D>(&self, events: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (E, D)>,
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let packets = events
+            .into_iter()
+            .map(|(event, data)| {
+                Packet::new_from_payload(self.seal(data.into())?, event.into(), &self.nsp, None)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        #[cfg(feature = "outbox")]
+        if let Some(outbox) = &self.outbox {
+            for packet in packets {
+                self.emit_or_keep(outbox, packet).await?;
+            }
+            return Ok(());
+        }
+        self.check_connected()?;
+        self.socket.read().await.send_many(packets).await
+    }
+
+    /// Streams the bytes of the reader to the server in chunks, so large files don't
+    /// have to be read into memory at once and may exceed the `maxPayload` of the
+    /// server. Each chunk is emitted as the event with two arguments, a header
+    /// `{"id": "<stream>", "seq": <n>, "last": <bool>}` and the chunk as binary
+    /// attachment, numbered from 0 on. The last chunk, which may be empty, is marked
+    /// `last`. The server reassembles the chunks of a stream by its `id`.
+    ///
+    /// A chunk is half the `maxPayload` of the server, at most 256 KiB, and only read
+    /// once the previous one is written. Chunks aren't queued by
+    /// [`ClientBuilder::outgoing_queue`], so its policy never drops part of a stream.
+    /// With encryption hooks, only the chunks are sealed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .connect()
+    ///         .await
+    ///         .expect("connection failed");
+    ///
+    ///     // any reader, e.g. a `tokio::fs::File`
+    ///     let backup: &[u8] = &[0; 1024 * 1024];
+    ///     socket.emit_binary_stream("upload", backup).await.expect("Server unreachable");
+    /// }
+    /// ```
+    pub async fn emit_binary_stream<E, R>(&self, event: E, reader: R) -> Result<()>
+    where
+        E: Into<Event>,
+        R: AsyncRead + Unpin,
+    {
+        self.check_connected()?;
+        let event = Value::String(event.into().into());
+        let max_payload = self.socket.read().await.max_payload();
+        let chunk_size = max_payload
+            .and_then(|max_payload| usize::try_from(max_payload / 2).ok())
+            .unwrap_or(MAX_STREAM_CHUNK)
+            .clamp(1, MAX_STREAM_CHUNK);
+        let id = format!("{:016x}", thread_rng().gen::<u64>());
+
+        let mut reader = reader.take(0);
+        for seq in 0.. {
+            let mut chunk = Vec::with_capacity(chunk_size);
+            reader.set_limit(chunk_size as u64);
+            reader.read_to_end(&mut chunk).await?;
+            let last = chunk.len() < chunk_size;
+
+            let data = match self.seal(Payload::Binary(chunk.into()))? {
+                Payload::Binary(data) => data,
+                // sealing keeps binary payloads binary
+                _ => unreachable!(),
+            };
+            let header = serde_json::json!({ "id": id, "seq": seq, "last": last });
+            let packet = Packet::new(
+                PacketId::BinaryEvent,
+                self.nsp.clone(),
+                Some(format!("{event},{header}")),
+                None,
+                1,
+                Some(vec![data]),
+                None,
+            );
+            self.socket.read().await.send_unqueued(packet).await?;
+            if last {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the event right away, failing if the client is disconnected.
+    async fn emit_now<E, D>(&self, event: E, data: D) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        self.check_connected()?;
+        let data = self.seal(data.into())?;
         self.socket
             .read()
             .await
-            .emit(&self.nsp, event.into(), data.into())
+            .emit(&self.nsp, event.into(), data)
             .await
     }
 
+    /// Encrypts the arguments of an event if the client has encryption hooks.
+    fn seal(&self, data: Payload) -> Result<Payload> {
+        match &self.encryption {
+            Some(encryption) => encryption.seal(data),
+            None => Ok(data),
+        }
+    }
+
+    /// Sends the event unless the client is disconnected or events are still waiting
+    /// in the outbox, in which case it is kept after them.
+    #[cfg(feature = "outbox")]
+    async fn emit_or_keep(&self, outbox: &Outbox, packet: Packet) -> Result<()> {
+        if outbox.is_empty() && self.check_connected().is_ok() {
+            match self.socket.read().await.send(packet.clone()).await {
+                Err(err) if err.is_disconnected() => (),
+                result => return result,
+            }
+        }
+        outbox.push(packet)?;
+        if self.check_connected().is_ok() {
+            self.flush_outbox().await;
+        }
+        Ok(())
+    }
+
+    /// Sends the events waiting in the outbox, keeping the ones that failed to send
+    /// for the next connection.
+    #[cfg(feature = "outbox")]
+    async fn flush_outbox(&self) {
+        let Some(outbox) = &self.outbox else {
+            return;
+        };
+        let result = outbox
+            .flush(|packet| {
+                let socket = self.socket.clone();
+                async move { socket.read().await.send(packet).await }
+            })
+            .await;
+        if let Err(e) = result {
+            trace!("Kept the events of the outbox for later: {e}");
+        }
+    }
+
+    /// Sends a packet as it is, e.g. for extensions of the protocol or to test a
+    /// server, bypassing what the client keeps track of: acks sent this way aren't
+    /// awaited, and the namespace of the packet doesn't have to be the one of the
+    /// client. Fails with [`Error::InvalidPacket`] if the packet doesn't carry as many
+    /// attachments as it announces.
+    /// # Example
+    /// ```
+    /// use rust_socketio::{asynchronous::ClientBuilder, Packet, PacketId};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect().await {
+    ///         let packet = Packet::new(
+    ///             PacketId::Event,
+    ///             "/".to_owned(),
+    ///             Some(r#"["ping",{"at":1}]"#.to_owned()),
+    ///             Some(7),
+    ///             0,
+    ///             None,
+    ///             None,
+    ///         );
+    ///
+    ///         assert!(socket.send_packet(packet).await.is_ok());
+    ///     }
+    /// }
+    /// ```
+    pub async fn send_packet(&self, packet: Packet) -> Result<()> {
+        packet.check_attachments()?;
+        self.socket.read().await.send(packet).await
+    }
+
+    /// Sends a message like [`Client::emit`], but silently drops it if the
+    /// connection to the server is down instead of failing, like `socket.volatile`
+    /// of the JavaScript client. It's also dropped if the outgoing queue is full,
+    /// whatever its [`BackpressurePolicy`](crate::asynchronous::BackpressurePolicy).
+    /// Meant for frequent updates, e.g. telemetry, that are stale by the time the
+    /// connection is back.
+    /// # Example
+    /// ```
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    /// use serde_json::json;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect().await {
+    ///         let result = socket
+    ///             .emit_volatile("position", json!({"x": 1, "y": 2}))
+    ///             .await;
+    ///
+    ///         assert!(result.is_ok());
+    ///     }
+    /// }
+    /// ```
+    pub async fn emit_volatile<E, D>(&self, event: E, data: D) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let send = async {
+            self.check_connected()?;
+            let data = self.seal(data.into())?;
+            let packet = Packet::new_from_payload(data, event.into(), &self.nsp, None)?;
+            self.socket.read().await.send_volatile(packet).await
+        };
+        match send.await {
+            Err(err) if err.is_disconnected() => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Sends a message like [`Client::emit`], but gives up with [`Error::Timeout`]
+    /// if the packet couldn't be written to the transport within the given time
+    /// span, e.g. because the link is congested.
+    /// # Example
+    /// ```
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    /// use serde_json::json;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect().await {
+    ///         let result = socket
+    ///             .emit_with_timeout("foo", json!({"token": 123}), Duration::from_secs(1))
+    ///             .await;
+    ///
+    ///         assert!(result.is_ok());
+    ///     }
+    /// }
+    /// ```
+    pub async fn emit_with_timeout<E, D>(&self, event: E, data: D, timeout: Duration) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        self.runtime
+            .timeout(timeout, self.emit(event, data))
+            .await
+            .unwrap_or(Err(Error::Timeout()))
+    }
+
     /// When receive server's emitwithack callback event, invoke socket.ack(..) function can react to server with ack signal
     /// use futures_util::FutureExt;
     ///
@@ -314,11 +1057,16 @@ impl Client {
     where
         D: Into<Payload>,
     {
+        self.check_connected()?;
         self.socket.read().await.ack(&self.nsp, data.into()).await
     }
 
     /// Disconnects this client from the server by sending a `socket.io` closing
-    /// packet.
+    /// packet. Events queued by [`ClientBuilder::outgoing_queue`] are written before.
+    /// If no other namespace uses the connection, it's closed as well and this only
+    /// returns once the task reading from it stopped, so the program can exit right
+    /// away. Event callbacks still running on that task are dropped, unless they called
+    /// this method themselves.
     /// # Example
     /// ```rust
     /// use rust_socketio::{asynchronous::{ClientBuilder, Client}, Payload};
@@ -351,7 +1099,7 @@ impl Client {
     /// }
     /// ```
     pub async fn disconnect(&self) -> Result<()> {
-        *(self.disconnect_reason.write().await) = DisconnectReason::Manual;
+        self.check_connected()?;
 
         let disconnect_packet = Packet::new(
             PacketId::Disconnect,
@@ -363,22 +1111,241 @@ impl Client {
             None,
         );
 
-        self.socket.read().await.send(disconnect_packet).await?;
-        self.socket.read().await.disconnect().await?;
+        let socket = self.socket.read().await;
+        // events emitted before are written ahead of the `Disconnect` packet
+        socket.flush().await?;
+        socket.send(disconnect_packet).await?;
+        drop(socket);
+        self.handle_disconnect(DisconnectReason::IoClientDisconnect)
+            .await?;
+
+        match &self.manager {
+            // only close the engine.io connection once no other namespace uses it
+            Some(manager) => {
+                if manager.unregister(&self.nsp).await {
+                    manager.close().await?;
+                }
+            }
+            None => self.socket.read().await.disconnect().await?,
+        }
 
         Ok(())
     }
 
+    /// Keeps the connection and its background task running once this client and all
+    /// its clones are dropped, regardless of the [`DropBehavior`] it was built with.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::{ClientBuilder, DropBehavior};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .drop_behavior(DropBehavior::Disconnect)
+    ///         .connect()
+    ///         .await;
+    ///
+    ///     // the callbacks keep being called
+    ///     if let Ok(socket) = socket {
+    ///         socket.detach();
+    ///     }
+    /// }
+    /// ```
+    pub fn detach(self) {
+        if let Some(handle) = &self.handle {
+            handle.detached.store(true, Ordering::Release);
+        }
+    }
+
+    /// Stops reading packets from the connection until [`Client::resume`] is called,
+    /// e.g. while the application is busy. The packets are left with the transport
+    /// instead of being buffered, so a websocket server is slowed down by the flow
+    /// control of tcp. The connection is shared by all namespaces connected over it,
+    /// which are paused as well. As the pings of the server aren't answered while
+    /// paused, pausing longer than its ping interval and timeout makes the server close
+    /// the connection.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect().await {
+    ///         socket.pause();
+    ///         // busy with something else
+    ///         socket.resume();
+    ///     }
+    /// }
+    /// ```
+    pub fn pause(&self) {
+        if let Some(manager) = &self.manager {
+            manager.pause();
+        }
+    }
+
+    /// Goes on reading packets from the connection after [`Client::pause`].
+    pub fn resume(&self) {
+        if let Some(manager) = &self.manager {
+            manager.resume();
+        }
+    }
+
+    /// Returns a future that resolves once the client is closed for good, as the task
+    /// polling its connection stopped, or `None` for clients without that task.
+    pub(crate) fn closed(&self) -> Option<impl std::future::Future<Output = ()> + Send + 'static> {
+        self.manager.as_ref().map(Manager::stopped)
+    }
+
+    /// Sends the heartbeat of the connection that is due, for clients built with
+    /// [`crate::asynchronous::ClientBuilder::manual_heartbeat`]: the pong answering the
+    /// latest ping of the server. Returns whether a heartbeat was sent, which it isn't
+    /// if the ping was answered already. Namespaces sharing a connection share its
+    /// heartbeat.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/")
+    ///         .manual_heartbeat(true)
+    ///         .connect()
+    ///         .await
+    ///     {
+    ///         // woke up, e.g. to send a reading
+    ///         let _ = socket.send_heartbeat().await;
+    ///     }
+    /// }
+    /// ```
+    pub async fn send_heartbeat(&self) -> Result<bool> {
+        self.socket.read().await.send_heartbeat().await
+    }
+
+    /// Leaves the namespace without sending the `Disconnect` packet, closing the
+    /// connection right away if no other namespace uses it.
+    async fn abort(&self) {
+        let _ = self
+            .handle_disconnect(DisconnectReason::IoClientDisconnect)
+            .await;
+
+        match &self.manager {
+            Some(manager) => {
+                if manager.unregister(&self.nsp).await {
+                    manager.abort().await;
+                }
+            }
+            None => {
+                let _ = self.socket.read().await.abort().await;
+            }
+        }
+    }
+
+    /// Joins another namespace over the engine.io connection of this client,
+    /// without opening a new connection. The returned [`Client`] is bound to
+    /// `namespace` and shares the event handlers registered on this client.
+    /// `auth` is sent along with the `Connect` packet of the namespace.
+    ///
+    /// Call [`Client::disconnect`] on the returned client (or
+    /// [`Client::disconnect_namespace`] on this one) to leave the namespace
+    /// again. The connection stays open as long as any namespace is joined.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    /// use serde_json::json;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .connect()
+    ///         .await
+    ///         .expect("connection failed");
+    ///
+    ///     let admin = socket
+    ///         .connect_namespace("/admin", Some(json!({"token": 123})))
+    ///         .await
+    ///         .expect("connection failed");
+    ///
+    ///     admin.emit("foo", json!("bar")).await.expect("Server unreachable");
+    ///
+    ///     socket
+    ///         .disconnect_namespace("/admin")
+    ///         .await
+    ///         .expect("Disconnect failed");
+    /// }
+    /// ```
+    pub async fn connect_namespace<T: Into<String>>(
+        &self,
+        namespace: T,
+        auth: Option<serde_json::Value>,
+    ) -> Result<Client> {
+        let manager = self
+            .manager
+            .clone()
+            .ok_or_else(Error::IllegalActionBeforeOpen)?;
+
+        let client = Client {
+            socket: self.socket.clone(),
+            outstanding_acks: Arc::new(RwLock::new(Vec::new())),
+            nsp: normalize_namespace(namespace.into()),
+            auth,
+            builder: self.builder.clone(),
+            disconnect_reason: Arc::new(RwLock::new(None)),
+            on_disconnect: self.on_disconnect.clone(),
+            on_ack_timeout: self.on_ack_timeout.clone(),
+            ack_timeout: self.ack_timeout,
+            on_ping_timeout: self.on_ping_timeout.clone(),
+            on_error_ext: self.on_error_ext.clone(),
+            health_check: self.health_check,
+            on_health: self.on_health.clone(),
+            callbacks: self.callbacks.clone(),
+            connected: Arc::new(AtomicBool::default()),
+            sid: Arc::new(Mutex::new(None)),
+            manager: Some(manager.clone()),
+            drop_behavior: self.drop_behavior,
+            runtime: self.runtime.clone(),
+            handle: None,
+            state: Arc::new(watch::channel(ConnectionState::Connecting).0),
+            packets: broadcast::channel(PACKET_STREAM_CAPACITY).0,
+            counters: self.counters.clone(),
+            encryption: self.encryption.clone(),
+            // the outbox only keeps the events of the namespace of the builder
+            #[cfg(feature = "outbox")]
+            outbox: None,
+        };
+
+        manager.join(client).await
+    }
+
+    /// Leaves a namespace that was joined over the connection of this client,
+    /// e.g. via [`Client::connect_namespace`], by sending a `Disconnect` packet
+    /// for it. The engine.io connection is only closed if no other namespace is
+    /// left.
+    pub async fn disconnect_namespace<T: Into<String>>(&self, namespace: T) -> Result<()> {
+        let nsp = normalize_namespace(namespace.into());
+        let client = match &self.manager {
+            Some(manager) => manager.client(&nsp).await,
+            None => None,
+        };
+
+        match client {
+            Some(client) => client.disconnect().await,
+            None => Err(Error::IllegalNamespaceNotConnected(nsp)),
+        }
+    }
+
     /// Sends a message to the server but `alloc`s an `ack` to check whether the
     /// server responded in a given time span. This message takes an event, which
     /// could either be one of the common events like "message" or "error" or a
     /// custom event like "foo", as well as a data parameter. But be careful,
     /// in case you send a [`Payload::String`], the string needs to be valid JSON.
     /// It's even recommended to use a library like serde_json to serialize the data properly.
-    /// It also requires a timeout `Duration` in which the client needs to answer.
-    /// If the ack is acked in the correct time span, the specified callback is
+    /// It also takes the timeout `Duration` in which the server needs to answer, or
+    /// `None` for the [`crate::asynchronous::ClientBuilder::ack_timeout`] of the
+    /// client. If the ack is acked in the correct time span, the specified callback is
     /// called. The callback consumes a [`Payload`] which represents the data send
-    /// by the server.
+    /// by the server. Otherwise the ack is dropped and the
+    /// [`crate::asynchronous::ClientBuilder::on_ack_timeout`] callback is called.
     ///
     /// Please note that the requirements on the provided callbacks are similar to the ones
     /// for [`crate::asynchronous::ClientBuilder::on`].
@@ -417,11 +1384,11 @@ impl Client {
     /// }
     /// ```
     #[inline]
-    pub async fn emit_with_ack<F, E, D>(
+    pub async fn emit_with_ack<F, E, D, T>(
         &self,
         event: E,
         data: D,
-        timeout: Duration,
+        timeout: T,
         callback: F,
     ) -> Result<()>
     where
@@ -431,14 +1398,20 @@ impl Client {
             + Sync,
         E: Into<Event>,
         D: Into<Payload>,
+        T: Into<Option<Duration>>,
     {
+        self.check_connected()?;
+        let timeout = timeout.into().unwrap_or(self.ack_timeout);
+        let event = event.into();
         let id = thread_rng().gen_range(0..999);
         let socket_packet =
-            Packet::new_from_payload(data.into(), event.into(), &self.nsp, Some(id))?;
+            Packet::new_from_payload(self.seal(data.into())?, event.clone(), &self.nsp, Some(id))?;
 
+        let time_started = Instant::now();
         let ack = Ack {
             id,
-            time_started: Instant::now(),
+            event,
+            time_started,
             timeout,
             callback: Callback::<DynAsyncCallback>::new(callback),
         };
@@ -446,28 +1419,187 @@ impl Client {
         // add the ack to the tuple of outstanding acks
         self.outstanding_acks.write().await.push(ack);
 
+        // without a handle, so a pending ack doesn't keep the client from being dropped
+        let client = Client {
+            handle: None,
+            ..self.clone()
+        };
+        self.runtime.spawn(async move {
+            runtime::sleep(timeout).await;
+            client.expire_ack(id, time_started).await;
+        });
+
         self.socket.read().await.send(socket_packet).await
     }
 
-    async fn callback<P: Into<Payload>>(&self, event: &Event, payload: P) -> Result<()> {
-        let mut builder = self.builder.write().await;
-        let payload = payload.into();
-
-        if let Some(callback) = builder.on.get_mut(event) {
-            callback(payload.clone(), self.clone()).await;
-        }
-
-        // Call on_any for all common and custom events.
-        match event {
-            Event::Message | Event::Custom(_) => {
-                if let Some(callback) = builder.on_any.as_mut() {
-                    callback(event.clone(), payload, self.clone()).await;
-                }
-            }
-            _ => (),
-        }
-
-        Ok(())
+    /// Drops the ack if the server still didn't answer it and reports it to the
+    /// `on_ack_timeout` callback.
+    async fn expire_ack(&self, id: i32, time_started: Instant) {
+        let mut acks = self.outstanding_acks.write().await;
+        let Some(index) = acks
+            .iter()
+            .position(|ack| ack.id == id && ack.time_started == time_started)
+        else {
+            return;
+        };
+        let ack = acks.remove(index);
+        drop(acks);
+
+        trace!("Ack {} for {:?} timed out", id, ack.event);
+        if let Some(callback) = self.on_ack_timeout.write().await.as_mut() {
+            callback(ack.event, self.clone()).await;
+        }
+    }
+
+    /// Sends a message like [`Client::emit_with_ack`] until the server acks it, and
+    /// returns the ack of the server, like the `retries` option of the JavaScript
+    /// client. An attempt fails if the server doesn't ack within the
+    /// [`crate::asynchronous::ClientBuilder::ack_timeout`] or the connection is down,
+    /// e.g. while the client reconnects. After a failed attempt the message is sent
+    /// again once `backoff` has passed, up to `retries` times, before giving up with
+    /// the error of the last attempt.
+    ///
+    /// The server may receive the message more than once, if an ack got lost or was
+    /// too late, so its handler should be idempotent.
+    /// # Example
+    /// ```
+    /// use rust_socketio::asynchronous::ClientBuilder;
+    /// use serde_json::json;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/")
+    ///         .ack_timeout(Duration::from_secs(2))
+    ///         .connect()
+    ///         .await
+    ///     {
+    ///         let answer = socket
+    ///             .emit_reliable("order", json!({"id": 7}), 3, Duration::from_secs(1))
+    ///             .await;
+    ///
+    ///         println!("{:?}", answer);
+    ///     }
+    /// }
+    /// ```
+    pub async fn emit_reliable<E, D>(
+        &self,
+        event: E,
+        data: D,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Payload>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let event = event.into();
+        let data = data.into();
+        let mut attempt = 0;
+        loop {
+            let err = match self.ask(event.clone(), data.clone()).await {
+                Ok(answer) => return Ok(answer),
+                Err(err) if err.is_disconnected() || matches!(err, Error::AckTimeout()) => err,
+                Err(err) => return Err(err),
+            };
+            if attempt == retries {
+                return Err(err);
+            }
+            attempt += 1;
+            trace!("Retrying {:?} after {}", event, err);
+            self.runtime.sleep(backoff).await;
+        }
+    }
+
+    /// Calls a remote procedure of the server: emits the request as the argument of
+    /// the event of the [`Rpc`] and returns the first argument of the ack as the
+    /// response. Fails with [`Error::AckTimeout`] if the server doesn't ack within the
+    /// [`crate::asynchronous::ClientBuilder::ack_timeout`], and with
+    /// [`Error::InvalidJson`] if the response doesn't deserialize.
+    /// # Example
+    /// ```
+    /// use rust_socketio::asynchronous::{ClientBuilder, Rpc};
+    /// use serde_json::{json, Value};
+    ///
+    /// struct GetUser;
+    ///
+    /// impl Rpc for GetUser {
+    ///     const EVENT: &'static str = "get_user";
+    ///     type Request = u32;
+    ///     type Response = Option<Value>;
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect().await {
+    ///         let user = socket.call::<GetUser>(7).await;
+    ///         println!("{:?}", user);
+    ///     }
+    /// }
+    /// ```
+    pub async fn call<R: Rpc>(&self, request: R::Request) -> Result<R::Response> {
+        let request = serde_json::to_value(request)?;
+        // the arguments of an ack arrive as one array
+        let args: Vec<Value> = self.ask(R::EVENT, request).await?.arg(0)?;
+        Ok(serde_json::from_value(
+            args.into_iter().next().unwrap_or_default(),
+        )?)
+    }
+
+    /// Emits the event and waits for the ack of the server, for as long as the
+    /// default ack timeout.
+    async fn ask<E, D>(&self, event: E, data: D) -> Result<Payload>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let (answered, answer) = oneshot::channel();
+        // the callback is called twice for an ack with binary data, the first payload
+        // is the answer
+        let answered = Mutex::new(Some(answered));
+        self.emit_with_ack(event, data, None, move |payload, _| {
+            if let Some(answered) = answered.lock().ok().and_then(|mut a| a.take()) {
+                let _ = answered.send(payload);
+            }
+            async {}.boxed()
+        })
+        .await?;
+
+        match self.runtime.timeout(self.ack_timeout, answer).await {
+            Some(Ok(answer)) => Ok(answer),
+            _ => Err(Error::AckTimeout()),
+        }
+    }
+
+    /// Returns a [`ClientSink`] that emits every `(event, payload)` item sent
+    /// into it. This makes it possible to forward a stream of messages into the
+    /// client, e.g. with [`futures_util::StreamExt::forward`].
+    ///
+    /// # Example
+    /// ```
+    /// use futures_util::SinkExt;
+    /// use rust_socketio::{asynchronous::ClientBuilder, Event, Payload};
+    /// use serde_json::json;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let socket = ClientBuilder::new("http://localhost:4200/")
+    ///         .connect()
+    ///         .await
+    ///         .expect("connection failed");
+    ///
+    ///     let mut sink = socket.sink();
+    ///     sink.send(("foo", json!({"token": 123})))
+    ///         .await
+    ///         .expect("Server unreachable");
+    /// }
+    /// ```
+    pub fn sink(&self) -> ClientSink {
+        ClientSink::new(self.clone())
+    }
+
+    pub(super) async fn callback<P: Into<Payload>>(&self, event: &Event, payload: P) -> Result<()> {
+        self.callbacks.call(event, payload.into(), self).await
     }
 
     /// Handles the incoming acks and classifies what callbacks to call and how.
@@ -479,13 +1611,12 @@ impl Client {
                 if ack.id == id {
                     to_be_removed.push(index);
 
-                    if ack.time_started.elapsed() < ack.timeout {
+                    let latency = ack.time_started.elapsed();
+                    if latency < ack.timeout {
+                        stats::record_ack_latency(latency);
                         if let Some(ref payload) = socket_packet.data {
-                            ack.callback.deref_mut()(
-                                Payload::from(payload.to_owned()),
-                                self.clone(),
-                            )
-                            .await;
+                            ack.callback.deref_mut()(Payload::from(payload.as_str()), self.clone())
+                                .await;
                         }
                         if let Some(ref attachments) = socket_packet.attachments {
                             if let Some(payload) = attachments.get(0) {
@@ -533,27 +1664,43 @@ impl Client {
             return Ok(());
         };
 
+        // the `on_raw` callbacks share the text of the packet, it's only parsed if other
+        // callbacks take the event
+        if self.callbacks.has_raw() {
+            if let Ok(raw) = RawEvent::new(data.clone()) {
+                let takes_payload = self.callbacks.takes_payload(raw.event());
+                self.callbacks.call_raw(raw, self).await?;
+                if !takes_payload {
+                    return Ok(());
+                }
+            }
+        }
+
         // a socketio message always comes in one of the following two flavors (both JSON):
         // 1: `["event", "msg", ...]`
         // 2: `["msg"]`
         // in case 2, the message is ment for the default message event, in case 1 the event
         // is specified
-        if let Ok(Value::Array(contents)) = serde_json::from_str::<Value>(data) {
-            let (event, payloads) = match contents.len() {
+        if let Ok(Value::Array(mut contents)) = json::from_str::<Value>(data) {
+            let event = match contents.len() {
                 0 => return Err(Error::IncompletePacket()),
                 // Incorrect packet, ignore it
-                1 => (Event::Message, contents.as_slice()),
+                1 => Event::Message,
                 // it's a message event
                 _ => match contents.first() {
-                    Some(Value::String(ev)) => (Event::from(ev.as_str()), &contents[1..]),
-                    // get rest(1..) of them as data, not just take the 2nd element
-                    _ => (Event::Message, contents.as_slice()),
+                    Some(Value::String(ev)) => {
+                        let event = Event::from(ev.as_str());
+                        // get rest(1..) of them as data, not just take the 2nd element
+                        contents.remove(0);
+                        event
+                    }
                     // take them all as data
+                    _ => Event::Message,
                 },
             };
 
-            // call the correct callback
-            self.callback(&event, payloads.to_vec()).await?;
+            // call the correct callback, handing over the parsed arguments
+            self.callback(&event, contents).await?;
         }
 
         Ok(())
@@ -563,41 +1710,74 @@ impl Client {
     /// This method is later registered as the callback for the `on_data` event of the
     /// engineio client.
     #[inline]
-    async fn handle_socketio_packet(&self, packet: &Packet) -> Result<()> {
+    pub(super) async fn handle_socketio_packet(&self, packet: &Packet) -> Result<()> {
         if packet.nsp == self.nsp {
+            if self.packets.receiver_count() > 0 {
+                let _ = self.packets.send(packet.clone());
+            }
             match packet.packet_type {
                 PacketId::Ack | PacketId::BinaryAck => {
                     if let Err(err) = self.handle_ack(packet).await {
+                        let err = err.in_context(ErrorContext::of_packet(packet));
+                        self.handle_error(Severity::Warning, &err).await;
                         self.callback(&Event::Error, err.to_string()).await?;
                         return Err(err);
                     }
                 }
                 PacketId::BinaryEvent => {
                     if let Err(err) = self.handle_binary_event(packet).await {
+                        let err = err.in_context(ErrorContext::of_packet(packet));
+                        self.handle_error(Severity::Warning, &err).await;
                         self.callback(&Event::Error, err.to_string()).await?;
                     }
                 }
                 PacketId::Connect => {
-                    *(self.disconnect_reason.write().await) = DisconnectReason::default();
+                    self.connected.store(true, Ordering::Release);
+                    *self.sid.lock()? = packet.sid();
+                    *(self.disconnect_reason.write().await) = None;
+                    self.set_state(ConnectionState::Connected);
+                    #[cfg(feature = "outbox")]
+                    if self.outbox.is_some() {
+                        // without a handle, so sending doesn't keep the client from being dropped
+                        let client = Client {
+                            handle: None,
+                            ..self.clone()
+                        };
+                        runtime::spawn(async move { client.flush_outbox().await });
+                    }
                     self.callback(&Event::Connect, "").await?;
                 }
                 PacketId::Disconnect => {
-                    *(self.disconnect_reason.write().await) = DisconnectReason::Server;
+                    self.handle_disconnect(DisconnectReason::IoServerDisconnect)
+                        .await?;
                     self.callback(&Event::Close, "").await?;
                 }
                 PacketId::ConnectError => {
+                    self.connected.store(false, Ordering::Release);
+                    *self.sid.lock()? = None;
+                    self.set_state(ConnectionState::Disconnected {
+                        reason: DisconnectReason::IoServerDisconnect,
+                    });
+                    if let Some(err) = packet.connect_error() {
+                        let mut builder = self.builder.write().await;
+                        if let Some(callback) = builder.on_connect_error.as_mut() {
+                            callback(err, self.clone()).await;
+                        }
+                    }
                     self.callback(
                         &Event::Error,
                         String::from("Received an ConnectError frame: ")
                             + packet
                                 .data
-                                .as_ref()
-                                .unwrap_or(&String::from("\"No error message provided\"")),
+                                .as_deref()
+                                .unwrap_or("\"No error message provided\""),
                     )
                     .await?;
                 }
                 PacketId::Event => {
                     if let Err(err) = self.handle_event(packet).await {
+                        let err = err.in_context(ErrorContext::of_packet(packet));
+                        self.handle_error(Severity::Warning, &err).await;
                         self.callback(&Event::Error, err.to_string()).await?;
                     }
                 }
@@ -606,7 +1786,41 @@ impl Client {
         Ok(())
     }
 
+    /// Reads the next packet of the connection and handles it like the task polling the
+    /// connection would, for clients connected without that task. Returns `None` if no
+    /// packet arrived within `timeout`, which only bounds the read, so a packet that
+    /// was read is always handled. Once the connection is closed, the namespace is
+    /// marked as disconnected and [`Error::StoppedEngineIoSocket`] is returned.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn next_packet(&self, timeout: Duration) -> Result<Option<Packet>> {
+        if self.manager.is_some() {
+            return Err(Error::IllegalManualPoll());
+        }
+
+        let mut socket = self.socket.read().await.clone();
+        // reading is cancel safe, an unfinished read resumes on the next call
+        let Ok(packet) = tokio::time::timeout(timeout, socket.next()).await else {
+            return Ok(None);
+        };
+        match packet {
+            Some(Ok(packet)) => {
+                self.handle_socketio_packet(&packet).await?;
+                Ok(Some(packet))
+            }
+            Some(Err(err)) => {
+                self.callback(&Event::Error, err.to_string()).await?;
+                Err(err)
+            }
+            None => {
+                self.handle_disconnect(DisconnectReason::TransportClose)
+                    .await?;
+                Err(Error::StoppedEngineIoSocket)
+            }
+        }
+    }
+
     /// Returns the packet stream for the client.
+    #[cfg(test)]
     pub(crate) async fn as_stream<'a>(
         &'a self,
     ) -> Pin<Box<dyn Stream<Item = Result<Packet>> + Send + 'a>> {
@@ -641,13 +1855,14 @@ mod test {
     use std::{
         sync::{
             atomic::{AtomicUsize, Ordering},
-            Arc,
+            Arc, Mutex,
         },
         time::Duration,
     };
 
     use bytes::Bytes;
-    use futures_util::{FutureExt, StreamExt};
+    use futures_util::{stream, FutureExt, StreamExt};
+    #[cfg(feature = "native-tls")]
     use native_tls::TlsConnector;
     use serde_json::json;
     use serial_test::serial;
@@ -659,11 +1874,12 @@ mod test {
     use crate::{
         asynchronous::{
             client::{builder::ClientBuilder, client::Client},
-            ReconnectSettings,
+            test_util::{Fault, FaultyTransport, MockServer},
+            ConnectionState, DropBehavior, ReconnectSettings,
         },
         error::Result,
         packet::{Packet, PacketId},
-        Payload, TransportType,
+        Direction, DisconnectReason, Event, Payload, TransportType,
     };
 
     #[tokio::test]
@@ -756,6 +1972,7 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "native-tls")]
     #[tokio::test]
     async fn socket_io_builder_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
@@ -889,6 +2106,7 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "native-tls")]
     #[tokio::test]
     async fn socket_io_builder_integration_iterator() -> Result<()> {
         let url = crate::test::socket_io_server();
@@ -935,109 +2153,438 @@ mod test {
             .await
             .is_ok());
 
-        test_socketio_socket(socket, "/admin".to_owned()).await
+        test_socketio_socket(socket, "/admin".to_owned()).await
+    }
+
+    #[tokio::test]
+    async fn socket_io_on_any_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let (tx, mut rx) = mpsc::channel(2);
+
+        let mut _socket = ClientBuilder::new(url)
+            .namespace("/")
+            .auth(json!({ "password": "123" }))
+            .on_any(move |event, payload, _| {
+                let clone_tx = tx.clone();
+                async move {
+                    if let Payload::Text(values) = payload {
+                        println!("{event}: {values:#?}");
+                    }
+                    clone_tx.send(String::from(event)).await.unwrap();
+                }
+                .boxed()
+            })
+            .connect()
+            .await?;
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event, "message");
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event, "test");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_auth_builder_integration() -> Result<()> {
+        let url = crate::test::socket_io_auth_server();
+        let nsp = String::from("/admin");
+        let socket = ClientBuilder::new(url)
+            .namespace(nsp.clone())
+            .auth(json!({ "password": "123" }))
+            .connect_manual()
+            .await?;
+
+        // open packet
+        let mut socket_stream = socket.as_stream().await;
+        let _ = socket_stream.next().await.unwrap()?;
+
+        let packet = socket_stream.next().await.unwrap()?;
+        assert_eq!(
+            packet,
+            Packet::new(
+                PacketId::Event,
+                nsp,
+                Some("[\"auth\",\"success\"]".to_owned()),
+                None,
+                0,
+                None,
+                None,
+            )
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socketio_polling_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url.clone())
+            .transport_type(TransportType::Polling)
+            .connect_manual()
+            .await?;
+        test_socketio_socket(socket, "/".to_owned()).await
+    }
+
+    #[tokio::test]
+    async fn socket_io_websocket_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url.clone())
+            .transport_type(TransportType::Websocket)
+            .connect_manual()
+            .await?;
+        test_socketio_socket(socket, "/".to_owned()).await
+    }
+
+    #[tokio::test]
+    async fn socket_io_websocket_upgrade_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url)
+            .transport_type(TransportType::WebsocketUpgrade)
+            .connect_manual()
+            .await?;
+        test_socketio_socket(socket, "/".to_owned()).await
+    }
+
+    #[tokio::test]
+    async fn socket_io_any_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url)
+            .transport_type(TransportType::Any)
+            .connect_manual()
+            .await?;
+        test_socketio_socket(socket, "/".to_owned()).await
+    }
+
+    #[tokio::test]
+    async fn socket_io_transports_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url)
+            .transports([TransportType::Websocket, TransportType::Polling])
+            .connect_manual()
+            .await?;
+        test_socketio_socket(socket, "/".to_owned()).await
+    }
+
+    #[tokio::test]
+    async fn socket_io_query_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let (tx, mut rx) = mpsc::channel(1);
+
+        let socket = ClientBuilder::new(url)
+            .query("token", "first")
+            .query("token", "secret")
+            .on("query-received", move |payload, _| {
+                let clone_tx = tx.clone();
+                async move {
+                    clone_tx.send(payload).await.unwrap();
+                }
+                .boxed()
+            })
+            .connect()
+            .await?;
+
+        socket.emit("query", json!("token")).await?;
+
+        let payload = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("query was not echoed in time")
+            .unwrap();
+        assert_eq!(payload, Payload::Text(vec![json!("secret")]));
+
+        socket.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_sink_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let (tx, mut rx) = mpsc::channel(3);
+
+        let socket = ClientBuilder::new(url)
+            .on("test-received", move |payload, _| {
+                let clone_tx = tx.clone();
+                async move {
+                    clone_tx.send(payload).await.unwrap();
+                }
+                .boxed()
+            })
+            .connect()
+            .await?;
+
+        let messages =
+            stream::iter(0..3).map(|i| Ok::<_, crate::Error>(("test", json!({ "count": i }))));
+        messages.forward(socket.sink()).await?;
+
+        for i in 0..3 {
+            let payload = timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("sink emit was not echoed in time")
+                .unwrap();
+            assert_eq!(payload, Payload::Text(vec![json!({ "count": i })]));
+        }
+
+        socket.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_on_disconnect_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let socket = ClientBuilder::new(url)
+            .on_disconnect(move |reason, _| {
+                let clone_tx = tx.clone();
+                async move { clone_tx.send(reason).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+
+        socket.disconnect().await?;
+
+        let reason = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("disconnect handler was not called in time")
+            .unwrap();
+        assert_eq!(reason, DisconnectReason::IoClientDisconnect);
+
+        // the handler is only called once per disconnect
+        sleep(Duration::from_millis(500)).await;
+        assert!(rx.try_recv().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_stats_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let socket = ClientBuilder::new(url).connect().await?;
+        let connected = socket.stats();
+        // the `Connect` packet of the namespace and its answer
+        assert_eq!(connected.packets_sent, 1);
+        assert!(connected.packets_received >= 1);
+
+        socket.emit("test", json!("stats")).await?;
+        let emitted = socket.stats();
+        assert_eq!(emitted.packets_sent, 2);
+        assert!(emitted.bytes_sent > connected.bytes_sent);
+        assert_eq!(emitted.reconnects, 0);
+
+        let events = socket.stats_by_event();
+        let test = events.iter().find(|stats| stats.event == "test").unwrap();
+        assert_eq!((test.namespace.as_str(), test.sent), ("/", 1));
+
+        socket.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_packet_observer_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let observed = Arc::new(Mutex::new(Vec::new()));
+
+        let socket = ClientBuilder::new(url)
+            .packet_observer({
+                let observed = observed.clone();
+                move |direction, packet| {
+                    observed
+                        .lock()
+                        .unwrap()
+                        .push((direction, packet.packet_type));
+                }
+            })
+            .connect()
+            .await?;
+        socket.emit("test", json!("observed")).await?;
+        socket.disconnect().await?;
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed[0], (Direction::Outbound, PacketId::Connect));
+        assert!(observed.contains(&(Direction::Inbound, PacketId::Connect)));
+        assert!(observed.contains(&(Direction::Outbound, PacketId::Event)));
+        assert_eq!(
+            observed.last(),
+            Some(&(Direction::Outbound, PacketId::Disconnect))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_state_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let socket = ClientBuilder::new(url).connect().await?;
+        assert_eq!(socket.state(), ConnectionState::Connected);
+
+        let mut states = Box::pin(socket.state_stream());
+        assert_eq!(states.next().await, Some(ConnectionState::Connected));
+
+        socket.disconnect().await?;
+        let disconnected = ConnectionState::Disconnected {
+            reason: DisconnectReason::IoClientDisconnect,
+        };
+        assert_eq!(socket.state(), disconnected);
+        assert_eq!(
+            timeout(Duration::from_secs(5), states.next())
+                .await
+                .expect("the state is yielded in time"),
+            Some(disconnected)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_io_drop_behavior_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let builder = |behavior| {
+            let tx = tx.clone();
+            ClientBuilder::new(url.clone())
+                .drop_behavior(behavior)
+                .on_disconnect(move |reason, _| {
+                    let clone_tx = tx.clone();
+                    async move { clone_tx.send(reason).unwrap() }.boxed()
+                })
+        };
+
+        // dropping the last clone disconnects
+        let socket = builder(DropBehavior::Disconnect).connect().await?;
+        let clone = socket.clone();
+        drop(socket);
+        sleep(Duration::from_millis(200)).await;
+        assert!(rx.try_recv().is_err());
+        drop(clone);
+        let reason = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("disconnect handler was not called in time")
+            .unwrap();
+        assert_eq!(reason, DisconnectReason::IoClientDisconnect);
+
+        // a detached client keeps its connection
+        let socket = builder(DropBehavior::Disconnect).connect().await?;
+        socket.detach();
+        sleep(Duration::from_millis(500)).await;
+        assert!(rx.try_recv().is_err());
+
+        Ok(())
     }
 
     #[tokio::test]
-    async fn socket_io_on_any_integration() -> Result<()> {
+    async fn socket_io_connect_error_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
 
-        let (tx, mut rx) = mpsc::channel(2);
+        let (tx, mut rx) = mpsc::unbounded_channel();
 
-        let mut _socket = ClientBuilder::new(url)
-            .namespace("/")
-            .auth(json!({ "password": "123" }))
-            .on_any(move |event, payload, _| {
+        let socket = ClientBuilder::new(url)
+            .namespace("/invalid")
+            .on_connect_error(move |err, _| {
                 let clone_tx = tx.clone();
-                async move {
-                    if let Payload::Text(values) = payload {
-                        println!("{event}: {values:#?}");
-                    }
-                    clone_tx.send(String::from(event)).await.unwrap();
-                }
-                .boxed()
+                async move { clone_tx.send(err).unwrap() }.boxed()
             })
             .connect()
             .await?;
 
-        let event = rx.recv().await.unwrap();
-        assert_eq!(event, "message");
+        let err = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("connect error handler was not called in time")
+            .unwrap();
+        assert!(matches!(
+            err,
+            crate::Error::ConnectRejected { message, .. } if message == "Invalid namespace"
+        ));
+        assert!(socket.emit("test", json!("")).await.is_err());
 
-        let event = rx.recv().await.unwrap();
-        assert_eq!(event, "test");
+        // joining a namespace at runtime surfaces the rejection as error
+        assert!(matches!(
+            socket.connect_namespace("/invalid2", None).await,
+            Err(crate::Error::ConnectRejected { message, .. }) if message == "Invalid namespace"
+        ));
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn socket_io_auth_builder_integration() -> Result<()> {
-        let url = crate::test::socket_io_auth_server();
-        let nsp = String::from("/admin");
-        let socket = ClientBuilder::new(url)
-            .namespace(nsp.clone())
-            .auth(json!({ "password": "123" }))
-            .connect_manual()
-            .await?;
+    async fn socket_io_sid_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url).namespace("admin").connect().await?;
+        assert_eq!(socket.nsp(), "/admin");
 
-        // open packet
-        let mut socket_stream = socket.as_stream().await;
-        let _ = socket_stream.next().await.unwrap()?;
+        // the sid arrives with the server's answer to the `Connect` packet
+        for _ in 0..10 {
+            if socket.sid().is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        let sid = socket.sid();
+        assert!(sid.is_some());
 
-        let packet = socket_stream.next().await.unwrap()?;
-        assert_eq!(
-            packet,
-            Packet::new(
-                PacketId::Event,
-                nsp,
-                Some("[\"auth\",\"success\"]".to_owned()),
-                None,
-                0,
-                None,
-                None,
-            )
-        );
+        let root = socket.connect_namespace("/", None).await?;
+        assert!(root.sid().is_some());
+        assert_ne!(root.sid(), sid);
 
+        socket.disconnect().await?;
+        assert!(socket.sid().is_none());
         Ok(())
     }
 
     #[tokio::test]
-    async fn socketio_polling_integration() -> Result<()> {
+    async fn socket_io_dynamic_namespace_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url.clone())
-            .transport_type(TransportType::Polling)
-            .connect_manual()
-            .await?;
-        test_socketio_socket(socket, "/".to_owned()).await
-    }
 
-    #[tokio::test]
-    async fn socket_io_websocket_integration() -> Result<()> {
-        let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url.clone())
-            .transport_type(TransportType::Websocket)
-            .connect_manual()
-            .await?;
-        test_socketio_socket(socket, "/".to_owned()).await
-    }
+        let (tx, mut rx) = mpsc::unbounded_channel();
 
-    #[tokio::test]
-    async fn socket_io_websocket_upgrade_integration() -> Result<()> {
-        let url = crate::test::socket_io_server();
         let socket = ClientBuilder::new(url)
-            .transport_type(TransportType::WebsocketUpgrade)
-            .connect_manual()
+            .on("test-received", move |payload, client: Client| {
+                let clone_tx = tx.clone();
+                async move {
+                    clone_tx.send((client.nsp().to_owned(), payload)).unwrap();
+                }
+                .boxed()
+            })
+            .connect()
             .await?;
-        test_socketio_socket(socket, "/".to_owned()).await
-    }
 
-    #[tokio::test]
-    async fn socket_io_any_integration() -> Result<()> {
-        let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url)
-            .transport_type(TransportType::Any)
-            .connect_manual()
-            .await?;
-        test_socketio_socket(socket, "/".to_owned()).await
+        let admin = socket.connect_namespace("admin", None).await?;
+        assert!(matches!(
+            socket.connect_namespace("/admin", None).await,
+            Err(crate::Error::IllegalNamespaceAlreadyConnected(_))
+        ));
+
+        admin.emit("test", json!("admin")).await?;
+        let (nsp, payload) = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("namespace emit was not echoed in time")
+            .unwrap();
+        assert_eq!(nsp, "/admin");
+        assert_eq!(payload, Payload::Text(vec![json!("admin")]));
+
+        // leaving the namespace keeps the connection of the default namespace
+        socket.disconnect_namespace("/admin").await?;
+        assert!(admin.emit("test", json!("admin")).await.is_err());
+        assert!(matches!(
+            socket.disconnect_namespace("/admin").await,
+            Err(crate::Error::IllegalNamespaceNotConnected(_))
+        ));
+
+        socket.emit("test", json!("root")).await?;
+        let (nsp, _) = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("emit was not echoed in time")
+            .unwrap();
+        assert_eq!(nsp, "/");
+
+        socket.disconnect().await?;
+        Ok(())
     }
 
     async fn test_socketio_socket(socket: Client, nsp: String) -> Result<()> {
@@ -1229,7 +2776,488 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn socket_io_connect_timeout() -> Result<()> {
+        // accepts the tcp connection but never answers the handshake
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        tokio::spawn(async move {
+            let _stream = listener.accept().await;
+            sleep(Duration::from_secs(2)).await;
+        });
+
+        let result = timeout(
+            Duration::from_secs(2),
+            ClientBuilder::new(url)
+                .transport_type(TransportType::Websocket)
+                .connect_timeout(Duration::from_millis(200))
+                .connect(),
+        )
+        .await
+        .expect("the connect timeout elapses first");
+
+        assert!(matches!(result, Err(crate::Error::ConnectTimeout())));
+        Ok(())
+    }
+
     fn load(num: &AtomicUsize) -> usize {
         num.load(Ordering::Acquire)
     }
+
+    #[tokio::test]
+    async fn observes_frames() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .frame_observer(move |frame| tx.send(*frame).unwrap())
+            .connect()
+            .await?;
+        server.recv().await;
+        server.emit("greet", json!("hello"))?;
+
+        // the connect packet is written, the event read
+        let mut directions = Vec::new();
+        while directions.len() < 2 {
+            let frame = timeout(Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if frame.packet_id == rust_engineio::PacketId::Message {
+                assert_eq!(frame.transport, "custom");
+                directions.push(frame.direction);
+            }
+        }
+        assert!(directions.contains(&Direction::Outbound));
+        assert!(directions.contains(&Direction::Inbound));
+
+        let stats = client.stats();
+        assert!(stats.engine_bytes_sent >= stats.bytes_sent);
+        assert!(stats.engine_bytes_received > 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn waits_until_connected() -> Result<()> {
+        let (server, transport) = MockServer::new();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        // connecting already waited for the server's answer
+        assert_eq!(
+            client.state(),
+            crate::asynchronous::ConnectionState::Connected
+        );
+        client.wait_until_connected(Duration::from_secs(1)).await?;
+
+        server.send(Packet::new(
+            PacketId::Disconnect,
+            "/".to_owned(),
+            None,
+            None,
+            0,
+            None,
+            None,
+        ))?;
+        let mut states = client.state_stream().boxed();
+        while states.next().await == Some(ConnectionState::Connected) {}
+        assert!(matches!(
+            client.wait_until_connected(Duration::from_secs(1)).await,
+            Err(crate::Error::IllegalNamespaceNotConnected(nsp)) if nsp == "/"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn emits_many() -> Result<()> {
+        let (server, transport) = MockServer::new();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        client
+            .emit_many((1..=3).map(|tick| ("tick", json!(tick))))
+            .await?;
+        for tick in 1..=3 {
+            let packet = server.recv().await.unwrap();
+            assert_eq!(
+                packet.data.as_deref(),
+                Some(format!(r#"["tick",{}]"#, tick).as_str())
+            );
+        }
+
+        client.disconnect().await?;
+        assert!(client.emit_many([("tick", json!(4))]).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn emits_with_timeout() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        // the writer is stuck on the first event, after the frames opening the connection
+        let transport =
+            FaultyTransport::new(transport).outgoing(2, Fault::Delay(Duration::from_millis(300)));
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        let result = client
+            .emit_with_timeout("tick", json!(1), Duration::from_millis(50))
+            .await;
+        assert!(matches!(result, Err(crate::Error::Timeout())));
+
+        // the timed out write is given up
+        client
+            .emit_with_timeout("tick", json!(2), Duration::from_secs(1))
+            .await?;
+        server.assert_emitted("tick", json!(2)).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn emits_volatile_events() -> Result<()> {
+        use crate::asynchronous::BackpressurePolicy;
+
+        let (server, transport) = MockServer::new();
+        // the writer is stuck on the first event, after the frames opening the connection
+        let transport =
+            FaultyTransport::new(transport).outgoing(2, Fault::Delay(Duration::from_millis(300)));
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .outgoing_queue(1, BackpressurePolicy::Block)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        client.emit("tick", json!(1)).await?;
+        client.emit("tick", json!(2)).await?;
+        // the queue is full, so it's dropped instead of waiting for the writer
+        timeout(
+            Duration::from_millis(100),
+            client.emit_volatile("tick", json!(3)),
+        )
+        .await
+        .unwrap()?;
+        server.assert_emitted("tick", json!(1)).await;
+        server.assert_emitted("tick", json!(2)).await;
+
+        client.emit_volatile("tick", json!(4)).await?;
+        server.assert_emitted("tick", json!(4)).await;
+
+        // dropped as the connection is closed
+        client.disconnect().await?;
+        assert!(client.emit("tick", json!(5)).await.is_err());
+        assert!(client.emit_volatile("tick", json!(5)).await.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn emits_binary_streams() -> Result<()> {
+        let (server, transport) = MockServer::new();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // the mock server takes payloads of up to 100000 bytes, so chunks of 50000
+        let file: Vec<u8> = (0..100_000u32).map(|byte| byte as u8).collect();
+        client.emit_binary_stream("upload", file.as_slice()).await?;
+
+        let mut received = Vec::new();
+        let mut id = None;
+        for seq in 0..3 {
+            let packet = server.recv().await.unwrap();
+            assert_eq!(packet.packet_type, PacketId::BinaryEvent);
+            let data = packet.data.as_deref().unwrap();
+            let header: serde_json::Value =
+                serde_json::from_str(data.strip_prefix(r#""upload","#).unwrap())?;
+            assert_eq!(header["seq"], seq);
+            assert_eq!(header["last"], seq == 2);
+            assert_eq!(id.get_or_insert(header["id"].clone()), &header["id"]);
+            received.extend_from_slice(&packet.attachments.unwrap()[0]);
+        }
+        assert_eq!(received, file);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_the_health() -> Result<()> {
+        use crate::{Health, HealthCheck};
+
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let check = HealthCheck::new(Duration::from_millis(20))
+            .ack_age(Duration::from_millis(50), Duration::from_secs(10));
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .health_check(check, move |report, _| {
+                let tx = tx.clone();
+                async move { tx.send(report.health).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            timeout(second, rx.recv()).await.unwrap(),
+            Some(Health::Healthy)
+        );
+
+        // an ack the server doesn't answer degrades the client
+        client
+            .emit_with_ack("slow", json!(1), Duration::from_secs(10), |_, _| {
+                async {}.boxed()
+            })
+            .await?;
+        while timeout(second, rx.recv()).await.unwrap() != Some(Health::Degraded) {}
+
+        server.send(Packet::new(
+            PacketId::Disconnect,
+            "/".to_owned(),
+            None,
+            None,
+            0,
+            None,
+            None,
+        ))?;
+        while timeout(second, rx.recv()).await.unwrap() != Some(Health::Unhealthy) {}
+        assert_eq!(client.health().await.health, Health::Unhealthy);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pings_time_out() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let disconnected = tx.clone();
+
+        let _client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport.ping_interval(Duration::from_millis(100)))
+            .reconnect(false)
+            .ping_timeout(Duration::from_millis(100))
+            .on_ping_timeout(move |_| {
+                let tx = tx.clone();
+                async move { tx.send("on_ping_timeout".to_owned()).unwrap() }.boxed()
+            })
+            .on_disconnect(move |reason, _| {
+                let disconnected = disconnected.clone();
+                async move { disconnected.send(reason.to_string()).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // the server never pings, so the connection is lost within a second, the hook is
+        // called before the disconnect
+        for expected in ["on_ping_timeout", "ping timeout"] {
+            assert_eq!(
+                timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+                Some(expected.to_owned())
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn acks_time_out() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (timed_out, mut timeouts) = mpsc::unbounded_channel();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .ack_timeout(Duration::from_millis(100))
+            .on_ack_timeout(move |event, _| {
+                let timed_out = timed_out.clone();
+                async move { timed_out.send(event).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // the first question waits for the default timeout, the second one longer
+        for (event, ack_timeout) in [("first", None), ("second", Some(Duration::from_secs(1)))] {
+            let tx = tx.clone();
+            client
+                .emit_with_ack(event, json!(1), ack_timeout, move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send(payload).unwrap() }.boxed()
+                })
+                .await?;
+        }
+        let first = server.recv().await.and_then(|packet| packet.id).unwrap();
+        let second = server.recv().await.and_then(|packet| packet.id).unwrap();
+        assert_eq!(client.pending_acks().await, 2);
+        assert!(client.oldest_pending_ack().await.is_some());
+        assert_eq!(client.outgoing_queue_len().await, 0);
+
+        assert_eq!(
+            timeout(Duration::from_secs(1), timeouts.recv())
+                .await
+                .unwrap(),
+            Some(Event::from("first"))
+        );
+        assert_eq!(client.pending_acks().await, 1);
+        // the timed out ack is forgotten, the other one still answered
+        server.ack(first, json!(1))?;
+        server.ack(second, json!(2))?;
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(json!([2])))
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(timeouts.try_recv().is_err());
+        assert_eq!(client.oldest_pending_ack().await, None);
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retries_until_acked() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .ack_timeout(Duration::from_millis(100))
+            .connect()
+            .await?;
+        server.recv().await;
+
+        let emitting = client.clone();
+        let answer = tokio::spawn(async move {
+            emitting
+                .emit_reliable("order", json!(7), 2, Duration::from_millis(10))
+                .await
+        });
+        // the first attempt goes unanswered, the second one is acked
+        let first = server.recv().await.unwrap();
+        let second = server.recv().await.unwrap();
+        assert_eq!(first.data, second.data);
+        assert_ne!(first.id, second.id);
+        server.ack(second.id.unwrap(), json!("done"))?;
+        assert_eq!(answer.await.unwrap()?, Payload::from(json!(["done"])));
+
+        // an unanswered message is given up after the retries
+        let answer = client
+            .emit_reliable("order", json!(8), 1, Duration::from_millis(10))
+            .await;
+        assert!(matches!(answer, Err(crate::Error::AckTimeout())));
+        for _ in 0..2 {
+            assert_eq!(
+                server
+                    .recv()
+                    .await
+                    .and_then(|packet| packet.data)
+                    .as_deref(),
+                Some(r#"["order",8]"#)
+            );
+        }
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sends_packets_as_they_are() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        let packet = Packet::new(
+            PacketId::BinaryEvent,
+            "/".to_owned(),
+            Some("\"file\"".to_owned()),
+            Some(7),
+            1,
+            Some(vec![Bytes::from_static(&[1, 2, 3])]),
+            None,
+        );
+        client.send_packet(packet.clone()).await?;
+        assert_eq!(server.recv().await, Some(packet.clone()));
+
+        let announced_two = Packet {
+            attachment_count: 2,
+            ..packet
+        };
+        assert!(matches!(
+            client.send_packet(announced_two).await,
+            Err(crate::Error::InvalidPacket())
+        ));
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streams_the_packets_of_the_namespace() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on("chat", move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+        let mut packets = client.packet_stream();
+
+        server.emit("chat", json!("hi"))?;
+        server.send(Packet::new(
+            PacketId::Disconnect,
+            "/".to_owned(),
+            None,
+            None,
+            0,
+            None,
+            None,
+        ))?;
+
+        let packet = timeout(Duration::from_secs(1), packets.next())
+            .await
+            .unwrap()
+            .unwrap()?;
+        assert_eq!(packet.packet_type, PacketId::Event);
+        assert_eq!(packet.data.as_deref(), Some("[\"chat\",\"hi\"]"));
+        let packet = timeout(Duration::from_secs(1), packets.next())
+            .await
+            .unwrap()
+            .unwrap()?;
+        assert_eq!(packet.packet_type, PacketId::Disconnect);
+        // the callbacks still get the events
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(json!("hi")))
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, ops::Deref};
+
+use futures_util::future::{self, BoxFuture, FutureExt, LocalBoxFuture};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use super::{builder::ClientBuilder, client::Client};
+use crate::{asynchronous::runtime, error::Result, Event, Payload};
+
+type LocalCallback = Box<dyn FnMut(Payload, LocalClient) -> LocalBoxFuture<'static, ()>>;
+type LocalAnyCallback = Box<dyn FnMut(Event, Payload, LocalClient) -> LocalBoxFuture<'static, ()>>;
+
+/// An event handed from the callbacks of the client to the task calling the local ones.
+struct Call {
+    event: Event,
+    payload: Payload,
+    client: Client,
+    // whether it's for the `on_any` callback
+    any: bool,
+}
+
+/// Builds a [`LocalClient`], whose callbacks don't have to be `Send` or `Sync`, so they
+/// can capture `Rc`-based state, e.g. of a GUI. The client itself is configured with a
+/// regular [`ClientBuilder`], the callbacks are called one after the other on a task
+/// spawned onto the current [`tokio::task::LocalSet`] (or the event loop of the
+/// javascript host on wasm targets).
+///
+/// # Example
+/// ```rust
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// use futures_util::FutureExt;
+/// use rust_socketio::asynchronous::{ClientBuilder, LocalClientBuilder};
+/// use tokio::task::LocalSet;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let received = Rc::new(RefCell::new(Vec::new()));
+///     let log = received.clone();
+///
+///     LocalSet::new()
+///         .run_until(async move {
+///             let socket = LocalClientBuilder::new(ClientBuilder::new("http://localhost:4200/"))
+///                 .on("test", move |payload, _| {
+///                     log.borrow_mut().push(payload);
+///                     async {}.boxed_local()
+///                 })
+///                 .connect()
+///                 .await;
+///         })
+///         .await;
+/// }
+/// ```
+pub struct LocalClientBuilder {
+    builder: ClientBuilder,
+    on: HashMap<Event, LocalCallback>,
+    on_any: Option<LocalAnyCallback>,
+}
+
+impl LocalClientBuilder {
+    /// Creates a builder connecting a client with the settings of the given builder.
+    /// Its callbacks are called as well, an `on_any` callback is replaced by the one of
+    /// this builder though, if there is one.
+    pub fn new(builder: ClientBuilder) -> Self {
+        LocalClientBuilder {
+            builder,
+            on: HashMap::new(),
+            on_any: None,
+        }
+    }
+
+    /// Registers a callback for a certain [`Event`], like [`ClientBuilder::on`].
+    pub fn on<T: Into<Event>, F>(mut self, event: T, callback: F) -> Self
+    where
+        F: FnMut(Payload, LocalClient) -> LocalBoxFuture<'static, ()> + 'static,
+    {
+        self.on.insert(event.into(), Box::new(callback));
+        self
+    }
+
+    /// Registers a callback for all [`Event::Custom`] and [`Event::Message`] events, like
+    /// [`ClientBuilder::on_any`].
+    pub fn on_any<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(Event, Payload, LocalClient) -> LocalBoxFuture<'static, ()> + 'static,
+    {
+        self.on_any = Some(Box::new(callback));
+        self
+    }
+
+    /// Connects the client like [`ClientBuilder::connect`] and spawns the task calling
+    /// the callbacks. The task ends once the client and all its clones are dropped.
+    ///
+    /// # Panics
+    /// On native targets, if called outside of a [`tokio::task::LocalSet`].
+    pub async fn connect(self) -> Result<LocalClient> {
+        let (calls, receiver) = unbounded_channel();
+        let mut builder = self.builder;
+
+        // the client only calls `Send` callbacks, which hand the events over to the task
+        for event in self.on.keys() {
+            let calls = calls.clone();
+            builder = builder.on(event.clone(), forward(calls, event.clone()));
+        }
+        if self.on_any.is_some() {
+            builder = builder.on_any(move |event, payload, client| {
+                let _ = calls.send(Call {
+                    event,
+                    payload,
+                    client,
+                    any: true,
+                });
+                future::ready(()).boxed()
+            });
+        }
+
+        let client = builder.connect().await?;
+        runtime::spawn_local(dispatch(receiver, self.on, self.on_any));
+        Ok(LocalClient { client })
+    }
+}
+
+/// Returns a callback for the client handing its events over to the task.
+fn forward(
+    calls: UnboundedSender<Call>,
+    event: Event,
+) -> impl FnMut(Payload, Client) -> BoxFuture<'static, ()> + Send + Sync {
+    move |payload, client| {
+        let _ = calls.send(Call {
+            event: event.clone(),
+            payload,
+            client,
+            any: false,
+        });
+        future::ready(()).boxed()
+    }
+}
+
+/// Calls the local callbacks with the events, in the order they were received.
+async fn dispatch(
+    mut calls: UnboundedReceiver<Call>,
+    mut on: HashMap<Event, LocalCallback>,
+    mut on_any: Option<LocalAnyCallback>,
+) {
+    while let Some(call) = calls.recv().await {
+        let client = LocalClient {
+            client: call.client,
+        };
+        if call.any {
+            if let Some(callback) = on_any.as_mut() {
+                callback(call.event, call.payload, client).await;
+            }
+        } else if let Some(callback) = on.get_mut(&call.event) {
+            callback(call.payload, client).await;
+        }
+    }
+}
+
+/// A [`Client`] whose callbacks don't have to be `Send`, see [`LocalClientBuilder`]. It
+/// dereferences to the client, so it's used the same way.
+#[derive(Clone)]
+pub struct LocalClient {
+    client: Client,
+}
+
+impl LocalClient {
+    /// Returns the client the callbacks are called for.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Deref for LocalClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use serde_json::json;
+    use tokio::task::LocalSet;
+
+    use super::*;
+    use crate::asynchronous::test_util::MockServer;
+
+    #[tokio::test]
+    async fn calls_local_callbacks() -> Result<()> {
+        LocalSet::new()
+            .run_until(async {
+                let (server, transport) = MockServer::new();
+                let received = Rc::new(RefCell::new(Vec::new()));
+                let (foo, any) = (received.clone(), received.clone());
+
+                let _client = LocalClientBuilder::new(
+                    ClientBuilder::new("http://localhost/")
+                        .mock_transport(transport)
+                        .reconnect(false),
+                )
+                .on("foo", move |payload, client| {
+                    foo.borrow_mut().push(("on", payload.clone()));
+                    async move {
+                        client.emit("bar", payload).await.unwrap();
+                    }
+                    .boxed_local()
+                })
+                .on_any(move |event, _, _| {
+                    any.borrow_mut()
+                        .push(("any", Payload::from(event.as_str())));
+                    async {}.boxed_local()
+                })
+                .connect()
+                .await?;
+                server.recv().await;
+
+                server.emit("foo", json!(1))?;
+                server.assert_emitted("bar", json!(1)).await;
+                tokio::task::yield_now().await;
+                assert_eq!(
+                    *received.borrow(),
+                    vec![
+                        ("on", Payload::from(json!(1))),
+                        ("any", Payload::from("foo"))
+                    ]
+                );
+                Ok(())
+            })
+            .await
+    }
+}
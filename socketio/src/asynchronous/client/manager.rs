@@ -0,0 +1,599 @@
+use std::{collections::HashMap, future::Future, pin::pin, sync::Arc, time::Duration};
+
+use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
+use futures_util::{
+    future::{select, Either},
+    StreamExt,
+};
+use log::trace;
+use tokio::sync::{oneshot, watch, RwLock};
+
+use super::{
+    builder::ClientBuilder,
+    client::{Client, ConnectionState},
+};
+use crate::{
+    asynchronous::{
+        runtime::{sleep, Runtime},
+        socket::Socket as InnerSocket,
+    },
+    error::{Error, Result, Severity},
+    packet::{Packet, PacketId},
+    DisconnectReason, Event,
+};
+
+type PendingConnects = HashMap<String, oneshot::Sender<Result<()>>>;
+
+tokio::task_local! {
+    // set within the task polling the connection, which can't wait for its own end
+    static POLLING_TASK: ();
+}
+
+/// Runs the future as part of the task polling the connection, for work that task waits
+/// for without running it itself, like the callbacks of the sync client.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn within_polling_task<F: std::future::Future>(future: F) -> F::Output {
+    POLLING_TASK.scope((), future).await
+}
+
+/// Lets the task polling the connection be stopped, paused and its end be awaited.
+struct StreamTask {
+    stop: watch::Sender<bool>,
+    stopped: watch::Sender<bool>,
+    paused: watch::Sender<bool>,
+}
+
+impl Default for StreamTask {
+    fn default() -> Self {
+        StreamTask {
+            stop: watch::channel(false).0,
+            stopped: watch::channel(false).0,
+            paused: watch::channel(false).0,
+        }
+    }
+}
+
+/// A manager owns a single engine.io connection and multiplexes any number of
+/// namespaces over it, similar to the `Manager` of the JavaScript client.
+/// Connecting to `/chat` and `/admin` through the same manager only opens one
+/// websocket (or polling session).
+///
+/// The [`ClientBuilder`] passed to [`Manager::new`] describes the connection
+/// (address, TLS, headers, transport and reconnection settings). Namespaces are
+/// joined by configuring the builder returned by [`Manager::socket`] and calling
+/// [`ClientBuilder::connect`] on it, which returns a [`Client`] bound to that
+/// namespace.
+///
+/// # Example
+/// ```rust
+/// use futures_util::FutureExt;
+/// use rust_socketio::{asynchronous::Manager, asynchronous::ClientBuilder, Payload};
+/// use serde_json::json;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let manager = Manager::new(ClientBuilder::new("http://localhost:4200/"))
+///         .await
+///         .expect("connection failed");
+///
+///     let chat = manager
+///         .socket("/chat")
+///         .on("message", |payload: Payload, _| {
+///             async move { println!("chat: {:#?}", payload) }.boxed()
+///         })
+///         .connect()
+///         .await
+///         .expect("connection failed");
+///
+///     let admin = manager
+///         .socket("/admin")
+///         .connect()
+///         .await
+///         .expect("connection failed");
+///
+///     chat.emit("message", json!("hello chat")).await.expect("Server unreachable");
+///     admin.emit("message", json!("hello admin")).await.expect("Server unreachable");
+///
+///     manager.disconnect().await.expect("Disconnect failed");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Manager {
+    socket: Arc<RwLock<InnerSocket>>,
+    // builder holding the connection related settings
+    builder: Arc<RwLock<ClientBuilder>>,
+    // all namespaces that are currently connected through this manager
+    namespaces: Arc<RwLock<HashMap<String, Client>>>,
+    // namespaces that sent a `Connect` packet and wait for the server's answer
+    pending: Arc<RwLock<PendingConnects>>,
+    stream: Arc<StreamTask>,
+    runtime: Runtime,
+}
+
+impl Manager {
+    /// Opens the engine.io connection described by `builder`. Only the
+    /// connection related settings of the builder are used, namespace,
+    /// callbacks and auth data are configured per namespace via
+    /// [`Manager::socket`].
+    pub async fn new(builder: ClientBuilder) -> Result<Self> {
+        let runtime = builder.runtime.clone();
+        let socket = runtime.enter(builder.inner_create()).await?;
+        runtime.enter(socket.connect()).await?;
+
+        let manager = Manager {
+            socket: Arc::new(RwLock::new(socket)),
+            builder: Arc::new(RwLock::new(builder)),
+            namespaces: Arc::new(RwLock::new(HashMap::new())),
+            pending: Arc::new(RwLock::new(HashMap::new())),
+            stream: Arc::default(),
+            runtime,
+        };
+        manager.poll_stream();
+
+        Ok(manager)
+    }
+
+    /// Creates a manager for the connection of an already connected client.
+    pub(crate) fn from_client(client: &Client) -> Self {
+        Manager {
+            socket: client.socket(),
+            builder: client.builder(),
+            namespaces: Arc::new(RwLock::new(HashMap::new())),
+            pending: Arc::new(RwLock::new(HashMap::new())),
+            stream: Arc::default(),
+            runtime: client.runtime(),
+        }
+    }
+
+    /// Returns a [`ClientBuilder`] for the given namespace that is bound to the
+    /// connection of this manager. Register callbacks and auth data on it and
+    /// call [`ClientBuilder::connect`] to join the namespace. Transport related
+    /// settings of the returned builder are ignored.
+    pub fn socket<T: Into<String>>(&self, namespace: T) -> ClientBuilder {
+        let mut builder = ClientBuilder::new(String::new()).namespace(namespace);
+        builder.manager = Some(self.clone());
+        builder.runtime = self.runtime.clone();
+        builder
+    }
+
+    /// Returns the namespaces that are currently connected through this manager.
+    pub async fn namespaces(&self) -> Vec<String> {
+        self.namespaces.read().await.keys().cloned().collect()
+    }
+
+    /// Disconnects all namespaces and closes the underlying engine.io connection, see
+    /// [`Client::disconnect`].
+    pub async fn disconnect(&self) -> Result<()> {
+        for client in self.clients().await {
+            client.disconnect().await?;
+        }
+
+        // also close the connection if no namespace was ever joined
+        self.close().await
+    }
+
+    /// Closes the engine.io connection and waits for the task polling it to stop,
+    /// unless called from within that task.
+    pub(crate) async fn close(&self) -> Result<()> {
+        self.socket.read().await.disconnect().await?;
+
+        self.stream.stop.send_replace(true);
+        if POLLING_TASK.try_with(|_| ()).is_err() {
+            // the sender is kept by the manager, so this only returns once stopped
+            let _ = self
+                .stream
+                .stopped
+                .subscribe()
+                .wait_for(|stopped| *stopped)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a future that resolves once the task polling the connection stopped, as
+    /// the connection is closed and won't be re-established. It doesn't keep the manager
+    /// alive, a dropped manager counts as stopped.
+    pub(crate) fn stopped(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut stopped = self.stream.stopped.subscribe();
+        async move {
+            let _ = stopped.wait_for(|stopped| *stopped).await;
+        }
+    }
+
+    /// Stops reading from the connection until [`Manager::resume`] is called, see
+    /// [`Client::pause`].
+    pub(crate) fn pause(&self) {
+        self.stream.paused.send_replace(true);
+    }
+
+    /// Goes on reading from the connection after [`Manager::pause`].
+    pub(crate) fn resume(&self) {
+        self.stream.paused.send_replace(false);
+    }
+
+    /// Stops the task polling the connection and closes the connection right away,
+    /// dropping queued events.
+    pub(crate) async fn abort(&self) {
+        self.stream.stop.send_replace(true);
+        let _ = self.socket.read().await.abort().await;
+    }
+
+    /// Connects the namespace described by `builder` over the connection of this
+    /// manager and waits for the server to accept it.
+    pub(crate) async fn connect_client(&self, mut builder: ClientBuilder) -> Result<Client> {
+        // the traffic is counted per connection
+        builder.counters = self.builder.read().await.counters.clone();
+        let mut client = Client::new_shared(self.socket.clone(), builder)?;
+        client.set_manager(self.clone());
+        client.notify_transport_upgrade().await;
+
+        self.join(client).await
+    }
+
+    /// Sends the `Connect` packet for an already configured client and waits for
+    /// the server to accept its namespace.
+    pub(crate) async fn join(&self, client: Client) -> Result<Client> {
+        let nsp = client.nsp().to_owned();
+        if self.namespaces.read().await.contains_key(&nsp) {
+            return Err(Error::IllegalNamespaceAlreadyConnected(nsp));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.write().await.insert(nsp.clone(), tx);
+        self.register(client.clone()).await;
+
+        if let Err(err) = client.send_connect_packet().await {
+            self.pending.write().await.remove(&nsp);
+            self.namespaces.write().await.remove(&nsp);
+            return Err(err);
+        }
+
+        let connect_timeout = client.builder().read().await.connect_timeout;
+        let answer = match connect_timeout {
+            Some(connect_timeout) => self.runtime.timeout(connect_timeout, rx).await,
+            None => Some(rx.await),
+        };
+        let Some(answer) = answer else {
+            self.pending.write().await.remove(&nsp);
+            self.namespaces.write().await.remove(&nsp);
+            return Err(Error::ConnectTimeout());
+        };
+
+        match answer {
+            Ok(Ok(())) => {
+                client.watch_health();
+                Ok(client.with_handle())
+            }
+            Ok(Err(err)) => Err(err),
+            // the connection closed before the server answered
+            Err(_) => Err(Error::StoppedEngineIoSocket),
+        }
+    }
+
+    pub(crate) async fn register(&self, client: Client) {
+        self.namespaces
+            .write()
+            .await
+            .insert(client.nsp().to_owned(), client);
+    }
+
+    /// Removes a namespace from this manager. Returns `true` if no namespace is
+    /// left, meaning the engine.io connection is not needed anymore.
+    pub(crate) async fn unregister(&self, nsp: &str) -> bool {
+        let mut namespaces = self.namespaces.write().await;
+        namespaces.remove(nsp);
+        namespaces.is_empty()
+    }
+
+    /// Returns the client connected to the given namespace, if any.
+    pub(crate) async fn client(&self, nsp: &str) -> Option<Client> {
+        self.namespaces.read().await.get(nsp).cloned()
+    }
+
+    async fn clients(&self) -> Vec<Client> {
+        self.namespaces.read().await.values().cloned().collect()
+    }
+
+    /// Hands a packet to the client of its namespace and resolves a pending
+    /// namespace connect.
+    async fn dispatch(&self, packet: &Packet) -> Result<()> {
+        let client = self.namespaces.read().await.get(&packet.nsp).cloned();
+
+        let result = match client {
+            Some(client) => client.handle_socketio_packet(packet).await,
+            None => Ok(()),
+        };
+
+        match packet.packet_type {
+            PacketId::Connect => {
+                if let Some(tx) = self.pending.write().await.remove(&packet.nsp) {
+                    let _ = tx.send(Ok(()));
+                }
+            }
+            PacketId::ConnectError => {
+                if let Some(tx) = self.pending.write().await.remove(&packet.nsp) {
+                    self.namespaces.write().await.remove(&packet.nsp);
+                    if let Some(err) = packet.connect_error() {
+                        let _ = tx.send(Err(err));
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        result
+    }
+
+    /// Re-establishes the engine.io connection and reconnects all namespaces.
+    async fn reconnect(&self) -> Result<()> {
+        let mut address = None;
+        for client in self.namespaces.write().await.values_mut() {
+            if let Some(new_address) = client.apply_reconnect_settings().await {
+                address = Some(new_address);
+            }
+        }
+
+        let mut builder = self.builder.write().await;
+        if let Some(address) = address {
+            builder.address = address;
+        }
+        let socket = builder.inner_create().await?;
+        drop(builder);
+
+        // New inner socket that can be connected
+        *self.socket.write().await = socket;
+        self.socket.read().await.connect().await?;
+
+        self.builder.read().await.counters.reconnected();
+        for client in self.clients().await {
+            client.notify_transport_upgrade().await;
+            client.send_connect_packet().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Spawns a task that reads from the shared connection, dispatches packets to
+    /// their namespaces and reconnects if the connection gets lost. The task stops once
+    /// the connection is closed by [`Manager::close`].
+    pub(crate) fn poll_stream(&self) {
+        let manager = self.clone();
+
+        self.runtime.spawn(POLLING_TASK.scope((), async move {
+            manager.read_stream().await;
+            manager.stream.stopped.send_replace(true);
+        }));
+    }
+
+    /// Reads from the connection until it's closed and not re-established.
+    async fn read_stream(&self) {
+        let mut stop = self.stream.stop.subscribe();
+        let mut paused = self.stream.paused.subscribe();
+
+        loop {
+            let mut socket = self.socket.read().await.clone();
+            let read = async {
+                let mut reason = DisconnectReason::TransportClose;
+                // Consume the stream until it returns None and the stream is closed.
+                loop {
+                    let Some(item) = socket.next().await else {
+                        break;
+                    };
+                    // a packet read before the pause is held back, the following ones are
+                    // left with the transport
+                    let _ = paused.wait_for(|paused| !*paused).await;
+                    match item {
+                        Ok(packet) => {
+                            reason = DisconnectReason::TransportClose;
+                            if let Err(e) = self.dispatch(&packet).await {
+                                trace!("Network error occurred: {}", e);
+                            }
+                        }
+                        Err(e) => {
+                            trace!("Network error occurred: {}", e);
+                            reason = match e {
+                                Error::IncompleteResponseFromEngineIo(
+                                    rust_engineio::Error::PingTimeout(),
+                                ) => DisconnectReason::PingTimeout,
+                                _ => DisconnectReason::TransportError,
+                            };
+                            for client in self.clients().await {
+                                if reason == DisconnectReason::PingTimeout {
+                                    client.handle_ping_timeout().await;
+                                }
+                                client.handle_error(Severity::Error, &e).await;
+                                let _ = client.callback(&Event::Error, e.to_string()).await;
+                            }
+                        }
+                    }
+                }
+                reason
+            };
+
+            // stop reading once the connection is closed on purpose, even while a
+            // callback is running
+            let reason = match select(pin!(read), pin!(stop.wait_for(|stop| *stop))).await {
+                Either::Left((reason, _)) => reason,
+                Either::Right(_) => return,
+            };
+
+            // namespaces waiting for an answer won't get one on this connection
+            self.pending.write().await.clear();
+
+            for client in self.clients().await {
+                let _ = client.handle_disconnect(reason).await;
+            }
+
+            if !self.should_reconnect().await {
+                break;
+            }
+
+            let builder = self.builder.read().await;
+            let reconnect_delay_min = builder.reconnect_delay_min;
+            let reconnect_delay_max = builder.reconnect_delay_max;
+            let max_reconnect_attempts = builder.max_reconnect_attempts;
+            drop(builder);
+
+            let mut reconnect_attempts: u32 = 0;
+            let mut backoff = ExponentialBackoffBuilder::new()
+                .with_initial_interval(Duration::from_millis(reconnect_delay_min))
+                .with_max_interval(Duration::from_millis(reconnect_delay_max))
+                .build();
+
+            let mut last_error = None;
+            let reconnected = loop {
+                if *stop.borrow() {
+                    return;
+                }
+                reconnect_attempts += 1;
+                if let Some(max_reconnect_attempts) = max_reconnect_attempts {
+                    if reconnect_attempts > u32::from(max_reconnect_attempts) {
+                        trace!("Max reconnect attempts reached without success");
+                        break false;
+                    }
+                }
+                for client in self.clients().await {
+                    client.set_state(ConnectionState::Reconnecting {
+                        attempt: reconnect_attempts,
+                    });
+                }
+                match self.reconnect().await {
+                    Ok(_) => {
+                        trace!("Reconnected after {reconnect_attempts} attempts");
+                        break true;
+                    }
+                    Err(e) => {
+                        trace!("Failed to reconnect: {e:?}");
+                        let retry = e.is_retryable() || self.reconfigures_reconnect().await;
+                        if retry {
+                            for client in self.clients().await {
+                                client.handle_error(Severity::Warning, &e).await;
+                            }
+                        }
+                        last_error = Some(e);
+                        if !retry {
+                            trace!("Stopped reconnecting, retrying won't fix the error");
+                            break false;
+                        }
+                        if let Some(delay) = backoff.next_backoff() {
+                            let delay_ms = delay.as_millis();
+                            trace!("Waiting for {delay_ms}ms before reconnecting");
+                            sleep(delay).await;
+                        }
+                    }
+                }
+            };
+
+            if !reconnected {
+                let err = last_error.unwrap_or(Error::StoppedEngineIoSocket);
+                for client in self.clients().await {
+                    client.set_state(ConnectionState::Disconnected { reason });
+                    client.handle_error(Severity::Fatal, &err).await;
+                }
+                break;
+            }
+        }
+    }
+
+    /// Whether the next reconnect may use another address or auth data, which could
+    /// fix an error that retrying as is wouldn't.
+    async fn reconfigures_reconnect(&self) -> bool {
+        if self.builder.read().await.endpoint_resolver.is_some() {
+            return true;
+        }
+        for client in self.clients().await {
+            if client.reconfigures_reconnect().await {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Decides whether the connection should be re-established, based on why the
+    /// connected namespaces were disconnected.
+    async fn should_reconnect(&self) -> bool {
+        let builder = self.builder.read().await;
+        let reconnect = builder.reconnect;
+        let reconnect_on_disconnect = builder.reconnect_on_disconnect;
+        drop(builder);
+
+        let mut should_reconnect = false;
+        for client in self.clients().await {
+            should_reconnect |= match client.disconnect_reason().await {
+                Some(DisconnectReason::IoClientDisconnect) => false,
+                Some(DisconnectReason::IoServerDisconnect) => reconnect_on_disconnect,
+                _ => reconnect,
+            };
+        }
+        should_reconnect
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{sync::mpsc, time::timeout};
+
+    use super::Manager;
+    use crate::{
+        asynchronous::ClientBuilder,
+        error::{Error, Result},
+        Payload,
+    };
+
+    #[tokio::test]
+    async fn manager_multiplexes_namespaces() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let manager = Manager::new(ClientBuilder::new(url)).await?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut clients = Vec::new();
+        for nsp in ["/", "/admin"] {
+            let tx = tx.clone();
+            let client = manager
+                .socket(nsp)
+                .on("message", move |_, _| {
+                    let tx = tx.clone();
+                    async move { tx.send(nsp).unwrap() }.boxed()
+                })
+                .connect()
+                .await?;
+            clients.push(client);
+        }
+
+        // both namespaces get greeted over the same connection
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let nsp = timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+            if !received.contains(&nsp) {
+                received.push(nsp);
+            }
+        }
+        received.sort();
+        assert_eq!(received, vec![Some("/"), Some("/admin")]);
+
+        let mut namespaces = manager.namespaces().await;
+        namespaces.sort();
+        assert_eq!(namespaces, vec!["/", "/admin"]);
+
+        assert!(matches!(
+            manager.socket("/admin").connect().await,
+            Err(Error::IllegalNamespaceAlreadyConnected(nsp)) if nsp == "/admin"
+        ));
+
+        // leaving one namespace keeps the connection open for the others
+        clients[0].disconnect().await?;
+        assert_eq!(manager.namespaces().await, vec!["/admin"]);
+        clients[1].emit("test", json!("admin")).await?;
+        assert!(clients[0].emit("test", Payload::from("")).await.is_err());
+
+        manager.disconnect().await?;
+        assert!(manager.namespaces().await.is_empty());
+
+        Ok(())
+    }
+}
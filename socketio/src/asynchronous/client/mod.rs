@@ -1,5 +1,7 @@
 mod ack;
 pub(crate) mod builder;
-#[cfg(feature = "async-callbacks")]
-mod callback;
+pub(crate) mod callback;
 pub(crate) mod client;
+pub(crate) mod local;
+pub(crate) mod manager;
+pub(crate) mod sink;
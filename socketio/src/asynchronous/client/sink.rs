@@ -0,0 +1,97 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{future::BoxFuture, ready, FutureExt, Sink};
+
+use super::client::Client;
+use crate::{
+    error::{Error, Result},
+    Event, Payload,
+};
+
+/// A [`Sink`] adapter over a [`Client`] that emits every `(event, payload)` item
+/// it receives. This allows driving emits from other streams with
+/// [`futures_util::StreamExt::forward`] or [`futures_util::SinkExt::send_all`].
+///
+/// Only one emit is in flight at a time: [`Sink::poll_ready`] resolves once the
+/// previous packet was handed to the underlying engine.io transport, so a slow
+/// transport naturally slows down the producing stream.
+///
+/// Closing the sink only flushes the pending emit, the client stays connected.
+///
+/// # Example
+/// ```rust
+/// use futures_util::{stream, StreamExt};
+/// use rust_socketio::{asynchronous::ClientBuilder, Event, Payload};
+/// use serde_json::json;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let socket = ClientBuilder::new("http://localhost:4200/")
+///         .connect()
+///         .await
+///         .expect("connection failed");
+///
+///     let messages = stream::iter(0..10)
+///         .map(|i| Ok((Event::from("test"), Payload::from(json!({ "count": i })))));
+///
+///     messages
+///         .forward(socket.sink())
+///         .await
+///         .expect("Server unreachable");
+/// }
+/// ```
+pub struct ClientSink {
+    client: Client,
+    in_flight: Option<BoxFuture<'static, Result<()>>>,
+}
+
+impl ClientSink {
+    pub(crate) fn new(client: Client) -> Self {
+        ClientSink {
+            client,
+            in_flight: None,
+        }
+    }
+
+    /// Drives the currently pending emit (if any) to completion.
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if let Some(fut) = self.in_flight.as_mut() {
+            let result = ready!(fut.poll_unpin(cx));
+            self.in_flight = None;
+            return Poll::Ready(result);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<E, D> Sink<(E, D)> for ClientSink
+where
+    E: Into<Event>,
+    D: Into<Payload>,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_in_flight(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: (E, D)) -> Result<()> {
+        let this = self.get_mut();
+        let client = this.client.clone();
+        let (event, data) = (item.0.into(), item.1.into());
+
+        this.in_flight = Some(async move { client.emit(event, data).await }.boxed());
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_in_flight(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_in_flight(cx)
+    }
+}
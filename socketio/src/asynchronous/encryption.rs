@@ -0,0 +1,174 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use serde_json::Value;
+
+use crate::{error::Result, Error, Event, Payload};
+
+/// Encrypts or decrypts the bytes of a payload, see
+/// [`crate::asynchronous::ClientBuilder::encryption`].
+pub(crate) type CipherHook = Box<
+    dyn Fn(&[u8]) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>
+        + 'static
+        + Send
+        + Sync,
+>;
+
+// the first byte of a sealed payload, telling what its arguments were
+const TEXT: u8 = 0;
+const BINARY: u8 = 1;
+
+/// Seals the arguments of outgoing events and opens the ones of incoming events. A
+/// sealed payload is a single binary attachment holding the encrypted arguments: a
+/// byte telling whether they were JSON or binary, followed by the JSON array of the
+/// arguments or the bytes.
+pub(crate) struct Encryption {
+    encrypt: CipherHook,
+    decrypt: CipherHook,
+}
+
+impl Encryption {
+    pub(crate) fn new(encrypt: CipherHook, decrypt: CipherHook) -> Self {
+        Encryption { encrypt, decrypt }
+    }
+
+    /// Encrypts the arguments of an outgoing event.
+    pub(crate) fn seal(&self, payload: Payload) -> Result<Payload> {
+        let mut plaintext = BytesMut::new();
+        match payload {
+            Payload::Binary(bytes) => {
+                plaintext.put_u8(BINARY);
+                plaintext.put(bytes);
+            }
+            Payload::Text(values) => {
+                plaintext.put_u8(TEXT);
+                plaintext.put(serde_json::to_vec(&values)?.as_slice());
+            }
+            #[allow(deprecated)]
+            Payload::String(string) => {
+                plaintext.put_u8(TEXT);
+                let values = vec![Payload::string_to_value(string)];
+                plaintext.put(serde_json::to_vec(&values)?.as_slice());
+            }
+        }
+        let ciphertext = (self.encrypt)(&plaintext).map_err(Error::InvalidEncryption)?;
+        Ok(Payload::Binary(Bytes::from(ciphertext)))
+    }
+
+    /// Decrypts the arguments of an incoming event, failing if they weren't sealed.
+    pub(crate) fn open(&self, event: &Event, payload: Payload) -> Result<Payload> {
+        let Payload::Binary(ciphertext) = payload else {
+            return Err(Error::InvalidEncryption(
+                format!("the payload of {event} isn't encrypted").into(),
+            ));
+        };
+        let plaintext = (self.decrypt)(&ciphertext).map_err(Error::InvalidEncryption)?;
+        match plaintext.split_first() {
+            Some((&TEXT, json)) => Ok(Payload::Text(serde_json::from_slice::<Vec<Value>>(json)?)),
+            Some((&BINARY, bytes)) => Ok(Payload::Binary(Bytes::copy_from_slice(bytes))),
+            _ => Err(Error::InvalidEncryption(
+                format!("the payload of {event} has an unknown format").into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{sync::mpsc, time::timeout};
+
+    use super::*;
+    use crate::{
+        asynchronous::{test_util::MockServer, ClientBuilder},
+        packet::PacketId,
+    };
+
+    // not a cipher, just enough to tell sealed bytes apart from the plain ones
+    fn xor(data: &[u8]) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(data.iter().map(|byte| byte ^ 0x5a).collect())
+    }
+
+    #[test]
+    fn seals_and_opens_payloads() -> Result<()> {
+        let encryption = Encryption::new(Box::new(xor), Box::new(xor));
+        let event = Event::from("chat");
+
+        for payload in [
+            Payload::from(vec![json!("lobby"), json!({"text": "hi"})]),
+            Payload::Text(vec![]),
+            Payload::from(vec![1u8, 2, 3]),
+        ] {
+            let sealed = encryption.seal(payload.clone())?;
+            let Payload::Binary(ref bytes) = sealed else {
+                panic!("sealed payloads are binary");
+            };
+            assert!(!bytes.windows(5).any(|window| window == b"lobby"));
+            assert_eq!(encryption.open(&event, sealed)?, payload);
+        }
+
+        assert!(matches!(
+            encryption.open(&event, Payload::from(json!("plain"))),
+            Err(Error::InvalidEncryption(_))
+        ));
+        assert!(matches!(
+            encryption.open(&event, Payload::Binary(Bytes::from_static(&[0x5a ^ 7]))),
+            Err(Error::InvalidEncryption(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encrypts_event_arguments() -> Result<()> {
+        fn xor(data: &[u8]) -> std::result::Result<Vec<u8>, String> {
+            Ok(data.iter().map(|byte| byte ^ 0x5a).collect())
+        }
+
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .encryption(xor, xor)
+            .on("chat", {
+                let tx = tx.clone();
+                move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send((Event::from("chat"), payload)).unwrap() }.boxed()
+                }
+            })
+            .on(Event::Error, move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send((Event::Error, payload)).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // the server only sees the encrypted arguments, and echoes them back
+        client.emit("chat", json!({"text": "secret"})).await?;
+        let packet = server.recv().await.unwrap();
+        assert_eq!(packet.packet_type, PacketId::BinaryEvent);
+        assert!(!format!("{packet:?}").contains("secret"));
+        server.send(packet)?;
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some((
+                Event::from("chat"),
+                Payload::from(json!({"text": "secret"}))
+            ))
+        );
+
+        server.emit("chat", json!("plain"))?;
+        let (event, payload) = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, Event::Error);
+        assert!(format!("{payload:?}").contains("isn't encrypted"));
+
+        client.disconnect().await?;
+        Ok(())
+    }
+}
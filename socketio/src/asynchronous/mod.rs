@@ -1,10 +1,28 @@
-mod client;
+pub(crate) mod client;
+mod encryption;
 mod generator;
+#[cfg(feature = "outbox")]
+mod outbox;
+mod queue;
+mod rate_limit;
+mod rpc;
+mod runtime;
+#[cfg(feature = "json-schema")]
+mod schema;
 mod socket;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
-#[cfg(feature = "async")]
 pub use client::builder::ClientBuilder;
-pub use client::client::{Client, ReconnectSettings};
+pub use client::callback::HandlerConcurrency;
+pub use client::client::{Client, ConnectionState, DropBehavior, ReconnectSettings};
+pub use client::local::{LocalClient, LocalClientBuilder};
+pub use client::manager::Manager;
+pub use client::sink::ClientSink;
+pub use queue::BackpressurePolicy;
+pub use rate_limit::RateLimitPolicy;
+pub use rpc::Rpc;
+pub use rust_engineio::asynchronous::TransportUpgrade;
 
 // re-export the macro
 pub use crate::{async_any_callback, async_callback};
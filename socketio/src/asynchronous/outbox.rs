@@ -0,0 +1,260 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::trace;
+
+use crate::{
+    error::Result,
+    packet::Packet,
+    record::{decode_packet, encode_packet},
+};
+
+/// The events a client emitted while it was disconnected, kept in an append-only log
+/// so they survive a restart of the process. A line of JSON is written per packet,
+/// encoded like the packets of a [`crate::record::Recorder`]. The log is replaced by
+/// one with the packets that are left once they were sent, or couldn't all be sent.
+#[derive(Debug)]
+pub(crate) struct Outbox {
+    state: Mutex<State>,
+    // held while sending, so the packets are sent once and in order
+    flushing: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug)]
+struct State {
+    path: PathBuf,
+    file: File,
+    // the packets in the log, oldest first
+    packets: VecDeque<Packet>,
+}
+
+impl Outbox {
+    /// Opens the log at the path, creating it if it doesn't exist, and reads the
+    /// packets left in it by an earlier process.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut packets = VecDeque::new();
+        for line in BufReader::new(&file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // a line cut short by a crash is dropped, the packets before it are kept
+            match serde_json::from_str(&line)
+                .map_err(Into::into)
+                .and_then(|value| decode_packet(&value))
+            {
+                Ok(packet) => packets.push_back(packet),
+                Err(e) => trace!("Skipping a line of the outbox: {e}"),
+            }
+        }
+        Ok(Outbox {
+            state: Mutex::new(State {
+                path: path.to_owned(),
+                file,
+                packets,
+            }),
+            flushing: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.state
+            .lock()
+            .map(|state| state.packets.is_empty())
+            .unwrap_or(true)
+    }
+
+    /// Appends a packet to the log.
+    pub(crate) fn push(&self, packet: Packet) -> Result<()> {
+        let mut state = self.state.lock()?;
+        writeln!(state.file, "{}", encode_packet(&packet))?;
+        state.file.flush()?;
+        state.packets.push_back(packet);
+        Ok(())
+    }
+
+    /// Sends the packets oldest first until none are left, including the ones pushed
+    /// meanwhile. Stops at the first packet that fails to send, which is kept with the
+    /// ones after it.
+    pub(crate) async fn flush<F, Fut>(&self, mut send: F) -> Result<()>
+    where
+        F: FnMut(Packet) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let _flushing = self.flushing.lock().await;
+        let result = loop {
+            let Some(packet) = self.state.lock()?.packets.front().cloned() else {
+                break Ok(());
+            };
+            if let Err(e) = send(packet).await {
+                break Err(e);
+            }
+            self.state.lock()?.packets.pop_front();
+        };
+        self.state.lock()?.rewrite()?;
+        result
+    }
+}
+
+impl State {
+    /// Replaces the log with one holding the packets that are left. It's written next
+    /// to the log and renamed over it once it's on disk, so a crash leaves either the
+    /// old or the new log behind, never a truncated one.
+    fn rewrite(&mut self) -> Result<()> {
+        let mut name = self.path.file_name().unwrap_or_default().to_owned();
+        name.push(".tmp");
+        let temp = self.path.with_file_name(name);
+
+        let file = File::create(&temp)?;
+        let mut writer = BufWriter::new(&file);
+        for packet in &self.packets {
+            writeln!(writer, "{}", encode_packet(packet))?;
+        }
+        writer.flush()?;
+        drop(writer);
+        file.sync_data()?;
+        fs::rename(&temp, &self.path)?;
+        sync_dir(&self.path)?;
+
+        self.file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Syncs the directory of the file, so a rename within it is on disk as well.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Ok(File::open(dir)?.sync_all()?)
+}
+
+/// Directories can't be opened, and don't need to be synced, on other platforms.
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Error, Event, Payload};
+
+    fn event(number: i32) -> Packet {
+        Packet::new_from_payload(
+            Payload::from(json!(number)),
+            Event::from("reading"),
+            "/",
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn survives_a_restart() -> Result<()> {
+        let path = env::temp_dir().join(format!("outbox-restart-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let outbox = Outbox::open(&path)?;
+        for number in 0..3 {
+            outbox.push(event(number))?;
+        }
+        drop(outbox);
+
+        // the packets are sent until one fails, the others are kept for the next try
+        let outbox = Outbox::open(&path)?;
+        let mut sent = Vec::new();
+        let result = outbox
+            .flush(|packet| {
+                let failed = sent.len() == 1;
+                if !failed {
+                    sent.push(packet);
+                }
+                async move {
+                    match failed {
+                        true => Err(Error::StoppedEngineIoSocket),
+                        false => Ok(()),
+                    }
+                }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(sent, vec![event(0)]);
+        drop(outbox);
+
+        let outbox = Outbox::open(&path)?;
+        outbox.push(event(3))?;
+        let mut sent = Vec::new();
+        outbox
+            .flush(|packet| {
+                sent.push(packet);
+                async { Ok(()) }
+            })
+            .await?;
+        assert_eq!(sent, vec![event(1), event(2), event(3)]);
+        assert!(outbox.is_empty());
+        assert!(fs::read_to_string(&path)?.is_empty());
+        // the log was replaced by the one written next to it
+        let temp = format!("outbox-restart-{}.jsonl.tmp", std::process::id());
+        assert!(!path.with_file_name(temp).exists());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sends_events_once_connected() -> Result<()> {
+        use crate::asynchronous::{test_util::MockServer, ClientBuilder};
+
+        let path = env::temp_dir().join(format!("outbox-connect-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        // left by an earlier process
+        Outbox::open(&path)?.push(event(1))?;
+
+        let (server, transport) = MockServer::new();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .outbox(&path)
+            .connect()
+            .await?;
+        server.recv().await;
+        server.assert_emitted("reading", json!(1)).await;
+
+        client.emit("reading", json!(2)).await?;
+        server.assert_emitted("reading", json!(2)).await;
+
+        // events emitted while disconnected are kept instead of failing
+        client.disconnect().await?;
+        client.emit("reading", json!(3)).await?;
+        client.emit_volatile("reading", json!(4)).await?;
+        let outbox = Outbox::open(&path)?;
+        assert_eq!(
+            outbox.state.lock()?.packets.iter().collect::<Vec<_>>(),
+            vec![&event(3)]
+        );
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
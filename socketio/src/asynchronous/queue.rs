@@ -0,0 +1,408 @@
+use std::{
+    collections::VecDeque,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use log::trace;
+use tokio::sync::Notify;
+
+use crate::{error::Result, packet::Packet, Error};
+
+/// Decides what happens to an event that is emitted while the outgoing queue of a
+/// client is full, see [`crate::asynchronous::ClientBuilder::outgoing_queue`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BackpressurePolicy {
+    /// Waits until the queue has room again.
+    #[default]
+    Block,
+    /// Drops the emitted event.
+    DropNewest,
+    /// Drops the oldest queued event to make room for the emitted one.
+    DropOldest,
+    /// Fails with [`Error::OutgoingQueueFull`].
+    Error,
+}
+
+/// The size from which events go to the bulk lane of the outgoing queue by default, see
+/// [`crate::asynchronous::ClientBuilder::bulk_threshold`].
+pub(crate) const DEFAULT_BULK_THRESHOLD: usize = 64 * 1024;
+
+/// The lanes of the outgoing queue. Control packets are small events, which are written
+/// before the bulk packets, events whose data and attachments add up to the bulk
+/// threshold. The order is kept within each lane.
+#[derive(Debug, Default)]
+struct Lanes {
+    control: VecDeque<Packet>,
+    bulk: VecDeque<Packet>,
+}
+
+impl Lanes {
+    fn len(&self) -> usize {
+        self.control.len() + self.bulk.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, packet: Packet, is_bulk: bool) {
+        if is_bulk {
+            self.bulk.push_back(packet);
+        } else {
+            self.control.push_back(packet);
+        }
+    }
+
+    /// Drops the oldest packet of the lane, or of the other lane if it's empty.
+    fn drop_oldest(&mut self, is_bulk: bool) {
+        let (lane, other) = if is_bulk {
+            (&mut self.bulk, &mut self.control)
+        } else {
+            (&mut self.control, &mut self.bulk)
+        };
+        if lane.pop_front().is_none() {
+            other.pop_front();
+        }
+    }
+}
+
+/// A bounded queue of packets waiting to be written to the server by a background
+/// task. Large events take the bulk lane, so small ones aren't stuck behind them on
+/// slow links.
+#[derive(Debug)]
+pub(crate) struct OutgoingQueue {
+    lanes: Mutex<Lanes>,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    bulk_threshold: usize,
+    closed: AtomicBool,
+    /// Whether the writer is writing the packets it took last.
+    writing: AtomicBool,
+    /// Wakes the writer once a packet was queued or the queue closed.
+    pushed: Notify,
+    /// Wakes blocked senders once the writer took the queued packets.
+    popped: Notify,
+    /// Wakes flushing senders once the writer wrote the packets it took.
+    written: Notify,
+}
+
+impl OutgoingQueue {
+    /// Creates a queue holding at most `capacity` packets, in which events of at least
+    /// `bulk_threshold` bytes take the bulk lane.
+    pub(crate) fn new(capacity: usize, policy: BackpressurePolicy, bulk_threshold: usize) -> Self {
+        OutgoingQueue {
+            lanes: Mutex::default(),
+            capacity: capacity.max(1),
+            policy,
+            bulk_threshold,
+            closed: AtomicBool::default(),
+            writing: AtomicBool::default(),
+            pushed: Notify::new(),
+            popped: Notify::new(),
+            written: Notify::new(),
+        }
+    }
+
+    /// Queues a packet in its lane, applying the policy if the queue is full.
+    pub(crate) async fn push(&self, packet: Packet) -> Result<()> {
+        let is_bulk = self.is_bulk(&packet);
+        loop {
+            // register for wake ups before checking, so a packet taken in between
+            // isn't missed
+            let mut popped = pin!(self.popped.notified());
+            popped.as_mut().enable();
+
+            {
+                let mut lanes = self.lanes.lock()?;
+                if self.closed.load(Ordering::Acquire) {
+                    return Err(Error::StoppedEngineIoSocket);
+                }
+                if lanes.len() < self.capacity {
+                    lanes.push(packet, is_bulk);
+                    self.pushed.notify_one();
+                    return Ok(());
+                }
+                match self.policy {
+                    BackpressurePolicy::Block => (),
+                    BackpressurePolicy::DropNewest => {
+                        trace!("Outgoing queue is full, dropped the emitted packet");
+                        return Ok(());
+                    }
+                    BackpressurePolicy::DropOldest => {
+                        trace!("Outgoing queue is full, dropped the oldest packet");
+                        lanes.drop_oldest(is_bulk);
+                        lanes.push(packet, is_bulk);
+                        self.pushed.notify_one();
+                        return Ok(());
+                    }
+                    BackpressurePolicy::Error => return Err(Error::OutgoingQueueFull()),
+                }
+            }
+
+            popped.await;
+        }
+    }
+
+    /// Queues a packet in its lane if the queue has room, without waiting or applying the
+    /// policy. Returns whether it was queued.
+    pub(crate) fn try_push(&self, packet: Packet) -> Result<bool> {
+        let is_bulk = self.is_bulk(&packet);
+        let mut lanes = self.lanes.lock()?;
+        if self.closed.load(Ordering::Acquire) {
+            return Err(Error::StoppedEngineIoSocket);
+        }
+        if lanes.len() >= self.capacity {
+            return Ok(false);
+        }
+        lanes.push(packet, is_bulk);
+        self.pushed.notify_one();
+        Ok(true)
+    }
+
+    /// Takes all queued control packets followed by at most one bulk packet, waiting for
+    /// one if the queue is empty, so control packets queued while a bulk packet is
+    /// written are written before the next one. Returns `None` once the queue is closed
+    /// and drained. The writer has to call [`OutgoingQueue::written`] once it wrote the
+    /// taken packets.
+    pub(crate) async fn pop(&self) -> Option<Vec<Packet>> {
+        loop {
+            let mut pushed = pin!(self.pushed.notified());
+            pushed.as_mut().enable();
+
+            let packets: Vec<Packet> = {
+                let mut lanes = self.lanes.lock().ok()?;
+                if !lanes.is_empty() {
+                    self.writing.store(true, Ordering::Release);
+                }
+                let bulk = lanes.bulk.pop_front();
+                lanes.control.drain(..).chain(bulk).collect()
+            };
+            if !packets.is_empty() {
+                self.popped.notify_waiters();
+                return Some(packets);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+
+            pushed.await;
+        }
+    }
+
+    /// Marks the packets taken last as written.
+    pub(crate) fn written(&self) {
+        if let Ok(_lanes) = self.lanes.lock() {
+            self.writing.store(false, Ordering::Release);
+        }
+        self.written.notify_waiters();
+    }
+
+    /// Waits until the writer wrote all packets queued so far.
+    pub(crate) async fn flush(&self) -> Result<()> {
+        loop {
+            let mut written = pin!(self.written.notified());
+            written.as_mut().enable();
+
+            {
+                let lanes = self.lanes.lock()?;
+                if lanes.is_empty() && !self.writing.load(Ordering::Acquire) {
+                    return Ok(());
+                }
+            }
+
+            written.await;
+        }
+    }
+
+    /// Drops the queued packets and closes the queue.
+    pub(crate) fn clear(&self) {
+        if let Ok(mut lanes) = self.lanes.lock() {
+            *lanes = Lanes::default();
+        }
+        self.close();
+    }
+
+    /// Rejects further packets and lets the writer stop once the queue is drained.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.pushed.notify_one();
+        self.popped.notify_waiters();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lanes.lock().map_or(0, |lanes| lanes.len())
+    }
+
+    fn is_bulk(&self, packet: &Packet) -> bool {
+        let data = packet.data.as_ref().map_or(0, |data| data.len());
+        let attachments = packet
+            .attachments
+            .iter()
+            .flatten()
+            .map(|attachment| attachment.len());
+        data + attachments.sum::<usize>() >= self.bulk_threshold
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::packet::PacketId;
+
+    fn event(data: &str) -> Packet {
+        Packet::new(
+            PacketId::Event,
+            "/".to_owned(),
+            Some(data.to_owned()),
+            None,
+            0,
+            None,
+            None,
+        )
+    }
+
+    fn data(packets: Option<Vec<Packet>>) -> Vec<String> {
+        packets
+            .into_iter()
+            .flatten()
+            .filter_map(|packet| packet.data)
+            .map(|data| data.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn drop_policies() -> Result<()> {
+        let queue = OutgoingQueue::new(2, BackpressurePolicy::DropNewest, DEFAULT_BULK_THRESHOLD);
+        queue.push(event("1")).await?;
+        queue.push(event("2")).await?;
+        queue.push(event("3")).await?;
+        assert_eq!(queue.len(), 2);
+        assert_eq!(data(queue.pop().await), vec!["1", "2"]);
+
+        let queue = OutgoingQueue::new(2, BackpressurePolicy::DropOldest, DEFAULT_BULK_THRESHOLD);
+        queue.push(event("1")).await?;
+        queue.push(event("2")).await?;
+        queue.push(event("3")).await?;
+        assert_eq!(queue.len(), 2);
+        assert_eq!(data(queue.pop().await), vec!["2", "3"]);
+
+        let queue = OutgoingQueue::new(1, BackpressurePolicy::Error, DEFAULT_BULK_THRESHOLD);
+        queue.push(event("1")).await?;
+        assert!(matches!(
+            queue.push(event("2")).await,
+            Err(Error::OutgoingQueueFull())
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lanes() -> Result<()> {
+        let queue = OutgoingQueue::new(4, BackpressurePolicy::DropOldest, 10);
+        queue.push(event("\"large one\"")).await?;
+        queue.push(event("\"large two\"")).await?;
+        queue.push(event("1")).await?;
+        // control packets are taken first, bulk ones one at a time
+        assert_eq!(data(queue.pop().await), vec!["1", "\"large one\""]);
+        queue.written();
+        queue.push(event("2")).await?;
+        assert_eq!(data(queue.pop().await), vec!["2", "\"large two\""]);
+        queue.written();
+
+        // attachments count towards the size
+        let mut binary = event("1");
+        binary.attachments = Some(vec![vec![0; 10].into()]);
+        queue.push(binary).await?;
+        queue.push(event("2")).await?;
+        assert_eq!(data(queue.pop().await), vec!["2", "1"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn block_policy() -> Result<()> {
+        let queue = Arc::new(OutgoingQueue::new(
+            1,
+            BackpressurePolicy::Block,
+            DEFAULT_BULK_THRESHOLD,
+        ));
+        queue.push(event("1")).await?;
+
+        let blocked = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(event("2")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(data(queue.pop().await), vec!["1"]);
+        timeout(Duration::from_secs(1), blocked)
+            .await
+            .expect("the sender is woken up")
+            .unwrap()?;
+        assert_eq!(data(queue.pop().await), vec!["2"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn try_push() -> Result<()> {
+        let queue = OutgoingQueue::new(1, BackpressurePolicy::Block, DEFAULT_BULK_THRESHOLD);
+        assert!(queue.try_push(event("1"))?);
+        // dropped even though the policy would wait
+        assert!(!queue.try_push(event("2"))?);
+        assert_eq!(data(queue.pop().await), vec!["1"]);
+
+        queue.close();
+        assert!(queue.try_push(event("3")).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flush() -> Result<()> {
+        let queue = Arc::new(OutgoingQueue::new(
+            2,
+            BackpressurePolicy::Block,
+            DEFAULT_BULK_THRESHOLD,
+        ));
+        queue.flush().await?;
+        queue.push(event("1")).await?;
+
+        let flushed = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.flush().await }
+        });
+        assert_eq!(data(queue.pop().await), vec!["1"]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // taken, but not written yet
+        assert!(!flushed.is_finished());
+
+        queue.written();
+        timeout(Duration::from_secs(1), flushed)
+            .await
+            .expect("the flush finishes")
+            .unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn close() -> Result<()> {
+        let queue = Arc::new(OutgoingQueue::new(
+            1,
+            BackpressurePolicy::Block,
+            DEFAULT_BULK_THRESHOLD,
+        ));
+        queue.push(event("1")).await?;
+        queue.close();
+
+        assert!(queue.push(event("2")).await.is_err());
+        assert_eq!(data(queue.pop().await), vec!["1"]);
+        assert_eq!(queue.pop().await, None);
+        Ok(())
+    }
+}
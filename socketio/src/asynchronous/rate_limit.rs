@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+use super::runtime::{self, Instant};
+use crate::{error::Result, Error};
+
+/// Decides what happens to an event that is emitted faster than the rate limit of a
+/// client allows, see [`crate::asynchronous::ClientBuilder::rate_limit`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum RateLimitPolicy {
+    /// Waits until the event may be sent.
+    #[default]
+    Wait,
+    /// Fails with [`Error::RateLimited`].
+    Error,
+}
+
+/// A token bucket, refilled with a token every interval up to the burst, kept as
+/// the time the bucket is full again.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    // how far the bucket may be from full, the time it takes to refill the burst
+    tolerance: Duration,
+    policy: RateLimitPolicy,
+    // locked while waiting for a token, so waiting events are sent in order
+    full_at: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_second: u32, burst: u32, policy: RateLimitPolicy) -> Self {
+        let interval = Duration::from_secs(1) / per_second.max(1);
+        RateLimiter {
+            interval,
+            tolerance: interval * (burst.max(1) - 1),
+            policy,
+            full_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Takes a token, waiting for one or failing with [`Error::RateLimited`] if the
+    /// bucket is empty, depending on the policy.
+    pub(crate) async fn acquire(&self) -> Result<()> {
+        let mut full_at = self.full_at.lock().await;
+        let wait = full_at
+            .saturating_duration_since(Instant::now())
+            .saturating_sub(self.tolerance);
+        if !wait.is_zero() {
+            match self.policy {
+                RateLimitPolicy::Wait => runtime::sleep(wait).await,
+                RateLimitPolicy::Error => return Err(Error::RateLimited()),
+            }
+        }
+        *full_at = (*full_at).max(Instant::now()) + self.interval;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_after_the_burst() -> Result<()> {
+        let limiter = RateLimiter::new(20, 3, RateLimitPolicy::Wait);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await?;
+        }
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        limiter.acquire().await?;
+        limiter.acquire().await?;
+        // two tokens refill in two intervals of 50ms
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_after_the_burst() -> Result<()> {
+        let limiter = RateLimiter::new(20, 2, RateLimitPolicy::Error);
+        limiter.acquire().await?;
+        limiter.acquire().await?;
+        assert!(matches!(limiter.acquire().await, Err(Error::RateLimited())));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        limiter.acquire().await?;
+        assert!(matches!(limiter.acquire().await, Err(Error::RateLimited())));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refills_up_to_the_burst() -> Result<()> {
+        let limiter = RateLimiter::new(100, 2, RateLimitPolicy::Error);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // ten intervals passed, but only two tokens fit
+        limiter.acquire().await?;
+        limiter.acquire().await?;
+        assert!(limiter.acquire().await.is_err());
+        Ok(())
+    }
+}
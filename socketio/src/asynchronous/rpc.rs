@@ -0,0 +1,95 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A remote procedure of the server, called with [`crate::asynchronous::Client::call`].
+/// The request is emitted as the only argument of the event, and the server answers
+/// with the response as the first argument of its ack, like a JavaScript server does
+/// with `socket.on(event, (request, callback) => callback(response))`.
+///
+/// ```rust
+/// use rust_socketio::asynchronous::Rpc;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize)]
+/// struct Search {
+///     query: String,
+///     limit: u32,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Hit {
+///     title: String,
+/// }
+///
+/// struct SearchRpc;
+///
+/// impl Rpc for SearchRpc {
+///     const EVENT: &'static str = "search";
+///     type Request = Search;
+///     type Response = Vec<Hit>;
+/// }
+/// ```
+pub trait Rpc {
+    /// The event the request is emitted with.
+    const EVENT: &'static str;
+    /// The request, serialized to JSON.
+    type Request: Serialize;
+    /// The response, deserialized from the JSON the server acks with.
+    type Response: DeserializeOwned;
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        asynchronous::{test_util::MockServer, ClientBuilder},
+        error::Result,
+    };
+
+    #[tokio::test]
+    async fn calls_remote_procedures() -> Result<()> {
+        struct Add;
+
+        impl Rpc for Add {
+            const EVENT: &'static str = "add";
+            type Request = (i32, i32);
+            type Response = i32;
+        }
+
+        let (server, transport) = MockServer::new();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .ack_timeout(Duration::from_millis(100))
+            .connect()
+            .await?;
+        server.recv().await;
+
+        let calling = client.clone();
+        let sum = tokio::spawn(async move { calling.call::<Add>((1, 2)).await });
+        let request = server.recv().await.unwrap();
+        assert_eq!(request.data.as_deref(), Some(r#"["add",[1,2]]"#));
+        server.ack(request.id.unwrap(), json!(3))?;
+        assert_eq!(sum.await.unwrap()?, 3);
+
+        // an answer of the wrong type fails, as does no answer
+        let calling = client.clone();
+        let sum = tokio::spawn(async move { calling.call::<Add>((1, 2)).await });
+        let request = server.recv().await.unwrap();
+        server.ack(request.id.unwrap(), json!("three"))?;
+        assert!(matches!(
+            sum.await.unwrap(),
+            Err(crate::Error::InvalidJson(_))
+        ));
+        assert!(matches!(
+            client.call::<Add>((1, 2)).await,
+            Err(crate::Error::AckTimeout())
+        ));
+
+        client.disconnect().await?;
+        Ok(())
+    }
+}
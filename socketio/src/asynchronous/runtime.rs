@@ -0,0 +1,240 @@
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+/// Spawns a background task. On native targets it runs on the current tokio runtime, on
+/// wasm targets on the event loop of the javascript host.
+pub(crate) fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::runtime::Handle::current().spawn(future);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Spawns a task that doesn't have to be `Send`. On native targets it runs on the current
+/// [`tokio::task::LocalSet`], on wasm targets on the event loop of the javascript host.
+pub(crate) fn spawn_local<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::task::spawn_local(future);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Spawns a background task like [`spawn`], unless called outside of a tokio runtime,
+/// e.g. from a destructor running after the runtime shut down.
+pub(crate) fn try_spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(future);
+    }
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Runs the future to completion unless the duration elapses first, in which case
+/// `None` is returned.
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return tokio::time::timeout(duration, future).await.ok();
+    #[cfg(target_arch = "wasm32")]
+    match futures_util::future::select(Box::pin(future), Box::pin(sleep(duration))).await {
+        futures_util::future::Either::Left((output, _)) => Some(output),
+        futures_util::future::Either::Right(_) => None,
+    }
+}
+
+/// Waits until the duration has elapsed.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    send_wrapper::SendWrapper::new(gloo_timers::future::sleep(duration)).await;
+}
+
+/// The tokio runtime a client runs on, see
+/// [`crate::asynchronous::ClientBuilder::runtime_handle`].
+/// Without a handle, the client runs on the runtime it was connected in.
+#[derive(Clone, Default)]
+pub(crate) struct Runtime {
+    #[cfg(not(target_arch = "wasm32"))]
+    handle: Option<tokio::runtime::Handle>,
+}
+
+impl Runtime {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new(handle: tokio::runtime::Handle) -> Self {
+        Runtime {
+            handle: Some(handle),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn handle(&self) -> Option<&tokio::runtime::Handle> {
+        self.handle.as_ref()
+    }
+
+    /// Polls the future within the runtime, so it can spawn tasks on it and use its timers
+    /// and sockets when awaited from another executor.
+    pub(crate) async fn enter<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(handle) = &self.handle {
+            futures_util::pin_mut!(future);
+            return std::future::poll_fn(|cx| {
+                let _entered = handle.enter();
+                future.as_mut().poll(cx)
+            })
+            .await;
+        }
+        future.await
+    }
+
+    /// Spawns a background task on the runtime, see [`spawn`].
+    pub(crate) fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(handle) = &self.handle {
+            handle.spawn(future);
+            return;
+        }
+        spawn(future);
+    }
+
+    /// Spawns a background task on the runtime, see [`try_spawn`].
+    pub(crate) fn try_spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(handle) = &self.handle {
+            handle.spawn(future);
+            return;
+        }
+        try_spawn(future);
+    }
+
+    /// Like [`timeout`], with the timer of the runtime.
+    pub(crate) async fn timeout<F: Future>(
+        &self,
+        duration: Duration,
+        future: F,
+    ) -> Option<F::Output> {
+        self.enter(timeout(duration, future)).await
+    }
+
+    /// Like [`sleep`], with the timer of the runtime.
+    pub(crate) async fn sleep(&self, duration: Duration) {
+        self.enter(sleep(duration)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake},
+        thread::{self, Thread},
+    };
+
+    use futures_util::FutureExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        asynchronous::{test_util::MockServer, ClientBuilder},
+        error::Result,
+        packet::PacketId,
+    };
+
+    // an executor of its own, polling the future on the current thread
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn runs_outside_of_the_runtime() -> Result<()> {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+
+        let client = block_on(
+            ClientBuilder::new("http://localhost/")
+                .mock_transport(transport)
+                .reconnect(false)
+                .runtime_handle(runtime.handle().clone())
+                .connect(),
+        )?;
+        block_on(client.emit_with_timeout("answer", json!(42), Duration::from_secs(1)))?;
+        runtime.block_on(async {
+            assert_eq!(
+                server.recv().await.map(|packet| packet.packet_type),
+                Some(PacketId::Connect)
+            );
+            server.assert_emitted("answer", json!(42)).await;
+        });
+
+        block_on(client.disconnect())?;
+        Ok(())
+    }
+
+    #[test]
+    fn spawns_on_the_given_runtime() -> Result<()> {
+        let isolated = tokio::runtime::Builder::new_multi_thread()
+            .thread_name("isolated")
+            .enable_all()
+            .build()
+            .unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let client = runtime.block_on(
+            ClientBuilder::new("http://localhost/")
+                .mock_transport(transport)
+                .reconnect(false)
+                .runtime_handle(isolated.handle().clone())
+                .on("greet", move |_, _| {
+                    let _ = tx.send(thread::current().name().map(str::to_owned));
+                    async {}.boxed()
+                })
+                .connect(),
+        )?;
+        server.emit("greet", json!("hello"))?;
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)).unwrap().as_deref(),
+            Some("isolated")
+        );
+
+        runtime.block_on(client.disconnect())?;
+        Ok(())
+    }
+}
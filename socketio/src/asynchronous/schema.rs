@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use jsonschema::Validator;
+use serde_json::Value;
+
+use crate::{error::Result, Error, Event, Payload};
+
+/// The compiled JSON schemas the payloads of incoming events are checked against, see
+/// [`crate::asynchronous::ClientBuilder::schema`].
+#[derive(Default)]
+pub(crate) struct Schemas {
+    validators: HashMap<Event, Validator>,
+}
+
+impl Schemas {
+    /// Compiles the schemas registered with the builder.
+    pub(crate) fn compile(schemas: &HashMap<Event, Value>) -> Result<Self> {
+        let validators = schemas
+            .iter()
+            .map(|(event, schema)| {
+                jsonschema::validator_for(schema)
+                    .map(|validator| (event.clone(), validator))
+                    .map_err(|e| Error::InvalidSchema(event.to_string(), e.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Schemas { validators })
+    }
+
+    /// Checks the arguments of the event, as a JSON array, against its schema. Events
+    /// without a schema and binary payloads always pass.
+    pub(crate) fn check(&self, event: &Event, payload: &Payload) -> Result<()> {
+        let Some(validator) = self.validators.get(event) else {
+            return Ok(());
+        };
+        let arguments = match payload {
+            Payload::Text(values) => Value::Array(values.clone()),
+            #[allow(deprecated)]
+            Payload::String(string) => Value::Array(vec![
+                serde_json::from_str(string).unwrap_or_else(|_| Value::String(string.clone()))
+            ]),
+            Payload::Binary(_) => return Ok(()),
+        };
+        validator
+            .validate(&arguments)
+            .map_err(|e| Error::InvalidPayload(event.to_string(), e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{sync::mpsc, time::timeout};
+
+    use super::*;
+    use crate::asynchronous::{test_util::MockServer, ClientBuilder};
+
+    #[test]
+    fn checks_the_arguments() -> Result<()> {
+        let schemas = Schemas::compile(&HashMap::from([(
+            Event::from("chat"),
+            json!({"type": "array", "prefixItems": [{"type": "string"}], "minItems": 1}),
+        )]))?;
+
+        let chat = Event::from("chat");
+        assert!(schemas.check(&chat, &Payload::from(json!("hi"))).is_ok());
+        assert!(matches!(
+            schemas.check(&chat, &Payload::from(json!(1))),
+            Err(Error::InvalidPayload(event, _)) if event == "chat"
+        ));
+        assert!(schemas.check(&chat, &Payload::Text(vec![])).is_err());
+        assert!(schemas
+            .check(&Event::from("typing"), &Payload::from(json!(1)))
+            .is_ok());
+
+        assert!(matches!(
+            Schemas::compile(&HashMap::from([(Event::from("chat"), json!({"type": 1}))])),
+            Err(Error::InvalidSchema(..))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn routes_invalid_payloads_to_the_error_callback() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .schema(
+                "chat",
+                json!({"type": "array", "prefixItems": [{"type": "string"}]}),
+            )
+            .on("chat", {
+                let tx = tx.clone();
+                move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send((Event::from("chat"), payload)).unwrap() }.boxed()
+                }
+            })
+            .on(Event::Error, move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send((Event::Error, payload)).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        server.emit("chat", json!(1))?;
+        server.emit("chat", json!("hi"))?;
+        let (event, payload) = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, Event::Error);
+        assert!(format!("{payload:?}").contains("does not match its JSON schema"));
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some((Event::from("chat"), Payload::from(json!("hi"))))
+        );
+
+        client.disconnect().await?;
+        Ok(())
+    }
+}
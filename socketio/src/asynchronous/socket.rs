@@ -1,14 +1,22 @@
-use super::generator::StreamGenerator;
+use super::{
+    generator::StreamGenerator,
+    queue::{BackpressurePolicy, OutgoingQueue},
+    rate_limit::RateLimiter,
+    runtime::{self, Runtime},
+};
 use crate::{
-    error::Result,
-    packet::{Packet, PacketId},
-    Error, Event, Payload,
+    error::{ErrorContext, Result},
+    packet::{Direction, EncodeBuffer, Packet, PacketId, PacketObserver},
+    stats::Counters,
+    trace, Error, Event, Payload,
 };
-use async_stream::try_stream;
+use async_stream::stream;
 use bytes::Bytes;
 use futures_util::{Stream, StreamExt};
+use log::trace;
 use rust_engineio::{
-    asynchronous::Client as EngineClient, Packet as EnginePacket, PacketId as EnginePacketId,
+    asynchronous::{Client as EngineClient, TransportUpgrade},
+    Packet as EnginePacket, PacketId as EnginePacketId,
 };
 use std::{
     fmt::Debug,
@@ -17,28 +25,107 @@ use std::{
         atomic::{AtomicBool, AtomicI32, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
+/// How the packets of the server are read.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ReadOptions {
+    // packets failing to decode are yielded as errors without ending the stream
+    pub(crate) skip_malformed: bool,
+    // packets are decoded with `Packet::decode_strict`
+    pub(crate) strict: bool,
+    pub(crate) max_attachments: Option<u8>,
+    pub(crate) max_attachment_bytes: Option<usize>,
+    // of the frame and its attachments together
+    pub(crate) max_packet_bytes: Option<usize>,
+    // None waits for the attachments of a packet as long as it takes
+    pub(crate) attachment_timeout: Option<Duration>,
+}
+
+impl ReadOptions {
+    /// Fails if the bytes read of a packet so far exceed the limit.
+    fn check_packet_bytes(&self, bytes: usize) -> Result<()> {
+        match self.max_packet_bytes {
+            Some(max) if bytes > max => Err(Error::IllegalPacketSize(format!(
+                "{bytes} bytes, at most {max} are allowed"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Socket {
     engine_client: Arc<EngineClient>,
     connected: Arc<AtomicBool>,
     generator: StreamGenerator<Packet>,
     ack_id: Arc<AtomicI32>,
+    // events are written by a background task if set
+    outgoing: Option<Arc<OutgoingQueue>>,
+    // shared by the sockets of a client, so reconnecting doesn't refill it
+    rate_limiter: Option<Arc<RateLimiter>>,
+    encode_buffer: Arc<EncodeBuffer>,
+    counters: Arc<Counters>,
+    observer: Option<PacketObserver>,
+    // entered to write and close, which might happen outside of the runtime
+    runtime: Runtime,
 }
 
 impl Socket {
-    /// Creates an instance of `Socket`.
-    pub(super) fn new(engine_client: EngineClient) -> Result<Self> {
-        let connected = Arc::new(AtomicBool::default());
+    /// Creates an instance of `Socket`, counting its traffic in `counters` and passing
+    /// every packet to `observer`. With an outgoing queue of the given capacity, policy
+    /// and bulk threshold, emitted events are written by a background task. With a rate limiter,
+    /// emitted events take a token of it first. Packets are read as set by `options`, and
+    /// the socket is written and closed within `runtime`.
+    pub(super) fn new(
+        engine_client: EngineClient,
+        outgoing_queue: Option<(usize, BackpressurePolicy, usize)>,
+        rate_limiter: Option<Arc<RateLimiter>>,
+        counters: Arc<Counters>,
+        observer: Option<PacketObserver>,
+        options: ReadOptions,
+        runtime: Runtime,
+    ) -> Result<Self> {
         let ack_id = Arc::new(AtomicI32::new(-1));
 
-        Ok(Socket {
+        let socket = Socket {
             engine_client: Arc::new(engine_client.clone()),
-            connected: connected.clone(),
+            connected: Arc::new(AtomicBool::default()),
             ack_id: ack_id.clone(),
-            generator: StreamGenerator::new(Self::stream(engine_client, connected, ack_id)),
-        })
+            generator: StreamGenerator::new(Self::stream(
+                engine_client,
+                ack_id,
+                counters.clone(),
+                observer.clone(),
+                options,
+            )),
+            outgoing: outgoing_queue.map(|(capacity, policy, bulk_threshold)| {
+                Arc::new(OutgoingQueue::new(capacity, policy, bulk_threshold))
+            }),
+            rate_limiter,
+            encode_buffer: Arc::default(),
+            counters,
+            observer,
+            runtime,
+        };
+        if let Some(queue) = socket.outgoing.clone() {
+            socket.runtime.spawn(socket.clone().write_queued(queue));
+        }
+
+        Ok(socket)
+    }
+
+    /// Writes the queued packets until the queue is closed. The control packets queued
+    /// while a write is in progress are written at once along with the next bulk packet,
+    /// which polling transports send as a single request.
+    async fn write_queued(self, queue: Arc<OutgoingQueue>) {
+        while let Some(packets) = queue.pop().await {
+            if let Err(err) = self.write(packets).await {
+                trace!("Failed to write queued packets: {}", err);
+            }
+            queue.written();
+        }
     }
 
     /// Connects to the server. This includes a connection of the underlying
@@ -46,24 +133,23 @@ impl Socket {
     pub async fn connect(&self) -> Result<()> {
         self.engine_client.connect().await?;
 
-        // store the connected value as true, if the connection process fails
-        // later, the value will be updated
+        // the connection state of the single namespaces is tracked by the
+        // clients, this only reflects whether the socket is usable at all
         self.connected.store(true, Ordering::Release);
 
         Ok(())
     }
 
-    //[KDJ]
-    pub async fn wait_connect_incoming_sid(&self) -> Result<()> {
-        self.engine_client.wait_connect_incoming_sid().await?;
-        Ok(())
-    }
-
     /// Disconnects from the server by sending a socket.io `Disconnect` packet. This results
-    /// in the underlying engine.io transport to get closed as well.
+    /// in the underlying engine.io transport to get closed as well. Queued packets are
+    /// written before.
     pub async fn disconnect(&self) -> Result<()> {
+        if let Some(queue) = &self.outgoing {
+            queue.close();
+            queue.flush().await?;
+        }
         if self.is_engineio_connected() {
-            self.engine_client.disconnect().await?;
+            self.runtime.enter(self.engine_client.disconnect()).await?;
         }
         if self.connected.load(Ordering::Acquire) {
             self.connected.store(false, Ordering::Release);
@@ -76,22 +162,113 @@ impl Socket {
         Ok(())
     }
 
-    /// Sends a `socket.io` packet to the server using the `engine.io` client.
+    /// Disconnects like [`Socket::disconnect`], but drops the queued packets.
+    pub(crate) async fn abort(&self) -> Result<()> {
+        if let Some(queue) = &self.outgoing {
+            queue.clear();
+        }
+        self.disconnect().await
+    }
+
+    /// Waits until the queued packets are written, if the socket has an outgoing queue.
+    pub(crate) async fn flush(&self) -> Result<()> {
+        match &self.outgoing {
+            Some(queue) => queue.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Sends a `socket.io` packet to the server using the `engine.io` client. Events
+    /// only get queued if the socket has an outgoing queue.
     pub async fn send(&self, packet: Packet) -> Result<()> {
         if !self.is_engineio_connected() || !self.connected.load(Ordering::Acquire) {
             return Err(Error::IllegalActionBeforeOpen());
         }
 
-        // the packet, encoded as an engine.io message packet
-        let engine_packet = EnginePacket::new(EnginePacketId::Message, Bytes::from(&packet));
-        self.engine_client.emit(engine_packet).await?;
+        let is_event = matches!(packet.packet_type, PacketId::Event | PacketId::BinaryEvent);
+        if let (true, Some(limiter)) = (is_event, &self.rate_limiter) {
+            self.runtime.enter(limiter.acquire()).await?;
+        }
+        match &self.outgoing {
+            Some(queue) if is_event => queue.push(packet).await,
+            _ => self.runtime.enter(self.write(vec![packet])).await,
+        }
+    }
+
+    /// Sends a `socket.io` packet like [`Socket::send`], but drops it if the outgoing
+    /// queue is full instead of waiting or failing as its policy says.
+    pub(crate) async fn send_volatile(&self, packet: Packet) -> Result<()> {
+        if !self.is_engineio_connected() || !self.connected.load(Ordering::Acquire) {
+            return Err(Error::IllegalActionBeforeOpen());
+        }
+
+        if let Some(limiter) = &self.rate_limiter {
+            self.runtime.enter(limiter.acquire()).await?;
+        }
+        match &self.outgoing {
+            Some(queue) => {
+                if !queue.try_push(packet)? {
+                    trace!("Outgoing queue is full, dropped the volatile packet");
+                }
+                Ok(())
+            }
+            None => self.runtime.enter(self.write(vec![packet])).await,
+        }
+    }
+
+    /// Sends a `socket.io` packet without queueing it, e.g. a chunk of a stream that
+    /// mustn't be dropped by the policy of the outgoing queue.
+    pub(crate) async fn send_unqueued(&self, packet: Packet) -> Result<()> {
+        if !self.is_engineio_connected() || !self.connected.load(Ordering::Acquire) {
+            return Err(Error::IllegalActionBeforeOpen());
+        }
+
+        if let Some(limiter) = &self.rate_limiter {
+            self.runtime.enter(limiter.acquire()).await?;
+        }
+        self.runtime.enter(self.write(vec![packet])).await
+    }
 
-        if let Some(attachments) = packet.attachments {
-            for attachment in attachments {
-                let engine_packet = EnginePacket::new(EnginePacketId::MessageBinary, attachment);
-                self.engine_client.emit(engine_packet).await?;
+    /// Sends several `socket.io` packets, writing them in one go unless the socket has
+    /// an outgoing queue.
+    pub async fn send_many(&self, packets: Vec<Packet>) -> Result<()> {
+        if !self.is_engineio_connected() || !self.connected.load(Ordering::Acquire) {
+            return Err(Error::IllegalActionBeforeOpen());
+        }
+
+        if let Some(limiter) = &self.rate_limiter {
+            for _ in &packets {
+                self.runtime.enter(limiter.acquire()).await?;
+            }
+        }
+        match &self.outgoing {
+            Some(queue) => {
+                for packet in packets {
+                    queue.push(packet).await?;
+                }
+                Ok(())
             }
+            None => self.runtime.enter(self.write(packets)).await,
         }
+    }
+
+    async fn write(&self, packets: Vec<Packet>) -> Result<()> {
+        let mut engine_packets = Vec::with_capacity(packets.len());
+        for packet in packets {
+            if let Some(observer) = &self.observer {
+                observer(Direction::Outbound, &packet);
+            }
+            // the packet, encoded as an engine.io message packet, followed by its attachments
+            let data = self.encode_buffer.encode(&packet)?;
+            let size = trace::size(&data, &packet);
+            trace::packet_sent(&packet, size);
+            self.counters.sent(&packet, size);
+            engine_packets.push(EnginePacket::new(EnginePacketId::Message, data));
+            for attachment in packet.attachments.into_iter().flatten() {
+                engine_packets.push(EnginePacket::new(EnginePacketId::MessageBinary, attachment));
+            }
+        }
+        self.engine_client.emit_batch(engine_packets).await?;
 
         Ok(())
     }
@@ -121,84 +298,199 @@ impl Socket {
 
     fn stream(
         client: EngineClient,
-        is_connected: Arc<AtomicBool>,
         ack_id: Arc<AtomicI32>,
+        counters: Arc<Counters>,
+        observer: Option<PacketObserver>,
+        options: ReadOptions,
     ) -> Pin<Box<impl Stream<Item = Result<Packet>> + Send>> {
-        Box::pin(try_stream! {
+        Box::pin(stream! {
                 for await received_data in client.clone() {
-                    let packet = received_data?;
+                    let packet = match received_data {
+                        Ok(packet) => packet,
+                        Err(err) => {
+                            yield Err(err.into());
+                            return;
+                        }
+                    };
 
                     if packet.packet_id == EnginePacketId::Message
                         || packet.packet_id == EnginePacketId::MessageBinary
                     {
-                        let packet = Self::handle_engineio_packet(packet, client.clone()).await?;
+                        if let Err(err) = options.check_packet_bytes(packet.data.len()) {
+                            yield Err(err);
+                            return;
+                        }
+                        let decoded = match options.strict {
+                            true => Packet::decode_strict(&packet.data),
+                            false => Packet::try_from(&packet.data),
+                        };
+                        let socket_packet = match decoded {
+                            Ok(socket_packet) => socket_packet,
+                            Err(err) if options.skip_malformed => {
+                                trace!("Skipping a malformed packet: {}", err);
+                                let frame = String::from_utf8_lossy(&packet.data).into_owned();
+                                yield Err(Error::InvalidFrame(frame, Box::new(err)));
+                                continue;
+                            }
+                            Err(err) => {
+                                yield Err(err.in_context(ErrorContext::of_frame(&packet.data)));
+                                return;
+                            }
+                        };
+                        let packet = match Self::handle_engineio_packet(
+                            packet,
+                            socket_packet,
+                            client.clone(),
+                            &counters,
+                            &options,
+                        )
+                        .await
+                        {
+                            Ok(packet) => packet,
+                            Err(err) => {
+                                yield Err(err);
+                                return;
+                            }
+                        };
+                        if let Some(observer) = &observer {
+                            observer(Direction::Inbound, &packet);
+                        }
 
                         if ack_id.load(Ordering::Acquire) != packet.id.unwrap_or(-1) {
                             ack_id.store(packet.id.unwrap_or(-1), Ordering::Release);
                         }
 
-                        Self::handle_socketio_packet(&packet, is_connected.clone());
-
-                        yield packet;
+                        yield Ok(packet);
                     }
                 }
         })
     }
 
-    /// Handles the connection/disconnection.
-    #[inline]
-    fn handle_socketio_packet(socket_packet: &Packet, is_connected: Arc<AtomicBool>) {
-        match socket_packet.packet_type {
-            PacketId::Connect => {
-                is_connected.store(true, Ordering::Release);
-            }
-            PacketId::ConnectError => {
-                is_connected.store(false, Ordering::Release);
-            }
-            PacketId::Disconnect => {
-                is_connected.store(false, Ordering::Release);
-            }
-            _ => (),
-        }
-    }
-
-    /// Handles new incoming engineio packets
+    /// Handles new incoming engineio packets, decoded into `socket_packet`, failing
+    /// as soon as its attachments exceed the limits of `options` or don't arrive in
+    /// time
     async fn handle_engineio_packet(
         packet: EnginePacket,
+        mut socket_packet: Packet,
         mut client: EngineClient,
+        counters: &Counters,
+        options: &ReadOptions,
     ) -> Result<Packet> {
-        let mut socket_packet = Packet::try_from(&packet.data)?;
         // Only handle attachments if there are any
         if socket_packet.attachment_count > 0 {
+            if let Some(max) = options.max_attachments {
+                if socket_packet.attachment_count > max {
+                    return Err(Error::IllegalPacketSize(format!(
+                        "{} attachments, at most {max} are allowed",
+                        socket_packet.attachment_count
+                    )));
+                }
+            }
+            let mut packet_bytes = packet.data.len();
             let mut attachments_left = socket_packet.attachment_count;
             let mut attachments = Vec::new();
+            let started = runtime::Instant::now();
             while attachments_left > 0 {
-                // TODO: This is not nice! Find a different way to peek the next element while mapping the stream
-                let next = client.next().await.unwrap();
-                match next {
-                    Err(err) => return Err(err.into()),
-                    Ok(packet) => match packet.packet_id {
-                        EnginePacketId::MessageBinary | EnginePacketId::Message => {
-                            attachments.push(packet.data);
-                            attachments_left -= 1;
-                        }
-                        _ => {
-                            return Err(Error::InvalidAttachmentPacketType(
-                                packet.packet_id.into(),
-                            ));
-                        }
-                    },
+                let next = match options.attachment_timeout {
+                    Some(timeout) => {
+                        let left = timeout.saturating_sub(started.elapsed());
+                        runtime::timeout(left, Self::next_attachment(&mut client))
+                            .await
+                            .unwrap_or_else(|| {
+                                trace!(
+                                    "Timed out after {:?} waiting for {} of the {} attachments of a packet",
+                                    timeout,
+                                    attachments_left,
+                                    socket_packet.attachment_count
+                                );
+                                Err(Error::IncompletePacket())
+                            })
+                    }
+                    None => Self::next_attachment(&mut client).await,
+                };
+                let attachment = next?;
+                if let Some(max) = options.max_attachment_bytes {
+                    if attachment.len() > max {
+                        return Err(Error::IllegalPacketSize(format!(
+                            "an attachment of {} bytes, at most {max} are allowed",
+                            attachment.len()
+                        )));
+                    }
                 }
+                packet_bytes += attachment.len();
+                options.check_packet_bytes(packet_bytes)?;
+                attachments.push(attachment);
+                attachments_left -= 1;
             }
             socket_packet.attachments = Some(attachments);
         }
+        let size = trace::size(&packet.data, &socket_packet);
+        trace::packet_received(&socket_packet, size);
+        counters.received(&socket_packet, size);
 
         Ok(socket_packet)
     }
 
-    fn is_engineio_connected(&self) -> bool {
+    /// Reads the next attachment of a packet from the engine.io connection. Heartbeats
+    /// and other control packets arriving in between were already handled by the
+    /// engine.io client and are passed over.
+    async fn next_attachment(client: &mut EngineClient) -> Result<Bytes> {
+        while let Some(packet) = client.next().await {
+            let packet = packet?;
+            match packet.packet_id {
+                EnginePacketId::MessageBinary | EnginePacketId::Message => return Ok(packet.data),
+                EnginePacketId::Ping
+                | EnginePacketId::Pong
+                | EnginePacketId::Noop
+                | EnginePacketId::Upgrade => {
+                    trace!(
+                        "Got a {:?} packet while reading attachments",
+                        packet.packet_id
+                    )
+                }
+                // the attachments won't arrive anymore
+                EnginePacketId::Close => break,
+                EnginePacketId::Open => {
+                    return Err(Error::InvalidAttachmentPacketType(packet.packet_id.into()))
+                }
+            }
+        }
+        Err(Error::IncompletePacket())
+    }
+
+    pub(crate) fn is_engineio_connected(&self) -> bool {
         self.engine_client.is_connected()
     }
+
+    pub(crate) async fn send_heartbeat(&self) -> Result<bool> {
+        Ok(self.engine_client.send_heartbeat().await?)
+    }
+
+    pub(crate) fn transport_upgrade(&self) -> Option<TransportUpgrade> {
+        self.engine_client.transport_upgrade()
+    }
+
+    pub(crate) async fn since_heartbeat(&self) -> Duration {
+        self.engine_client.since_heartbeat().await
+    }
+
+    pub(crate) fn ping_interval(&self) -> Duration {
+        Duration::from_millis(self.engine_client.handshake_info().ping_interval)
+    }
+
+    /// The size of the largest payload the server accepts, if it announced one.
+    pub(crate) fn max_payload(&self) -> Option<u64> {
+        self.engine_client.handshake_info().max_payload
+    }
+
+    pub(crate) fn heartbeat_timeout(&self) -> Duration {
+        self.engine_client.heartbeat_timeout()
+    }
+
+    /// The number of packets waiting in the outgoing queue, if the socket has one.
+    pub(crate) fn queued(&self) -> usize {
+        self.outgoing.as_ref().map_or(0, |queue| queue.len())
+    }
 }
 
 impl Stream for Socket {
@@ -217,6 +509,213 @@ impl Debug for Socket {
         f.debug_struct("Socket")
             .field("engine_client", &self.engine_client)
             .field("connected", &self.connected)
+            .field("outgoing", &self.outgoing)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{sync::mpsc, time::timeout};
+
+    use super::*;
+    use crate::asynchronous::{
+        test_util::{frame, MockServer},
+        ClientBuilder,
+    };
+
+    #[tokio::test]
+    async fn skips_malformed_packets() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .skip_malformed_packets(true)
+            .on("chat", {
+                let tx = tx.clone();
+                move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send((Event::from("chat"), payload)).unwrap() }.boxed()
+                }
+            })
+            .on(Event::Error, move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send((Event::Error, payload)).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        let garbage = EnginePacket::new(EnginePacketId::Message, Bytes::from_static(b"9garbage"));
+        server.to_client.send(Bytes::from(garbage)).unwrap();
+        server.emit("chat", json!("hi"))?;
+
+        let (event, payload) = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, Event::Error);
+        assert!(format!("{payload:?}").contains("9garbage"));
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some((Event::from("chat"), Payload::from(json!("hi"))))
+        );
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_packets_strictly() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .strict_protocol(true)
+            .skip_malformed_packets(true)
+            .on(Event::Error, move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // an event without a name, handled as a message otherwise
+        server.send(Packet::try_from(&Bytes::from_static(b"2[1,2]"))?)?;
+        let payload = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(format!("{payload:?}").contains("an array starting with the event name"));
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn limits_the_size_of_packets() -> Result<()> {
+        type Limit = fn(ClientBuilder) -> ClientBuilder;
+        let limits: [(Limit, &str); 3] = [
+            (|builder| builder.max_attachments(2), "3 attachments"),
+            (
+                |builder| builder.max_attachment_size(8),
+                "an attachment of 10 bytes",
+            ),
+            (|builder| builder.max_packet_size(55), "61 bytes"),
+        ];
+        for (limit, reason) in limits {
+            let (server, transport) = MockServer::new();
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let builder = ClientBuilder::new("http://localhost/")
+                .mock_transport(transport)
+                .reconnect(false)
+                .on(Event::Error, move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send(payload).unwrap() }.boxed()
+                });
+            let client = limit(builder).connect().await?;
+            server.recv().await;
+
+            let attachments = vec![Bytes::from_static(b"0123456789"); 3];
+            server.send(Packet::new(
+                PacketId::BinaryEvent,
+                "/".to_owned(),
+                Some("\"file\"".to_owned()),
+                None,
+                3,
+                Some(attachments),
+                None,
+            ))?;
+            let payload = timeout(Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(format!("{payload:?}").contains(reason), "{payload:?}");
+            // the packet ended the connection
+            drop(client);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reads_attachments_around_heartbeats() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on("file", move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        let packet = Packet::new_from_payload(
+            Payload::from(vec![1u8, 2, 3]),
+            Event::from("file"),
+            "/",
+            None,
+        )?;
+        server.to_client.send(frame(&packet)).unwrap();
+        for heartbeat in [EnginePacketId::Ping, EnginePacketId::Noop] {
+            let heartbeat = EnginePacket::new(heartbeat, Bytes::new());
+            server.to_client.send(Bytes::from(heartbeat)).unwrap();
+        }
+        let attachment = EnginePacket::new(EnginePacketId::MessageBinary, vec![1u8, 2, 3]);
+        server.to_client.send(Bytes::from(attachment)).unwrap();
+
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(vec![1u8, 2, 3]))
+        );
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn times_out_waiting_for_attachments() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .attachment_timeout(Duration::from_millis(50))
+            .on(Event::Error, move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // announces two attachments, but only sends one
+        server.send(Packet::new(
+            PacketId::BinaryEvent,
+            "/".to_owned(),
+            Some("\"file\"".to_owned()),
+            None,
+            2,
+            Some(vec![Bytes::from_static(b"first")]),
+            None,
+        ))?;
+        let payload = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            payload,
+            Payload::from(crate::Error::IncompletePacket().to_string())
+        );
+        // the packet ended the connection
+        drop(client);
+        Ok(())
+    }
+}
@@ -0,0 +1,873 @@
+//! An in-memory transport to unit test code using the async [`Client`](super::Client)
+//! without a socket.io server. [`MockServer::new`] returns the server end and a
+//! [`MockTransport`], which is passed to
+//! [`ClientBuilder::mock_transport`](super::ClientBuilder::mock_transport). The
+//! server end injects packets for the client and reads what the client emitted.
+//! Wrapping the transport in a [`FaultyTransport`] delays, drops, duplicates or
+//! corrupts frames and breaks connections on a schedule, to test how the client
+//! recovers. A [`Replayer`] plays a session recorded with a
+//! [`Recorder`](crate::record::Recorder) back to a client.
+//!
+//! ```rust
+//! use futures_util::FutureExt;
+//! use rust_socketio::{asynchronous::{test_util::MockServer, ClientBuilder}, Payload};
+//! use serde_json::json;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let (server, transport) = MockServer::new();
+//!     let client = ClientBuilder::new("http://localhost/")
+//!         .mock_transport(transport)
+//!         .on("ping", |_, client| {
+//!             async move { client.emit("pong", json!(1)).await.unwrap() }.boxed()
+//!         })
+//!         .connect()
+//!         .await
+//!         .unwrap();
+//!
+//!     server.emit("ping", json!(0)).unwrap();
+//!     server.assert_emitted("pong", json!(1)).await;
+//!     client.disconnect().await.unwrap();
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    path::Path,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::{future::BoxFuture, task::AtomicWaker, Stream};
+use log::trace;
+use rust_engineio::{
+    asynchronous::{
+        transport::AsyncTransport, Client as EngineIoClient, ClientBuilder as EngineIoClientBuilder,
+    },
+    Error as EngineError, Packet as EnginePacket, PacketId as EnginePacketId,
+};
+use serde_json::Value;
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        Mutex as AsyncMutex,
+    },
+    time::{sleep, Sleep},
+};
+use url::Url;
+
+use crate::{
+    error::Result,
+    packet::{Packet, PacketId},
+    record::Record,
+    Direction, Event, Payload,
+};
+
+type EngineResult<T> = std::result::Result<T, EngineError>;
+
+/// Opens the engine.io connection of a client over the transport passed to
+/// [`ClientBuilder::mock_transport`](super::ClientBuilder::mock_transport).
+pub(crate) type TransportConnector = Arc<
+    dyn Fn(EngineIoClientBuilder) -> BoxFuture<'static, EngineResult<EngineIoClient>> + Send + Sync,
+>;
+
+/// The `pingInterval` the mock server opens each connection with, slow enough for the
+/// heartbeat to never time out during a test.
+const PING_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The client end of an in-memory connection, see [`MockServer`]. Clones share the
+/// server end, and a clone that wasn't read from yet opens a new connection.
+#[derive(Clone)]
+pub struct MockTransport {
+    /// Whether the handshake was read, which a fresh transport starts a connection with.
+    opened: bool,
+    ping_interval: Duration,
+    /// Frames sent to the client.
+    incoming: Arc<Mutex<UnboundedReceiver<Bytes>>>,
+    /// Lets the transport answer the `Connect` packets of namespaces itself.
+    replies: UnboundedSender<Bytes>,
+    /// Frames written by the client, with whether they are binary attachments.
+    outgoing: UnboundedSender<(Bytes, bool)>,
+}
+
+impl Stream for MockTransport {
+    type Item = EngineResult<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if !self.opened {
+            self.opened = true;
+            let handshake = format!(
+                r#"0{{"sid":"mock","upgrades":[],"pingInterval":{},"pingTimeout":3600000,"maxPayload":100000}}"#,
+                self.ping_interval.as_millis()
+            );
+            return Poll::Ready(Some(Ok(Bytes::from(handshake))));
+        }
+        match self.incoming.lock() {
+            Ok(mut incoming) => incoming.poll_recv(cx).map(|frame| frame.map(Ok)),
+            Err(_) => Poll::Ready(None),
+        }
+    }
+}
+
+impl MockTransport {
+    /// Sets the `pingInterval` of the handshake. The server end never pings, so a client
+    /// with a short [`ClientBuilder::ping_timeout`](super::ClientBuilder::ping_timeout)
+    /// considers the connection lost once both passed.
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for MockTransport {
+    async fn emit(&self, data: Bytes, is_binary_att: bool) -> EngineResult<()> {
+        // namespaces are accepted right away, like a server without middlewares does
+        if let Some(packet) = decode(&data, is_binary_att) {
+            if packet.packet_type == PacketId::Connect {
+                let sid = format!(r#"{{"sid":"mock{}"}}"#, packet.nsp);
+                let accepted = Packet::new(
+                    PacketId::Connect,
+                    packet.nsp,
+                    Some(sid),
+                    None,
+                    0,
+                    None,
+                    None,
+                );
+                let _ = self.replies.send(frame(&accepted));
+            }
+        }
+        // the server end may be gone already, which the client doesn't have to know
+        let _ = self.outgoing.send((data, is_binary_att));
+        Ok(())
+    }
+
+    async fn base_url(&self) -> EngineResult<Url> {
+        Ok(Url::parse("memory://socket.io/")?)
+    }
+
+    async fn set_base_url(&self, _base_url: Url) -> EngineResult<()> {
+        Ok(())
+    }
+}
+
+/// The server end of an in-memory connection to a client. Namespaces the client
+/// connects to are accepted by the transport. A client reconnecting opens a new
+/// connection to the same server end, over which the frames it didn't read yet are
+/// delivered.
+pub struct MockServer {
+    pub(crate) to_client: UnboundedSender<Bytes>,
+    from_client: AsyncMutex<ClientFrames>,
+}
+
+struct ClientFrames {
+    frames: UnboundedReceiver<(Bytes, bool)>,
+    closed: bool,
+}
+
+impl MockServer {
+    /// Creates a connection, returning its server end and the transport to pass to the
+    /// client.
+    pub fn new() -> (MockServer, MockTransport) {
+        let (to_client, incoming) = unbounded_channel();
+        let (outgoing, from_client) = unbounded_channel();
+
+        let transport = MockTransport {
+            opened: false,
+            ping_interval: PING_INTERVAL,
+            incoming: Arc::new(Mutex::new(incoming)),
+            replies: to_client.clone(),
+            outgoing,
+        };
+        let server = MockServer {
+            to_client,
+            from_client: AsyncMutex::new(ClientFrames {
+                frames: from_client,
+                closed: false,
+            }),
+        };
+        (server, transport)
+    }
+
+    /// Sends a packet to the client, followed by its attachments.
+    pub fn send(&self, packet: Packet) -> Result<()> {
+        self.to_client
+            .send(frame(&packet))
+            .map_err(|_| crate::Error::StoppedEngineIoSocket)?;
+        for attachment in packet.attachments.into_iter().flatten() {
+            let attachment = EnginePacket::new(EnginePacketId::MessageBinary, attachment);
+            self.to_client
+                .send(Bytes::from(attachment))
+                .map_err(|_| crate::Error::StoppedEngineIoSocket)?;
+        }
+        Ok(())
+    }
+
+    /// Emits an event to the default namespace of the client.
+    pub fn emit<E: Into<Event>, D: Into<Payload>>(&self, event: E, data: D) -> Result<()> {
+        self.send(Packet::new_from_payload(
+            data.into(),
+            event.into(),
+            "/",
+            None,
+        )?)
+    }
+
+    /// Acknowledges the event the client emitted with the given ack id, see
+    /// [`Packet::id`].
+    pub fn ack<D: Into<Payload>>(&self, id: i32, data: D) -> Result<()> {
+        self.send(Packet::ack_from_payload(
+            data.into(),
+            Event::Message,
+            "/",
+            Some(id),
+        )?)
+    }
+
+    /// Closes the connection, like a server shutting down.
+    pub fn close(&self) -> Result<()> {
+        let close = EnginePacket::new(EnginePacketId::Close, Bytes::new());
+        self.to_client
+            .send(Bytes::from(close))
+            .map_err(|_| crate::Error::StoppedEngineIoSocket)?;
+        Ok(())
+    }
+
+    /// Returns the next packet the client wrote, with its attachments. Returns `None`
+    /// once the client closed the connection.
+    pub async fn recv(&self) -> Option<Packet> {
+        let mut from_client = self.from_client.lock().await;
+        loop {
+            if from_client.closed {
+                return None;
+            }
+            let (data, is_binary) = from_client.frames.recv().await?;
+            if !is_binary
+                && EnginePacket::try_from(data.clone())
+                    .is_ok_and(|packet| packet.packet_id == EnginePacketId::Close)
+            {
+                from_client.closed = true;
+                continue;
+            }
+            let Some(mut packet) = decode(&data, is_binary) else {
+                continue;
+            };
+
+            if packet.attachment_count > 0 {
+                let mut attachments = Vec::new();
+                while attachments.len() < usize::from(packet.attachment_count) {
+                    let (attachment, _) = from_client.frames.recv().await?;
+                    attachments.push(attachment);
+                }
+                packet.attachments = Some(attachments);
+            }
+            return Some(packet);
+        }
+    }
+
+    /// Returns the next event the client emitted, skipping other packets. Returns
+    /// `None` once the client closed the connection.
+    pub async fn recv_event(&self) -> Option<(Event, Payload)> {
+        loop {
+            let packet = self.recv().await?;
+            match packet.packet_type {
+                PacketId::Event => {
+                    let data = packet.data.as_ref()?;
+                    let Ok(Value::Array(mut contents)) = serde_json::from_str(data.as_str()) else {
+                        continue;
+                    };
+                    let event = match contents.first() {
+                        Some(Value::String(event)) if contents.len() > 1 => {
+                            let event = Event::from(event.as_str());
+                            contents.remove(0);
+                            event
+                        }
+                        _ => Event::Message,
+                    };
+                    return Some((event, Payload::Text(contents)));
+                }
+                PacketId::BinaryEvent => {
+                    // binary packets only keep the event name of their arguments
+                    let event = packet
+                        .data
+                        .as_ref()
+                        .and_then(|data| serde_json::from_str::<String>(data.as_str()).ok())
+                        .map_or(Event::Message, Event::from);
+                    let attachment = packet.attachments?.into_iter().next()?;
+                    return Some((event, Payload::Binary(attachment)));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Asserts that the next event the client emitted is the given one.
+    ///
+    /// # Panics
+    /// If the client emitted another event or closed the connection.
+    pub async fn assert_emitted<E: Into<Event>, D: Into<Payload>>(&self, event: E, data: D) {
+        let expected = (event.into(), data.into());
+        match self.recv_event().await {
+            Some(emitted) => assert_eq!(emitted, expected, "the client emitted another event"),
+            None => panic!("the client closed the connection instead of emitting {expected:?}"),
+        }
+    }
+}
+
+/// A fault a [`FaultyTransport`] injects into a frame.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// Delivers the frame after the given time. The frames after it wait as well.
+    Delay(Duration),
+    /// Loses the frame.
+    Drop,
+    /// Delivers the frame twice.
+    Duplicate,
+    /// Flips all bits of the frame, so it can't be decoded.
+    Corrupt,
+    /// Loses the frame and breaks the connection, like a network failure.
+    Disconnect,
+}
+
+/// Wraps a transport, usually a [`MockTransport`], and injects [`Fault`]s into the
+/// frames passing it. Frames are numbered from 0 in each direction, over all
+/// connections opened with the transport and its clones. Each connection starts with
+/// its handshake frame.
+///
+/// ```rust
+/// use rust_socketio::asynchronous::test_util::{Fault, FaultyTransport, MockServer};
+///
+/// let (server, transport) = MockServer::new();
+/// // breaks the connection instead of delivering the third frame sent to the client,
+/// // and loses the first one the client writes
+/// let transport = FaultyTransport::new(transport)
+///     .incoming(2, Fault::Disconnect)
+///     .outgoing(0, Fault::Drop);
+/// ```
+pub struct FaultyTransport<T> {
+    inner: T,
+    schedule: Arc<Schedule>,
+    /// The number of frames read and written so far.
+    frames: Arc<(AtomicUsize, AtomicUsize)>,
+    /// The connection the transport is used for, set once it's read from.
+    connection: Option<Arc<Connection>>,
+    /// A frame that is delivered next, once the delay elapsed.
+    held: Option<(Bytes, Option<Pin<Box<Sleep>>>)>,
+}
+
+#[derive(Clone, Default)]
+struct Schedule {
+    incoming: HashMap<usize, Fault>,
+    outgoing: HashMap<usize, Fault>,
+}
+
+#[derive(Default)]
+struct Connection {
+    broken: AtomicBool,
+    /// Wakes the reader once writing broke the connection.
+    reader: AtomicWaker,
+}
+
+impl Connection {
+    fn break_off(&self) {
+        self.broken.store(true, Ordering::Release);
+        self.reader.wake();
+    }
+}
+
+impl<T> FaultyTransport<T> {
+    /// Wraps the transport without injecting any faults yet.
+    pub fn new(inner: T) -> Self {
+        FaultyTransport {
+            inner,
+            schedule: Arc::default(),
+            frames: Arc::default(),
+            connection: None,
+            held: None,
+        }
+    }
+
+    /// Injects the fault into the frame with the given number that is sent to the
+    /// client.
+    pub fn incoming(mut self, frame: usize, fault: Fault) -> Self {
+        Arc::make_mut(&mut self.schedule)
+            .incoming
+            .insert(frame, fault);
+        self
+    }
+
+    /// Injects the fault into the frame with the given number that the client writes.
+    pub fn outgoing(mut self, frame: usize, fault: Fault) -> Self {
+        Arc::make_mut(&mut self.schedule)
+            .outgoing
+            .insert(frame, fault);
+        self
+    }
+
+    fn is_broken(&self) -> bool {
+        self.connection
+            .as_ref()
+            .is_some_and(|connection| connection.broken.load(Ordering::Acquire))
+    }
+}
+
+impl<T: Clone> Clone for FaultyTransport<T> {
+    fn clone(&self) -> Self {
+        FaultyTransport {
+            inner: self.inner.clone(),
+            schedule: self.schedule.clone(),
+            frames: self.frames.clone(),
+            connection: self.connection.clone(),
+            held: None,
+        }
+    }
+}
+
+impl<T: AsyncTransport> Stream for FaultyTransport<T> {
+    type Item = EngineResult<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let connection = this.connection.get_or_insert_with(Arc::default).clone();
+        connection.reader.register(cx.waker());
+
+        loop {
+            if connection.broken.load(Ordering::Acquire) {
+                return Poll::Ready(None);
+            }
+            if let Some((_, Some(delay))) = &mut this.held {
+                ready!(delay.as_mut().poll(cx));
+            }
+            if let Some((frame, _)) = this.held.take() {
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            let frame = match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                Some(Ok(frame)) => frame,
+                other => return Poll::Ready(other),
+            };
+            let number = this.frames.0.fetch_add(1, Ordering::Relaxed);
+            let Some(fault) = this.schedule.incoming.get(&number) else {
+                return Poll::Ready(Some(Ok(frame)));
+            };
+            trace!("Injecting {fault:?} into incoming frame {number}");
+
+            match *fault {
+                Fault::Delay(delay) => this.held = Some((frame, Some(Box::pin(sleep(delay))))),
+                Fault::Drop => (),
+                Fault::Duplicate => {
+                    this.held = Some((frame.clone(), None));
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                Fault::Corrupt => return Poll::Ready(Some(Ok(corrupt(&frame)))),
+                Fault::Disconnect => {
+                    connection.break_off();
+                    return Poll::Ready(Some(Err(connection_reset())));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T: AsyncTransport + Send + Sync> AsyncTransport for FaultyTransport<T> {
+    async fn emit(&self, data: Bytes, is_binary_att: bool) -> EngineResult<()> {
+        if self.is_broken() {
+            return Err(connection_reset());
+        }
+        let number = self.frames.1.fetch_add(1, Ordering::Relaxed);
+        let Some(fault) = self.schedule.outgoing.get(&number).copied() else {
+            return self.inner.emit(data, is_binary_att).await;
+        };
+        trace!("Injecting {fault:?} into outgoing frame {number}");
+
+        match fault {
+            Fault::Delay(delay) => {
+                sleep(delay).await;
+                self.inner.emit(data, is_binary_att).await
+            }
+            Fault::Drop => Ok(()),
+            Fault::Duplicate => {
+                self.inner.emit(data.clone(), is_binary_att).await?;
+                self.inner.emit(data, is_binary_att).await
+            }
+            Fault::Corrupt => self.inner.emit(corrupt(&data), is_binary_att).await,
+            Fault::Disconnect => {
+                if let Some(connection) = &self.connection {
+                    connection.break_off();
+                }
+                Err(connection_reset())
+            }
+        }
+    }
+
+    async fn base_url(&self) -> EngineResult<Url> {
+        self.inner.base_url().await
+    }
+
+    async fn set_base_url(&self, base_url: Url) -> EngineResult<()> {
+        self.inner.set_base_url(base_url).await
+    }
+
+    async fn upgrade(&self) -> EngineResult<()> {
+        self.inner.upgrade().await
+    }
+
+    async fn close(&self) -> EngineResult<()> {
+        self.inner.close().await
+    }
+}
+
+fn corrupt(frame: &Bytes) -> Bytes {
+    frame.iter().map(|byte| !byte).collect()
+}
+
+fn connection_reset() -> EngineError {
+    EngineError::IncompleteIo(io::ErrorKind::ConnectionReset.into())
+}
+
+/// Plays a session recorded with a [`Recorder`](crate::record::Recorder) back to a
+/// client through a [`MockServer`]. The recorded packets the client read are sent
+/// again, keeping the time between them. Before sending the packets that followed a
+/// packet the client wrote, the replayer waits for the client to write a packet, so the
+/// code under test has to act like it did during the session. Acks are sent with the
+/// ids of the events the client emitted this time. `Connect` packets of the server are
+/// skipped, as the mock accepts namespaces itself.
+///
+/// ```rust,no_run
+/// use rust_socketio::asynchronous::{
+///     test_util::{MockServer, Replayer},
+///     ClientBuilder,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (server, transport) = MockServer::new();
+///     let client = ClientBuilder::new("http://localhost/")
+///         .mock_transport(transport)
+///         .connect()
+///         .await
+///         .unwrap();
+///
+///     Replayer::open("session.jsonl")
+///         .unwrap()
+///         .replay(&server)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Replayer {
+    records: Vec<Record>,
+    keep_timing: bool,
+}
+
+impl Replayer {
+    /// Creates a replayer playing back the given records.
+    pub fn new(records: Vec<Record>) -> Self {
+        Replayer {
+            records,
+            keep_timing: true,
+        }
+    }
+
+    /// Creates a replayer playing back the session recorded to the file at the given
+    /// path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(Record::open(path)?))
+    }
+
+    /// Sends the packets as soon as possible instead of keeping the recorded time
+    /// between them.
+    pub fn without_delays(mut self) -> Self {
+        self.keep_timing = false;
+        self
+    }
+
+    /// Plays the session back. Fails if the client closes the connection before the
+    /// session ends.
+    pub async fn replay(&self, server: &MockServer) -> Result<()> {
+        let mut previous = None;
+        // the recorded ack ids of the client mapped to the ones it uses now
+        let mut ack_ids = HashMap::new();
+
+        for record in &self.records {
+            match record.direction {
+                Direction::Outbound => {
+                    let packet = server
+                        .recv()
+                        .await
+                        .ok_or(crate::Error::StoppedEngineIoSocket)?;
+                    if let (Some(recorded), Some(id)) = (record.packet.id, packet.id) {
+                        ack_ids.insert(recorded, id);
+                    }
+                }
+                Direction::Inbound if record.packet.packet_type == PacketId::Connect => (),
+                Direction::Inbound => {
+                    if let (true, Some(previous)) = (self.keep_timing, previous) {
+                        sleep(record.elapsed.saturating_sub(previous)).await;
+                    }
+                    let mut packet = record.packet.clone();
+                    if matches!(packet.packet_type, PacketId::Ack | PacketId::BinaryAck) {
+                        packet.id = packet.id.map(|id| ack_ids.get(&id).copied().unwrap_or(id));
+                    }
+                    server.send(packet)?;
+                }
+            }
+            previous = Some(record.elapsed);
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a socket.io packet as the engine.io message carrying it.
+pub(crate) fn frame(packet: &Packet) -> Bytes {
+    Bytes::from(EnginePacket::new(
+        EnginePacketId::Message,
+        Bytes::from(packet),
+    ))
+}
+
+/// Decodes the socket.io packet of an engine.io message written by the client.
+fn decode(data: &Bytes, is_binary: bool) -> Option<Packet> {
+    if is_binary {
+        return None;
+    }
+    let packet = EnginePacket::try_from(data.clone()).ok()?;
+    if packet.packet_id != EnginePacketId::Message {
+        return None;
+    }
+    Packet::try_from(&packet.data).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{sync::mpsc, time::timeout};
+
+    use super::*;
+    use crate::{asynchronous::ClientBuilder, record::Recorder};
+
+    #[tokio::test]
+    async fn mock_server() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on("greet", move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        assert_eq!(
+            server.recv().await.map(|packet| packet.packet_type),
+            Some(PacketId::Connect)
+        );
+
+        server.emit("greet", json!("hello"))?;
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(json!("hello")))
+        );
+
+        client.emit("answer", json!({"text": "hi"})).await?;
+        server.assert_emitted("answer", json!({"text": "hi"})).await;
+
+        client.emit("file", Bytes::from_static(b"\x00\x1e")).await?;
+        server
+            .assert_emitted("file", Bytes::from_static(b"\x00\x1e"))
+            .await;
+
+        client.disconnect().await?;
+        assert_eq!(
+            server.recv().await.map(|packet| packet.packet_type),
+            Some(PacketId::Disconnect)
+        );
+        assert_eq!(server.recv().await, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn faulty_transport_reconnects() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        // the handshake and the namespace's acceptance come first
+        let transport = FaultyTransport::new(transport).incoming(2, Fault::Disconnect);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect_delay(10, 10)
+            .on("greet", move |payload, _| {
+                let tx = tx.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+        assert_eq!(
+            server.recv().await.map(|packet| packet.packet_type),
+            Some(PacketId::Connect)
+        );
+
+        server.emit("greet", json!(1))?;
+        // the namespace is connected again over a new connection
+        assert_eq!(
+            timeout(Duration::from_secs(5), server.recv())
+                .await
+                .unwrap()
+                .map(|packet| packet.packet_type),
+            Some(PacketId::Connect)
+        );
+        server.emit("greet", json!(2))?;
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(json!(2)))
+        );
+        assert_eq!(client.stats().reconnects, 1);
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn faulty_transport_delays_acks() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let transport = FaultyTransport::new(transport)
+            .incoming(2, Fault::Delay(Duration::from_millis(300)))
+            .incoming(3, Fault::Duplicate);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let client = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        // the second ack waits for the delayed first one
+        for (number, ack_timeout) in [(0, 100), (1, 1000)] {
+            let tx = tx.clone();
+            client
+                .emit_with_ack(
+                    "question",
+                    json!(number),
+                    Duration::from_millis(ack_timeout),
+                    move |payload, _| {
+                        let tx = tx.clone();
+                        async move { tx.send(payload).unwrap() }.boxed()
+                    },
+                )
+                .await?;
+            let id = server.recv().await.and_then(|packet| packet.id).unwrap();
+            server.ack(id, json!(number))?;
+        }
+
+        // the first ack arrives too late, the second one twice but is handled once
+        assert_eq!(
+            timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+            Some(Payload::from(json!([1])))
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    /// Emits a question and waits for its answer and a greeting, like the code under
+    /// test would.
+    async fn converse(builder: ClientBuilder) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let greeted = tx.clone();
+        let client = builder
+            .reconnect(false)
+            .on("greet", move |payload, _| {
+                let tx = greeted.clone();
+                async move { tx.send(payload).unwrap() }.boxed()
+            })
+            .connect()
+            .await?;
+
+        client
+            .emit_with_ack(
+                "question",
+                json!(1),
+                Duration::from_secs(1),
+                move |payload, _| {
+                    let tx = tx.clone();
+                    async move { tx.send(payload).unwrap() }.boxed()
+                },
+            )
+            .await?;
+        for expected in [json!(["answer"]), json!("hi")] {
+            assert_eq!(
+                timeout(Duration::from_secs(1), rx.recv()).await.unwrap(),
+                Some(Payload::from(expected))
+            );
+        }
+        client.disconnect().await
+    }
+
+    #[tokio::test]
+    async fn replay() -> Result<()> {
+        let (server, transport) = MockServer::new();
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder::new(SharedBuffer(buffer.clone()));
+
+        let session = async {
+            server.recv().await;
+            let id = server.recv().await.and_then(|packet| packet.id).unwrap();
+            server.ack(id, json!("answer"))?;
+            server.emit("greet", json!("hi"))
+        };
+        let builder = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .packet_observer(recorder.observer());
+        let (conversed, session) = tokio::join!(converse(builder), session);
+        conversed?;
+        session?;
+
+        let records = Record::read_all(&buffer.lock().unwrap()[..])?;
+        assert_eq!(records.len(), 6);
+
+        // the recorded server plays its part again
+        let (server, transport) = MockServer::new();
+        let builder = ClientBuilder::new("http://localhost/").mock_transport(transport);
+        let replayer = Replayer::new(records);
+        let (conversed, replayed) = tokio::join!(converse(builder), replayer.replay(&server));
+        conversed?;
+        replayed?;
+        // the replayer read up to the client's disconnect
+        assert_eq!(server.recv().await, None);
+        Ok(())
+    }
+
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
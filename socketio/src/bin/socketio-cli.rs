@@ -0,0 +1,324 @@
+//! `socketio-cli`, a command line client to try out socket.io servers, enabled by
+//! the `cli` feature. It connects to a url, prints every event it receives and
+//! emits what is typed on stdin, one event per line:
+//!
+//! ```text
+//! chat "hello" {"room": "lobby"}   emits `chat` with the two JSON arguments
+//! /ask status 1                    emits `status` and prints the ack of the server
+//! /ack "ok"                        acks the last event that asked for an ack
+//! /quit                            disconnects, like the end of stdin
+//! ```
+
+use std::{env, process::ExitCode, time::Duration};
+
+use futures_util::FutureExt;
+use rust_socketio::{asynchronous::ClientBuilder, Payload, TransportType};
+use serde_json::Value;
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+
+const USAGE: &str = "\
+Usage: socketio-cli [OPTIONS] <URL>
+
+Connects to a socket.io server, prints the events it sends and emits the events
+typed on stdin as `<event> [<json>...]`. `/ask <event> [<json>...]` waits for the
+ack of the server, `/ack [<json>...]` acks the last event that asked for one and
+`/quit` disconnects.
+
+Options:
+  -n, --namespace <NSP>        the namespace to connect to [default: /]
+  -a, --auth <JSON>            the auth payload of the connect packet
+  -H, --header <NAME: VALUE>   a header of the opening request, may be repeated
+  -t, --transport <TRANSPORT>  any, polling, websocket or websocket-upgrade [default: any]
+      --ack-timeout <SECS>     how long `/ask` waits for an ack [default: 5]
+  -h, --help                   prints this help";
+
+/// The options given on the command line.
+#[derive(Debug, PartialEq)]
+struct Options {
+    url: String,
+    namespace: String,
+    auth: Option<Value>,
+    headers: Vec<(String, String)>,
+    transport_type: TransportType,
+    ack_timeout: Duration,
+}
+
+/// A line typed on stdin.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Emit(String, Vec<Value>),
+    Ask(String, Vec<Value>),
+    Ack(Vec<Value>),
+    Quit,
+    Nothing,
+}
+
+/// Parses the arguments, returning `None` if the usage was asked for.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut args = args.into_iter();
+    let mut url = None;
+    let mut options = Options {
+        url: String::new(),
+        namespace: "/".to_owned(),
+        auth: None,
+        headers: Vec::new(),
+        transport_type: TransportType::Any,
+        ack_timeout: Duration::from_secs(5),
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-n" | "--namespace" => options.namespace = value(&arg)?,
+            "-a" | "--auth" => {
+                let auth = value(&arg)?;
+                options.auth =
+                    Some(serde_json::from_str(&auth).map_err(|e| format!("invalid auth: {e}"))?);
+            }
+            "-H" | "--header" => {
+                let header = value(&arg)?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or(format!("header `{header}` is not `name: value`"))?;
+                options
+                    .headers
+                    .push((name.trim().to_owned(), value.trim().to_owned()));
+            }
+            "-t" | "--transport" => {
+                options.transport_type = match value(&arg)?.as_str() {
+                    "any" => TransportType::Any,
+                    "polling" => TransportType::Polling,
+                    "websocket" => TransportType::Websocket,
+                    "websocket-upgrade" => TransportType::WebsocketUpgrade,
+                    other => return Err(format!("unknown transport `{other}`")),
+                }
+            }
+            "--ack-timeout" => {
+                let secs = value(&arg)?;
+                let secs: f64 = secs
+                    .parse()
+                    .map_err(|_| format!("invalid ack timeout `{secs}`"))?;
+                options.ack_timeout = Duration::try_from_secs_f64(secs)
+                    .map_err(|e| format!("invalid ack timeout: {e}"))?;
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+            _ if url.is_none() => url = Some(arg),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+
+    options.url = url.ok_or("missing the url to connect to")?;
+    Ok(Some(options))
+}
+
+/// Parses the JSON values separated by whitespace.
+fn parse_values(input: &str) -> Result<Vec<Value>, String> {
+    serde_json::Deserializer::from_str(input)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid JSON: {e}"))
+}
+
+fn parse_line(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    match word {
+        "" => Ok(Command::Nothing),
+        "/quit" => Ok(Command::Quit),
+        "/ack" => Ok(Command::Ack(parse_values(rest)?)),
+        "/ask" => {
+            let rest = rest.trim_start();
+            let (event, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if event.is_empty() {
+                return Err("/ask needs an event".to_owned());
+            }
+            Ok(Command::Ask(event.to_owned(), parse_values(rest)?))
+        }
+        _ if word.starts_with('/') => Err(format!("unknown command `{word}`")),
+        _ => Ok(Command::Emit(word.to_owned(), parse_values(rest)?)),
+    }
+}
+
+/// Formats a payload as its JSON arguments, or as the hex of its bytes.
+fn format_payload(payload: &Payload) -> String {
+    match payload {
+        Payload::Text(values) => values
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(" "),
+        Payload::Binary(data) => {
+            let hex: String = data.iter().map(|byte| format!("{byte:02x}")).collect();
+            format!("<{} bytes: {hex}>", data.len())
+        }
+        #[allow(deprecated)]
+        Payload::String(string) => string.clone(),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut builder = ClientBuilder::new(options.url)
+        .namespace(options.namespace)
+        .transport_type(options.transport_type)
+        .on_any(|event, payload, _| {
+            async move { println!("< {} {}", event.as_str(), format_payload(&payload)) }.boxed()
+        })
+        .on("error", |payload, _| {
+            async move { eprintln!("! error {}", format_payload(&payload)) }.boxed()
+        })
+        .on_connect_error(|e, _| async move { eprintln!("! rejected: {e}") }.boxed())
+        .on_disconnect(|reason, _| async move { eprintln!("! disconnected: {reason}") }.boxed());
+    if let Some(auth) = options.auth {
+        builder = builder.auth(auth);
+    }
+    for (name, value) in options.headers {
+        builder = builder.opening_header(name, value);
+    }
+
+    // the connecting future is large, keep it off the stack of main
+    let client = match Box::pin(builder.connect()).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("! could not connect: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("! connected as {}", client.sid().unwrap_or_default());
+
+    let mut lines = BufReader::new(stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let sent = match parse_line(&line) {
+            Ok(Command::Emit(event, values)) => client.emit(event, values).await,
+            Ok(Command::Ask(event, values)) => {
+                let answered = event.clone();
+                client
+                    .emit_with_ack(event, values, options.ack_timeout, move |payload, _| {
+                        let answered = answered.clone();
+                        async move { println!("< ack {answered} {}", format_payload(&payload)) }
+                            .boxed()
+                    })
+                    .await
+            }
+            Ok(Command::Ack(values)) => client.ack(values).await,
+            Ok(Command::Quit) => break,
+            Ok(Command::Nothing) => Ok(()),
+            Err(e) => {
+                eprintln!("! {e}");
+                Ok(())
+            }
+        };
+        if let Err(e) = sent {
+            eprintln!("! could not send: {e}");
+        }
+    }
+
+    if let Err(e) = client.disconnect().await {
+        eprintln!("! could not disconnect: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_args() {
+        let options = args(&[
+            "-n",
+            "/admin",
+            "--auth",
+            r#"{"token": "abc"}"#,
+            "-H",
+            "x-tenant: 7",
+            "-t",
+            "websocket",
+            "--ack-timeout",
+            "0.5",
+            "http://localhost:4200",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                url: "http://localhost:4200".to_owned(),
+                namespace: "/admin".to_owned(),
+                auth: Some(json!({"token": "abc"})),
+                headers: vec![("x-tenant".to_owned(), "7".to_owned())],
+                transport_type: TransportType::Websocket,
+                ack_timeout: Duration::from_millis(500),
+            }
+        );
+
+        assert_eq!(args(&["-h"]), Ok(None));
+        assert!(args(&[]).is_err());
+        assert!(args(&["-t", "carrier-pigeon", "http://localhost"]).is_err());
+        assert!(args(&["http://localhost", "http://localhost"]).is_err());
+        assert!(args(&["http://localhost", "--namespace"]).is_err());
+    }
+
+    #[test]
+    fn parses_lines() {
+        assert_eq!(
+            parse_line(r#"chat "hello" {"room": "lobby"}"#),
+            Ok(Command::Emit(
+                "chat".to_owned(),
+                vec![json!("hello"), json!({"room": "lobby"})]
+            ))
+        );
+        assert_eq!(
+            parse_line("ping"),
+            Ok(Command::Emit("ping".to_owned(), vec![]))
+        );
+        assert_eq!(
+            parse_line("/ask  status 1 [2]"),
+            Ok(Command::Ask(
+                "status".to_owned(),
+                vec![json!(1), json!([2])]
+            ))
+        );
+        assert_eq!(
+            parse_line(r#"/ack "ok""#),
+            Ok(Command::Ack(vec![json!("ok")]))
+        );
+        assert_eq!(parse_line("/quit"), Ok(Command::Quit));
+        assert_eq!(parse_line("   "), Ok(Command::Nothing));
+        assert!(parse_line("chat hello").is_err());
+        assert!(parse_line("/ask").is_err());
+        assert!(parse_line("/shout").is_err());
+    }
+
+    #[test]
+    fn formats_payloads() {
+        assert_eq!(
+            format_payload(&Payload::Text(vec![json!("hi"), json!({"a": 1})])),
+            r#""hi" {"a":1}"#
+        );
+        assert_eq!(
+            format_payload(&Payload::Binary(vec![1, 2, 255].into())),
+            "<3 bytes: 0102ff>"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Runs the async client behind the sync [`super::Client`] and [`super::RawClient`]. The
+//! clients share a runtime driven by a thread of its own, the blocking methods spawn their
+//! work onto it and wait for the result, so they can be called from any thread, including
+//! the callbacks and the threads of another runtime.
+
+use std::{
+    cell::Cell,
+    future::Future,
+    io,
+    sync::{mpsc, OnceLock},
+};
+
+use futures_util::{future::BoxFuture, FutureExt};
+use tokio::runtime::{Builder, Handle};
+
+use crate::{asynchronous::client::manager, error::Result, Error};
+
+static RUNTIME: OnceLock<io::Result<Handle>> = OnceLock::new();
+
+thread_local! {
+    // set while a callback runs, whose blocking calls belong to the task polling the connection
+    static IN_CALLBACK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns the runtime of the sync clients, starting it with the first client. It's kept for
+/// the lifetime of the process, so a client that isn't disconnected keeps calling its
+/// callbacks once it's dropped, like before.
+fn runtime() -> Result<&'static Handle> {
+    RUNTIME
+        .get_or_init(|| {
+            let runtime = Builder::new_current_thread().enable_all().build()?;
+            let handle = runtime.handle().clone();
+            std::thread::Builder::new()
+                .name("rust-socketio".to_owned())
+                .spawn(move || runtime.block_on(std::future::pending::<()>()))?;
+            Ok(handle)
+        })
+        .as_ref()
+        .map_err(|err| Error::IncompleteIo(io::Error::new(err.kind(), err.to_string())))
+}
+
+/// Runs the future on the runtime of the sync clients and blocks until it's done.
+pub(crate) fn block_on<F, T>(future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = mpsc::sync_channel(1);
+    let send = async move {
+        let _ = tx.send(future.await);
+    };
+    // the polling task waits for the callback, so it mustn't wait for itself in turn
+    if IN_CALLBACK.with(Cell::get) {
+        runtime()?.spawn(manager::within_polling_task(send));
+    } else {
+        runtime()?.spawn(send);
+    }
+    // the result is only missing if the runtime was shut down
+    rx.recv().unwrap_or(Err(Error::StoppedEngineIoSocket))
+}
+
+/// Runs the future on the runtime of the sync clients without waiting for it.
+pub(crate) fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    if let Ok(runtime) = runtime() {
+        runtime.spawn(future);
+    }
+}
+
+/// Turns a callback of the sync client into the future of an async callback. It's called on
+/// a blocking thread of the runtime, as it may block, e.g. by emitting on the client it's
+/// given.
+pub(crate) fn callback<F>(callback: F) -> BoxFuture<'static, ()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let _in_callback = InCallback::enter();
+        callback();
+    })
+    .map(drop)
+    .boxed()
+}
+
+/// Marks the current thread as running a callback until it's dropped, even if the callback
+/// panicked.
+struct InCallback;
+
+impl InCallback {
+    fn enter() -> Self {
+        IN_CALLBACK.with(|in_callback| in_callback.set(true));
+        InCallback
+    }
+}
+
+impl Drop for InCallback {
+    fn drop(&mut self) {
+        IN_CALLBACK.with(|in_callback| in_callback.set(false));
+    }
+}
@@ -1,13 +1,24 @@
 use std::{
+    collections::HashMap,
     fmt::Debug,
     ops::{Deref, DerefMut},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
 };
 
 use super::RawClient;
-use crate::{Event, Payload};
+use crate::{
+    error::Result, ClientError, DisconnectReason, Error, Event, HealthReport, Payload, Severity,
+};
 
 pub(crate) type SocketCallback = Box<dyn FnMut(Payload, RawClient) + 'static + Send>;
 pub(crate) type SocketAnyCallback = Box<dyn FnMut(Event, Payload, RawClient) + 'static + Send>;
+pub(crate) type SocketDisconnectCallback =
+    Box<dyn FnMut(DisconnectReason, RawClient) + 'static + Send>;
+pub(crate) type SocketConnectErrorCallback = Box<dyn FnMut(Error, RawClient) + 'static + Send>;
+pub(crate) type SocketPingTimeoutCallback = Box<dyn FnMut(RawClient) + 'static + Send>;
+pub(crate) type SocketErrorExtCallback = Box<dyn FnMut(ClientError, RawClient) + 'static + Send>;
+pub(crate) type SocketHealthCallback = Box<dyn FnMut(HealthReport, RawClient) + 'static + Send>;
 
 pub(crate) struct Callback<T> {
     inner: T,
@@ -78,3 +89,269 @@ impl Callback<SocketAnyCallback> {
         }
     }
 }
+
+// SocketDisconnectCallback implementations
+
+impl Debug for Callback<SocketDisconnectCallback> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl Deref for Callback<SocketDisconnectCallback> {
+    type Target = dyn FnMut(DisconnectReason, RawClient) + 'static + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<SocketDisconnectCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<SocketDisconnectCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: FnMut(DisconnectReason, RawClient) + 'static + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+// SocketConnectErrorCallback implementations
+
+impl Debug for Callback<SocketConnectErrorCallback> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl Deref for Callback<SocketConnectErrorCallback> {
+    type Target = dyn FnMut(Error, RawClient) + 'static + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<SocketConnectErrorCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<SocketConnectErrorCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: FnMut(Error, RawClient) + 'static + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+// SocketErrorExtCallback implementations
+
+impl Debug for Callback<SocketErrorExtCallback> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl Deref for Callback<SocketErrorExtCallback> {
+    type Target = dyn FnMut(ClientError, RawClient) + 'static + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<SocketErrorExtCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<SocketErrorExtCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: FnMut(ClientError, RawClient) + 'static + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+// SocketHealthCallback implementations
+
+impl Debug for Callback<SocketHealthCallback> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl Deref for Callback<SocketHealthCallback> {
+    type Target = dyn FnMut(HealthReport, RawClient) + 'static + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<SocketHealthCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<SocketHealthCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: FnMut(HealthReport, RawClient) + 'static + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+// SocketPingTimeoutCallback implementations
+
+impl Debug for Callback<SocketPingTimeoutCallback> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl Deref for Callback<SocketPingTimeoutCallback> {
+    type Target = dyn FnMut(RawClient) + 'static + Send;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref()
+    }
+}
+
+impl DerefMut for Callback<SocketPingTimeoutCallback> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut()
+    }
+}
+
+impl Callback<SocketPingTimeoutCallback> {
+    pub(crate) fn new<T>(callback: T) -> Self
+    where
+        T: FnMut(RawClient) + 'static + Send,
+    {
+        Callback {
+            inner: Box::new(callback),
+        }
+    }
+}
+
+/// The callbacks a client dispatches incoming events to. As callbacks can't be added once
+/// the client is connected, they are looked up without locking; only the callback that is
+/// called gets locked, as it may mutate its state.
+#[derive(Clone, Default)]
+pub(crate) struct EventCallbacks {
+    on: HashMap<Event, Arc<Mutex<Callback<SocketCallback>>>>,
+    on_any: Option<Arc<Mutex<Callback<SocketAnyCallback>>>>,
+}
+
+impl EventCallbacks {
+    pub(crate) fn insert(&mut self, event: Event, callback: Callback<SocketCallback>) {
+        self.on.insert(event, Arc::new(Mutex::new(callback)));
+    }
+
+    pub(crate) fn set_any(&mut self, callback: Callback<SocketAnyCallback>) {
+        self.on_any = Some(Arc::new(Mutex::new(callback)));
+    }
+
+    /// Returns the events that have a callback of their own.
+    pub(crate) fn events(&self) -> impl Iterator<Item = &Event> {
+        self.on.keys()
+    }
+
+    pub(crate) fn has_any(&self) -> bool {
+        self.on_any.is_some()
+    }
+
+    /// Calls the callback of the event, if there is one.
+    pub(crate) fn call(&self, event: &Event, payload: Payload, client: &RawClient) -> Result<()> {
+        let Some(on) = self.on.get(event) else {
+            return Ok(());
+        };
+        let result = {
+            let mut on = on.lock()?;
+            catch_panic(event.as_str(), || on(payload, client.clone()))
+        };
+        match result {
+            // a panic in the `error` callback itself is returned instead
+            Err(err) if *event != Event::Error => self.report(err, client),
+            result => result,
+        }
+    }
+
+    /// Calls the `on_any` callback, if there is one, which the async client only calls for
+    /// messages and custom events.
+    pub(crate) fn call_any(
+        &self,
+        event: Event,
+        payload: Payload,
+        client: &RawClient,
+    ) -> Result<()> {
+        let Some(on_any) = &self.on_any else {
+            return Ok(());
+        };
+        let result = {
+            let mut on_any = on_any.lock()?;
+            catch_panic(event.as_str(), || {
+                on_any(event.clone(), payload, client.clone())
+            })
+        };
+        result.or_else(|err| self.report(err, client))
+    }
+
+    /// Hands the error of a callback to the `error` and `on_error_ext` callbacks.
+    pub(crate) fn report(&self, err: Error, client: &RawClient) -> Result<()> {
+        let payload = Payload::from(err.to_string());
+        client.handle_error(Severity::Error, err);
+        self.call(&Event::Error, payload, client)
+    }
+}
+
+/// Calls a callback, catching a panic in it so it neither kills the thread polling for
+/// packets nor poisons the lock the callback is kept in, which callers have to hold
+/// outside of `call`. The panic is turned into [`Error::CallbackPanicked`].
+pub(crate) fn catch_panic(name: &str, call: impl FnOnce()) -> Result<()> {
+    panic::catch_unwind(AssertUnwindSafe(call))
+        .map_err(|_| Error::CallbackPanicked(name.to_owned()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_catch_panic() {
+        let counter = Mutex::new(0);
+
+        let mut count = counter.lock().unwrap();
+        assert!(catch_panic("foo", || *count += 1).is_ok());
+        let result = catch_panic("foo", || {
+            *count += 1;
+            panic!("callback failed");
+        });
+        drop(count);
+
+        assert!(matches!(result, Err(Error::CallbackPanicked(name)) if name == "foo"));
+        // the lock held around the panicking callback isn't poisoned
+        assert_eq!(*counter.lock().unwrap(), 2);
+    }
+}
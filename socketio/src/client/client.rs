@@ -1,51 +1,63 @@
 use std::{
-    sync::{Arc, Mutex, RwLock},
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
 use super::{ClientBuilder, RawClient};
-use crate::{
-    error::Result,
-    packet::{Packet, PacketId},
-    Error,
-};
+use crate::{error::Result, packet::Packet, EventStats, HealthReport, Stats};
 pub(crate) use crate::{event::Event, payload::Payload};
-use backoff::ExponentialBackoff;
-use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
 
+/// A client that reconnects on its own, see [`ClientBuilder::reconnect`]. Its
+/// callbacks keep being called across reconnects.
 #[derive(Clone)]
 pub struct Client {
-    builder: Arc<Mutex<ClientBuilder>>,
-    client: Arc<RwLock<RawClient>>,
-    backoff: ExponentialBackoff,
+    client: RawClient,
+    // the address set with `set_reconnect_url`, connected to by all following reconnects
+    reconnect_url: Arc<Mutex<Option<String>>>,
 }
 
 impl Client {
     pub(crate) fn new(builder: ClientBuilder) -> Result<Self> {
-        let builder_clone = builder.clone();
-        let client = builder_clone.connect_raw()?;
-        let backoff = ExponentialBackoffBuilder::new()
-            .with_initial_interval(Duration::from_millis(builder.reconnect_delay_min))
-            .with_max_interval(Duration::from_millis(builder.reconnect_delay_max))
-            .build();
-
-        let s = Self {
-            builder: Arc::new(Mutex::new(builder)),
-            client: Arc::new(RwLock::new(client)),
-            backoff,
-        };
-        s.poll_callback();
+        let reconnect_url = Arc::new(Mutex::new(None));
+        let client = builder.connect_reconnecting(reconnect_url.clone())?;
 
-        Ok(s)
+        Ok(Client {
+            client,
+            reconnect_url,
+        })
     }
 
     /// Updates the URL the client will connect to when reconnecting.
     /// This is especially useful for updating query parameters.
     pub fn set_reconnect_url<T: Into<String>>(&self, address: T) -> Result<()> {
-        self.builder.lock()?.address = address.into();
+        *self.reconnect_url.lock()? = Some(address.into());
         Ok(())
     }
 
+    /// Returns the session id the server assigned to the namespace of this
+    /// client, or `None` if the namespace is not connected (yet). The id
+    /// changes whenever the client reconnects.
+    pub fn sid(&self) -> Option<String> {
+        self.client.sid()
+    }
+
+    /// Returns a snapshot of the traffic of this client, counted over all its
+    /// reconnects.
+    pub fn stats(&self) -> Stats {
+        self.client.stats()
+    }
+
+    /// Returns the traffic of this client per namespace and event name, the events
+    /// with the most bytes first, see [`crate::asynchronous::Client::stats_by_event`].
+    pub fn stats_by_event(&self) -> Vec<EventStats> {
+        self.client.stats_by_event()
+    }
+
+    /// Returns the namespace this client is connected to.
+    pub fn nsp(&self) -> String {
+        self.client.nsp().to_owned()
+    }
+
     /// Sends a message to the server using the underlying `engine.io` protocol.
     /// This message takes an event, which could either be one of the common
     /// events like "message" or "error" or a custom event like "foo". But be
@@ -76,9 +88,90 @@ impl Client {
         E: Into<Event>,
         D: Into<Payload>,
     {
-        let client = self.client.read()?;
-        // TODO(#230): like js client, buffer emit, resend after reconnect
-        client.emit(event, data)
+        self.client.emit(event, data)
+    }
+
+    /// Sends several events, writing them to the transport at once, see
+    /// [`RawClient::emit_many`].
+    pub fn emit_many<I, E, D>(&self, events: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (E, D)>,
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        self.client.emit_many(events)
+    }
+
+    /// Sends a packet as it is, see [`RawClient::send_packet`].
+    /// # Example
+    /// ```
+    /// use rust_socketio::{ClientBuilder, Packet, PacketId};
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect() {
+    ///     let packet = Packet::new(
+    ///         PacketId::Event,
+    ///         "/".to_owned(),
+    ///         Some(r#"["ping",{"at":1}]"#.to_owned()),
+    ///         Some(7),
+    ///         0,
+    ///         None,
+    ///         None,
+    ///     );
+    ///
+    ///     assert!(socket.send_packet(packet).is_ok());
+    /// }
+    /// ```
+    pub fn send_packet(&self, packet: Packet) -> Result<()> {
+        self.client.send_packet(packet)
+    }
+
+    /// Sends a message like [`Client::emit`], but silently drops it if the
+    /// connection to the server is down instead of failing, like `socket.volatile`
+    /// of the JavaScript client. Meant for frequent updates, e.g. telemetry, that
+    /// are stale by the time the connection is back.
+    /// # Example
+    /// ```
+    /// use rust_socketio::ClientBuilder;
+    /// use serde_json::json;
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect() {
+    ///     let result = socket.emit_volatile("position", json!({"x": 1, "y": 2}));
+    ///
+    ///     assert!(result.is_ok());
+    /// }
+    /// ```
+    pub fn emit_volatile<E, D>(&self, event: E, data: D) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        self.client.emit_volatile(event, data)
+    }
+
+    /// Sends a message like [`Client::emit`], but gives up with
+    /// [`Error::Timeout`](crate::Error::Timeout) if the packet couldn't be written to
+    /// the transport within the given time span. The write itself can't be
+    /// interrupted and keeps going in the background, so the message may still reach
+    /// the server.
+    /// # Example
+    /// ```
+    /// use rust_socketio::ClientBuilder;
+    /// use serde_json::json;
+    /// use std::time::Duration;
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect() {
+    ///     let payload = json!({"token": 123});
+    ///     let result = socket.emit_with_timeout("foo", payload, Duration::from_secs(1));
+    ///
+    ///     assert!(result.is_ok());
+    /// }
+    /// ```
+    pub fn emit_with_timeout<E, D>(&self, event: E, data: D, timeout: Duration) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        self.client.emit_with_timeout(event, data, timeout)
     }
 
     /// Sends a message to the server but `alloc`s an `ack` to check whether the
@@ -130,9 +223,66 @@ impl Client {
         E: Into<Event>,
         D: Into<Payload>,
     {
-        let client = self.client.read()?;
-        // TODO(#230): like js client, buffer emit, resend after reconnect
-        client.emit_with_ack(event, data, timeout, callback)
+        self.client.emit_with_ack(event, data, timeout, callback)
+    }
+
+    /// Returns an iterator over the events the client receives, see
+    /// [`RawClient::iter`]. It goes on across reconnects, yielding a [`Event::Close`]
+    /// event for every connection that closed, and ends once the client is closed for
+    /// good.
+    pub fn iter(&self) -> impl Iterator<Item = (Event, Payload)> + '_ {
+        self.client.iter()
+    }
+
+    /// Takes the next event like [`Client::iter`], but waits at most the given time
+    /// span for it, see [`RawClient::try_recv`].
+    pub fn try_recv(&self, timeout: Duration) -> Option<(Event, Payload)> {
+        self.client.try_recv(timeout)
+    }
+
+    /// Stops reading packets from the connection until [`Client::resume`] is called,
+    /// see [`RawClient::pause`]. A reconnect doesn't resume the client.
+    pub fn pause(&self) {
+        self.client.pause();
+    }
+
+    /// Goes on reading packets from the connection after [`Client::pause`].
+    pub fn resume(&self) {
+        self.client.resume();
+    }
+
+    /// Returns the number of acks the server didn't answer yet, see
+    /// [`RawClient::pending_acks`].
+    pub fn pending_acks(&self) -> Result<usize> {
+        self.client.pending_acks()
+    }
+
+    /// Returns how long the oldest pending ack is waiting, see
+    /// [`RawClient::oldest_pending_ack`].
+    pub fn oldest_pending_ack(&self) -> Result<Option<Duration>> {
+        self.client.oldest_pending_ack()
+    }
+
+    /// Returns the number of packets waiting in the outgoing queue, see
+    /// [`RawClient::outgoing_queue_len`].
+    pub fn outgoing_queue_len(&self) -> Result<usize> {
+        self.client.outgoing_queue_len()
+    }
+
+    /// Probes the liveness of the client, see [`RawClient::health`].
+    pub fn health(&self) -> Result<HealthReport> {
+        self.client.health()
+    }
+
+    /// Blocks until the server acknowledged the namespace, see
+    /// [`RawClient::wait_until_connected`].
+    pub fn wait_until_connected(&self, timeout: Duration) -> Result<()> {
+        self.client.wait_until_connected(timeout)
+    }
+
+    /// Sends the heartbeat that is due, see [`RawClient::send_heartbeat`].
+    pub fn send_heartbeat(&self) -> Result<bool> {
+        self.client.send_heartbeat()
     }
 
     /// Disconnects this client from the server by sending a `socket.io` closing
@@ -161,109 +311,7 @@ impl Client {
     ///
     /// ```
     pub fn disconnect(&self) -> Result<()> {
-        let client = self.client.read()?;
-        client.disconnect()
-    }
-
-    fn reconnect(&mut self) -> Result<()> {
-        let mut reconnect_attempts = 0;
-        let (reconnect, max_reconnect_attempts) = {
-            let builder = self.builder.lock()?;
-            (builder.reconnect, builder.max_reconnect_attempts)
-        };
-
-        if reconnect {
-            loop {
-                if let Some(max_reconnect_attempts) = max_reconnect_attempts {
-                    reconnect_attempts += 1;
-                    if reconnect_attempts > max_reconnect_attempts {
-                        break;
-                    }
-                }
-
-                if let Some(backoff) = self.backoff.next_backoff() {
-                    std::thread::sleep(backoff);
-                }
-
-                if self.do_reconnect().is_ok() {
-                    break;
-                }
-            }
-        }
-
-        Ok(())
-    }
-
-    fn do_reconnect(&self) -> Result<()> {
-        let builder = self.builder.lock()?;
-        let new_client = builder.clone().connect_raw()?;
-        let mut client = self.client.write()?;
-        *client = new_client;
-
-        Ok(())
-    }
-
-    pub(crate) fn iter(&self) -> Iter {
-        Iter {
-            socket: self.client.clone(),
-        }
-    }
-
-    fn poll_callback(&self) {
-        let mut self_clone = self.clone();
-        // Use thread to consume items in iterator in order to call callbacks
-        std::thread::spawn(move || {
-            // tries to restart a poll cycle whenever a 'normal' error occurs,
-            // it just panics on network errors, in case the poll cycle returned
-            // `Result::Ok`, the server receives a close frame so it's safe to
-            // terminate
-            for packet in self_clone.iter() {
-                let should_reconnect = match packet {
-                    Err(Error::IncompleteResponseFromEngineIo(_)) => {
-                        //TODO: 0.3.X handle errors
-                        //TODO: logging error
-                        true
-                    }
-                    Ok(Packet {
-                        packet_type: PacketId::Disconnect,
-                        ..
-                    }) => match self_clone.builder.lock() {
-                        Ok(builder) => builder.reconnect_on_disconnect,
-                        Err(_) => false,
-                    },
-                    _ => false,
-                };
-                if should_reconnect {
-                    let _ = self_clone.disconnect();
-                    let _ = self_clone.reconnect();
-                }
-            }
-        });
-    }
-}
-
-pub(crate) struct Iter {
-    socket: Arc<RwLock<RawClient>>,
-}
-
-impl Iterator for Iter {
-    type Item = Result<Packet>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let socket = self.socket.read();
-        match socket {
-            Ok(socket) => match socket.poll() {
-                Err(err) => Some(Err(err)),
-                Ok(Some(packet)) => Some(Ok(packet)),
-                // If the underlying engineIO connection is closed,
-                // throw an error so we know to reconnect
-                Ok(None) => Some(Err(Error::StoppedEngineIoSocket)),
-            },
-            Err(_) => {
-                // Lock is poisoned, our iterator is useless.
-                None
-            }
-        }
+        self.client.disconnect()
     }
 }
 
@@ -276,7 +324,7 @@ mod test {
 
     use super::*;
     use crate::error::Result;
-    use crate::ClientBuilder;
+    use crate::{ClientBuilder, DisconnectReason, Error};
     use serde_json::json;
     use serial_test::serial;
     use std::time::{Duration, SystemTime};
@@ -419,56 +467,169 @@ mod test {
     }
 
     #[test]
-    fn socket_io_iterator_integration() -> Result<()> {
+    fn socket_io_on_disconnect_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
-        let builder = ClientBuilder::new(url);
-        let builder_clone = builder.clone();
 
-        let client = Arc::new(RwLock::new(builder_clone.connect_raw()?));
-        let mut socket = Client {
-            builder: Arc::new(Mutex::new(builder)),
-            client,
-            backoff: Default::default(),
-        };
-        let socket_clone = socket.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
 
-        let packets: Arc<RwLock<Vec<Packet>>> = Default::default();
-        let packets_clone = packets.clone();
+        let socket = ClientBuilder::new(url)
+            .on_disconnect(move |reason, _| tx.send(reason).unwrap())
+            .connect()?;
 
-        std::thread::spawn(move || {
-            for packet in socket_clone.iter() {
-                {
-                    let mut packets = packets_clone.write().unwrap();
-                    if let Ok(packet) = packet {
-                        (*packets).push(packet);
-                    }
-                }
+        socket.disconnect()?;
+
+        let reason = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(reason, DisconnectReason::IoClientDisconnect);
+
+        // the handler is only called once per disconnect
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(rx.try_recv().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn socket_io_connect_error_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let _socket = ClientBuilder::new(url)
+            .namespace("/invalid")
+            .on_connect_error(move |err, _| tx.send(err).unwrap())
+            .connect()?;
+
+        let err = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(
+            err,
+            Error::ConnectRejected { message, .. } if message == "Invalid namespace"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn socket_io_sid_integration() -> Result<()> {
+        let url = crate::test::socket_io_server();
+        let socket = ClientBuilder::new(url).namespace("admin").connect()?;
+        assert_eq!(socket.nsp(), "/admin");
+
+        // the sid arrives with the server's answer to the `Connect` packet
+        for _ in 0..10 {
+            if socket.sid().is_some() {
+                break;
             }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(socket.sid().is_some());
+
+        socket.disconnect()?;
+        assert!(socket.sid().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn socket_io_connect_timeout() -> Result<()> {
+        // accepts the tcp connection but never answers the handshake
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        std::thread::spawn(move || {
+            let _stream = listener.accept();
+            std::thread::sleep(Duration::from_secs(2));
         });
 
-        // waiting for client to emit messages
-        std::thread::sleep(Duration::from_millis(100));
-        let lock = packets.read().unwrap();
-        let pre_num = lock.len();
-        drop(lock);
+        let started = std::time::Instant::now();
+        let result = ClientBuilder::new(url)
+            .transport_type(crate::TransportType::Websocket)
+            .connect_timeout(Duration::from_millis(200))
+            .connect();
 
-        let _ = socket.disconnect();
-        socket.reconnect()?;
+        assert!(matches!(result, Err(Error::ConnectTimeout())));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        Ok(())
+    }
+
+    #[test]
+    fn calls_the_client_from_callbacks() -> Result<()> {
+        use crate::{asynchronous::test_util::MockServer, packet::PacketId, Event};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on("ping", |payload, socket| {
+                socket.emit("pong", payload).unwrap();
+            })
+            // the connection is closed while its callback runs
+            .on("bye", |_, socket| socket.disconnect().unwrap())
+            .on(Event::Close, move |_, _| tx.send(()).unwrap())
+            .connect()?;
+        runtime.block_on(async {
+            assert_eq!(
+                server.recv().await.map(|packet| packet.packet_type),
+                Some(PacketId::Connect)
+            );
+        });
 
-        // waiting for client to emit messages
-        std::thread::sleep(Duration::from_millis(100));
+        server.emit("ping", json!(1))?;
+        runtime.block_on(server.assert_emitted("pong", json!(1)));
 
-        let lock = packets.read().unwrap();
-        let post_num = lock.len();
-        drop(lock);
+        server.emit("bye", json!(null))?;
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(socket.sid().is_none());
+        assert!(matches!(
+            socket.emit("foo", json!(null)),
+            Err(Error::IllegalActionBeforeOpen())
+        ));
+        Ok(())
+    }
 
-        assert!(
-            pre_num < post_num,
-            "pre_num {} should less than post_num {}",
-            pre_num,
-            post_num
+    #[test]
+    fn iterates_across_reconnects() -> Result<()> {
+        use crate::asynchronous::test_util::{Fault, FaultyTransport, MockServer};
+        use crate::packet::PacketId;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        // the handshake, the namespace's acceptance and the first event come first
+        let transport = FaultyTransport::new(transport).incoming(3, Fault::Disconnect);
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect_delay(10, 10)
+            .queue_events(true)
+            .connect()?;
+        let connected = || {
+            runtime.block_on(async {
+                let packet = tokio::time::timeout(Duration::from_secs(5), server.recv()).await;
+                assert_eq!(
+                    packet.unwrap().map(|packet| packet.packet_type),
+                    Some(PacketId::Connect)
+                );
+            })
+        };
+        connected();
+
+        server.emit("foo", json!(1))?;
+        server.emit("foo", json!(2))?;
+        // the namespace is connected again over a new connection
+        connected();
+        server.emit("foo", json!(3))?;
+
+        let events: Vec<_> = socket.iter().take(3).collect();
+        assert_eq!(
+            events,
+            vec![
+                (Event::from("foo"), Payload::from(json!(1))),
+                (Event::Close, Payload::from("")),
+                (Event::from("foo"), Payload::from(json!(3))),
+            ]
         );
+        assert_eq!(socket.stats().reconnects, 1);
 
+        socket.disconnect()?;
         Ok(())
     }
 
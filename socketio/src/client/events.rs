@@ -0,0 +1,151 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+use log::trace;
+
+use crate::{Event, Payload};
+
+// how many events are kept for `RawClient::iter` and `RawClient::try_recv`
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+/// The events a sync client received, for consuming them in a loop of its own instead
+/// of with callbacks. Events are only queued once somebody listens for them, and it
+/// keeps the latest 1024 events, dropping the oldest ones if nobody takes them. Once
+/// it's closed, waiting for an event ends as soon as the queued ones are taken.
+#[derive(Default)]
+pub(crate) struct EventQueue {
+    events: Mutex<VecDeque<(Event, Payload)>>,
+    available: Condvar,
+    listening: AtomicBool,
+    closed: AtomicBool,
+}
+
+impl EventQueue {
+    /// Queues the events received from now on.
+    pub(crate) fn listen(&self) {
+        self.listening.store(true, Ordering::Release);
+    }
+
+    /// Whether received events are queued, so they only need to be copied if so.
+    pub(crate) fn is_listening(&self) -> bool {
+        self.listening.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn push(&self, event: Event, payload: Payload) {
+        if !self.is_listening() {
+            return;
+        }
+        let Ok(mut events) = self.events.lock() else {
+            return;
+        };
+        if events.len() == EVENT_QUEUE_CAPACITY {
+            trace!("Event queue is full, dropping the oldest event");
+            events.pop_front();
+        }
+        events.push_back((event, payload));
+        drop(events);
+        self.available.notify_one();
+    }
+
+    /// Wakes everybody waiting for an event, as no more are coming once the ones
+    /// that are queued are taken.
+    pub(crate) fn close(&self) {
+        // set under the lock, so a waiter can't miss it between checking and waiting
+        let Ok(events) = self.events.lock() else {
+            return;
+        };
+        self.closed.store(true, Ordering::Release);
+        drop(events);
+        self.available.notify_all();
+    }
+
+    /// Blocks until there is an event, or returns `None` once the queue is closed and
+    /// empty.
+    pub(crate) fn recv(&self) -> Option<(Event, Payload)> {
+        let events = self.events.lock().ok()?;
+        let mut events = self
+            .available
+            .wait_while(events, |events| events.is_empty() && !self.is_closed())
+            .ok()?;
+        events.pop_front()
+    }
+
+    /// Blocks until there is an event or the timeout passed.
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Option<(Event, Payload)> {
+        let events = self.events.lock().ok()?;
+        let (mut events, _) = self
+            .available
+            .wait_timeout_while(events, timeout, |events| {
+                events.is_empty() && !self.is_closed()
+            })
+            .ok()?;
+        events.pop_front()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drops_the_oldest_events() {
+        let queue = EventQueue::default();
+        queue.listen();
+        for i in 0..=EVENT_QUEUE_CAPACITY {
+            queue.push(Event::from("foo"), Payload::from(i.to_string()));
+        }
+
+        let (_, payload) = queue.recv().unwrap();
+        assert_eq!(payload, Payload::from("1"));
+        assert!(queue.recv_timeout(Duration::from_millis(10)).is_some());
+    }
+
+    #[test]
+    fn times_out_without_events() {
+        let queue = EventQueue::default();
+        assert!(queue.recv_timeout(Duration::from_millis(10)).is_none());
+    }
+
+    #[test]
+    fn only_queues_events_while_listening() {
+        let queue = EventQueue::default();
+        queue.push(Event::from("foo"), Payload::from("1"));
+        assert!(queue.recv_timeout(Duration::from_millis(10)).is_none());
+
+        queue.listen();
+        queue.push(Event::from("foo"), Payload::from("2"));
+        let (_, payload) = queue.recv().unwrap();
+        assert_eq!(payload, Payload::from("2"));
+    }
+
+    #[test]
+    fn ends_once_closed() {
+        let queue = std::sync::Arc::new(EventQueue::default());
+        queue.listen();
+        queue.push(Event::Close, Payload::from(""));
+
+        let waiting = queue.clone();
+        let waiter = std::thread::spawn(move || {
+            let mut events = Vec::new();
+            while let Some((event, _)) = waiting.recv() {
+                events.push(event);
+            }
+            events
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        queue.close();
+
+        assert_eq!(waiter.join().unwrap(), vec![Event::Close]);
+        assert!(queue.recv().is_none());
+    }
+}
@@ -1,11 +1,23 @@
 mod builder;
+#[cfg(not(target_arch = "wasm32"))]
 mod raw_client;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub use builder::ClientBuilder;
-pub use builder::TransportType;
+pub use builder::{ProtocolVersion, TransportType};
+#[cfg(not(target_arch = "wasm32"))]
 pub use client::Client;
+#[cfg(not(target_arch = "wasm32"))]
 pub use raw_client::RawClient;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod blocking;
 /// Internal callback type
+#[cfg(not(target_arch = "wasm32"))]
 mod callback;
+#[cfg(not(target_arch = "wasm32"))]
 mod client;
+#[cfg(not(target_arch = "wasm32"))]
+mod events;
+#[cfg(not(target_arch = "wasm32"))]
+mod workers;
@@ -1,93 +1,97 @@
-use super::callback::Callback;
-use crate::packet::{Packet, PacketId};
-use crate::Error;
+use super::blocking;
+use super::callback::{catch_panic, EventCallbacks};
+use super::events::EventQueue;
+use crate::asynchronous::{Client as AsyncClient, ClientBuilder as AsyncClientBuilder};
+use crate::error::{Error, Result, Severity};
+use crate::packet::Packet;
+use crate::HealthReport;
 pub(crate) use crate::{event::Event, payload::Payload};
-use rand::{thread_rng, Rng};
-use serde_json::Value;
-
-use crate::client::callback::{SocketAnyCallback, SocketCallback};
-use crate::error::Result;
-use std::collections::HashMap;
-use std::ops::DerefMut;
+use std::future::Future;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use std::time::Instant;
-
-use crate::socket::Socket as InnerSocket;
-
-/// Represents an `Ack` as given back to the caller. Holds the internal `id` as
-/// well as the current ack'ed state. Holds data which will be accessible as
-/// soon as the ack'ed state is set to true. An `Ack` that didn't get ack'ed
-/// won't contain data.
-#[derive(Debug)]
-pub struct Ack {
-    pub id: i32,
-    timeout: Duration,
-    time_started: Instant,
-    callback: Callback<SocketCallback>,
-}
 
 /// A socket which handles communication with the server. It's initialized with
 /// a specific address as well as an optional namespace to connect to. If `None`
 /// is given the server will connect to the default namespace `"/"`.
+///
+/// It's a blocking facade over the async [`crate::asynchronous::Client`], which runs on a
+/// runtime the sync clients share.
 #[derive(Clone)]
 pub struct RawClient {
-    /// The inner socket client to delegate the methods to.
-    socket: InnerSocket,
-    on: Arc<Mutex<HashMap<Event, Callback<SocketCallback>>>>,
-    on_any: Arc<Mutex<Option<Callback<SocketAnyCallback>>>>,
-    outstanding_acks: Arc<Mutex<Vec<Ack>>>,
-    // namespace, for multiplexing messages
-    nsp: String,
-    // Data send in the opening packet (commonly used as for auth)
-    auth: Option<Value>,
+    /// The async client to delegate the methods to.
+    client: AsyncClient,
+    callbacks: Arc<EventCallbacks>,
+    // the received events, for `iter` and `try_recv`
+    events: Arc<EventQueue>,
 }
 
 impl RawClient {
-    /// Creates a socket with a certain address to connect to as well as a
-    /// namespace. If `None` is passed in as namespace, the default namespace
-    /// `"/"` is taken.
-    /// ```
-    pub(crate) fn new<T: Into<String>>(
-        socket: InnerSocket,
-        namespace: T,
-        on: Arc<Mutex<HashMap<Event, Callback<SocketCallback>>>>,
-        on_any: Arc<Mutex<Option<Callback<SocketAnyCallback>>>>,
-        auth: Option<Value>,
+    pub(crate) fn new(
+        client: AsyncClient,
+        callbacks: Arc<EventCallbacks>,
+        events: Arc<EventQueue>,
+    ) -> Self {
+        RawClient {
+            client,
+            callbacks,
+            events,
+        }
+    }
+
+    /// Connects the async client the builder of the sync one was turned into.
+    pub(crate) fn connect(
+        builder: AsyncClientBuilder,
+        callbacks: Arc<EventCallbacks>,
+        events: Arc<EventQueue>,
     ) -> Result<Self> {
-        Ok(RawClient {
-            socket,
-            nsp: namespace.into(),
-            on,
-            on_any,
-            outstanding_acks: Arc::new(Mutex::new(Vec::new())),
-            auth,
-        })
-    }
-
-    /// Connects the client to a server. Afterwards the `emit_*` methods can be
-    /// called to interact with the server. Attention: it's not allowed to add a
-    /// callback after a call to this method.
-    pub(crate) fn connect(&self) -> Result<()> {
-        // Connect the underlying socket
-        self.socket.connect()?;
-
-        let auth = self.auth.as_ref().map(|data| data.to_string());
-
-        // construct the opening packet
-        let open_packet = Packet::new(
-            PacketId::Connect,
-            self.nsp.clone(),
-            auth,
-            None,
-            0,
-            None,
-            None,
-        );
+        let client = blocking::block_on(builder.connect())?;
+        if let Some(closed) = client.closed() {
+            let events = events.clone();
+            blocking::spawn(async move {
+                closed.await;
+                events.close();
+            });
+        }
+        Ok(RawClient::new(client, callbacks, events))
+    }
+
+    /// Connects the async client without the task polling its connection, see
+    /// [`RawClient::poll_packet`].
+    pub(crate) fn connect_manual(
+        builder: AsyncClientBuilder,
+        callbacks: Arc<EventCallbacks>,
+        events: Arc<EventQueue>,
+    ) -> Result<Self> {
+        let client = blocking::block_on(async move {
+            builder.connect_manual().await.map(AsyncClient::with_handle)
+        })?;
+        Ok(RawClient::new(client, callbacks, events))
+    }
 
-        self.socket.send(open_packet)?;
+    /// Passes an error of a callback to the `on_error_ext` callback without waiting for
+    /// it, as the callback may be the one the error came from.
+    pub(crate) fn handle_error(&self, severity: Severity, err: Error) {
+        let client = self.client.clone();
+        blocking::spawn(async move { client.handle_error(severity, &err).await });
+    }
 
-        Ok(())
+    /// Returns the session id the server assigned to the namespace of this
+    /// client, or `None` if the namespace is not connected (yet).
+    pub fn sid(&self) -> Option<String> {
+        self.client.sid()
+    }
+
+    /// Returns the namespace this client is connected to.
+    pub fn nsp(&self) -> &str {
+        self.client.nsp()
+    }
+
+    pub(crate) fn stats(&self) -> crate::Stats {
+        self.client.stats()
+    }
+
+    pub(crate) fn stats_by_event(&self) -> Vec<crate::EventStats> {
+        self.client.stats_by_event()
     }
 
     /// Sends a message to the server using the underlying `engine.io` protocol.
@@ -121,7 +125,83 @@ impl RawClient {
         E: Into<Event>,
         D: Into<Payload>,
     {
-        self.socket.emit(&self.nsp, event.into(), data.into())
+        let (event, data) = (event.into(), data.into());
+        self.block_on(|client| async move { client.emit(event, data).await })
+    }
+
+    /// Sends several events, writing them to the transport at once, see
+    /// [`Client::emit_many`](crate::asynchronous::Client::emit_many).
+    pub fn emit_many<I, E, D>(&self, events: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (E, D)>,
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let events: Vec<(Event, Payload)> = events
+            .into_iter()
+            .map(|(event, data)| (event.into(), data.into()))
+            .collect();
+        self.block_on(|client| async move { client.emit_many(events).await })
+    }
+
+    /// Sends a packet as it is, e.g. for extensions of the protocol or to test a
+    /// server, bypassing what the client keeps track of: acks sent this way aren't
+    /// awaited, and the namespace of the packet doesn't have to be the one of the
+    /// client. Fails with [`Error::InvalidPacket`](crate::Error::InvalidPacket) if the packet doesn't carry as many
+    /// attachments as it announces.
+    pub fn send_packet(&self, packet: Packet) -> Result<()> {
+        self.block_on(|client| async move { client.send_packet(packet).await })
+    }
+
+    /// Sends a message like [`RawClient::emit`], but silently drops it if the
+    /// connection to the server is down instead of failing, like `socket.volatile`
+    /// of the JavaScript client. Meant for frequent updates, e.g. telemetry, that
+    /// are stale by the time the connection is back.
+    /// # Example
+    /// ```
+    /// use rust_socketio::ClientBuilder;
+    /// use serde_json::json;
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect() {
+    ///     let result = socket.emit_volatile("position", json!({"x": 1, "y": 2}));
+    ///
+    ///     assert!(result.is_ok());
+    /// }
+    /// ```
+    pub fn emit_volatile<E, D>(&self, event: E, data: D) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let (event, data) = (event.into(), data.into());
+        self.block_on(|client| async move { client.emit_volatile(event, data).await })
+    }
+
+    /// Sends a message like [`RawClient::emit`], but gives up with
+    /// [`Error::Timeout`](crate::Error::Timeout) if the packet couldn't be written to the
+    /// transport within the given time span, see
+    /// [`crate::asynchronous::Client::emit_with_timeout`]. The write is given up along
+    /// with it.
+    /// # Example
+    /// ```
+    /// use rust_socketio::ClientBuilder;
+    /// use serde_json::json;
+    /// use std::time::Duration;
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect() {
+    ///     let payload = json!({"token": 123});
+    ///     let result = socket.emit_with_timeout("foo", payload, Duration::from_secs(1));
+    ///
+    ///     assert!(result.is_ok());
+    /// }
+    /// ```
+    pub fn emit_with_timeout<E, D>(&self, event: E, data: D, timeout: Duration) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let (event, data) = (event.into(), data.into());
+        self.block_on(|client| async move { client.emit_with_timeout(event, data, timeout).await })
     }
 
     /// Disconnects this client from the server by sending a `socket.io` closing
@@ -150,22 +230,9 @@ impl RawClient {
     ///
     /// ```
     pub fn disconnect(&self) -> Result<()> {
-        let disconnect_packet = Packet::new(
-            PacketId::Disconnect,
-            self.nsp.clone(),
-            None,
-            None,
-            0,
-            None,
-            None,
-        );
-
-        // TODO: logging
-        let _ = self.socket.send(disconnect_packet);
-        self.socket.disconnect()?;
-
-        let _ = self.callback(&Event::Close, ""); // trigger on_close
-        Ok(())
+        let result = self.block_on(|client| async move { client.disconnect().await });
+        self.events.close();
+        result
     }
 
     /// Sends a message to the server but `alloc`s an `ack` to check whether the
@@ -218,210 +285,154 @@ impl RawClient {
         E: Into<Event>,
         D: Into<Payload>,
     {
-        let id = thread_rng().gen_range(0..999);
-        let socket_packet =
-            Packet::new_from_payload(data.into(), event.into(), &self.nsp, Some(id))?;
-
-        let ack = Ack {
-            id,
-            time_started: Instant::now(),
-            timeout,
-            callback: Callback::<SocketCallback>::new(callback),
+        let (event, data) = (event.into(), data.into());
+        let (callbacks, events) = (self.callbacks.clone(), self.events.clone());
+        let callback = Arc::new(Mutex::new(callback));
+        let callback = move |payload: Payload, client: AsyncClient| {
+            let client = RawClient::new(client, callbacks.clone(), events.clone());
+            let callback = callback.clone();
+            blocking::callback(move || {
+                let Ok(mut callback) = callback.lock() else {
+                    return;
+                };
+                let result = catch_panic("ack", || callback(payload, client.clone()));
+                drop(callback);
+                if let Err(err) = result {
+                    let _ = client.callbacks.report(err, &client);
+                }
+            })
         };
-
-        // add the ack to the tuple of outstanding acks
-        self.outstanding_acks.lock()?.push(ack);
-
-        self.socket.send(socket_packet)?;
-        Ok(())
+        self.block_on(
+            |client| async move { client.emit_with_ack(event, data, timeout, callback).await },
+        )
     }
 
-    pub(crate) fn poll(&self) -> Result<Option<Packet>> {
-        loop {
-            match self.socket.poll() {
-                Err(err) => {
-                    self.callback(&Event::Error, err.to_string())?;
-                    return Err(err);
-                }
-                Ok(Some(packet)) => {
-                    if packet.nsp == self.nsp {
-                        self.handle_socketio_packet(&packet)?;
-                        return Ok(Some(packet));
-                    } else {
-                        // Not our namespace continue polling
-                    }
-                }
-                Ok(None) => return Ok(None),
-            }
-        }
+    /// Stops reading packets from the connection until [`RawClient::resume`] is called,
+    /// see [`crate::asynchronous::Client::pause`]. Clients connected with
+    /// [`ClientBuilder::connect_manual`](crate::ClientBuilder::connect_manual) aren't
+    /// affected, as they only read when polled.
+    pub fn pause(&self) {
+        self.client.pause();
     }
 
-    #[cfg(test)]
-    pub(crate) fn iter(&self) -> Iter {
-        Iter { socket: self }
+    /// Goes on reading packets from the connection after [`RawClient::pause`].
+    pub fn resume(&self) {
+        self.client.resume();
     }
 
-    fn callback<P: Into<Payload>>(&self, event: &Event, payload: P) -> Result<()> {
-        let mut on = self.on.lock()?;
-        let mut on_any = self.on_any.lock()?;
-        let lock = on.deref_mut();
-        let on_any_lock = on_any.deref_mut();
-
-        let payload = payload.into();
-
-        if let Some(callback) = lock.get_mut(event) {
-            callback(payload.clone(), self.clone());
-        }
-        match event {
-            Event::Message | Event::Custom(_) => {
-                if let Some(callback) = on_any_lock {
-                    callback(event.clone(), payload, self.clone())
-                }
-            }
-            _ => {}
-        }
-        drop(on);
-        drop(on_any);
-        Ok(())
+    /// Returns the number of acks the server didn't answer yet, see
+    /// [`crate::asynchronous::Client::pending_acks`].
+    pub fn pending_acks(&self) -> Result<usize> {
+        self.block_on(|client| async move { Ok(client.pending_acks().await) })
     }
 
-    /// Handles the incoming acks and classifies what callbacks to call and how.
-    #[inline]
-    fn handle_ack(&self, socket_packet: &Packet) -> Result<()> {
-        let Some(id) = socket_packet.id else {
-            return Ok(());
-        };
-
-        self.outstanding_acks.lock()?.retain_mut(|ack| {
-            if ack.id != id {
-                return true;
-            }
-
-            if ack.time_started.elapsed() < ack.timeout {
-                if let Some(ref payload) = socket_packet.data {
-                    ack.callback.deref_mut()(Payload::from(payload.to_owned()), self.clone());
-                }
-
-                if let Some(ref attachments) = socket_packet.attachments {
-                    if let Some(payload) = attachments.first() {
-                        ack.callback.deref_mut()(Payload::Binary(payload.to_owned()), self.clone());
-                    }
-                }
-            }
-            // nope, just ignore it, the official implment just remove the ack id when timeout
-            // https://github.com/socketio/socket.io-client/blob/main/lib/socket.ts#L467-L495
-            false
-        });
+    /// Returns how long the oldest pending ack is waiting, see
+    /// [`crate::asynchronous::Client::oldest_pending_ack`].
+    pub fn oldest_pending_ack(&self) -> Result<Option<Duration>> {
+        self.block_on(|client| async move { Ok(client.oldest_pending_ack().await) })
+    }
 
-        Ok(())
+    /// Returns the number of packets waiting in the outgoing queue, see
+    /// [`crate::asynchronous::Client::outgoing_queue_len`].
+    pub fn outgoing_queue_len(&self) -> Result<usize> {
+        self.block_on(|client| async move { Ok(client.outgoing_queue_len().await) })
     }
 
-    /// Handles a binary event.
-    #[inline]
-    fn handle_binary_event(&self, packet: &Packet) -> Result<()> {
-        let event = if let Some(string_data) = &packet.data {
-            string_data.replace('\"', "").into()
-        } else {
-            Event::Message
-        };
+    /// Probes the liveness of the client, see [`crate::asynchronous::Client::health`].
+    pub fn health(&self) -> Result<HealthReport> {
+        self.block_on(|client| async move { Ok(client.health().await) })
+    }
 
-        if let Some(attachments) = &packet.attachments {
-            if let Some(binary_payload) = attachments.first() {
-                self.callback(&event, Payload::Binary(binary_payload.to_owned()))?;
-            }
-        }
-        Ok(())
+    /// Blocks until the server acknowledged the namespace, see
+    /// [`crate::asynchronous::Client::wait_until_connected`]. Clients connected with
+    /// [`ClientBuilder::connect_manual`](crate::ClientBuilder::connect_manual) only
+    /// notice the acknowledgement once it's read by [`RawClient::poll_packet`].
+    pub fn wait_until_connected(&self, timeout: Duration) -> Result<()> {
+        self.block_on(|client| async move { client.wait_until_connected(timeout).await })
     }
 
-    /// A method that parses a packet and eventually calls the corresponding
-    /// callback with the supplied data.
-    fn handle_event(&self, packet: &Packet) -> Result<()> {
-        let Some(ref data) = packet.data else {
-            return Ok(());
-        };
+    /// Sends the heartbeat that is due, for clients built with
+    /// [`ClientBuilder::manual_heartbeat`](crate::ClientBuilder::manual_heartbeat). Returns
+    /// whether a heartbeat was sent, see [`crate::asynchronous::Client::send_heartbeat`].
+    pub fn send_heartbeat(&self) -> Result<bool> {
+        self.block_on(|client| async move { client.send_heartbeat().await })
+    }
 
-        // a socketio message always comes in one of the following two flavors (both JSON):
-        // 1: `["event", "msg", ...]`
-        // 2: `["msg"]`
-        // in case 2, the message is ment for the default message event, in case 1 the event
-        // is specified
-        if let Ok(Value::Array(contents)) = serde_json::from_str::<Value>(data) {
-            let (event, payloads) = match contents.len() {
-                0 => return Err(Error::IncompletePacket()),
-                1 => (Event::Message, contents.as_slice()),
-                // get rest of data if first is a event
-                _ => match contents.first() {
-                    Some(Value::String(ev)) => (Event::from(ev.as_str()), &contents[1..]),
-                    // get rest(1..) of them as data, not just take the 2nd element
-                    _ => (Event::Message, contents.as_slice()),
-                    // take them all as data
-                },
-            };
-
-            // call the correct callback
-            self.callback(&event, payloads.to_vec())?;
+    /// Reads the next packet from the server and handles it, calling its callbacks,
+    /// for clients connected with
+    /// [`ClientBuilder::connect_manual`](crate::ClientBuilder::connect_manual). This
+    /// lets the caller drive the client from a loop of its own, e.g. the one of a game,
+    /// and nothing is read from the server while it doesn't. Returns `None` if no
+    /// packet arrived within the given time span, and
+    /// [`Error::StoppedEngineIoSocket`](crate::Error::StoppedEngineIoSocket) once the
+    /// connection is closed, which isn't re-established. Fails with
+    /// [`Error::IllegalManualPoll`](crate::Error::IllegalManualPoll) for clients whose
+    /// packets are polled by a background task.
+    /// # Example
+    /// ```
+    /// use rust_socketio::ClientBuilder;
+    /// use std::time::Duration;
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/")
+    ///     .on("test", |payload, _| println!("Received: {payload:?}"))
+    ///     .connect_manual()
+    /// {
+    ///     // once per frame
+    ///     while let Ok(Some(packet)) = socket.poll_packet(Duration::from_millis(1)) {
+    ///         println!("Handled: {packet:?}");
+    ///     }
+    /// }
+    /// ```
+    pub fn poll_packet(&self, timeout: Duration) -> Result<Option<Packet>> {
+        let packet = self.block_on(|client| async move { client.next_packet(timeout).await });
+        if let Err(Error::StoppedEngineIoSocket) = packet {
+            self.events.close();
         }
-
-        Ok(())
+        packet
     }
 
-    /// Handles the incoming messages and classifies what callbacks to call and how.
-    /// This method is later registered as the callback for the `on_data` event of the
-    /// engineio client.
-    #[inline]
-    fn handle_socketio_packet(&self, packet: &Packet) -> Result<()> {
-        if packet.nsp == self.nsp {
-            match packet.packet_type {
-                PacketId::Ack | PacketId::BinaryAck => {
-                    if let Err(err) = self.handle_ack(packet) {
-                        self.callback(&Event::Error, err.to_string())?;
-                        return Err(err);
-                    }
-                }
-                PacketId::BinaryEvent => {
-                    if let Err(err) = self.handle_binary_event(packet) {
-                        self.callback(&Event::Error, err.to_string())?;
-                    }
-                }
-                PacketId::Connect => {
-                    self.callback(&Event::Connect, "")?;
-                }
-                PacketId::Disconnect => {
-                    self.callback(&Event::Close, "")?;
-                }
-                PacketId::ConnectError => {
-                    self.callback(
-                        &Event::Error,
-                        String::from("Received an ConnectError frame: ")
-                            + &packet
-                                .clone()
-                                .data
-                                .unwrap_or_else(|| String::from("\"No error message provided\"")),
-                    )?;
-                }
-                PacketId::Event => {
-                    if let Err(err) = self.handle_event(packet) {
-                        self.callback(&Event::Error, err.to_string())?;
-                    }
-                }
-            }
-        }
-        Ok(())
+    /// Returns an iterator over the messages and custom events the client receives,
+    /// for consuming them in a loop instead of with callbacks, which are still called
+    /// for every event. A [`Event::Close`] event is yielded whenever the connection
+    /// is closed. Waiting for the next event blocks, the iterator ends once the client
+    /// is closed for good: it's disconnected, or the connection is lost and isn't
+    /// re-established. Events are queued from the first call of this method or
+    /// [`RawClient::try_recv`] on, or right from the start with
+    /// [`ClientBuilder::queue_events`](crate::ClientBuilder::queue_events). The client
+    /// keeps the latest 1024 events nobody took yet, clones of it take them from the
+    /// same queue.
+    /// # Example
+    /// ```
+    /// use rust_socketio::ClientBuilder;
+    ///
+    /// if let Ok(socket) = ClientBuilder::new("http://localhost:4200/").connect_raw() {
+    ///     for (event, payload) in socket.iter() {
+    ///         println!("{event}: {payload:?}");
+    ///     }
+    /// }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (Event, Payload)> + '_ {
+        self.events.listen();
+        std::iter::from_fn(|| self.events.recv())
     }
-}
 
-pub struct Iter<'a> {
-    socket: &'a RawClient,
-}
+    /// Takes the next event like [`RawClient::iter`], but waits at most the given
+    /// time span for it, returning `None` if none arrived or the client is closed
+    /// for good.
+    pub fn try_recv(&self, timeout: Duration) -> Option<(Event, Payload)> {
+        self.events.listen();
+        self.events.recv_timeout(timeout)
+    }
 
-impl<'a> Iterator for Iter<'a> {
-    type Item = Result<Packet>;
-    fn next(&mut self) -> std::option::Option<<Self as std::iter::Iterator>::Item> {
-        match self.socket.poll() {
-            Err(err) => Some(Err(err)),
-            Ok(Some(packet)) => Some(Ok(packet)),
-            Ok(None) => None,
-        }
+    /// Runs a method of the async client on the runtime of the sync clients and waits for
+    /// it to finish.
+    fn block_on<F, Fut, T>(&self, call: F) -> Result<T>
+    where
+        F: FnOnce(AsyncClient) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        blocking::block_on(call(self.client.clone()))
     }
 }
 
@@ -431,8 +442,14 @@ mod test {
     use std::thread::sleep;
 
     use super::*;
-    use crate::{client::TransportType, payload::Payload, ClientBuilder};
+    use crate::{
+        client::TransportType,
+        packet::{Direction, PacketId},
+        payload::Payload,
+        ClientBuilder,
+    };
     use bytes::Bytes;
+    #[cfg(feature = "native-tls")]
     use native_tls::TlsConnector;
     use serde_json::json;
     use std::time::Duration;
@@ -482,6 +499,7 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "native-tls")]
     #[test]
     fn socket_io_builder_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
@@ -523,6 +541,7 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "native-tls")]
     #[test]
     fn socket_io_builder_integration_iterator() -> Result<()> {
         let url = crate::test::socket_io_server();
@@ -535,13 +554,14 @@ mod test {
             .build()
             .expect("Found illegal configuration");
 
-        let socket = socket_builder
-            .namespace("/admin")
-            .tls_config(tls_connector)
-            .opening_header("accept-encoding", "application/json")
-            .on("test", |str, _| println!("Received: {:#?}", str))
-            .on("message", |payload, _| println!("{:#?}", payload))
-            .connect_raw()?;
+        let (socket, packets) = connect_observed(
+            socket_builder
+                .namespace("/admin")
+                .tls_config(tls_connector)
+                .opening_header("accept-encoding", "application/json")
+                .on("test", |str, _| println!("Received: {:#?}", str))
+                .on("message", |payload, _| println!("{:#?}", payload)),
+        )?;
 
         assert!(socket.emit("message", json!("Hello World")).is_ok());
 
@@ -559,7 +579,7 @@ mod test {
             )
             .is_ok());
 
-        test_socketio_socket(socket, "/admin".to_owned())
+        test_socketio_socket(socket, packets, "/admin".to_owned())
     }
 
     #[test]
@@ -599,15 +619,14 @@ mod test {
     fn socket_io_auth_builder_integration() -> Result<()> {
         let url = crate::test::socket_io_auth_server();
         let nsp = String::from("/admin");
-        let socket = ClientBuilder::new(url)
-            .namespace(nsp.clone())
-            .auth(json!({ "password": "123" }))
-            .connect_raw()?;
+        let (_socket, packets) = connect_observed(
+            ClientBuilder::new(url)
+                .namespace(nsp.clone())
+                .auth(json!({ "password": "123" })),
+        )?;
 
-        let mut iter = socket
-            .iter()
-            .map(|packet| packet.unwrap())
-            .filter(|packet| packet.packet_type != PacketId::Connect);
+        let mut iter =
+            packets.filter(|packet| packet.nsp == nsp && packet.packet_type != PacketId::Connect);
 
         let packet: Option<Packet> = iter.next();
         assert!(packet.is_some());
@@ -633,44 +652,268 @@ mod test {
     #[test]
     fn socketio_polling_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url)
-            .transport_type(TransportType::Polling)
-            .connect_raw()?;
-        test_socketio_socket(socket, "/".to_owned())
+        let (socket, packets) =
+            connect_observed(ClientBuilder::new(url).transport_type(TransportType::Polling))?;
+        test_socketio_socket(socket, packets, "/".to_owned())
     }
 
     #[test]
     fn socket_io_websocket_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url)
-            .transport_type(TransportType::Websocket)
-            .connect_raw()?;
-        test_socketio_socket(socket, "/".to_owned())
+        let (socket, packets) =
+            connect_observed(ClientBuilder::new(url).transport_type(TransportType::Websocket))?;
+        test_socketio_socket(socket, packets, "/".to_owned())
     }
 
     #[test]
     fn socket_io_websocket_upgrade_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url)
-            .transport_type(TransportType::WebsocketUpgrade)
-            .connect_raw()?;
-        test_socketio_socket(socket, "/".to_owned())
+        let (socket, packets) = connect_observed(
+            ClientBuilder::new(url).transport_type(TransportType::WebsocketUpgrade),
+        )?;
+        test_socketio_socket(socket, packets, "/".to_owned())
     }
 
     #[test]
     fn socket_io_any_integration() -> Result<()> {
         let url = crate::test::socket_io_server();
-        let socket = ClientBuilder::new(url)
-            .transport_type(TransportType::Any)
+        let (socket, packets) =
+            connect_observed(ClientBuilder::new(url).transport_type(TransportType::Any))?;
+        test_socketio_socket(socket, packets, "/".to_owned())
+    }
+
+    #[test]
+    fn iterates_events() -> Result<()> {
+        use crate::asynchronous::test_util::MockServer;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
             .connect_raw()?;
-        test_socketio_socket(socket, "/".to_owned())
+        runtime.block_on(async {
+            assert_eq!(
+                server.recv().await.map(|packet| packet.packet_type),
+                Some(PacketId::Connect)
+            );
+        });
+        assert!(socket.try_recv(Duration::from_millis(50)).is_none());
+
+        server.emit("foo", json!(1))?;
+        server.emit("bar", json!(2))?;
+        let mut events = socket.iter();
+        assert_eq!(
+            events.next(),
+            Some((Event::from("foo"), Payload::from(json!(1))))
+        );
+        assert_eq!(
+            events.next(),
+            Some((Event::from("bar"), Payload::from(json!(2))))
+        );
+
+        server.send(Packet::new(
+            PacketId::Disconnect,
+            "/".to_owned(),
+            None,
+            None,
+            0,
+            None,
+            None,
+        ))?;
+        let (event, _) = socket.try_recv(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, Event::Close);
+        Ok(())
+    }
+
+    #[test]
+    fn ends_iterating_once_closed() -> Result<()> {
+        use crate::asynchronous::test_util::{Fault, FaultyTransport, MockServer};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        // the handshake and the namespace's acceptance come first
+        let transport = FaultyTransport::new(transport).incoming(3, Fault::Disconnect);
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .queue_events(true)
+            .connect_raw()?;
+        runtime.block_on(server.recv());
+
+        server.emit("foo", json!(1))?;
+        server.emit("bar", json!(2))?;
+        let events: Vec<_> = socket.iter().map(|(event, _)| event).collect();
+        assert_eq!(events, vec![Event::from("foo"), Event::Close]);
+
+        let (_server, transport) = MockServer::new();
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .connect_raw()?;
+        socket.disconnect()?;
+        assert_eq!(socket.iter().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn emits_with_timeout() -> Result<()> {
+        use crate::asynchronous::test_util::{Fault, FaultyTransport, MockServer};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        // the writer is stuck on the first event, after the frames opening the connection
+        let transport =
+            FaultyTransport::new(transport).outgoing(2, Fault::Delay(Duration::from_millis(300)));
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect_raw()?;
+        runtime.block_on(server.recv());
+
+        let result = socket.emit_with_timeout("tick", json!(1), Duration::from_millis(50));
+        assert!(matches!(result, Err(crate::Error::Timeout())));
+
+        // the timed out write is given up
+        socket.emit_with_timeout("tick", json!(2), Duration::from_secs(1))?;
+        runtime.block_on(server.assert_emitted("tick", json!(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn emits_volatile_events() -> Result<()> {
+        use crate::asynchronous::test_util::MockServer;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect_raw()?;
+        runtime.block_on(server.recv());
+
+        socket.emit_volatile("tick", json!(1))?;
+        runtime.block_on(server.assert_emitted("tick", json!(1)));
+
+        // dropped as the connection is closed
+        socket.disconnect()?;
+        assert!(socket.emit("tick", json!(2)).is_err());
+        assert!(socket.emit_volatile("tick", json!(2)).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn polls_packets_manually() -> Result<()> {
+        use crate::{asynchronous::test_util::MockServer, Error};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let (tx, rx) = mpsc::channel();
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .on("foo", move |payload, _| tx.send(payload).unwrap())
+            .connect_manual()?;
+        runtime.block_on(server.recv());
+
+        // nothing is read until the client is polled
+        server.emit("foo", json!(1))?;
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        let mut types = Vec::new();
+        while let Some(packet) = socket.poll_packet(Duration::from_millis(100))? {
+            types.push(packet.packet_type);
+        }
+        assert_eq!(types, vec![PacketId::Connect, PacketId::Event]);
+        assert_eq!(rx.try_recv().unwrap(), Payload::from(json!(1)));
+        assert!(socket.sid().is_some());
+
+        let (_server, transport) = MockServer::new();
+        let polled = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .connect_raw()?;
+        assert!(matches!(
+            polled.poll_packet(Duration::from_millis(10)),
+            Err(Error::IllegalManualPoll())
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn polls_packets_with_slow_callbacks() -> Result<()> {
+        use crate::asynchronous::test_util::MockServer;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let (tx, rx) = mpsc::channel();
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .on("foo", move |payload, _| {
+                std::thread::sleep(Duration::from_millis(200));
+                tx.send(payload).unwrap();
+            })
+            .connect_manual()?;
+        runtime.block_on(server.recv());
+        socket.poll_packet(Duration::from_millis(100))?;
+
+        // the timeout only bounds the read, the callbacks of a read packet always run
+        server.emit("foo", json!(1))?;
+        let packet = socket.poll_packet(Duration::from_millis(100))?.unwrap();
+        assert_eq!(packet.packet_type, PacketId::Event);
+        assert_eq!(rx.try_recv().unwrap(), Payload::from(json!(1)));
+        assert!(socket.poll_packet(Duration::from_millis(10))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn pauses_reading() -> Result<()> {
+        use crate::asynchronous::test_util::MockServer;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (server, transport) = MockServer::new();
+        let (tx, rx) = mpsc::channel();
+        let socket = ClientBuilder::new("http://localhost/")
+            .mock_transport(transport)
+            .reconnect(false)
+            .on("foo", move |payload, _| tx.send(payload).unwrap())
+            .connect_raw()?;
+        runtime.block_on(server.recv());
+
+        socket.pause();
+        server.emit("foo", json!(1))?;
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        socket.resume();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            Payload::from(json!(1))
+        );
+        Ok(())
+    }
+
+    /// Connects with the builder, returning the client and the packets it receives.
+    fn connect_observed(
+        builder: ClientBuilder,
+    ) -> Result<(RawClient, impl Iterator<Item = Packet>)> {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let socket = builder
+            .packet_observer(move |direction, packet| {
+                if direction == Direction::Inbound {
+                    let _ = tx.lock().unwrap().send(packet.clone());
+                }
+            })
+            .connect_raw()?;
+
+        let packets = std::iter::from_fn(move || rx.recv_timeout(Duration::from_secs(5)).ok());
+        Ok((socket, packets))
     }
 
-    fn test_socketio_socket(socket: RawClient, nsp: String) -> Result<()> {
-        let mut iter = socket
-            .iter()
-            .map(|packet| packet.unwrap())
-            .filter(|packet| packet.packet_type != PacketId::Connect);
+    fn test_socketio_socket(
+        socket: RawClient,
+        packets: impl Iterator<Item = Packet>,
+        nsp: String,
+    ) -> Result<()> {
+        let mut iter =
+            packets.filter(|packet| packet.nsp == nsp && packet.packet_type != PacketId::Connect);
 
         let packet: Option<Packet> = iter.next();
         assert!(packet.is_some());
@@ -0,0 +1,139 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io,
+    sync::mpsc::{self, Sender},
+    time::Instant,
+};
+
+use futures_util::future::{self, BoxFuture, FutureExt};
+
+use super::blocking;
+use crate::{stats, Event};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of threads running the callbacks of a sync client, see
+/// [`super::ClientBuilder::callback_workers`]. The callbacks of an event always run on
+/// the same worker, so they are called in the order the events arrived. The threads end
+/// once the pool is dropped, after running the callbacks queued so far.
+pub(crate) struct Workers {
+    queues: Vec<Sender<Job>>,
+}
+
+impl Workers {
+    pub(crate) fn new(size: usize) -> io::Result<Self> {
+        let queues = (0..size)
+            .map(|index| {
+                let (queue, jobs) = mpsc::channel::<Job>();
+                std::thread::Builder::new()
+                    .name(format!("rust-socketio-worker-{index}"))
+                    .spawn(move || jobs.into_iter().for_each(|job| job()))?;
+                Ok(queue)
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Workers { queues })
+    }
+
+    /// Returns the index of the worker running the callbacks of the event.
+    fn worker(&self, event: &Event) -> usize {
+        let mut hasher = DefaultHasher::new();
+        event.hash(&mut hasher);
+        (hasher.finish() % self.queues.len() as u64) as usize
+    }
+
+    /// Queues the callback on the worker of the event.
+    fn run(&self, event: &Event, callback: Job) {
+        // the worker only stops once the pool is dropped
+        let _ = self.queues[self.worker(event)].send(callback);
+    }
+}
+
+/// Turns a callback of the sync client into the future of an async callback. With a pool
+/// of workers, the callback is queued on the worker of the event and the future is ready
+/// right away, so the client goes on with the next packet, and the worker records how long
+/// the callback took instead. Without one, the future runs the callback like
+/// [`blocking::callback`].
+pub(crate) fn callback<F>(
+    workers: Option<&Workers>,
+    event: &Event,
+    callback: F,
+) -> BoxFuture<'static, ()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    match workers {
+        Some(workers) => {
+            workers.run(
+                event,
+                Box::new(move || {
+                    let started = Instant::now();
+                    callback();
+                    stats::record_handler_duration(started.elapsed());
+                }),
+            );
+            future::ready(()).boxed()
+        }
+        None => blocking::callback(callback),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{mpsc, Arc, Barrier},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn keeps_the_order_of_an_event() {
+        let workers = Workers::new(4).unwrap();
+        let (tx, rx) = mpsc::channel();
+        for i in 0..100 {
+            let tx = tx.clone();
+            workers.run(&Event::from("foo"), Box::new(move || tx.send(i).unwrap()));
+        }
+
+        let received: Vec<_> = rx.iter().take(100).collect();
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn runs_events_concurrently() {
+        let workers = Workers::new(2).unwrap();
+        let (tx, rx) = mpsc::channel();
+        // the events only get past the barrier together, so they must run at once
+        let barrier = Arc::new(Barrier::new(2));
+        // finds two events handled by different workers
+        let events = [
+            Event::Message,
+            Event::Close,
+            Event::Connect,
+            Event::Error,
+            Event::from("foo"),
+            Event::from("bar"),
+        ];
+        let first = &events[0];
+        let second = events
+            .iter()
+            .find(|event| workers.worker(event) != workers.worker(first))
+            .unwrap();
+
+        for event in [first, second] {
+            let (tx, barrier) = (tx.clone(), barrier.clone());
+            workers.run(
+                event,
+                Box::new(move || {
+                    barrier.wait();
+                    tx.send(()).unwrap();
+                }),
+            );
+        }
+
+        for _ in 0..2 {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+    }
+}
@@ -1,11 +1,17 @@
 use base64::DecodeError;
+use bytes::Bytes;
 use serde_json::Error as JsonError;
+use std::fmt;
 use std::io::Error as IoError;
 use std::num::ParseIntError;
 use std::str::Utf8Error;
 use thiserror::Error;
 use url::ParseError as UrlParseError;
 
+pub use rust_engineio::ErrorKind;
+
+use crate::packet::{Packet, PacketId};
+
 /// Enumeration of all possible errors in the `socket.io` context.
 /// TODO: 0.4.X Do not expose non-trivial internal errors. Convert error to string.
 #[derive(Error, Debug)]
@@ -42,14 +48,267 @@ pub enum Error {
     InvalidInteger(#[from] ParseIntError),
     #[error("EngineIO Error")]
     IncompleteResponseFromEngineIo(#[from] rust_engineio::Error),
+    #[error("Malformed packet {frame:?}, expected {expected} at byte {offset}")]
+    InvalidPacketSyntax {
+        frame: Bytes,
+        offset: usize,
+        expected: &'static str,
+    },
+    #[error("Skipped the malformed packet {0:?}: {1}")]
+    InvalidFrame(String, Box<Error>),
+    #[error("Packet exceeds a size limit: {0}")]
+    IllegalPacketSize(String),
     #[error("Invalid packet type while reading attachments")]
     InvalidAttachmentPacketType(u8),
     #[error("Underlying Engine.IO connection has closed")]
     StoppedEngineIoSocket,
+    #[error("Namespace is already connected: {0}")]
+    IllegalNamespaceAlreadyConnected(String),
+    #[error("Namespace is not connected: {0}")]
+    IllegalNamespaceNotConnected(String),
+    #[error("The packets of the client are polled by its background task")]
+    IllegalManualPoll(),
+    #[error("WebTransport is only supported by the async client")]
+    IllegalWebtransport(),
+    #[error("Timed out while connecting to the server")]
+    ConnectTimeout(),
+    #[error("Timed out while writing to the transport")]
+    Timeout(),
+    #[error("Timed out waiting for an ack")]
+    AckTimeout(),
+    #[error("The queue of outgoing events is full")]
+    OutgoingQueueFull(),
+    #[error("Events are emitted faster than the rate limit allows")]
+    RateLimited(),
+    #[error("The JSON schema of {0} is invalid: {1}")]
+    InvalidSchema(String, String),
+    #[error("The payload of {0} does not match its JSON schema: {1}")]
+    InvalidPayload(String, String),
+    #[error("Failed to encrypt or decrypt a payload: {0}")]
+    InvalidEncryption(Box<dyn std::error::Error + Send + Sync>),
+    #[error("The packet stream fell behind and skipped {0} packets")]
+    IncompletePacketStream(u64),
+    #[error("A callback for {0} panicked")]
+    CallbackPanicked(String),
+    #[error("The adapter of the server failed: {0}")]
+    IncompleteAdapter(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Server rejected the namespace connection: {message}")]
+    ConnectRejected {
+        message: String,
+        data: Option<serde_json::Value>,
+    },
+    #[error("{source} ({context})")]
+    PacketFailed {
+        context: Box<ErrorContext>,
+        source: Box<Error>,
+    },
+}
+
+/// The packet an error occurred with while it was decoded or dispatched, see
+/// [`Error::context`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    /// The namespace of the packet, unless it couldn't be decoded.
+    pub namespace: Option<String>,
+    /// The name of the event, for event packets.
+    pub event: Option<String>,
+    /// The type of the packet, unless it couldn't be decoded.
+    pub packet_type: Option<PacketId>,
+    /// The id of the ack the packet requests or answers.
+    pub ack_id: Option<i32>,
+    /// The start of the data of the packet, at most [`ErrorContext::PAYLOAD_LEN`] bytes.
+    pub payload: String,
+}
+
+impl ErrorContext {
+    /// How many bytes of the data of the packet are kept.
+    pub const PAYLOAD_LEN: usize = 64;
+
+    pub(crate) fn of_packet(packet: &Packet) -> Self {
+        let data = packet.data.as_deref().unwrap_or_default();
+        let event = match packet.packet_type {
+            PacketId::Event | PacketId::BinaryEvent => {
+                Some(match serde_json::from_str::<serde_json::Value>(data) {
+                    Ok(serde_json::Value::Array(contents)) if contents.len() > 1 => {
+                        match contents.first() {
+                            Some(serde_json::Value::String(event)) => event.clone(),
+                            _ => "message".to_owned(),
+                        }
+                    }
+                    _ => "message".to_owned(),
+                })
+            }
+            _ => None,
+        };
+        ErrorContext {
+            namespace: Some(packet.nsp.clone()),
+            event,
+            packet_type: Some(packet.packet_type),
+            ack_id: packet.id,
+            payload: Self::truncate(data.as_bytes()),
+        }
+    }
+
+    pub(crate) fn of_frame(frame: &[u8]) -> Self {
+        ErrorContext {
+            payload: Self::truncate(frame),
+            ..Default::default()
+        }
+    }
+
+    fn truncate(data: &[u8]) -> String {
+        let data = &data[..data.len().min(Self::PAYLOAD_LEN)];
+        String::from_utf8_lossy(data).into_owned()
+    }
+}
+
+/// How bad an error passed to the `on_error_ext` callback of a client is, see
+/// [`crate::asynchronous::ClientBuilder::on_error_ext`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// The client went on, e.g. after skipping a packet it couldn't handle or after a
+    /// failed reconnect attempt.
+    Warning,
+    /// The client recovers on its own, e.g. the connection was lost and is
+    /// re-established, or a callback panicked.
+    Error,
+    /// The client gave up reconnecting and stays disconnected.
+    Fatal,
+}
+
+/// An error the background task of a client ran into, passed to its `on_error_ext`
+/// callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClientError {
+    pub severity: Severity,
+    pub kind: ErrorKind,
+    /// The packet the error occurred with, see [`Error::context`].
+    pub context: Option<ErrorContext>,
+    /// The message of the error.
+    pub message: String,
+}
+
+impl ClientError {
+    pub(crate) fn new(severity: Severity, err: &Error) -> Self {
+        ClientError {
+            severity,
+            kind: err.kind(),
+            context: err.context().cloned(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.packet_type {
+            Some(packet_type) => write!(f, "{packet_type:?} packet")?,
+            None => write!(f, "undecoded packet")?,
+        }
+        if let Some(namespace) = &self.namespace {
+            write!(f, " on {namespace}")?;
+        }
+        if let Some(event) = &self.event {
+            write!(f, ", event {event:?}")?;
+        }
+        if let Some(ack_id) = self.ack_id {
+            write!(f, ", ack id {ack_id}")?;
+        }
+        write!(f, ", payload {:?}", self.payload)
+    }
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// Returns what caused the error, telling apart e.g. a lost connection from a
+    /// namespace the server refused to connect, see [`ErrorKind`].
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::IncompleteResponseFromEngineIo(err) => err.kind(),
+            Error::PacketFailed { source, .. } => source.kind(),
+            Error::IncompleteIo(_)
+            | Error::IllegalActionBeforeOpen()
+            | Error::StoppedEngineIoSocket
+            | Error::ConnectTimeout()
+            | Error::Timeout()
+            | Error::AckTimeout()
+            | Error::IncompleteAdapter(_) => ErrorKind::Network,
+            Error::InvalidPacketId(_)
+            | Error::IncompletePacket()
+            | Error::InvalidPacket()
+            | Error::InvalidUtf8(_)
+            | Error::InvalidBase64(_)
+            | Error::InvalidHandshake(_)
+            | Error::InvalidJson(_)
+            | Error::InvalidInteger(_)
+            | Error::InvalidPacketSyntax { .. }
+            | Error::InvalidFrame(..)
+            | Error::IllegalPacketSize(_)
+            | Error::InvalidAttachmentPacketType(_) => ErrorKind::Protocol,
+            Error::ConnectRejected { .. } => ErrorKind::Auth,
+            Error::InvalidUrl(_)
+            | Error::InvalidUrlScheme(_)
+            | Error::InvalidPoisonedLock()
+            | Error::IllegalNamespaceAlreadyConnected(_)
+            | Error::IllegalNamespaceNotConnected(_)
+            | Error::IllegalManualPoll()
+            | Error::IllegalWebtransport()
+            | Error::OutgoingQueueFull()
+            | Error::RateLimited()
+            | Error::InvalidSchema(..)
+            | Error::InvalidPayload(..)
+            | Error::InvalidEncryption(_)
+            | Error::IncompletePacketStream(_)
+            | Error::CallbackPanicked(_) => ErrorKind::User,
+        }
+    }
+
+    /// Whether retrying later may succeed: for network errors, and for events that
+    /// were turned away by the outgoing queue or the rate limit. A rejected namespace
+    /// connection or a misconfigured client needs a fix instead.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::PacketFailed { source, .. } => source.is_retryable(),
+            Error::OutgoingQueueFull() | Error::RateLimited() => true,
+            _ => self.kind() == ErrorKind::Network,
+        }
+    }
+
+    /// Returns the packet the error occurred with, if it occurred while a packet of
+    /// the server was decoded or dispatched to the callbacks. The `Event::Error`
+    /// callbacks receive it as part of the message.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::PacketFailed { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Attaches the packet the error occurred with, see [`Error::context`].
+    pub(crate) fn in_context(self, context: ErrorContext) -> Self {
+        match self {
+            Error::PacketFailed { .. } => self,
+            source => Error::PacketFailed {
+                context: Box::new(context),
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Whether the error means the connection to the server isn't usable right now.
+    pub(crate) fn is_disconnected(&self) -> bool {
+        matches!(
+            self,
+            Error::IllegalActionBeforeOpen()
+                | Error::StoppedEngineIoSocket
+                | Error::IncompleteResponseFromEngineIo(_)
+        )
+    }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(_: std::sync::PoisonError<T>) -> Self {
         Self::InvalidPoisonedLock()
@@ -79,4 +338,66 @@ mod tests {
         let _error = std::io::Error::new(std::io::ErrorKind::Other, Error::IncompletePacket());
         assert!(matches!(_io_error, _error));
     }
+
+    #[test]
+    fn test_is_disconnected() {
+        assert!(Error::IllegalActionBeforeOpen().is_disconnected());
+        assert!(Error::StoppedEngineIoSocket.is_disconnected());
+        assert!(Error::IncompleteResponseFromEngineIo(
+            rust_engineio::Error::IllegalActionBeforeOpen()
+        )
+        .is_disconnected());
+        assert!(!Error::InvalidPacket().is_disconnected());
+    }
+
+    #[test]
+    fn test_context() {
+        let packet = Packet::new(
+            PacketId::Event,
+            "/chat".to_owned(),
+            Some(format!(r#"["greet","{}"]"#, "x".repeat(100))),
+            Some(3),
+            0,
+            None,
+            None,
+        );
+        let err = Error::InvalidPacket().in_context(ErrorContext::of_packet(&packet));
+        let context = err.context().unwrap();
+        assert_eq!(context.namespace.as_deref(), Some("/chat"));
+        assert_eq!(context.event.as_deref(), Some("greet"));
+        assert_eq!(context.packet_type, Some(PacketId::Event));
+        assert_eq!(context.ack_id, Some(3));
+        assert_eq!(context.payload.len(), ErrorContext::PAYLOAD_LEN);
+        assert!(err
+            .to_string()
+            .starts_with(r#"Got an invalid packet which did not follow the protocol format (Event packet on /chat, event "greet", ack id 3, payload "[\"greet\",\"xx"#));
+        assert_eq!(err.kind(), ErrorKind::Protocol);
+
+        let err = Error::InvalidPacket().in_context(ErrorContext::of_frame(b"9/chat"));
+        assert!(err
+            .to_string()
+            .ends_with(r#"(undecoded packet, payload "9/chat")"#));
+        assert!(Error::InvalidPacket().context().is_none());
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Error::StoppedEngineIoSocket.kind(), ErrorKind::Network);
+        assert_eq!(
+            Error::IncompleteResponseFromEngineIo(rust_engineio::Error::IncompleteHttp(403)).kind(),
+            ErrorKind::Auth
+        );
+        assert_eq!(Error::InvalidPacket().kind(), ErrorKind::Protocol);
+        let rejected = Error::ConnectRejected {
+            message: "Not authorized".to_owned(),
+            data: None,
+        };
+        assert_eq!(rejected.kind(), ErrorKind::Auth);
+        assert!(!rejected.is_retryable());
+
+        assert!(Error::AckTimeout().is_retryable());
+        assert!(Error::RateLimited().is_retryable());
+        assert_eq!(Error::RateLimited().kind(), ErrorKind::User);
+        assert!(!Error::IllegalManualPoll().is_retryable());
+    }
 }
@@ -57,3 +57,39 @@ impl Display for Event {
         f.write_str(self.as_str())
     }
 }
+
+/// The reason why a client got disconnected from its namespace, mirroring the
+/// disconnect reasons of the JavaScript client. It's passed to the handler
+/// registered with `on_disconnect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DisconnectReason {
+    /// The server disconnected the namespace by sending a `Disconnect` packet.
+    IoServerDisconnect,
+    /// The client disconnected manually by calling `disconnect`.
+    IoClientDisconnect,
+    /// The server did not send a ping within `pingInterval + pingTimeout`.
+    PingTimeout,
+    /// The underlying connection was closed.
+    TransportClose,
+    /// The underlying connection encountered an error.
+    TransportError,
+}
+
+impl DisconnectReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DisconnectReason::IoServerDisconnect => "io server disconnect",
+            DisconnectReason::IoClientDisconnect => "io client disconnect",
+            DisconnectReason::PingTimeout => "ping timeout",
+            DisconnectReason::TransportClose => "transport close",
+            DisconnectReason::TransportError => "transport error",
+        }
+    }
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
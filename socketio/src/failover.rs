@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// How long an address that failed to connect is only tried after the others.
+const QUARANTINE: Duration = Duration::from_secs(30);
+
+/// The address the next client connecting round robin starts with, counted over
+/// all clients of the process.
+static NEXT_START: AtomicUsize = AtomicUsize::new(0);
+
+/// The health of the addresses a client fails over between, to try them in the
+/// order they most likely connect in: the address of the last connection first and
+/// the addresses that failed recently last. Addresses are tracked by value, as a
+/// resolver may return others for every connection.
+#[derive(Debug, Default)]
+pub(crate) struct Endpoints {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // the address connected to last
+    current: Option<String>,
+    // when addresses last failed to connect, if they did since they last connected
+    failed_at: HashMap<String, Instant>,
+}
+
+impl Endpoints {
+    /// The addresses in the order to try them. Without a previous connection to one
+    /// of them, the first address is tried first, or the next one in turn if
+    /// connecting round robin.
+    pub(crate) fn order<'a>(&self, addresses: &[&'a str], round_robin: bool) -> Vec<&'a str> {
+        let state = self.state.lock().unwrap();
+        let current = state
+            .current
+            .as_deref()
+            .and_then(|current| addresses.iter().position(|&address| address == current));
+        let start = match current {
+            Some(current) => current,
+            None if round_robin && !addresses.is_empty() => {
+                NEXT_START.fetch_add(1, Ordering::Relaxed) % addresses.len()
+            }
+            None => 0,
+        };
+        let mut order: Vec<&str> = addresses[start..]
+            .iter()
+            .chain(&addresses[..start])
+            .copied()
+            .collect();
+        // the sort is stable, so the addresses stay in turn within both groups
+        order.sort_by_key(|&address| {
+            state
+                .failed_at
+                .get(address)
+                .is_some_and(|failed_at| failed_at.elapsed() < QUARANTINE)
+        });
+        order
+    }
+
+    /// Records whether connecting to the address succeeded.
+    pub(crate) fn record(&self, address: &str, connected: bool) {
+        let mut state = self.state.lock().unwrap();
+        if connected {
+            state.current = Some(address.to_owned());
+            state.failed_at.remove(address);
+        } else {
+            state.failed_at.insert(address.to_owned(), Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tries_healthy_addresses_first() {
+        let endpoints = Endpoints::default();
+        assert_eq!(
+            endpoints.order(&["a", "b", "c"], false),
+            vec!["a", "b", "c"]
+        );
+
+        endpoints.record("a", false);
+        assert_eq!(
+            endpoints.order(&["a", "b", "c"], false),
+            vec!["b", "c", "a"]
+        );
+
+        endpoints.record("b", false);
+        endpoints.record("c", true);
+        assert_eq!(
+            endpoints.order(&["a", "b", "c"], false),
+            vec!["c", "a", "b"]
+        );
+
+        // an address that connected again is healthy again
+        endpoints.record("a", true);
+        assert_eq!(
+            endpoints.order(&["a", "b", "c"], false),
+            vec!["a", "c", "b"]
+        );
+
+        // addresses that weren't around before are healthy
+        assert_eq!(
+            endpoints.order(&["d", "b", "a"], false),
+            vec!["a", "d", "b"]
+        );
+        assert!(endpoints.order(&[], true).is_empty());
+    }
+
+    #[test]
+    fn starts_round_robin() {
+        let starts: Vec<&str> = (0..6)
+            .map(|_| Endpoints::default().order(&["a", "b", "c"], true)[0])
+            .collect();
+        // other tests may connect round robin at the same time, which skips starts
+        assert!(starts.iter().any(|&start| start != starts[0]));
+
+        // once connected, a client sticks to its address
+        let endpoints = Endpoints::default();
+        endpoints.record("b", true);
+        assert_eq!(endpoints.order(&["a", "b", "c"], true), vec!["b", "c", "a"]);
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn fails_over_to_the_next_address() -> crate::error::Result<()> {
+        use tokio::{net::TcpListener, time::timeout};
+
+        use crate::server::{Server, ServerBuilder};
+
+        async fn wait_for_sockets(server: &Server, count: usize) {
+            timeout(Duration::from_secs(5), async {
+                while server.sockets("/").len() != count {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+        }
+
+        // nothing listens on the port of a closed listener
+        let closed = TcpListener::bind("127.0.0.1:0").await?;
+        let down = format!("http://{}/", closed.local_addr()?);
+        drop(closed);
+
+        let server = ServerBuilder::new().build();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let up = format!("http://{}/", listener.local_addr()?);
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = crate::test::connect(
+            crate::asynchronous::ClientBuilder::new(&down).failover_addresses([&up]),
+        )
+        .await?;
+        wait_for_sockets(&server, 1).await;
+        client.disconnect().await?;
+        wait_for_sockets(&server, 0).await;
+
+        let client = tokio::task::spawn_blocking(move || {
+            crate::ClientBuilder::new(down)
+                .failover_addresses([up])
+                .connect()
+        })
+        .await
+        .unwrap()?;
+        // the blocking client doesn't wait for the server to accept the namespace
+        wait_for_sockets(&server, 1).await;
+        tokio::task::spawn_blocking(move || client.disconnect())
+            .await
+            .unwrap()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn connects_to_resolved_addresses() -> crate::error::Result<()> {
+        use std::sync::{atomic::AtomicUsize, Arc};
+
+        use futures_util::FutureExt;
+        use tokio::net::TcpListener;
+        use url::Url;
+
+        use crate::{asynchronous::ClientBuilder, server::ServerBuilder};
+
+        let server = ServerBuilder::new().build();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let up = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let resolved = Arc::new(AtomicUsize::new(0));
+        let counted = resolved.clone();
+        let addresses = vec![Url::parse("http://127.0.0.1:1/")?, up.clone()];
+        let client = crate::test::connect(
+            ClientBuilder::new("http://127.0.0.1:1/").endpoint_resolver(move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                let addresses = addresses.clone();
+                async move { addresses }.boxed()
+            }),
+        )
+        .await?;
+        assert_eq!(resolved.load(Ordering::SeqCst), 1);
+        assert!(server.socket("/", &client.sid().unwrap()).is_some());
+        client.disconnect().await?;
+
+        // without resolved addresses, the address of the builder is used
+        let client = crate::test::connect(
+            ClientBuilder::new(up.as_str()).endpoint_resolver(|| async { Vec::new() }.boxed()),
+        )
+        .await?;
+        assert!(server.socket("/", &client.sid().unwrap()).is_some());
+        client.disconnect().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use crate::asynchronous::ConnectionState;
+
+/// How a client is doing, as judged by a [`HealthCheck`]. Ordered from best to worst.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Health {
+    /// The namespace is connected and nothing exceeds its degraded threshold.
+    Healthy,
+    /// The client is connecting or reconnecting, or something exceeds its degraded
+    /// threshold.
+    Degraded,
+    /// The namespace is disconnected for good, or something exceeds its unhealthy
+    /// threshold. A client that stays unhealthy is likely stuck and worth restarting.
+    Unhealthy,
+}
+
+/// What a probe of a [`HealthCheck`] found, passed to its callback.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct HealthReport {
+    /// The verdict over all of the values below.
+    pub health: Health,
+    /// The state of the connection to the namespace.
+    pub state: ConnectionState,
+    /// The time since the latest heartbeat of the server.
+    pub since_heartbeat: Duration,
+    /// The time the oldest ack the server didn't answer yet is waiting, if there is one.
+    pub oldest_ack: Option<Duration>,
+    /// The number of packets waiting in the outgoing queue.
+    pub queued: usize,
+}
+
+/// Thresholds past which a value makes a client degraded and unhealthy.
+type Thresholds<T> = Option<(T, T)>;
+
+/// A watchdog that probes the liveness of a client every interval and reports a
+/// [`HealthReport`] to a callback, see `ClientBuilder::health_check`.
+///
+/// The age of the server's heartbeat is always checked: a client is degraded once the
+/// heartbeat is half the `pingTimeout` late and unhealthy once the connection is about
+/// to be considered lost, unless other thresholds are set. The age of pending acks and
+/// the depth of the outgoing queue are only checked if thresholds are set for them.
+///
+/// # Example
+/// ```rust
+/// use rust_socketio::HealthCheck;
+/// use std::time::Duration;
+///
+/// let check = HealthCheck::new(Duration::from_secs(10))
+///     .ack_age(Duration::from_secs(5), Duration::from_secs(30))
+///     .queue_depth(100, 1000);
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HealthCheck {
+    interval: Duration,
+    heartbeat_age: Thresholds<Duration>,
+    ack_age: Thresholds<Duration>,
+    queue_depth: Thresholds<usize>,
+}
+
+impl HealthCheck {
+    /// Probes the client every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        HealthCheck {
+            interval,
+            heartbeat_age: None,
+            ack_age: None,
+            queue_depth: None,
+        }
+    }
+
+    /// Sets how old the latest heartbeat of the server may get before the client is
+    /// degraded and unhealthy, instead of the thresholds derived from the handshake.
+    pub fn heartbeat_age(mut self, degraded: Duration, unhealthy: Duration) -> Self {
+        self.heartbeat_age = Some((degraded, unhealthy));
+        self
+    }
+
+    /// Sets how long the oldest ack may be pending before the client is degraded and
+    /// unhealthy.
+    pub fn ack_age(mut self, degraded: Duration, unhealthy: Duration) -> Self {
+        self.ack_age = Some((degraded, unhealthy));
+        self
+    }
+
+    /// Sets how many packets may wait in the outgoing queue before the client is
+    /// degraded and unhealthy.
+    pub fn queue_depth(mut self, degraded: usize, unhealthy: usize) -> Self {
+        self.queue_depth = Some((degraded, unhealthy));
+        self
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Judges the probed values. `ping_interval` and `heartbeat_timeout` are those of
+    /// the connection, the latter being the time after which it's considered lost.
+    pub(crate) fn report(
+        &self,
+        state: ConnectionState,
+        since_heartbeat: Duration,
+        (ping_interval, heartbeat_timeout): (Duration, Duration),
+        oldest_ack: Option<Duration>,
+        queued: usize,
+    ) -> HealthReport {
+        let heartbeat_age = self.heartbeat_age.unwrap_or_else(|| {
+            let late = heartbeat_timeout.saturating_sub(ping_interval) / 2;
+            (ping_interval + late, heartbeat_timeout)
+        });
+        let health = match state {
+            ConnectionState::Connected => Health::Healthy,
+            ConnectionState::Connecting | ConnectionState::Reconnecting { .. } => Health::Degraded,
+            ConnectionState::Disconnected { .. } => Health::Unhealthy,
+        }
+        .max(judge(since_heartbeat, Some(heartbeat_age)))
+        .max(oldest_ack.map_or(Health::Healthy, |age| judge(age, self.ack_age)))
+        .max(judge(queued, self.queue_depth));
+
+        HealthReport {
+            health,
+            state,
+            since_heartbeat,
+            oldest_ack,
+            queued,
+        }
+    }
+}
+
+fn judge<T: PartialOrd>(value: T, thresholds: Thresholds<T>) -> Health {
+    match thresholds {
+        Some((_, unhealthy)) if value > unhealthy => Health::Unhealthy,
+        Some((degraded, _)) if value > degraded => Health::Degraded,
+        _ => Health::Healthy,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DisconnectReason;
+
+    const HEARTBEAT: (Duration, Duration) = (Duration::from_secs(25), Duration::from_secs(45));
+
+    fn health(check: &HealthCheck, state: ConnectionState, heartbeat: u64, ack: u64) -> Health {
+        let ack = Some(Duration::from_secs(ack)).filter(|age| !age.is_zero());
+        check
+            .report(state, Duration::from_secs(heartbeat), HEARTBEAT, ack, 0)
+            .health
+    }
+
+    #[test]
+    fn judges_the_heartbeat() {
+        let check = HealthCheck::new(Duration::from_secs(1));
+        let connected = ConnectionState::Connected;
+        assert_eq!(health(&check, connected, 25, 0), Health::Healthy);
+        // degraded once half the timeout passed
+        assert_eq!(health(&check, connected, 36, 0), Health::Degraded);
+        assert_eq!(health(&check, connected, 46, 0), Health::Unhealthy);
+
+        let check = check.heartbeat_age(Duration::from_secs(5), Duration::from_secs(10));
+        assert_eq!(health(&check, connected, 6, 0), Health::Degraded);
+    }
+
+    #[test]
+    fn judges_the_worst_value() {
+        let check = HealthCheck::new(Duration::from_secs(1))
+            .ack_age(Duration::from_secs(5), Duration::from_secs(30))
+            .queue_depth(10, 100);
+        let connected = ConnectionState::Connected;
+        assert_eq!(health(&check, connected, 1, 3), Health::Healthy);
+        assert_eq!(health(&check, connected, 1, 6), Health::Degraded);
+        assert_eq!(health(&check, connected, 40, 31), Health::Unhealthy);
+
+        let report = check.report(connected, Duration::ZERO, HEARTBEAT, None, 11);
+        assert_eq!(report.health, Health::Degraded);
+
+        let reconnecting = ConnectionState::Reconnecting { attempt: 1 };
+        assert_eq!(health(&check, reconnecting, 1, 0), Health::Degraded);
+        let disconnected = ConnectionState::Disconnected {
+            reason: DisconnectReason::IoServerDisconnect,
+        };
+        assert_eq!(health(&check, disconnected, 1, 0), Health::Unhealthy);
+    }
+}
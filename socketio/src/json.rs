@@ -0,0 +1,45 @@
+use serde::de::DeserializeOwned;
+use serde_json::Error as JsonError;
+
+/// Parses incoming json. With the `simd-json` feature it's parsed by `simd-json`, which
+/// parses in place and therefore works on a copy of the text; the copy is cheap compared
+/// to the time saved on large payloads. Invalid json is parsed again by `serde_json`, so
+/// the returned error doesn't depend on the feature. Json that is only validated, like the
+/// data of every decoded packet, is checked by `serde_json` directly to avoid the copy.
+pub(crate) fn from_str<T: DeserializeOwned>(json: &str) -> Result<T, JsonError> {
+    #[cfg(feature = "simd-json")]
+    {
+        let mut scratch = json.as_bytes().to_vec();
+        if let Ok(value) = simd_json::serde::from_slice(&mut scratch) {
+            return Ok(value);
+        }
+    }
+    serde_json::from_str(json)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::de::IgnoredAny;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_from_str() {
+        let value: Value =
+            from_str(r#"["event",{"nested":[1,-2,3.5,"™"]},null,true,18446744073709551615]"#)
+                .unwrap();
+        assert_eq!(
+            value,
+            json!(["event", {"nested": [1, -2, 3.5, "™"]}, null, true, u64::MAX])
+        );
+        assert!(from_str::<IgnoredAny>(r#"{"valid":true}"#).is_ok());
+
+        let invalid = r#"["event","#;
+        assert_eq!(
+            from_str::<Value>(invalid).unwrap_err().to_string(),
+            serde_json::from_str::<Value>(invalid)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
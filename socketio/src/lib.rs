@@ -75,20 +75,62 @@
 //! handling).
 //! - send JSON data to the server and receive an `ack`.
 //! - send and handle Binary data.
-#![cfg_attr(
-    feature = "async",
-    doc = r#"
+//! - connect to servers listening on a unix domain socket via `http+unix://` urls holding the
+//!   percent encoded socket path, e.g. `http+unix://%2Frun%2Fapp.sock`.
+//! - connect via WebTransport over http/3 with the async client and the `webtransport` feature,
+//!   falling back to websockets with `.transports([TransportType::Webtransport, TransportType::Websocket])`.
+//! - connect to legacy socket.io 2.x servers with `.protocol_version(ProtocolVersion::V2)`.
+//!
+//! ## TLS backends
+//!
+//! Secure connections use `native-tls` by default. To build without OpenSSL, e.g. for static
+//! musl targets, disable the default features and enable `rustls` instead:
+//! ```toml
+//! rust_socketio = { version = "*", default-features = false, features = ["rustls"] }
+//! ```
+//! A custom configuration, either a `native_tls::TlsConnector` or a `rustls::ClientConfig`, can
+//! be passed to [`ClientBuilder::tls_connector`]. Server certificates can be pinned with
+//! [`ClientBuilder::pinned_certificates`], which requires the `rustls` feature.
+//!
+//! ## HTTP backends
+//!
+//! The polling transport sends its requests with `reqwest`, through the default `http-reqwest`
+//! feature. Disabling it replaces `reqwest` with a minimal http/1.1 client built on `hyper`, which
+//! shrinks the dependency tree for applications that mostly connect via websockets:
+//! ```toml
+//! rust_socketio = { version = "*", default-features = false, features = ["native-tls"] }
+//! ```
+//! That client doesn't decompress responses, and
+//! [`asynchronous::ClientBuilder::http_client`] isn't available without `reqwest`.
+//!
+//! The websocket transport runs on `tungstenite`, or on `tokio-websockets` with the
+//! `tokio-websockets` feature, which has less overhead per message for applications exchanging
+//! many small events. Of the [`WebSocketConfig`], only `max_message_size` and
+//! `write_buffer_size` apply to `tokio-websockets`.
+//!
+//! ## WebAssembly
+//!
+//! The async client also runs on `wasm32` targets, in browsers as well as in wasi runtimes with a
+//! javascript host, using its `WebSocket` and `fetch` apis. Enable the `wasm` feature instead of
+//! the default ones:
+//! ```toml
+//! rust_socketio = { version = "*", default-features = false, features = ["wasm"] }
+//! ```
+//! The sync client, the tls and proxy options and the `webtransport` feature are not available
+//! there.
+#![doc = r#"
 ## Async version
 This library provides an ability for being executed in an asynchronous context using `tokio` as
-the execution runtime.
+the execution runtime. Applications running on another executor, like `async-std` or `smol`, can
+keep a `tokio` runtime in the background and pass its handle to
+[`asynchronous::ClientBuilder::runtime_handle`], the client then runs on that runtime and its
+futures can be awaited from their executor.
 Please note that the current async implementation is in beta, the interface can be object to
 drastic changes.
 The async `Client` and `ClientBuilder` support a similar interface to the sync version and live
-in the [`asynchronous`] module. In order to enable the support, you need to enable the `async`
-feature flag:
-```toml
-rust_socketio = { version = "^0.4.1", features = ["async"] }
-```
+in the [`asynchronous`] module. They are the core of this library: the sync client is a blocking
+facade over them, which runs them on a runtime of its own, so both support the same protocol
+features.
 
 The following code shows the example above in async fashion:
 
@@ -158,8 +200,7 @@ async fn main() {
 
     socket.disconnect().await.expect("Disconnect failed");
 }
-```"#
-)]
+```"#]
 #![allow(clippy::rc_buffer)]
 #![warn(clippy::complexity)]
 #![warn(clippy::style)]
@@ -171,31 +212,114 @@ pub mod client;
 /// Deprecated import since 0.3.0-alpha-2, use Event in the crate root instead.
 /// Defines the events that could be sent or received.
 pub mod event;
+mod failover;
+mod health;
+mod json;
 pub(crate) mod packet;
 /// Deprecated import since 0.3.0-alpha-2, use Event in the crate root instead.
 /// Defines the types of payload (binary or string), that
 /// could be sent or received.
 pub mod payload;
-pub(self) mod socket;
+mod raw_event;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod record;
+mod stats;
+mod trace;
 
 /// Deprecated import since 0.3.0-alpha-2, use Error in the crate root instead.
 /// Contains the error type which will be returned with every result in this
 /// crate.
 pub mod error;
 
-#[cfg(feature = "async")]
 /// Asynchronous version of the socket.io client. This module contains the async
 /// [`crate::asynchronous::Client`] as well as a builder
 /// ([`crate::asynchronous::ClientBuilder`]) that allows for configuring a client.
 pub mod asynchronous;
 
-pub use error::Error;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+/// An asynchronous socket.io server, built with a [`crate::server::ServerBuilder`].
+pub mod server;
 
-pub use {event::Event, payload::Payload};
+pub use error::{ClientError, Error, ErrorContext, ErrorKind, Severity};
+
+pub use {
+    event::{DisconnectReason, Event},
+    health::{Health, HealthCheck, HealthReport},
+    packet::{Direction, Packet, PacketId, Utf8Bytes},
+    payload::Payload,
+    raw_event::RawEvent,
+    stats::{EventStats, Stats},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use client::{ClientBuilder, RawClient};
+pub use client::{ProtocolVersion, TransportType};
+pub use rust_engineio::Frame as EngineFrame;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use rust_engineio::{
+    CertificatePin, Compression, TcpOptions, TlsConfig, TlsConnector, WebSocketConfig,
+};
 
-pub use client::{ClientBuilder, RawClient, TransportType};
+/// Routes events to the methods of a type marked with `#[on("event")]`, instead of a
+/// chain of `on` calls on the builder. Requires the `macros` feature.
+///
+/// ```rust
+/// use rust_socketio::{asynchronous::{Client, ClientBuilder}, handlers};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Message {
+///     room: String,
+///     text: String,
+/// }
+///
+/// struct Chat {
+///     nick: String,
+/// }
+///
+/// #[handlers]
+/// impl Chat {
+///     #[on("chat_message")]
+///     async fn chat_message(&self, message: Message, from: String) {
+///         println!("{} in {}: {}", from, message.room, message.text);
+///     }
+///
+///     #[on("ping")]
+///     async fn ping(&self, client: Client) {
+///         let _ = client.emit("pong", self.nick.as_str()).await;
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let chat = Chat { nick: "rusty".to_owned() };
+///     let socket = chat
+///         .register(ClientBuilder::new("http://localhost:4200/"))
+///         .connect()
+///         .await;
+/// }
+/// ```
+#[cfg(feature = "macros")]
+pub use rust_socketio_macros::handlers;
+
+// lets the code generated by the macros refer to the crate in its own tests
+#[cfg(all(test, feature = "macros"))]
+extern crate self as rust_socketio;
+
+/// Used by the code the macros generate, not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::Error;
+
+    /// Skips a handler whose arguments didn't deserialize.
+    pub fn skip_handler(event: &str, err: &Error) {
+        log::trace!("Skipped the handler of {event}, its arguments are invalid: {err}");
+    }
+}
 
 // TODO: 0.4.0 remove
+#[cfg(not(target_arch = "wasm32"))]
 #[deprecated(since = "0.3.0-alpha-2", note = "Socket renamed to Client")]
 pub use client::{ClientBuilder as SocketBuilder, RawClient as Socket};
 
@@ -203,6 +327,15 @@ pub use client::{ClientBuilder as SocketBuilder, RawClient as Socket};
 pub(crate) mod test {
     use url::Url;
 
+    /// Connects the async client of the builder. The connecting future is too large for the
+    /// stack of a test in debug builds, so it's boxed.
+    #[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+    pub(crate) async fn connect(
+        builder: crate::asynchronous::ClientBuilder,
+    ) -> crate::error::Result<crate::asynchronous::Client> {
+        Box::pin(builder.connect()).await
+    }
+
     /// The socket.io server for testing runs on port 4200
     const SERVER_URL: &str = "http://localhost:4200";
 
@@ -261,4 +394,56 @@ pub(crate) mod test {
 
         url
     }
+
+    #[cfg(feature = "macros")]
+    #[tokio::test]
+    async fn routes_events_to_handlers() -> crate::error::Result<()> {
+        use serde_json::json;
+        use tokio::sync::mpsc;
+
+        use crate::{
+            asynchronous::{test_util::MockServer, Client, ClientBuilder},
+            Payload,
+        };
+
+        struct Handlers {
+            received: mpsc::UnboundedSender<(String, Option<u32>)>,
+        }
+
+        #[crate::handlers]
+        impl Handlers {
+            #[on("greet")]
+            async fn greet(&self, name: String, times: Option<u32>) {
+                self.received.send((name, times)).unwrap();
+            }
+
+            #[on("ping")]
+            async fn ping(&self, payload: Payload, client: Client) {
+                client.emit("pong", payload).await.unwrap();
+            }
+        }
+
+        let (server, transport) = MockServer::new();
+        let (received, mut rx) = mpsc::unbounded_channel();
+        let client = Handlers { received }
+            .register(ClientBuilder::new("http://localhost/"))
+            .mock_transport(transport)
+            .reconnect(false)
+            .connect()
+            .await?;
+        server.recv().await;
+
+        server.emit("greet", vec![json!("ferris"), json!(3)])?;
+        // an event whose arguments don't fit is skipped
+        server.emit("greet", json!(7))?;
+        server.emit("greet", json!("crab"))?;
+        assert_eq!(rx.recv().await, Some(("ferris".to_owned(), Some(3))));
+        assert_eq!(rx.recv().await, Some(("crab".to_owned(), None)));
+
+        server.emit("ping", json!(1))?;
+        server.assert_emitted("pong", json!(1)).await;
+
+        client.disconnect().await?;
+        Ok(())
+    }
 }
@@ -1,11 +1,13 @@
 use crate::error::{Error, Result};
-use crate::{Event, Payload};
-use bytes::Bytes;
+use crate::{EngineFrame, Event, Payload};
+use bytes::{BufMut, Bytes, BytesMut};
 use serde::de::IgnoredAny;
 
 use std::convert::TryFrom;
-use std::fmt::Write;
+use std::fmt::{self, Write};
+use std::ops::Deref;
 use std::str::from_utf8 as str_from_utf8;
+use std::sync::{Arc, Mutex};
 
 /// An enumeration of the different `Packet` types in the `socket.io` protocol.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -19,12 +21,94 @@ pub enum PacketId {
     BinaryAck = 6,
 }
 
+pub use rust_engineio::Direction;
+
+/// Observes every packet a client reads or writes.
+pub(crate) type PacketObserver = Arc<dyn Fn(Direction, &Packet) + Send + Sync>;
+
+/// Observes every engine.io packet going over the connection.
+pub(crate) type FrameObserver = Arc<dyn Fn(&EngineFrame) + Send + Sync>;
+
+/// Text backed by [`Bytes`], the type of [`Packet::data`]. It lets the data of a received
+/// packet share the memory of the frame it was decoded from instead of being copied into
+/// a `String`, and derefs to `str`.
+///
+/// The bytes are always valid utf-8: they are either taken from a `str` or validated once
+/// when converted from [`Bytes`], so reading the text doesn't check them again.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Utf8Bytes(Bytes);
+
+impl Utf8Bytes {
+    /// Returns the part of `frame` that `text` was borrowed from, without copying it.
+    fn slice_ref(frame: &Bytes, text: &str) -> Self {
+        Utf8Bytes(frame.slice_ref(text.as_bytes()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        // SAFETY: every constructor either takes the bytes from a `str` or validates them
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl Deref for Utf8Bytes {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for Utf8Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Utf8Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for Utf8Bytes {
+    fn from(string: String) -> Self {
+        Utf8Bytes(Bytes::from(string))
+    }
+}
+
+impl TryFrom<Bytes> for Utf8Bytes {
+    type Error = Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self> {
+        str_from_utf8(&bytes)?;
+        Ok(Utf8Bytes(bytes))
+    }
+}
+
+impl From<&'static str> for Utf8Bytes {
+    fn from(string: &'static str) -> Self {
+        Utf8Bytes(Bytes::from_static(string.as_bytes()))
+    }
+}
+
+impl PartialEq<str> for Utf8Bytes {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Utf8Bytes {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
 /// A packet which gets sent or received during in the `socket.io` protocol.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Packet {
     pub packet_type: PacketId,
     pub nsp: String,
-    pub data: Option<String>,
+    pub data: Option<Utf8Bytes>,
     pub id: Option<i32>,
     pub attachment_count: u8,
     pub attachments: Option<Vec<Bytes>>,
@@ -189,7 +273,7 @@ impl TryFrom<char> for PacketId {
 
 impl Packet {
     /// Creates an instance.
-    pub const fn new(
+    pub fn new(
         packet_type: PacketId,
         nsp: String,
         data: Option<String>,
@@ -201,13 +285,104 @@ impl Packet {
         Packet {
             packet_type,
             nsp,
-            data,
+            data: data.map(Utf8Bytes::from),
             id,
             attachment_count,
             attachments,
             ack_id,
         }
     }
+
+    /// Fails with [`Error::InvalidPacket`] unless the packet carries as many
+    /// attachments as it announces, and only binary packets announce any.
+    pub(crate) fn check_attachments(&self) -> Result<()> {
+        let attachments = self.attachments.as_ref().map_or(0, Vec::len);
+        let is_binary = matches!(
+            self.packet_type,
+            PacketId::BinaryEvent | PacketId::BinaryAck
+        );
+        if usize::from(self.attachment_count) != attachments || (attachments > 0 && !is_binary) {
+            return Err(Error::InvalidPacket());
+        }
+        Ok(())
+    }
+
+    /// Returns the session id of the namespace, which the server sends along
+    /// with the `Connect` packet acknowledging the connection.
+    pub(crate) fn sid(&self) -> Option<String> {
+        if self.packet_type != PacketId::Connect {
+            return None;
+        }
+
+        let data: serde_json::Value = serde_json::from_str(self.data.as_deref()?).ok()?;
+        data.get("sid")?.as_str().map(ToOwned::to_owned)
+    }
+
+    /// Returns the reason the server sent along with a `ConnectError` packet,
+    /// which is a `{ message, data }` object (or a plain message in older
+    /// versions of the protocol).
+    pub(crate) fn connect_error(&self) -> Option<Error> {
+        if self.packet_type != PacketId::ConnectError {
+            return None;
+        }
+
+        let raw = self.data.as_deref().unwrap_or_default().to_owned();
+        let (message, data) = match serde_json::from_str(&raw) {
+            Ok(serde_json::Value::Object(mut body)) => match body.remove("message") {
+                Some(serde_json::Value::String(message)) => (message, body.remove("data")),
+                _ => (raw, None),
+            },
+            Ok(serde_json::Value::String(message)) => (message, None),
+            _ => (raw, None),
+        };
+
+        Some(Error::ConnectRejected { message, data })
+    }
+
+    /// Returns the name of the event an event packet carries. Like the clients do,
+    /// events without a leading name are `message` events.
+    pub(crate) fn event_name(&self) -> Option<String> {
+        use serde::de::{Deserializer, SeqAccess, Visitor};
+
+        struct EventName;
+
+        impl<'de> Visitor<'de> for EventName {
+            type Value = Option<String>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an event array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let Some(first) = seq.next_element::<serde_json::Value>()? else {
+                    return Ok(None);
+                };
+                let has_data = seq.next_element::<IgnoredAny>()?.is_some();
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Some(match first {
+                    serde_json::Value::String(name) if has_data => name,
+                    _ => Event::Message.to_string(),
+                }))
+            }
+        }
+
+        let data = self.data.as_ref()?;
+        match self.packet_type {
+            PacketId::Event => serde_json::Deserializer::from_str(data.as_str())
+                .deserialize_seq(EventName)
+                .ok()
+                .flatten(),
+            // binary packets only keep the event name of their arguments
+            PacketId::BinaryEvent => Some(
+                serde_json::from_str::<String>(data.as_str())
+                    .unwrap_or_else(|_| Event::Message.to_string()),
+            ),
+            _ => None,
+        }
+    }
 }
 
 impl From<Packet> for Bytes {
@@ -221,32 +396,42 @@ impl From<&Packet> for Bytes {
     /// The binary payload of a packet is not put at the end of the
     /// stream as it gets handled and send by it's own logic via the socket.
     fn from(packet: &Packet) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(packet.encoded_len_hint());
+        packet.encode_into(&mut buffer);
+        buffer.freeze()
+    }
+}
+
+impl Packet {
+    /// Appends the encoded packet to `buffer`, see [`Bytes::from`] for the format.
+    pub(crate) fn encode_into(&self, buffer: &mut BytesMut) {
+        buffer.reserve(self.encoded_len_hint());
+
         // first the packet type
-        let mut buffer = String::new();
-        buffer.push((packet.packet_type as u8 + b'0') as char);
+        buffer.put_u8(self.packet_type as u8 + b'0');
 
         // eventually a number of attachments, followed by '-'
-        if let PacketId::BinaryAck | PacketId::BinaryEvent = packet.packet_type {
-            let _ = write!(buffer, "{}-", packet.attachment_count);
+        if let PacketId::BinaryAck | PacketId::BinaryEvent = self.packet_type {
+            let _ = write!(buffer, "{}-", self.attachment_count);
         }
 
         // if the namespace is different from the default one append it as well,
         // followed by ','
-        if packet.nsp != "/" {
-            buffer.push_str(&packet.nsp);
-            buffer.push(',');
+        if self.nsp != "/" {
+            buffer.put_slice(self.nsp.as_bytes());
+            buffer.put_u8(b',');
         }
 
         // if an id is present append it...
-        if let Some(id) = packet.id {
+        if let Some(id) = self.id {
             let _ = write!(buffer, "{id}");
         }
 
-        if packet.attachments.is_some() {
-            let num = packet.attachment_count - 1;
+        if self.attachments.is_some() {
+            let num = self.attachment_count - 1;
 
             // check if an event type is present
-            if let Some(event_type) = packet.data.as_ref() {
+            if let Some(event_type) = self.data.as_ref() {
                 let _ = write!(
                     buffer,
                     "[{event_type},{{\"_placeholder\":true,\"num\":{num}}}]",
@@ -254,11 +439,33 @@ impl From<&Packet> for Bytes {
             } else {
                 let _ = write!(buffer, "[{{\"_placeholder\":true,\"num\":{num}}}]");
             }
-        } else if let Some(data) = packet.data.as_ref() {
-            buffer.push_str(data);
+        } else if let Some(data) = self.data.as_ref() {
+            buffer.put_slice(data.as_bytes());
         }
+    }
 
-        Bytes::from(buffer)
+    /// Roughly the length of the encoded packet, which saves growing the buffer while
+    /// encoding.
+    fn encoded_len_hint(&self) -> usize {
+        // the packet type, counts, separators and placeholders fit into this
+        const OVERHEAD: usize = 48;
+        OVERHEAD + self.nsp.len() + self.data.as_ref().map_or(0, |data| data.len())
+    }
+}
+
+/// A buffer that packets are encoded into and split off from. As the buffer reclaims
+/// its memory once the packets split off from it are dropped, a socket reusing it for
+/// all of its packets stops allocating once the buffer has grown to fit the packets
+/// in flight.
+#[derive(Debug, Default)]
+pub(crate) struct EncodeBuffer(Mutex<BytesMut>);
+
+impl EncodeBuffer {
+    /// Encodes the packet, see [`Packet::encode_into`].
+    pub(crate) fn encode(&self, packet: &Packet) -> Result<Bytes> {
+        let mut buffer = self.0.lock()?;
+        packet.encode_into(&mut buffer);
+        Ok(buffer.split().freeze())
     }
 }
 
@@ -278,8 +485,8 @@ impl TryFrom<&Bytes> for Packet {
     /// binary data, instead the socket is responsible for handling
     /// this member. This is done because the attachment is usually
     /// send in another packet.
-    fn try_from(payload: &Bytes) -> Result<Packet> {
-        let mut payload = str_from_utf8(&payload).map_err(Error::InvalidUtf8)?;
+    fn try_from(frame: &Bytes) -> Result<Packet> {
+        let mut payload = str_from_utf8(frame).map_err(Error::InvalidUtf8)?;
         let mut packet = Packet::default();
 
         // packet_type
@@ -314,7 +521,8 @@ impl TryFrom<&Bytes> for Packet {
             packet.id = Some(prefix.parse().map_err(|_| Error::InvalidPacket())?);
         }
 
-        // validate json
+        // validate json, which `serde_json` does without building anything; `simd-json`
+        // would have to copy the payload of every frame for it
         serde_json::from_str::<IgnoredAny>(payload).map_err(Error::InvalidJson)?;
 
         match packet.packet_type {
@@ -330,16 +538,126 @@ impl TryFrom<&Bytes> for Packet {
                 }
 
                 if !str.is_empty() {
-                    packet.data = Some(str);
+                    packet.data = Some(Utf8Bytes::from(str));
                 }
             }
-            _ => packet.data = Some(payload.to_string()),
+            // the arguments are shared with the frame rather than copied
+            _ => packet.data = Some(Utf8Bytes::slice_ref(frame, payload)),
         }
 
         Ok(packet)
     }
 }
 
+impl Packet {
+    /// Decodes a packet like [`Packet::try_from`], but checks it against the grammar of
+    /// the protocol first, failing with [`Error::InvalidPacketSyntax`] on anything
+    /// decoding lets slip, e.g. an event without a name or an ack without an id.
+    pub(crate) fn decode_strict(frame: &Bytes) -> Result<Packet> {
+        check_syntax(frame)?;
+        Packet::try_from(frame)
+    }
+}
+
+/// Checks the frame against the grammar of a packet,
+/// `<type>[<attachments>-][<namespace>,][<ack id>][<JSON data>]`.
+fn check_syntax(frame: &Bytes) -> Result<()> {
+    let fail = |offset, expected| {
+        Err(Error::InvalidPacketSyntax {
+            frame: frame.clone(),
+            offset,
+            expected,
+        })
+    };
+    let text = match str_from_utf8(frame) {
+        Ok(text) => text,
+        Err(e) => return fail(e.valid_up_to(), "UTF-8 text"),
+    };
+    let digits = |offset: usize| {
+        text[offset..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count()
+    };
+
+    let Some(packet_type) = text.bytes().next().and_then(|b| PacketId::try_from(b).ok()) else {
+        return fail(0, "a packet type from 0 to 6");
+    };
+    let mut offset = 1;
+
+    if let PacketId::BinaryEvent | PacketId::BinaryAck = packet_type {
+        let count = digits(offset);
+        if count == 0 || text.as_bytes().get(offset + count) != Some(&b'-') {
+            return fail(offset + count, "the number of attachments followed by '-'");
+        }
+        if text[offset..offset + count].parse::<u8>().is_err() {
+            return fail(offset, "at most 255 attachments");
+        }
+        offset += count + 1;
+    }
+
+    if text[offset..].starts_with('/') {
+        match text[offset..].find(',') {
+            Some(end) => offset += end + 1,
+            None => return fail(text.len(), "',' after the namespace"),
+        }
+    }
+
+    let count = digits(offset);
+    if count > 0 {
+        if text[offset..offset + count].parse::<i32>().is_err() {
+            return fail(offset, "an ack id of at most 10 digits");
+        }
+        offset += count;
+    } else if let PacketId::Ack | PacketId::BinaryAck = packet_type {
+        return fail(offset, "an ack id");
+    }
+
+    let expected = match packet_type {
+        PacketId::Connect => "the end of the packet or an object",
+        PacketId::Disconnect => "the end of the packet",
+        PacketId::Event | PacketId::BinaryEvent => "an array starting with the event name",
+        PacketId::Ack | PacketId::BinaryAck => "an array of arguments",
+        PacketId::ConnectError => "an object or a string",
+    };
+    let data = &text[offset..];
+    if data.is_empty() {
+        return match packet_type {
+            PacketId::Connect | PacketId::Disconnect => Ok(()),
+            _ => fail(offset, expected),
+        };
+    }
+    let value = match serde_json::from_str::<serde_json::Value>(data) {
+        Ok(value) => value,
+        Err(e) => {
+            // the position of the error is given as its line and column
+            let line_start = data
+                .split_inclusive('\n')
+                .take(e.line().saturating_sub(1))
+                .map(str::len)
+                .sum::<usize>();
+            let position = (line_start + e.column().saturating_sub(1)).min(data.len());
+            return fail(offset + position, "valid JSON");
+        }
+    };
+    let fits = match packet_type {
+        PacketId::Connect => value.is_object(),
+        PacketId::Disconnect => false,
+        PacketId::Event | PacketId::BinaryEvent => {
+            matches!(
+                value.as_array().and_then(|args| args.first()),
+                Some(serde_json::Value::String(_))
+            )
+        }
+        PacketId::Ack | PacketId::BinaryAck => value.is_array(),
+        PacketId::ConnectError => value.is_object() || value.is_string(),
+    };
+    if !fits {
+        return fail(offset, expected);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -702,7 +1020,7 @@ mod test {
             Packet {
                 packet_type: PacketId::BinaryEvent,
                 nsp: "namespace".to_owned(),
-                data: Some("\"test_event\"".to_owned()),
+                data: Some("\"test_event\"".into()),
                 id: None,
                 attachment_count: 1,
                 attachments: Some(vec![Bytes::from_static(&[0, 4, 9])]),
@@ -727,7 +1045,7 @@ mod test {
             Packet {
                 packet_type: PacketId::Event,
                 nsp: "other_namespace".to_owned(),
-                data: Some("[\"other_event\",\"test\"]".to_owned()),
+                data: Some("[\"other_event\",\"test\"]".into()),
                 id: Some(10),
                 attachment_count: 0,
                 attachments: None,
@@ -749,7 +1067,7 @@ mod test {
             Packet {
                 packet_type: PacketId::Event,
                 nsp: "/".to_owned(),
-                data: Some("[\"third_event\",\"String test\",{\"type\":\"object\"}]".to_owned()),
+                data: Some("[\"third_event\",\"String test\",{\"type\":\"object\"}]".into()),
                 id: Some(10),
                 attachment_count: 0,
                 attachments: None,
@@ -794,7 +1112,7 @@ mod test {
             Packet {
                 packet_type: PacketId::Ack,
                 nsp: "other_namespace".to_owned(),
-                data: Some("[\"test\"]".to_owned()),
+                data: Some("[\"test\"]".into()),
                 id: Some(10),
                 attachment_count: 0,
                 attachments: None,
@@ -816,7 +1134,7 @@ mod test {
             Packet {
                 packet_type: PacketId::Ack,
                 nsp: "/".to_owned(),
-                data: Some("[\"String test\",{\"type\":\"object\"}]".to_owned()),
+                data: Some("[\"String test\",{\"type\":\"object\"}]".into()),
                 id: Some(10),
                 attachment_count: 0,
                 attachments: None,
@@ -824,4 +1142,199 @@ mod test {
             }
         )
     }
+
+    #[test]
+    fn test_encode_buffer() -> Result<()> {
+        let buffer = EncodeBuffer::default();
+        let event = Packet::new_from_payload(
+            Payload::from(serde_json::json!({"token": "123"})),
+            "event".into(),
+            "/admin",
+            Some(1),
+        )?;
+        let ack = Packet::new_from_payload(
+            Payload::Binary(Bytes::from_static(&[1, 2, 3])),
+            "binary".into(),
+            "/",
+            None,
+        )?;
+
+        // packets still in flight are not overwritten by the ones encoded later
+        let encoded: Vec<_> = (0..64)
+            .map(|i| buffer.encode(if i % 2 == 0 { &event } else { &ack }))
+            .collect::<Result<_>>()?;
+        for (i, bytes) in encoded.iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(bytes, "2/admin,1[\"event\",{\"token\":\"123\"}]");
+            } else {
+                assert_eq!(bytes, "51-[\"binary\",{\"_placeholder\":true,\"num\":0}]");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_shares_frame() {
+        let frame = Bytes::from(format!("2/admin,1[\"event\",\"{}\"]", "x".repeat(1024)));
+        let packet = Packet::try_from(&frame).unwrap();
+
+        let data = packet.data.unwrap();
+        assert!(data.starts_with("[\"event\""));
+        assert_eq!(data.as_ptr(), frame[9..].as_ptr());
+    }
+
+    #[test]
+    fn test_utf8_bytes_from_bytes() {
+        let text = Utf8Bytes::try_from(Bytes::from_static("grüße".as_bytes())).unwrap();
+        assert_eq!(text, "grüße");
+        assert!(matches!(
+            Utf8Bytes::try_from(Bytes::from_static(&[0x67, 0xff])),
+            Err(Error::InvalidUtf8(_))
+        ));
+    }
+
+    #[test]
+    fn connect_packet_sid() {
+        let packet = Packet::try_from(&Bytes::from_static(
+            b"0/admin,{\"sid\":\"oSO0OpakMV_3jnilAAAA\"}",
+        ))
+        .unwrap();
+        assert_eq!(packet.sid(), Some("oSO0OpakMV_3jnilAAAA".to_owned()));
+
+        let packet = Packet::try_from(&Bytes::from_static(b"0{\"token\":\"123\"}")).unwrap();
+        assert_eq!(packet.sid(), None);
+
+        let packet = Packet::try_from(&Bytes::from_static(b"2[\"sid\"]")).unwrap();
+        assert_eq!(packet.sid(), None);
+    }
+
+    #[test]
+    fn decode_strict_diagnostics() {
+        let diagnose =
+            |frame: &'static [u8]| match Packet::decode_strict(&Bytes::from_static(frame)) {
+                Err(Error::InvalidPacketSyntax {
+                    offset, expected, ..
+                }) => (offset, expected),
+                result => panic!("{:?} decoded to {:?}", frame, result),
+            };
+
+        assert_eq!(diagnose(b""), (0, "a packet type from 0 to 6"));
+        assert_eq!(diagnose(b"2[\"a\",\xff]"), (6, "UTF-8 text"));
+        assert_eq!(
+            diagnose(b"5[\"a\"]"),
+            (1, "the number of attachments followed by '-'")
+        );
+        assert_eq!(diagnose(b"2/admin[\"a\"]"), (12, "',' after the namespace"));
+        assert_eq!(diagnose(b"3[1]"), (1, "an ack id"));
+        assert_eq!(
+            diagnose(b"2/admin,1[1]"),
+            (9, "an array starting with the event name")
+        );
+        assert_eq!(diagnose(b"2[\"a\",}"), (6, "valid JSON"));
+        assert_eq!(diagnose(b"1{}"), (1, "the end of the packet"));
+
+        // what decoding accepts anyway
+        assert!(Packet::try_from(&Bytes::from_static(b"2/admin,1[1]")).is_ok());
+        assert!(Packet::try_from(&Bytes::from_static(b"3[1]")).is_ok());
+
+        for frame in [
+            &b"0"[..],
+            b"0/admin,{\"sid\":\"1\"}",
+            b"1/admin,",
+            b"2[\"chat\",\"hi\"]",
+            b"3/admin,12[]",
+            b"4{\"message\":\"denied\"}",
+            b"51-[\"file\",{\"_placeholder\":true,\"num\":0}]",
+        ] {
+            let frame = Bytes::from_static(frame);
+            assert_eq!(
+                Packet::decode_strict(&frame).unwrap(),
+                Packet::try_from(&frame).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn check_attachments() {
+        let packet = |packet_type, attachment_count, attachments: Option<Vec<Bytes>>| {
+            Packet::new(
+                packet_type,
+                "/".to_owned(),
+                Some("\"file\"".to_owned()),
+                None,
+                attachment_count,
+                attachments,
+                None,
+            )
+        };
+        let attachment = || Some(vec![Bytes::from_static(&[1, 2])]);
+
+        assert!(packet(PacketId::BinaryEvent, 1, attachment())
+            .check_attachments()
+            .is_ok());
+        assert!(packet(PacketId::Event, 0, None).check_attachments().is_ok());
+        assert!(packet(PacketId::BinaryEvent, 2, attachment())
+            .check_attachments()
+            .is_err());
+        assert!(packet(PacketId::BinaryAck, 1, None)
+            .check_attachments()
+            .is_err());
+        assert!(packet(PacketId::Event, 1, attachment())
+            .check_attachments()
+            .is_err());
+    }
+
+    #[test]
+    fn connect_error_packet() {
+        let packet = Packet::try_from(&Bytes::from_static(
+            b"4/admin,{\"message\":\"Not authorized\",\"data\":{\"code\":403}}",
+        ))
+        .unwrap();
+        assert!(matches!(
+            packet.connect_error(),
+            Some(Error::ConnectRejected { message, data })
+                if message == "Not authorized" && data == Some(serde_json::json!({ "code": 403 }))
+        ));
+
+        let packet = Packet::try_from(&Bytes::from_static(b"4\"Not authorized\"")).unwrap();
+        assert!(matches!(
+            packet.connect_error(),
+            Some(Error::ConnectRejected { message, data: None }) if message == "Not authorized"
+        ));
+
+        let packet = Packet::try_from(&Bytes::from_static(b"0{\"sid\":\"abc\"}")).unwrap();
+        assert!(packet.connect_error().is_none());
+    }
+
+    #[test]
+    fn test_event_name() {
+        let name = |packet_type, data: &str| {
+            Packet::new(
+                packet_type,
+                "/".to_owned(),
+                Some(data.to_owned()),
+                None,
+                0,
+                None,
+                None,
+            )
+            .event_name()
+        };
+
+        assert_eq!(
+            name(PacketId::Event, r#"["chat",{"text":"hi"},2]"#).as_deref(),
+            Some("chat")
+        );
+        assert_eq!(
+            name(PacketId::BinaryEvent, r#""file""#).as_deref(),
+            Some("file")
+        );
+        assert_eq!(
+            name(PacketId::Event, r#"["hi"]"#).as_deref(),
+            Some("message")
+        );
+        assert_eq!(name(PacketId::Event, "[]"), None);
+        assert_eq!(name(PacketId::Event, "{}"), None);
+        assert_eq!(name(PacketId::Ack, r#"["chat",1]"#), None);
+    }
 }
@@ -1,4 +1,7 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use serde::de::DeserializeOwned;
+
+use crate::{error::Result, json, Event};
 
 /// A type which represents a `payload` in the `socket.io` context.
 /// A payload could either be of the type `Payload::Binary`, which holds
@@ -15,8 +18,54 @@ pub enum Payload {
 }
 
 impl Payload {
+    /// Deserializes the argument at the index, e.g. the second one of an event emitted
+    /// as `socket.emit("chat", room, message)` by the JavaScript client with index 1.
+    /// A missing argument deserializes like `null`, so it fits an `Option`. The bytes
+    /// of a binary payload are its only argument.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::Payload;
+    /// use serde_json::json;
+    ///
+    /// let payload = Payload::from(vec![json!("lobby"), json!({"text": "hi"})]);
+    /// assert_eq!(payload.arg::<String>(0).unwrap(), "lobby");
+    /// assert_eq!(payload.arg::<Option<u32>>(2).unwrap(), None);
+    /// assert!(payload.arg::<u32>(0).is_err());
+    /// ```
+    pub fn arg<T: DeserializeOwned>(&self, index: usize) -> Result<T> {
+        let value = match self {
+            Payload::Text(values) => values.get(index).cloned(),
+            Payload::Binary(bytes) if index == 0 => Some(serde_json::Value::from(bytes.to_vec())),
+            Payload::Binary(_) => None,
+            #[allow(deprecated)]
+            Payload::String(string) if index == 0 => Some(Payload::string_to_value(string.clone())),
+            #[allow(deprecated)]
+            Payload::String(_) => None,
+        };
+        Ok(serde_json::from_value(value.unwrap_or_default())?)
+    }
+
+    /// Deserializes the event with this payload like an externally tagged enum, a map
+    /// from the name of the event to its only argument, its arguments as an array or
+    /// `null` without arguments.
+    pub(crate) fn to_enum<T: DeserializeOwned>(&self, event: &Event) -> Result<T> {
+        let data = match self {
+            Payload::Text(values) => match values.as_slice() {
+                [] => serde_json::Value::Null,
+                [value] => value.clone(),
+                values => serde_json::Value::Array(values.to_vec()),
+            },
+            Payload::Binary(bytes) => serde_json::Value::from(bytes.to_vec()),
+            #[allow(deprecated)]
+            Payload::String(string) => Payload::string_to_value(string.clone()),
+        };
+        let mut tagged = serde_json::Map::new();
+        tagged.insert(event.as_str().to_owned(), data);
+        Ok(serde_json::from_value(tagged.into())?)
+    }
+
     pub(crate) fn string_to_value(string: String) -> serde_json::Value {
-        if let Ok(value) = serde_json::from_str::<serde_json::Value>(&string) {
+        if let Ok(value) = json::from_str::<serde_json::Value>(&string) {
             value
         } else {
             serde_json::Value::String(string)
@@ -26,7 +75,10 @@ impl Payload {
 
 impl From<&str> for Payload {
     fn from(string: &str) -> Self {
-        Payload::from(string.to_owned())
+        // parsing the borrowed string only copies it if it isn't json
+        let value = json::from_str::<serde_json::Value>(string)
+            .unwrap_or_else(|_| serde_json::Value::String(string.to_owned()));
+        Self::Text(vec![value])
     }
 }
 
@@ -48,21 +100,47 @@ impl From<Vec<serde_json::Value>> for Payload {
     }
 }
 
+/// The values as the arguments of an event, e.g. `Payload::from([json!("lobby"), json!(1)])`.
+impl<const N: usize> From<[serde_json::Value; N]> for Payload {
+    fn from(values: [serde_json::Value; N]) -> Self {
+        Self::Text(values.into())
+    }
+}
+
 impl From<serde_json::Value> for Payload {
     fn from(value: serde_json::Value) -> Self {
         Self::Text(vec![value])
     }
 }
 
+impl From<&serde_json::Value> for Payload {
+    fn from(value: &serde_json::Value) -> Self {
+        Self::Text(vec![value.clone()])
+    }
+}
+
 impl From<Vec<u8>> for Payload {
     fn from(val: Vec<u8>) -> Self {
         Self::Binary(Bytes::from(val))
     }
 }
 
-impl From<&'static [u8]> for Payload {
-    fn from(val: &'static [u8]) -> Self {
-        Self::Binary(Bytes::from_static(val))
+/// Copies the bytes, use [`Bytes::from_static`] to send static data without copying it.
+impl From<&[u8]> for Payload {
+    fn from(val: &[u8]) -> Self {
+        Self::Binary(Bytes::copy_from_slice(val))
+    }
+}
+
+impl From<Box<[u8]>> for Payload {
+    fn from(val: Box<[u8]>) -> Self {
+        Self::Binary(Bytes::from(val))
+    }
+}
+
+impl From<BytesMut> for Payload {
+    fn from(bytes: BytesMut) -> Self {
+        Self::Binary(bytes.freeze())
     }
 }
 
@@ -150,5 +228,28 @@ mod tests {
 
         let sut = Payload::from(Bytes::from_static(&[1, 2, 3]));
         assert_eq!(Payload::Binary(Bytes::from_static(&[1, 2, 3])), sut);
+
+        // borrowed from a buffer that doesn't outlive the payload
+        let buffer = vec![1_u8, 2, 3];
+        let sut = Payload::from(buffer.as_slice());
+        assert_eq!(Payload::Binary(Bytes::from_static(&[1, 2, 3])), sut);
+
+        let sut = Payload::from(BytesMut::from(&[1_u8, 2, 3][..]));
+        assert_eq!(Payload::Binary(Bytes::from_static(&[1, 2, 3])), sut);
+
+        let sut = Payload::from(vec![1_u8, 2, 3].into_boxed_slice());
+        assert_eq!(Payload::Binary(Bytes::from_static(&[1, 2, 3])), sut);
+    }
+
+    #[test]
+    fn test_from_json_arguments() {
+        let sut = Payload::from([json!("lobby"), json!({"text": "hi"})]);
+        assert_eq!(
+            Payload::Text(vec![json!("lobby"), json!({"text": "hi"})]),
+            sut
+        );
+
+        let value = json!({"foo": "bar"});
+        assert_eq!(Payload::Text(vec![value.clone()]), Payload::from(&value));
     }
 }
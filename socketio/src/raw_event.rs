@@ -0,0 +1,432 @@
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{
+        self, DeserializeSeed, EnumAccess, IgnoredAny, IntoDeserializer, SeqAccess, VariantAccess,
+        Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+
+use crate::{error::Result, packet::Utf8Bytes, Error, Event};
+
+/// An event whose arguments are kept as the JSON text they were received as, which shares
+/// the memory of the frame it was read from. Its arguments are deserialized on demand and
+/// may borrow from the text, e.g. into `&str` fields, so handling an event doesn't have
+/// to allocate at all. See [`crate::asynchronous::ClientBuilder::on_raw`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawEvent {
+    event: Event,
+    data: Utf8Bytes,
+    // whether the first element of the data is the name of the event
+    named: bool,
+}
+
+impl RawEvent {
+    /// Reads the name of the event from the data of an event packet, a JSON array like
+    /// `["event", ...args]`, or `[arg]` for a message.
+    pub(crate) fn new(data: Utf8Bytes) -> Result<Self> {
+        let (name, len) = serde_json::from_str::<Head>(&data)?.0;
+        let (event, named) = match (name, len) {
+            (_, 0) => return Err(Error::IncompletePacket()),
+            (Some(name), 2..) => (Event::from(name.as_str()), true),
+            _ => (Event::Message, false),
+        };
+        Ok(RawEvent { event, data, named })
+    }
+
+    /// The name of the event.
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// The JSON text of the event as received, an array of the name of the event
+    /// followed by its arguments.
+    pub fn as_str(&self) -> &str {
+        &self.data
+    }
+
+    /// Deserializes the argument at the index, like [`crate::Payload::arg`], but may
+    /// borrow from the text of the event. A missing argument deserializes like `null`.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::RawEvent;
+    ///
+    /// fn room(event: &RawEvent) -> Option<&str> {
+    ///     // borrowed unless the name holds escaped characters
+    ///     event.arg(0).ok()
+    /// }
+    /// ```
+    pub fn arg<'de, T: Deserialize<'de>>(&'de self, index: usize) -> Result<T> {
+        let index = index + usize::from(self.named);
+        let mut json = serde_json::Deserializer::from_str(&self.data);
+        let value = de::Deserializer::deserialize_seq(&mut json, Nth(index, PhantomData))?;
+        json.end()?;
+        Ok(value)
+    }
+
+    /// Deserializes the event like [`crate::asynchronous::ClientBuilder::on_typed`] does,
+    /// into an enum with a variant named like the event, but may borrow from the text
+    /// of the event.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::RawEvent;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// #[serde(rename_all = "snake_case")]
+    /// enum Incoming<'a> {
+    ///     // `socket.emit("chat_message", { from: "ferris", text: "hi" })`
+    ///     ChatMessage { from: &'a str, text: &'a str },
+    ///     // `socket.emit("moved", "ferris", 3, 4)`
+    ///     Moved(&'a str, i32, i32),
+    /// }
+    ///
+    /// fn handle(event: &RawEvent) {
+    ///     match event.deserialize::<Incoming>() {
+    ///         Ok(Incoming::ChatMessage { from, text }) => println!("{from}: {text}"),
+    ///         Ok(other) => println!("{other:?}"),
+    ///         Err(_) => (),
+    ///     }
+    /// }
+    /// ```
+    pub fn deserialize<'de, T: Deserialize<'de>>(&'de self) -> Result<T> {
+        Ok(T::deserialize(EventDeserializer {
+            data: &self.data,
+            name: self.event.as_str(),
+            named: self.named,
+        })?)
+    }
+}
+
+/// The name of the event if the first element is a string, and the number of elements.
+struct Head((Option<String>, usize));
+
+impl<'de> Deserialize<'de> for Head {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct HeadVisitor;
+
+        impl<'de> Visitor<'de> for HeadVisitor {
+            type Value = Head;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Head, A::Error> {
+                let Some(Name(name)) = seq.next_element()? else {
+                    return Ok(Head((None, 0)));
+                };
+                let mut len = 1;
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    len += 1;
+                }
+                Ok(Head((name, len)))
+            }
+        }
+
+        deserializer.deserialize_seq(HeadVisitor)
+    }
+}
+
+/// A string, or `None` for any other value.
+struct Name(Option<String>);
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct NameVisitor;
+
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = Name;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("any value")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> std::result::Result<Name, E> {
+                Ok(Name(Some(name.to_owned())))
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> std::result::Result<Name, E> {
+                Ok(Name(None))
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> std::result::Result<Name, E> {
+                Ok(Name(None))
+            }
+
+            fn visit_u64<E: de::Error>(self, _: u64) -> std::result::Result<Name, E> {
+                Ok(Name(None))
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> std::result::Result<Name, E> {
+                Ok(Name(None))
+            }
+
+            fn visit_unit<E: de::Error>(self) -> std::result::Result<Name, E> {
+                Ok(Name(None))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> std::result::Result<Name, A::Error> {
+                IgnoredAny.visit_seq(seq)?;
+                Ok(Name(None))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Name, A::Error> {
+                IgnoredAny.visit_map(map)?;
+                Ok(Name(None))
+            }
+        }
+
+        deserializer.deserialize_any(NameVisitor)
+    }
+}
+
+/// Visits an array, deserializing the element at the index and skipping the others.
+struct Nth<T>(usize, PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Nth<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<T, A::Error> {
+        let mut skipped = 0;
+        while skipped < self.0 && seq.next_element::<IgnoredAny>()?.is_some() {
+            skipped += 1;
+        }
+        let value = match seq.next_element()? {
+            Some(value) => value,
+            None => T::deserialize(().into_deserializer())?,
+        };
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(value)
+    }
+}
+
+/// Deserializes the text of an event like a map from its name to its arguments: a
+/// variant named like the event holding its only argument, or all of its arguments as a
+/// tuple.
+struct EventDeserializer<'de> {
+    data: &'de str,
+    name: &'de str,
+    named: bool,
+}
+
+impl<'de> Deserializer<'de> for EventDeserializer<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> serde_json::Result<V::Value> {
+        let mut json = serde_json::Deserializer::from_str(self.data);
+        let value = de::Deserializer::deserialize_seq(
+            &mut json,
+            EventVisitor {
+                visitor,
+                name: self.name,
+                named: self.named,
+            },
+        )?;
+        json.end()?;
+        Ok(value)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct EventVisitor<'de, V> {
+    visitor: V,
+    name: &'de str,
+    named: bool,
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for EventVisitor<'de, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<V::Value, A::Error> {
+        if self.named {
+            seq.next_element::<IgnoredAny>()?;
+        }
+        let value = self.visitor.visit_enum(Arguments {
+            name: self.name,
+            seq: &mut seq,
+        })?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(value)
+    }
+}
+
+/// The arguments of an event, as the content of the variant named like the event.
+struct Arguments<'a, 'de, A> {
+    name: &'de str,
+    seq: &'a mut A,
+}
+
+impl<'de, A: SeqAccess<'de>> EnumAccess<'de> for Arguments<'_, 'de, A> {
+    type Error = A::Error;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> std::result::Result<(S::Value, Self), A::Error> {
+        let variant = seed.deserialize(self.name.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, A: SeqAccess<'de>> VariantAccess<'de> for Arguments<'_, 'de, A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> std::result::Result<(), A::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> std::result::Result<S::Value, A::Error> {
+        // a missing argument deserializes like `null`
+        let mut seed = Some(seed);
+        match self.seq.next_element_seed(Take(&mut seed))? {
+            Some(value) => Ok(value),
+            None => match seed {
+                Some(seed) => seed.deserialize(().into_deserializer()),
+                None => Err(de::Error::custom("the argument was taken")),
+            },
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, A::Error> {
+        visitor.visit_seq(self.seq)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, A::Error> {
+        self.seq
+            .next_element_seed(Struct(fields, visitor))?
+            .ok_or_else(|| de::Error::invalid_length(0, &"an object"))
+    }
+}
+
+/// Takes the seed out of the option once an element is deserialized with it.
+struct Take<'a, S>(&'a mut Option<S>);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Take<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<S::Value, D::Error> {
+        match self.0.take() {
+            Some(seed) => seed.deserialize(deserializer),
+            None => Err(de::Error::custom("the argument was taken")),
+        }
+    }
+}
+
+struct Struct<V>(&'static [&'static str], V);
+
+impl<'de, V: Visitor<'de>> DeserializeSeed<'de> for Struct<V> {
+    type Value = V::Value;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<V::Value, D::Error> {
+        deserializer.deserialize_struct("", self.0, self.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Incoming<'a> {
+        ChatMessage { text: &'a str },
+        Typing(&'a str),
+        Moved(&'a str, i32),
+        Message(#[serde(borrow)] Cow<'a, str>),
+    }
+
+    fn raw(data: &'static str) -> RawEvent {
+        RawEvent::new(Utf8Bytes::from(data)).unwrap()
+    }
+
+    #[test]
+    fn reads_the_event() {
+        assert_eq!(
+            raw(r#"["chat",{"text":"hi"}]"#).event(),
+            &Event::from("chat")
+        );
+        assert_eq!(raw(r#"["hi"]"#).event(), &Event::Message);
+        assert_eq!(raw(r#"[1,"hi"]"#).event(), &Event::Message);
+        assert!(RawEvent::new(Utf8Bytes::from("[]")).is_err());
+        assert!(RawEvent::new(Utf8Bytes::from("{}")).is_err());
+    }
+
+    #[test]
+    fn borrows_arguments() -> Result<()> {
+        let event = raw(r#"["moved","ferris",3,{"nested":[1]}]"#);
+        assert_eq!(event.arg::<&str>(0)?, "ferris");
+        assert_eq!(event.arg::<i32>(1)?, 3);
+        assert_eq!(event.arg::<Option<&str>>(3)?, None);
+        assert!(event.arg::<i32>(0).is_err());
+
+        let message = raw(r#"["hi"]"#);
+        assert_eq!(message.arg::<&str>(0)?, "hi");
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_the_event() -> Result<()> {
+        let event = raw(r#"["chat_message",{"text":"hi"}]"#);
+        assert_eq!(
+            event.deserialize::<Incoming>()?,
+            Incoming::ChatMessage { text: "hi" }
+        );
+        let event = raw(r#"["typing","ferris"]"#);
+        assert_eq!(event.deserialize::<Incoming>()?, Incoming::Typing("ferris"));
+        let event = raw(r#"["moved","ferris",3]"#);
+        assert_eq!(
+            event.deserialize::<Incoming>()?,
+            Incoming::Moved("ferris", 3)
+        );
+        // escaped text can't be borrowed
+        let event = raw(r#"["message","a \"quote\""]"#);
+        assert_eq!(
+            event.deserialize::<Incoming>()?,
+            Incoming::Message(Cow::Owned("a \"quote\"".to_owned()))
+        );
+
+        assert!(raw(r#"["unknown",1]"#).deserialize::<Incoming>().is_err());
+        assert!(raw(r#"["typing",1]"#).deserialize::<Incoming>().is_err());
+        Ok(())
+    }
+}
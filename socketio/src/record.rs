@@ -0,0 +1,246 @@
+//! Records the packets of a session to a file, e.g. to attach it to a bug report. A
+//! [`Recorder`] is passed to a client as its packet observer and writes a line of JSON
+//! per packet:
+//!
+//! ```json
+//! {"elapsed_ms":12,"direction":"inbound","packet":"2[\"greet\",\"hi\"]","attachments":[]}
+//! ```
+//!
+//! `packet` is the encoded socket.io packet and `attachments` holds its binary
+//! attachments, encoded as base64. With the `test-util` feature a recorded session is
+//! played back to a client by the `Replayer` of the async test utilities.
+//!
+//! ```rust
+//! use rust_socketio::{record::Recorder, ClientBuilder};
+//!
+//! let recorder = Recorder::create("session.jsonl").expect("file not writable");
+//! let builder = ClientBuilder::new("http://localhost:4200/")
+//!     .packet_observer(recorder.observer());
+//! # std::fs::remove_file("session.jsonl").unwrap();
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use log::trace;
+use serde_json::{json, Value};
+
+use crate::{error::Result, packet::Packet, Direction, Error};
+
+/// Writes the packets of a session with the time they passed the client. Clones write
+/// to the same file.
+#[derive(Clone)]
+pub struct Recorder {
+    start: Instant,
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Recorder {
+    /// Creates a recorder writing to the file at the given path, replacing an existing
+    /// file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Creates a recorder writing to the given writer.
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Recorder {
+            start: Instant::now(),
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// Writes a packet. Each packet is flushed right away, so the session survives a
+    /// crash of the application. Failing writes are logged and otherwise ignored.
+    pub fn record(&self, direction: Direction, packet: &Packet) {
+        let record = Record {
+            elapsed: self.start.elapsed(),
+            direction,
+            packet: packet.clone(),
+        };
+        let result = self
+            .writer
+            .lock()
+            .map_err(Error::from)
+            .and_then(|mut writer| {
+                writeln!(writer, "{}", record.to_json())?;
+                Ok(writer.flush()?)
+            });
+        if let Err(e) = result {
+            trace!("Failed to record a packet: {e}");
+        }
+    }
+
+    /// Returns a packet observer recording every packet, see
+    /// [`crate::ClientBuilder::packet_observer`].
+    pub fn observer(&self) -> impl Fn(Direction, &Packet) + Send + Sync + 'static {
+        let recorder = self.clone();
+        move |direction, packet| recorder.record(direction, packet)
+    }
+}
+
+/// A recorded packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// The time between creating the recorder and the packet passing the client.
+    pub elapsed: Duration,
+    pub direction: Direction,
+    pub packet: Packet,
+}
+
+impl Record {
+    /// Reads the records of a session, e.g. from a file opened with
+    /// [`std::fs::File::open`], skipping empty lines.
+    pub fn read_all<R: BufRead>(reader: R) -> Result<Vec<Record>> {
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(Record::from_json(&line)?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Reads the records of the session recorded to the file at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Vec<Record>> {
+        Self::read_all(BufReader::new(File::open(path)?))
+    }
+
+    fn to_json(&self) -> String {
+        let mut value = encode_packet(&self.packet);
+        value["elapsed_ms"] = json!(self.elapsed.as_millis() as u64);
+        value["direction"] = json!(match self.direction {
+            Direction::Inbound => "inbound",
+            Direction::Outbound => "outbound",
+        });
+        value.to_string()
+    }
+
+    fn from_json(line: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(line)?;
+        let elapsed = value["elapsed_ms"]
+            .as_u64()
+            .map(Duration::from_millis)
+            .ok_or(Error::InvalidPacket())?;
+        let direction = match value["direction"].as_str() {
+            Some("inbound") => Direction::Inbound,
+            Some("outbound") => Direction::Outbound,
+            _ => return Err(Error::InvalidPacket()),
+        };
+
+        Ok(Record {
+            elapsed,
+            direction,
+            packet: decode_packet(&value)?,
+        })
+    }
+}
+
+/// Encodes a packet as the `packet` and `attachments` fields of a line, which the
+/// outbox of the async client writes as well.
+pub(crate) fn encode_packet(packet: &Packet) -> Value {
+    let attachments: Vec<String> = packet
+        .attachments
+        .iter()
+        .flatten()
+        .map(|attachment| general_purpose::STANDARD.encode(attachment))
+        .collect();
+    json!({
+        "packet": String::from_utf8_lossy(&Bytes::from(packet)),
+        "attachments": attachments,
+    })
+}
+
+/// Decodes a packet encoded by [`encode_packet`].
+pub(crate) fn decode_packet(value: &Value) -> Result<Packet> {
+    let encoded = value["packet"].as_str().ok_or(Error::InvalidPacket())?;
+    let mut packet = Packet::try_from(&Bytes::copy_from_slice(encoded.as_bytes()))?;
+
+    let attachments = match value["attachments"].as_array() {
+        Some(attachments) => attachments
+            .iter()
+            .map(|attachment| {
+                let attachment = attachment.as_str().ok_or(Error::InvalidPacket())?;
+                Ok(Bytes::from(general_purpose::STANDARD.decode(attachment)?))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    if !attachments.is_empty() {
+        packet.attachments = Some(attachments);
+    }
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Event, Payload};
+
+    /// A writer the test can read back.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let buffer = Buffer::default();
+        let recorder = Recorder::new(buffer.clone());
+        let observer = recorder.observer();
+
+        let event = Packet::new_from_payload(
+            Payload::from(json!({"text": "hi"})),
+            Event::from("chat"),
+            "/admin",
+            Some(3),
+        )?;
+        let binary = Packet::new_from_payload(
+            Payload::Binary(Bytes::from_static(b"\x00\x1e")),
+            Event::from("file"),
+            "/",
+            None,
+        )?;
+        observer(Direction::Outbound, &event);
+        observer(Direction::Inbound, &binary);
+
+        let data = buffer.0.lock().unwrap().clone();
+        let records = Record::read_all(&data[..])?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Outbound);
+        assert_eq!(records[0].packet, event);
+        assert_eq!(records[1].direction, Direction::Inbound);
+        assert_eq!(
+            records[1].packet.attachments,
+            Some(vec![Bytes::from_static(b"\x00\x1e")])
+        );
+        assert_eq!(records[1].packet.data, binary.data);
+        assert!(records[0].elapsed <= records[1].elapsed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_record() {
+        assert!(Record::read_all(&b"{\"direction\":\"inbound\"}"[..]).is_err());
+        assert!(Record::read_all(&b"not json"[..]).is_err());
+        assert_eq!(Record::read_all(&b"\n\n"[..]).unwrap(), vec![]);
+    }
+}
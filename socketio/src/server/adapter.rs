@@ -0,0 +1,121 @@
+use std::sync::{Arc, Weak};
+
+use async_trait::async_trait;
+
+use crate::{error::Result, Event, Payload};
+
+use super::rooms::Rooms;
+
+/// Selects sockets of a namespace: those in any of the rooms, or all of them if no
+/// rooms are given, except those in any of the excepted rooms.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BroadcastOptions {
+    pub nsp: String,
+    pub rooms: Vec<String>,
+    pub except: Vec<String>,
+}
+
+impl BroadcastOptions {
+    pub fn new<T: Into<String>>(nsp: T) -> Self {
+        BroadcastOptions {
+            nsp: nsp.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Carries broadcasts and room changes between the nodes of a server that runs on
+/// several machines, like [`RedisAdapter`](super::RedisAdapter) does over redis.
+///
+/// A node applies broadcasts and room changes to its own sockets before passing
+/// them to its adapter, so an adapter only forwards them to the other nodes. What
+/// the other nodes sent is applied through the [`Node`] the adapter was attached to.
+#[async_trait]
+pub trait Adapter: Send + Sync + 'static {
+    /// Called once when the server the adapter was passed to is built.
+    fn attach(&self, node: Node);
+
+    /// Emits an event to the selected sockets of the other nodes.
+    async fn broadcast(
+        &self,
+        opts: &BroadcastOptions,
+        event: &Event,
+        payload: &Payload,
+    ) -> Result<()>;
+
+    /// Adds the selected sockets of the other nodes to the rooms.
+    async fn add_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) -> Result<()>;
+
+    /// Takes the selected sockets of the other nodes out of the rooms.
+    async fn del_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) -> Result<()>;
+
+    /// Disconnects the selected sockets of the other nodes from their namespace.
+    async fn disconnect_sockets(&self, opts: &BroadcastOptions) -> Result<()>;
+
+    /// The rooms of the namespace on the other nodes.
+    async fn remote_rooms(&self, nsp: &str) -> Result<Vec<String>>;
+}
+
+/// The sockets connected to one node of a server, for its [`Adapter`] to apply what
+/// the other nodes sent. Does nothing once the server is gone.
+#[derive(Clone)]
+pub struct Node {
+    rooms: Weak<Rooms>,
+    namespaces: Arc<[String]>,
+}
+
+impl Node {
+    pub(crate) fn new(rooms: &Arc<Rooms>, namespaces: Vec<String>) -> Self {
+        Node {
+            rooms: Arc::downgrade(rooms),
+            namespaces: namespaces.into(),
+        }
+    }
+
+    /// The namespaces clients can connect to.
+    pub fn namespaces(&self) -> &[String] {
+        &self.namespaces
+    }
+
+    /// Whether the server of the node is still around.
+    pub fn is_attached(&self) -> bool {
+        self.rooms.strong_count() > 0
+    }
+
+    /// Emits an event to the selected sockets of this node.
+    pub fn broadcast(&self, opts: &BroadcastOptions, event: Event, payload: Payload) -> Result<()> {
+        match self.rooms.upgrade() {
+            Some(rooms) => rooms.emit(opts, event, payload),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds the selected sockets of this node to the rooms.
+    pub fn add_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) {
+        if let Some(local) = self.rooms.upgrade() {
+            local.add_sockets(opts, rooms);
+        }
+    }
+
+    /// Takes the selected sockets of this node out of the rooms.
+    pub fn del_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) {
+        if let Some(local) = self.rooms.upgrade() {
+            local.del_sockets(opts, rooms);
+        }
+    }
+
+    /// Disconnects the selected sockets of this node from their namespace.
+    pub fn disconnect_sockets(&self, opts: &BroadcastOptions) {
+        if let Some(rooms) = self.rooms.upgrade() {
+            rooms.disconnect_sockets(opts);
+        }
+    }
+
+    /// The rooms of the namespace on this node.
+    pub fn rooms(&self, nsp: &str) -> Vec<String> {
+        self.rooms
+            .upgrade()
+            .map(|rooms| rooms.rooms(nsp))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures_util::future::BoxFuture;
+
+use crate::{Event, Payload};
+
+use super::{
+    adapter::Adapter,
+    middleware::{ConnectError, Handshake, Middleware},
+    service::Server,
+    socket::ServerSocket,
+};
+
+/// Handles an event a socket sent.
+pub(crate) type EventHandler =
+    Arc<dyn Fn(Payload, ServerSocket) -> BoxFuture<'static, ()> + Send + Sync>;
+/// Handles a socket connecting to or disconnecting from a namespace.
+pub(crate) type SocketHandler = Arc<dyn Fn(ServerSocket) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// The handlers of a namespace.
+#[derive(Clone, Default)]
+pub(crate) struct Namespace {
+    pub(crate) on: HashMap<Event, EventHandler>,
+    pub(crate) on_connect: Option<SocketHandler>,
+    pub(crate) on_disconnect: Option<SocketHandler>,
+    pub(crate) middlewares: Vec<Middleware>,
+}
+
+/// The settings of a server, shared by its connections.
+pub(crate) struct Config {
+    pub(crate) path: String,
+    pub(crate) ping_interval: Duration,
+    pub(crate) ping_timeout: Duration,
+    pub(crate) max_payload: usize,
+    pub(crate) max_attachments: Option<u8>,
+    pub(crate) max_attachment_size: Option<usize>,
+    // of a packet and its attachments together, None limits it to `max_payload`
+    pub(crate) max_packet_size: Option<usize>,
+    pub(crate) max_queued_packets: usize,
+    pub(crate) namespaces: HashMap<String, Namespace>,
+}
+
+/// A builder for a socket.io [`Server`]. Handlers belong to the namespace selected last
+/// with [`ServerBuilder::namespace`], the default namespace `/` unless another one
+/// was selected. Clients can only connect to namespaces the builder knows.
+pub struct ServerBuilder {
+    config: Config,
+    namespace: String,
+    adapter: Option<Arc<dyn Adapter>>,
+}
+
+impl ServerBuilder {
+    /// Creates a builder for a server answering at `/socket.io/`, with the heartbeat
+    /// and the payload limit of the reference implementation.
+    pub fn new() -> Self {
+        let mut namespaces = HashMap::new();
+        namespaces.insert("/".to_owned(), Namespace::default());
+        ServerBuilder {
+            config: Config {
+                path: "/socket.io/".to_owned(),
+                ping_interval: Duration::from_millis(25000),
+                ping_timeout: Duration::from_millis(20000),
+                max_payload: 1_000_000,
+                max_attachments: None,
+                max_attachment_size: None,
+                max_packet_size: None,
+                max_queued_packets: 1024,
+                namespaces,
+            },
+            namespace: "/".to_owned(),
+            adapter: None,
+        }
+    }
+
+    /// Sets the path the server answers at. A trailing `/` is added if it's missing.
+    pub fn path<T: Into<String>>(mut self, path: T) -> Self {
+        let mut path = path.into();
+        if !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        self.config.path = path;
+        self
+    }
+
+    /// Sets how often the server pings its clients.
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.config.ping_interval = interval;
+        self
+    }
+
+    /// Sets how long the server waits for the answer to a ping before closing the
+    /// connection.
+    pub fn ping_timeout(mut self, timeout: Duration) -> Self {
+        self.config.ping_timeout = timeout;
+        self
+    }
+
+    /// Sets the size in bytes of the largest polling request or websocket message the
+    /// server accepts.
+    pub fn max_payload(mut self, max_payload: usize) -> Self {
+        self.config.max_payload = max_payload;
+        self
+    }
+
+    /// Limits the number of binary attachments a packet of a client may have. The
+    /// connection of a client sending a packet with more is closed before any of them
+    /// is read. Unlimited by default.
+    pub fn max_attachments(mut self, max: u8) -> Self {
+        self.config.max_attachments = Some(max);
+        self
+    }
+
+    /// Limits the size in bytes of a binary attachment of a packet of a client. The
+    /// connection is closed once a client sends a larger one, like with
+    /// [`ServerBuilder::max_attachments`]. Each attachment is limited by
+    /// [`ServerBuilder::max_payload`] anyway.
+    pub fn max_attachment_size(mut self, bytes: usize) -> Self {
+        self.config.max_attachment_size = Some(bytes);
+        self
+    }
+
+    /// Limits the size in bytes of a packet of a client, its attachments included. The
+    /// connection is closed as soon as the bytes read of a packet exceed the limit,
+    /// like with [`ServerBuilder::max_attachments`]. Defaults to
+    /// [`ServerBuilder::max_payload`].
+    pub fn max_packet_size(mut self, bytes: usize) -> Self {
+        self.config.max_packet_size = Some(bytes);
+        self
+    }
+
+    /// Limits the number of engine.io packets waiting to be written to a client, e.g.
+    /// to one that doesn't read them. The connection of a client whose queue is full
+    /// is closed, dropping the packets. At least one packet is queued, 1024 by default.
+    pub fn max_queued_packets(mut self, max: usize) -> Self {
+        self.config.max_queued_packets = max.max(1);
+        self
+    }
+
+    /// Adds a namespace clients can connect to and selects it for the handlers
+    /// registered next. A leading `/` is added if it's missing.
+    pub fn namespace<T: Into<String>>(mut self, namespace: T) -> Self {
+        let mut namespace = namespace.into();
+        if !namespace.starts_with('/') {
+            namespace.insert(0, '/');
+        }
+        self.config.namespaces.entry(namespace.clone()).or_default();
+        self.namespace = namespace;
+        self
+    }
+
+    /// Registers a middleware deciding whether a socket may connect to the selected
+    /// namespace. The middlewares of a namespace run in the order they were
+    /// registered, and the first one returning an error rejects the connection with
+    /// it.
+    /// # Example
+    /// ```rust
+    /// use futures_util::FutureExt;
+    /// use rust_socketio::server::{ConnectError, ServerBuilder};
+    /// use serde_json::json;
+    ///
+    /// let server = ServerBuilder::new()
+    ///     .middleware(|handshake| {
+    ///         async move {
+    ///             match handshake.auth {
+    ///                 Some(auth) if auth["token"] == "secret" => Ok(()),
+    ///                 _ => Err(ConnectError::new("unauthorized").data(json!({"retry": false}))),
+    ///             }
+    ///         }
+    ///         .boxed()
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn middleware<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(Handshake) -> BoxFuture<'static, Result<(), ConnectError>> + 'static + Send + Sync,
+    {
+        self.selected().middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Registers a handler for an event of the selected namespace. The socket passed
+    /// to it answers the event with [`ServerSocket::ack`] if the client asked for an
+    /// ack.
+    /// # Example
+    /// ```rust
+    /// use futures_util::FutureExt;
+    /// use rust_socketio::server::ServerBuilder;
+    ///
+    /// let server = ServerBuilder::new()
+    ///     .namespace("/admin")
+    ///     .on("status", |_, socket| {
+    ///         async move {
+    ///             let _ = socket.ack("ok").await;
+    ///         }
+    ///         .boxed()
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn on<T: Into<Event>, F>(mut self, event: T, callback: F) -> Self
+    where
+        F: Fn(Payload, ServerSocket) -> BoxFuture<'static, ()> + 'static + Send + Sync,
+    {
+        self.selected().on.insert(event.into(), Arc::new(callback));
+        self
+    }
+
+    /// Registers a handler called once a socket connected to the selected namespace.
+    pub fn on_connect<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(ServerSocket) -> BoxFuture<'static, ()> + 'static + Send + Sync,
+    {
+        self.selected().on_connect = Some(Arc::new(callback));
+        self
+    }
+
+    /// Registers a handler called once a socket left the selected namespace, because
+    /// it disconnected, was disconnected or its connection was lost.
+    pub fn on_disconnect<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(ServerSocket) -> BoxFuture<'static, ()> + 'static + Send + Sync,
+    {
+        self.selected().on_disconnect = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets the adapter carrying broadcasts and room changes to the other nodes of a
+    /// server running on several machines. Without one, broadcasts only reach the
+    /// sockets connected to this server.
+    pub fn adapter<A: Adapter>(mut self, adapter: A) -> Self {
+        self.adapter = Some(Arc::new(adapter));
+        self
+    }
+
+    /// Builds the server. It doesn't accept connections until it's served with
+    /// [`Server::serve`] or requests are passed to [`Server::handle`].
+    pub fn build(self) -> Server {
+        Server::new(self.config, self.adapter)
+    }
+
+    fn selected(&mut self) -> &mut Namespace {
+        self.config
+            .namespaces
+            .entry(self.namespace.clone())
+            .or_default()
+    }
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
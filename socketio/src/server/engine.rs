@@ -0,0 +1,560 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use futures_util::{future::BoxFuture, FutureExt};
+use hyper::HeaderMap;
+use log::trace;
+use rand::{thread_rng, RngCore};
+use rust_engineio::{Packet as EnginePacket, PacketId as EnginePacketId};
+use serde_json::{json, Value};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError, unbounded_channel, UnboundedSender},
+    oneshot, watch, Mutex as AsyncMutex, Notify,
+};
+
+use crate::{
+    error::Result,
+    json,
+    packet::{Packet, PacketId},
+    Error, Event, Payload,
+};
+
+use super::{
+    builder::Config,
+    middleware::{ConnectError, Handshake},
+    rooms::Rooms,
+    socket::ServerSocket,
+};
+
+/// A socket connected to a namespace of a connection.
+struct Joined {
+    id: Arc<str>,
+    auth: Option<Arc<Value>>,
+}
+
+/// The id of the next ack the server asks for. Ids are unique across connections, so
+/// a broadcast asks all of its sockets with the same id.
+static NEXT_ACK_ID: AtomicI32 = AtomicI32::new(0);
+
+/// An ack the server waits for.
+struct Waiting {
+    nsp: String,
+    answer: oneshot::Sender<Payload>,
+}
+
+/// The engine.io connection of a client, carrying the sockets of the namespaces it
+/// connected to.
+pub(crate) struct Connection {
+    pub(crate) sid: String,
+    config: Arc<Config>,
+    pub(crate) rooms: Arc<Rooms>,
+    /// The headers of the request that opened the connection.
+    headers: HeaderMap,
+    /// The query parameters of the request that opened the connection.
+    query: HashMap<String, String>,
+    outgoing: mpsc::Sender<EnginePacket>,
+    /// The packets waiting to be written, taken by the poll or the websocket writing
+    /// them. A client not taking them fast enough fills it up and is closed.
+    queued: AsyncMutex<mpsc::Receiver<EnginePacket>>,
+    /// Whether the client upgraded from polling to a websocket.
+    upgraded: AtomicBool,
+    pub(crate) pong: Notify,
+    closed: watch::Sender<bool>,
+    sockets: Mutex<HashMap<String, Joined>>,
+    /// A binary packet waiting for its attachments, with the bytes read of it so far.
+    partial: Mutex<Option<(Packet, usize)>>,
+    acks: Mutex<HashMap<i32, Waiting>>,
+    /// Runs the handlers of the connection one after the other.
+    handlers: UnboundedSender<BoxFuture<'static, ()>>,
+}
+
+impl Connection {
+    pub(crate) fn new(
+        config: Arc<Config>,
+        rooms: Arc<Rooms>,
+        headers: HeaderMap,
+        query: HashMap<String, String>,
+    ) -> Arc<Self> {
+        let (outgoing, queued) = mpsc::channel(config.max_queued_packets);
+        let (handlers, mut pending) = unbounded_channel::<BoxFuture<'static, ()>>();
+        tokio::spawn(async move {
+            while let Some(handler) = pending.recv().await {
+                // a panicking handler only ends its own task
+                let _ = tokio::spawn(handler).await;
+            }
+        });
+
+        Arc::new(Connection {
+            sid: random_id(),
+            config,
+            rooms,
+            headers,
+            query,
+            outgoing,
+            queued: AsyncMutex::new(queued),
+            upgraded: AtomicBool::default(),
+            pong: Notify::new(),
+            closed: watch::Sender::new(false),
+            sockets: Mutex::default(),
+            partial: Mutex::default(),
+            acks: Mutex::default(),
+            handlers,
+        })
+    }
+
+    /// The packet opening the connection.
+    pub(crate) fn handshake(&self, upgrades: &[&str]) -> EnginePacket {
+        let handshake = json!({
+            "sid": self.sid,
+            "upgrades": upgrades,
+            "pingInterval": self.config.ping_interval.as_millis() as u64,
+            "pingTimeout": self.config.ping_timeout.as_millis() as u64,
+            "maxPayload": self.config.max_payload,
+        });
+        EnginePacket::new(EnginePacketId::Open, handshake.to_string())
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// Waits until the connection is closed.
+    pub(crate) async fn closed(&self) {
+        let _ = self.closed.subscribe().wait_for(|closed| *closed).await;
+    }
+
+    pub(crate) fn is_upgraded(&self) -> bool {
+        self.upgraded.load(Ordering::Acquire)
+    }
+
+    /// Switches the connection to the websocket, answering a waiting poll with a
+    /// `Noop` packet so it lets go of the queued packets.
+    pub(crate) fn upgrade(self: &Arc<Self>) {
+        self.upgraded.store(true, Ordering::Release);
+        let _ = self.push(EnginePacket::new(EnginePacketId::Noop, Bytes::new()));
+    }
+
+    /// Closes the connection, disconnecting its sockets. Returns whether it was open.
+    pub(crate) fn close(self: &Arc<Self>) -> bool {
+        if self.closed.send_replace(true) {
+            return false;
+        }
+        let namespaces: Vec<String> = match self.sockets.lock() {
+            Ok(sockets) => sockets.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        for nsp in namespaces {
+            self.leave(&nsp);
+        }
+        // dropping the answers lets the waiting emits know
+        if let Ok(mut acks) = self.acks.lock() {
+            acks.clear();
+        }
+        true
+    }
+
+    /// Registers an ack the server asks the socket of the namespace for. The answer
+    /// is dropped if the socket disconnects before answering.
+    pub(crate) fn expect_ack(&self, nsp: &str, id: i32) -> oneshot::Receiver<Payload> {
+        let (answer, answered) = oneshot::channel();
+        if let Ok(mut acks) = self.acks.lock() {
+            acks.insert(
+                id,
+                Waiting {
+                    nsp: nsp.to_owned(),
+                    answer,
+                },
+            );
+        }
+        answered
+    }
+
+    /// Stops waiting for an ack that timed out.
+    pub(crate) fn forget_ack(&self, id: i32) {
+        if let Ok(mut acks) = self.acks.lock() {
+            acks.remove(&id);
+        }
+    }
+
+    /// Queues a packet for the client. Closes the connection if its queue is full.
+    pub(crate) fn push(self: &Arc<Self>, packet: EnginePacket) -> Result<()> {
+        match self.outgoing.try_send(packet) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                trace!("Closing {}, it doesn't read its packets", self.sid);
+                self.close();
+                Err(Error::StoppedEngineIoSocket)
+            }
+            Err(TrySendError::Closed(_)) => Err(Error::StoppedEngineIoSocket),
+        }
+    }
+
+    /// Sends a socket.io packet, followed by its attachments.
+    pub(crate) fn send(self: &Arc<Self>, packet: &Packet) -> Result<()> {
+        trace!("Sending {:?} to {}", packet.packet_type, self.sid);
+        self.send_encoded(&encode(packet))
+    }
+
+    /// Sends the engine.io packets of an encoded socket.io packet.
+    pub(crate) fn send_encoded(self: &Arc<Self>, packets: &[EnginePacket]) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::StoppedEngineIoSocket);
+        }
+        for packet in packets {
+            self.push(packet.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Takes the queued packets, waiting for one if there are none. Answers with a
+    /// `Close` packet once the connection is closed.
+    pub(crate) async fn poll(&self) -> Vec<EnginePacket> {
+        let mut queued = self.queued.lock().await;
+        let first = tokio::select! {
+            packet = queued.recv() => packet,
+            _ = self.closed() => None,
+        };
+        let Some(first) = first else {
+            return vec![EnginePacket::new(EnginePacketId::Close, Bytes::new())];
+        };
+
+        let mut packets = vec![first];
+        while let Ok(packet) = queued.try_recv() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    /// Takes the next queued packet, for the websocket writing them one by one.
+    pub(crate) async fn next_queued(&self) -> Option<EnginePacket> {
+        let mut queued = self.queued.lock().await;
+        tokio::select! {
+            packet = queued.recv() => packet,
+            _ = self.closed() => None,
+        }
+    }
+
+    /// Handles a packet the client sent. Returns `false` if the client closed the
+    /// connection or sent a packet exceeding the limits of the server, so it's closed.
+    pub(crate) fn handle(self: &Arc<Self>, packet: EnginePacket) -> bool {
+        match packet.packet_id {
+            EnginePacketId::Pong => self.pong.notify_waiters(),
+            EnginePacketId::Ping => {
+                let _ = self.push(EnginePacket::new(EnginePacketId::Pong, packet.data));
+            }
+            EnginePacketId::Close => return false,
+            EnginePacketId::Message => match Packet::try_from(&packet.data) {
+                Ok(socket_packet) if socket_packet.attachment_count > 0 => {
+                    if let Some(max) = self.config.max_attachments {
+                        if socket_packet.attachment_count > max {
+                            trace!(
+                                "Closing {}, it sent a packet with {} attachments",
+                                self.sid,
+                                socket_packet.attachment_count
+                            );
+                            return false;
+                        }
+                    }
+                    if let Ok(mut partial) = self.partial.lock() {
+                        *partial = Some((socket_packet, packet.data.len()));
+                    }
+                }
+                Ok(packet) => self.dispatch(packet),
+                Err(e) => trace!("Dropping an invalid packet from {}: {e}", self.sid),
+            },
+            EnginePacketId::MessageBinary => {
+                let Ok(mut partial) = self.partial.lock() else {
+                    return true;
+                };
+                let Some((waiting, bytes)) = partial.as_mut() else {
+                    trace!("Dropping an unexpected attachment from {}", self.sid);
+                    return true;
+                };
+                *bytes += packet.data.len();
+                let config = &self.config;
+                let max_packet_size = config.max_packet_size.unwrap_or(config.max_payload);
+                if config
+                    .max_attachment_size
+                    .is_some_and(|max| packet.data.len() > max)
+                    || *bytes > max_packet_size
+                {
+                    trace!("Closing {}, it sent a packet of {bytes} bytes", self.sid);
+                    return false;
+                }
+                let attachments = waiting.attachments.get_or_insert_with(Vec::new);
+                attachments.push(packet.data);
+                if attachments.len() < usize::from(waiting.attachment_count) {
+                    return true;
+                }
+                let complete = partial.take();
+                drop(partial);
+                if let Some((packet, _)) = complete {
+                    self.dispatch(packet);
+                }
+            }
+            EnginePacketId::Open | EnginePacketId::Upgrade | EnginePacketId::Noop => (),
+        }
+        true
+    }
+
+    /// Handles a socket.io packet of the client.
+    fn dispatch(self: &Arc<Self>, packet: Packet) {
+        match packet.packet_type {
+            PacketId::Connect => self.join(packet),
+            PacketId::Disconnect => {
+                self.leave(&packet.nsp);
+            }
+            PacketId::Event | PacketId::BinaryEvent => {
+                let Some(socket) = self.socket(&packet.nsp) else {
+                    trace!("Dropping an event for {} without a socket", packet.nsp);
+                    return;
+                };
+                let Some((event, payload)) = event_payload(&packet) else {
+                    return;
+                };
+                let handler = self
+                    .config
+                    .namespaces
+                    .get(&packet.nsp)
+                    .and_then(|namespace| namespace.on.get(&event));
+                if let Some(handler) = handler {
+                    let _ = self
+                        .handlers
+                        .send(handler(payload, socket.with_ack(packet.id)));
+                }
+            }
+            PacketId::Ack | PacketId::BinaryAck => {
+                let waiting = packet
+                    .id
+                    .and_then(|id| self.acks.lock().ok()?.remove(&id))
+                    .filter(|waiting| waiting.nsp == packet.nsp);
+                match waiting {
+                    Some(waiting) => {
+                        let _ = waiting.answer.send(ack_payload(&packet));
+                    }
+                    None => trace!("Dropping an unexpected ack from {}", self.sid),
+                }
+            }
+            PacketId::ConnectError => (),
+        }
+    }
+
+    /// Connects a socket to the namespace of a `Connect` packet if the server knows
+    /// it and its middlewares let the socket in.
+    fn join(self: &Arc<Self>, packet: Packet) {
+        let Some(namespace) = self.config.namespaces.get(&packet.nsp) else {
+            self.reject(packet.nsp, &ConnectError::new("Invalid namespace"));
+            return;
+        };
+
+        let auth = packet
+            .data
+            .as_deref()
+            .and_then(|data| json::from_str::<Value>(data).ok());
+        if namespace.middlewares.is_empty() {
+            self.connect(packet.nsp, auth);
+            return;
+        }
+
+        // the middlewares run along with the handlers, so the events of the
+        // connection stay in order
+        let handshake = Handshake {
+            nsp: packet.nsp,
+            auth,
+            headers: self.headers.clone(),
+            query: self.query.clone(),
+        };
+        let middlewares = namespace.middlewares.clone();
+        let connection = self.clone();
+        let _ = self.handlers.send(
+            async move {
+                for middleware in middlewares.iter() {
+                    if let Err(error) = middleware(handshake.clone()).await {
+                        connection.reject(handshake.nsp, &error);
+                        return;
+                    }
+                }
+                connection.connect(handshake.nsp, handshake.auth);
+            }
+            .boxed(),
+        );
+    }
+
+    fn connect(self: &Arc<Self>, nsp: String, auth: Option<Value>) {
+        let id: Arc<str> = Arc::from(random_id());
+        if let Ok(mut sockets) = self.sockets.lock() {
+            sockets.insert(
+                nsp.clone(),
+                Joined {
+                    id: id.clone(),
+                    auth: auth.map(Arc::new),
+                },
+            );
+        }
+
+        let connected = Packet::new(
+            PacketId::Connect,
+            nsp.clone(),
+            Some(json!({ "sid": &*id }).to_string()),
+            None,
+            0,
+            None,
+            None,
+        );
+        let _ = self.send(&connected);
+
+        let Some(socket) = self.socket(&nsp) else {
+            return;
+        };
+        self.rooms.add(&socket);
+        let on_connect = self
+            .config
+            .namespaces
+            .get(&nsp)
+            .and_then(|namespace| namespace.on_connect.as_ref());
+        if let Some(on_connect) = on_connect {
+            let _ = self.handlers.send(on_connect(socket));
+        }
+    }
+
+    fn reject(self: &Arc<Self>, nsp: String, error: &ConnectError) {
+        trace!("Rejecting a connection to {nsp}: {}", error.message);
+        let packet = Packet::new(
+            PacketId::ConnectError,
+            nsp,
+            Some(error.to_json().to_string()),
+            None,
+            0,
+            None,
+            None,
+        );
+        let _ = self.send(&packet);
+    }
+
+    /// Disconnects the socket of a namespace. Returns whether it was connected.
+    pub(crate) fn leave(self: &Arc<Self>, nsp: &str) -> bool {
+        let Some(socket) = self.socket(nsp) else {
+            return false;
+        };
+        if let Ok(mut sockets) = self.sockets.lock() {
+            sockets.remove(nsp);
+        }
+        if let Ok(mut acks) = self.acks.lock() {
+            acks.retain(|_, waiting| waiting.nsp != nsp);
+        }
+        self.rooms.remove(&socket);
+        let on_disconnect = self
+            .config
+            .namespaces
+            .get(nsp)
+            .and_then(|namespace| namespace.on_disconnect.as_ref());
+        if let Some(on_disconnect) = on_disconnect {
+            let _ = self.handlers.send(on_disconnect(socket));
+        }
+        true
+    }
+
+    /// The socket connected to the namespace, if any.
+    pub(crate) fn socket(self: &Arc<Self>, nsp: &str) -> Option<ServerSocket> {
+        let sockets = self.sockets.lock().ok()?;
+        let joined = sockets.get(nsp)?;
+        Some(ServerSocket {
+            id: joined.id.clone(),
+            nsp: Arc::from(nsp),
+            auth: joined.auth.clone(),
+            connection: self.clone(),
+            ack_id: None,
+        })
+    }
+
+    /// The sockets of all namespaces the client connected to.
+    pub(crate) fn sockets(self: &Arc<Self>) -> Vec<ServerSocket> {
+        let namespaces: Vec<String> = match self.sockets.lock() {
+            Ok(sockets) => sockets.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        namespaces
+            .iter()
+            .filter_map(|nsp| self.socket(nsp))
+            .collect()
+    }
+}
+
+/// The engine.io packets of a socket.io packet: a message followed by its
+/// attachments.
+pub(crate) fn encode(packet: &Packet) -> Vec<EnginePacket> {
+    let mut packets = vec![EnginePacket::new(
+        EnginePacketId::Message,
+        Bytes::from(packet),
+    )];
+    for attachment in packet.attachments.iter().flatten() {
+        packets.push(EnginePacket::new(
+            EnginePacketId::MessageBinary,
+            attachment.clone(),
+        ));
+    }
+    packets
+}
+
+/// The event and the arguments of an event packet.
+fn event_payload(packet: &Packet) -> Option<(Event, Payload)> {
+    if packet.packet_type == PacketId::BinaryEvent {
+        // binary packets only keep the event name of their arguments
+        let event = packet
+            .data
+            .as_deref()
+            .and_then(|data| json::from_str::<String>(data).ok())
+            .map_or(Event::Message, Event::from);
+        let attachment = packet.attachments.as_ref()?.first()?.clone();
+        return Some((event, Payload::Binary(attachment)));
+    }
+
+    let Ok(Value::Array(mut contents)) = json::from_str::<Value>(packet.data.as_deref()?) else {
+        return None;
+    };
+    let event = match contents.first() {
+        Some(Value::String(event)) if contents.len() > 1 => {
+            let event = Event::from(event.as_str());
+            contents.remove(0);
+            event
+        }
+        _ => Event::Message,
+    };
+    Some((event, Payload::Text(contents)))
+}
+
+/// The arguments of an ack packet.
+fn ack_payload(packet: &Packet) -> Payload {
+    if let Some(attachment) = packet.attachments.iter().flatten().next() {
+        return Payload::Binary(attachment.clone());
+    }
+    match packet
+        .data
+        .as_deref()
+        .and_then(|data| json::from_str::<Value>(data).ok())
+    {
+        Some(Value::Array(values)) => Payload::Text(values),
+        Some(value) => Payload::Text(vec![value]),
+        None => Payload::Text(Vec::new()),
+    }
+}
+
+/// The id for the next ack the server asks for.
+pub(crate) fn next_ack_id() -> i32 {
+    // wrapping around keeps the ids positive
+    NEXT_ACK_ID.fetch_add(1, Ordering::Relaxed) & i32::MAX
+}
+
+/// A random id for a connection or a socket.
+pub(crate) fn random_id() -> String {
+    let mut bytes = [0; 15];
+    thread_rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
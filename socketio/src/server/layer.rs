@@ -0,0 +1,216 @@
+use std::{
+    convert::Infallible,
+    future::{ready, Ready},
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_util::{future::BoxFuture, FutureExt};
+use http_body_util::{Either, Full};
+use hyper::{body::Body, Request, Response, StatusCode};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use super::Server;
+
+/// Mounts a socket.io server in front of a `tower` service, e.g. an `axum` router,
+/// so both share a listener. Requests to the path of the server are answered by
+/// it, all others are passed on.
+///
+/// ```rust,no_run
+/// use axum::{routing::get, Router};
+/// use rust_socketio::server::ServerBuilder;
+/// use tokio::net::TcpListener;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let server = ServerBuilder::new().build();
+///     let app = Router::new()
+///         .route("/", get(|| async { "hello" }))
+///         .layer(server.layer());
+///
+///     let listener = TcpListener::bind("127.0.0.1:4200").await.unwrap();
+///     axum::serve(listener, app).await.unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SocketIoLayer {
+    server: Server,
+}
+
+impl SocketIoLayer {
+    pub(crate) fn new(server: Server) -> Self {
+        SocketIoLayer { server }
+    }
+}
+
+impl<S> Layer<S> for SocketIoLayer {
+    type Service = SocketIoService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SocketIoService {
+            server: self.server.clone(),
+            inner,
+        }
+    }
+}
+
+/// The `tower` service of a socket.io server, built with [`Server::service`] or by
+/// a [`SocketIoLayer`]. Requests to other paths go to the inner service, which
+/// answers them with `404 Not Found` for a service on its own.
+///
+/// Websockets are served once the connection of their request was upgraded, which
+/// `axum::serve` and the `hyper` connections built `with_upgrades` do.
+#[derive(Clone)]
+pub struct SocketIoService<S = NotFound> {
+    server: Server,
+    inner: S,
+}
+
+impl SocketIoService {
+    pub(crate) fn new(server: Server) -> Self {
+        SocketIoService {
+            server,
+            inner: NotFound,
+        }
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for SocketIoService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Response = Response<Either<Full<Bytes>, ResBody>>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        if self.server.serves(request.uri().path()) {
+            let server = self.server.clone();
+            return async move { Ok(server.handle(request).await.map(Either::Left)) }.boxed();
+        }
+        // the clone might not be ready, so the inner service that was polled is
+        // called and the clone is kept for the next request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let response = inner.call(request);
+        async move { Ok(response.await?.map(Either::Right)) }.boxed()
+    }
+}
+
+/// Answers every request with `404 Not Found`, the inner service of a
+/// [`SocketIoService`] on its own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NotFound;
+
+impl<B> Service<Request<B>> for NotFound {
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Request<B>) -> Self::Future {
+        let mut response = Response::new(Full::default());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use axum::{routing::get, Router};
+    use futures_util::FutureExt;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::mpsc::unbounded_channel,
+        time::timeout,
+    };
+
+    use crate::{
+        asynchronous::ClientBuilder, error::Result, server::ServerBuilder, Payload, TransportType,
+    };
+
+    /// Sends a bare `GET` request for the path, returning the whole response.
+    async fn get_raw(address: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn mounts_in_axum() -> Result<()> {
+        let server = ServerBuilder::new()
+            .on("echo", |payload, socket| {
+                async move {
+                    let _ = socket.emit("echo", payload).await;
+                }
+                .boxed()
+            })
+            .build();
+        let app = Router::new()
+            .route("/", get(|| async { "hello" }))
+            .layer(server.layer());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        for transport_type in [TransportType::Any, TransportType::Websocket] {
+            let (echoed, mut echoes) = unbounded_channel();
+            let client = crate::test::connect(
+                ClientBuilder::new(format!("http://{address}/"))
+                    .transport_type(transport_type)
+                    .on("echo", move |payload, _| {
+                        let _ = echoed.send(payload);
+                        async {}.boxed()
+                    }),
+            )
+            .await?;
+            client.emit("echo", json!("hi")).await?;
+            let echo = timeout(Duration::from_secs(5), echoes.recv())
+                .await
+                .unwrap();
+            assert_eq!(echo, Some(Payload::Text(vec![json!("hi")])));
+            client.disconnect().await?;
+        }
+
+        let response = get_raw(&address, "/").await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("hello"));
+        let response = get_raw(&address, "/missing").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serves_on_its_own() {
+        let server = ServerBuilder::new().build();
+        let app = Router::new().route_service("/socket.io/", server.service());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let response = get_raw(&address, "/socket.io/?EIO=4&transport=polling").await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(r#""sid":"#));
+        assert!(server.all_sockets().is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures_util::future::BoxFuture;
+use hyper::HeaderMap;
+use serde_json::{json, Value};
+
+/// Decides whether a socket may connect to a namespace.
+pub(crate) type Middleware =
+    Arc<dyn Fn(Handshake) -> BoxFuture<'static, Result<(), ConnectError>> + Send + Sync>;
+
+/// What a client connecting to a namespace sent, passed to the middlewares of the
+/// namespace.
+#[derive(Clone, Debug)]
+pub struct Handshake {
+    /// The namespace the client connects to.
+    pub nsp: String,
+    /// The authentication payload of the `CONNECT` packet, if any.
+    pub auth: Option<Value>,
+    /// The headers of the request that opened the connection.
+    pub headers: HeaderMap,
+    /// The query parameters of the request that opened the connection.
+    pub query: HashMap<String, String>,
+}
+
+/// Rejects a connection to a namespace. The client gets it as the body of a
+/// `CONNECT_ERROR` packet, which the clients of this crate surface as
+/// [`Error::ConnectRejected`](crate::Error::ConnectRejected).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectError {
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl ConnectError {
+    pub fn new<T: Into<String>>(message: T) -> Self {
+        ConnectError {
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Adds details for the client to the rejection.
+    pub fn data<T: Into<Value>>(mut self, data: T) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// The body of the `CONNECT_ERROR` packet.
+    pub(crate) fn to_json(&self) -> Value {
+        match &self.data {
+            Some(data) => json!({"message": self.message, "data": data}),
+            None => json!({"message": self.message}),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! An asynchronous socket.io server, enabled by the `server` feature. It accepts
+//! engine.io connections over polling and websockets, including the upgrade from
+//! polling to websockets, and speaks revision 4 of engine.io and revision 5 of
+//! socket.io, like the clients of this crate and socket.io 3 and 4.
+//!
+//! Handlers are registered per namespace on a [`ServerBuilder`] and get a
+//! [`ServerSocket`] to answer with. The handlers of a connection run one after the
+//! other, in the order the events arrived. [Middlewares](ServerBuilder::middleware)
+//! decide whether a socket may connect to a namespace, e.g. by its auth payload.
+//!
+//! Sockets [join](ServerSocket::join) rooms, and a [`Broadcast`] emits an event to
+//! every socket in a selection of rooms, e.g. with
+//! `socket.to("lobby").emit("chat", payload)` to the lobby except the sender, or
+//! with `server.to("lobby").emit(...)` to all of it.
+//!
+//! ```rust,no_run
+//! use futures_util::FutureExt;
+//! use rust_socketio::server::ServerBuilder;
+//! use tokio::net::TcpListener;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let server = ServerBuilder::new()
+//!         .on("chat", |payload, socket| {
+//!             async move {
+//!                 socket.emit("chat", payload).await.expect("connection closed");
+//!             }
+//!             .boxed()
+//!         })
+//!         .build();
+//!
+//!     let listener = TcpListener::bind("127.0.0.1:4200").await.unwrap();
+//!     server.serve(listener).await.unwrap();
+//! }
+//! ```
+//!
+//! To serve socket.io next to other routes, requests to the socket.io path can be
+//! passed to [`Server::handle`] from an existing `hyper` service. With the `tower`
+//! feature, the server can be mounted in an `axum` router with [`Server::layer`] or
+//! [`Server::service`] instead.
+
+mod adapter;
+mod builder;
+mod engine;
+#[cfg(feature = "tower")]
+mod layer;
+mod middleware;
+#[cfg(feature = "redis-adapter")]
+mod redis_adapter;
+mod rooms;
+mod service;
+mod socket;
+
+pub use adapter::{Adapter, BroadcastOptions, Node};
+pub use builder::ServerBuilder;
+#[cfg(feature = "tower")]
+pub use layer::{NotFound, SocketIoLayer, SocketIoService};
+pub use middleware::{ConnectError, Handshake};
+#[cfg(feature = "redis-adapter")]
+pub use redis_adapter::RedisAdapter;
+pub use rooms::Broadcast;
+pub use service::Server;
+pub use socket::ServerSocket;
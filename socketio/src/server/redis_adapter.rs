@@ -0,0 +1,747 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use log::trace;
+use redis::{aio::MultiplexedConnection, AsyncCommands, Client, Msg};
+use rmpv::Value as MsgValue;
+use serde_json::{json, Value};
+use tokio::{
+    sync::{oneshot, watch},
+    time::timeout,
+};
+
+use crate::{error::Result, Error, Event, Payload};
+
+use super::{
+    adapter::{Adapter, BroadcastOptions, Node},
+    engine::random_id,
+};
+
+/// The type of a socket.io packet carrying an event.
+const EVENT: u64 = 2;
+const BINARY_EVENT: u64 = 5;
+
+/// The types of the requests between the nodes.
+const ALL_ROOMS: u64 = 1;
+const REMOTE_JOIN: u64 = 2;
+const REMOTE_LEAVE: u64 = 3;
+const REMOTE_DISCONNECT: u64 = 4;
+
+/// A request for the rooms of the other nodes, waiting for their answers.
+struct Pending {
+    remaining: usize,
+    rooms: HashSet<String>,
+    done: Option<oneshot::Sender<HashSet<String>>>,
+}
+
+/// What the adapter shares with the task reading its subscriptions.
+struct Shared {
+    pending: Mutex<HashMap<String, Pending>>,
+    /// Set once the subscriptions of the node were made.
+    subscribed: watch::Sender<bool>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Shared {
+            pending: Mutex::default(),
+            subscribed: watch::channel(false).0,
+        }
+    }
+}
+
+/// An [`Adapter`] connecting the nodes of a server over redis pub/sub, speaking the
+/// protocol of the `@socket.io/redis-adapter` package, so nodes written in rust and
+/// in javascript can share one redis.
+///
+/// Broadcasts are published in msgpack, the requests to join, leave or disconnect
+/// sockets and to list the rooms in JSON, on the same channels as the javascript
+/// adapter. The server has to be built inside a tokio runtime, which the adapter
+/// reads its subscriptions on.
+/// # Example
+/// ```rust,no_run
+/// use rust_socketio::server::{RedisAdapter, ServerBuilder};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = redis::Client::open("redis://127.0.0.1:6379").unwrap();
+///     let adapter = RedisAdapter::new(client).await.unwrap();
+///     let server = ServerBuilder::new().adapter(adapter).build();
+///
+///     // reaches the lobby on every node
+///     server.to("lobby").emit("notice", "closing soon").await.unwrap();
+/// }
+/// ```
+pub struct RedisAdapter {
+    client: Client,
+    publisher: MultiplexedConnection,
+    uid: String,
+    key: String,
+    requests_timeout: Duration,
+    shared: Arc<Shared>,
+}
+
+impl RedisAdapter {
+    /// Connects to redis with the prefix `socket.io` for the channels and a timeout
+    /// of five seconds for requests to the other nodes, like the javascript adapter.
+    pub async fn new(client: Client) -> Result<Self> {
+        let publisher = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .map_err(adapter_error)?;
+        Ok(RedisAdapter {
+            client,
+            publisher,
+            uid: random_id(),
+            key: "socket.io".to_owned(),
+            requests_timeout: Duration::from_secs(5),
+            shared: Arc::default(),
+        })
+    }
+
+    /// Sets the prefix of the channels, which has to match the `key` of the other
+    /// nodes.
+    pub fn key<T: Into<String>>(mut self, key: T) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Sets how long to wait for the other nodes to answer a request.
+    pub fn requests_timeout(mut self, timeout: Duration) -> Self {
+        self.requests_timeout = timeout;
+        self
+    }
+
+    async fn publish(&self, channel: String, message: Vec<u8>) -> Result<()> {
+        let mut publisher = self.publisher.clone();
+        publisher
+            .publish::<_, _, ()>(channel, message)
+            .await
+            .map_err(adapter_error)
+    }
+
+    async fn request(&self, nsp: &str, request: Value) -> Result<()> {
+        self.publish(
+            request_channel(&self.key, nsp),
+            request.to_string().into_bytes(),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl Adapter for RedisAdapter {
+    fn attach(&self, node: Node) {
+        let mut subscriber = Subscriber {
+            uid: self.uid.clone(),
+            key: self.key.clone(),
+            publisher: self.publisher.clone(),
+            shared: self.shared.clone(),
+            node,
+        };
+        let client = self.client.clone();
+        tokio::spawn(async move {
+            let mut pubsub = match client.get_async_pubsub().await {
+                Ok(pubsub) => pubsub,
+                Err(e) => {
+                    trace!("Subscribing the redis adapter failed: {e}");
+                    return;
+                }
+            };
+            for nsp in subscriber.node.namespaces() {
+                let key = &subscriber.key;
+                let subscribed = pubsub.psubscribe(format!("{key}#{nsp}#*")).await.and(
+                    pubsub
+                        .subscribe(&[request_channel(key, nsp), response_channel(key, nsp)])
+                        .await,
+                );
+                if let Err(e) = subscribed {
+                    trace!("Subscribing the redis adapter failed: {e}");
+                    return;
+                }
+            }
+            subscriber.shared.subscribed.send_replace(true);
+
+            let mut messages = pubsub.into_on_message();
+            while let Some(message) = messages.next().await {
+                if !subscriber.node.is_attached() {
+                    break;
+                }
+                subscriber.handle(message).await;
+            }
+        });
+    }
+
+    async fn broadcast(
+        &self,
+        opts: &BroadcastOptions,
+        event: &Event,
+        payload: &Payload,
+    ) -> Result<()> {
+        let message = encode_broadcast(&self.uid, opts, event, payload);
+        let channel = match opts.rooms.as_slice() {
+            // nodes can drop broadcasts to rooms they don't know without decoding them
+            [room] => format!("{}#{}#{room}#", self.key, opts.nsp),
+            _ => format!("{}#{}#", self.key, opts.nsp),
+        };
+        self.publish(channel, message).await
+    }
+
+    async fn add_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) -> Result<()> {
+        let request = json!({
+            "uid": self.uid,
+            "type": REMOTE_JOIN,
+            "opts": {"rooms": opts.rooms, "except": opts.except},
+            "rooms": rooms,
+        });
+        self.request(&opts.nsp, request).await
+    }
+
+    async fn del_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) -> Result<()> {
+        let request = json!({
+            "uid": self.uid,
+            "type": REMOTE_LEAVE,
+            "opts": {"rooms": opts.rooms, "except": opts.except},
+            "rooms": rooms,
+        });
+        self.request(&opts.nsp, request).await
+    }
+
+    async fn disconnect_sockets(&self, opts: &BroadcastOptions) -> Result<()> {
+        let request = json!({
+            "uid": self.uid,
+            "type": REMOTE_DISCONNECT,
+            "opts": {"rooms": opts.rooms, "except": opts.except},
+            "close": false,
+        });
+        self.request(&opts.nsp, request).await
+    }
+
+    async fn remote_rooms(&self, nsp: &str) -> Result<Vec<String>> {
+        // the answers arrive on the subscriptions of this node
+        let mut subscribed = self.shared.subscribed.subscribe();
+        timeout(
+            self.requests_timeout,
+            subscribed.wait_for(|subscribed| *subscribed),
+        )
+        .await
+        .map_err(|_| Error::IncompleteAdapter("the redis adapter isn't subscribed".into()))?
+        .map_err(adapter_error)?;
+
+        // every node subscribes to the request channel
+        let channel = request_channel(&self.key, nsp);
+        let (_, nodes): (String, usize) = redis::cmd("PUBSUB")
+            .arg("NUMSUB")
+            .arg(&channel)
+            .query_async(&mut self.publisher.clone())
+            .await
+            .map_err(adapter_error)?;
+        if nodes <= 1 {
+            return Ok(Vec::new());
+        }
+
+        let request_id = random_id();
+        let (done, answered) = oneshot::channel();
+        if let Ok(mut pending) = self.shared.pending.lock() {
+            pending.insert(
+                request_id.clone(),
+                Pending {
+                    remaining: nodes - 1,
+                    rooms: HashSet::new(),
+                    done: Some(done),
+                },
+            );
+        }
+        let request = json!({"uid": self.uid, "requestId": request_id, "type": ALL_ROOMS});
+        let answered = match self.request(nsp, request).await {
+            Ok(()) => timeout(self.requests_timeout, answered).await,
+            Err(e) => {
+                self.shared.pending.lock()?.remove(&request_id);
+                return Err(e);
+            }
+        };
+        let pending = self.shared.pending.lock()?.remove(&request_id);
+        match answered {
+            Ok(Ok(rooms)) => Ok(rooms.into_iter().collect()),
+            _ => {
+                let remaining = pending.map_or(0, |pending| pending.remaining);
+                Err(Error::IncompleteAdapter(
+                    format!("{remaining} of {} nodes didn't answer in time", nodes - 1).into(),
+                ))
+            }
+        }
+    }
+}
+
+/// Reads the subscriptions of a node.
+struct Subscriber {
+    uid: String,
+    key: String,
+    publisher: MultiplexedConnection,
+    shared: Arc<Shared>,
+    node: Node,
+}
+
+impl Subscriber {
+    async fn handle(&mut self, message: Msg) {
+        let channel = message.get_channel_name().to_owned();
+        let payload = message.get_payload_bytes();
+        let Some(nsp) = self
+            .node
+            .namespaces()
+            .iter()
+            .find(|nsp| channel.starts_with(&format!("{}#{nsp}#", self.key)))
+            .or_else(|| {
+                self.node.namespaces().iter().find(|nsp| {
+                    channel == request_channel(&self.key, nsp)
+                        || channel == response_channel(&self.key, nsp)
+                })
+            })
+            .cloned()
+        else {
+            return;
+        };
+
+        if channel == request_channel(&self.key, &nsp) {
+            self.on_request(&nsp, payload).await;
+        } else if channel == response_channel(&self.key, &nsp) {
+            self.on_response(payload);
+        } else {
+            self.on_broadcast(&nsp, payload);
+        }
+    }
+
+    fn on_broadcast(&self, nsp: &str, message: &[u8]) {
+        let Some((uid, opts, event, payload)) = decode_broadcast(message) else {
+            trace!("Dropping an invalid broadcast");
+            return;
+        };
+        if uid != self.uid && opts.nsp == nsp {
+            let _ = self.node.broadcast(&opts, event, payload);
+        }
+    }
+
+    async fn on_request(&mut self, nsp: &str, message: &[u8]) {
+        let Some(request) = decode_json(message) else {
+            return;
+        };
+        if request["uid"].as_str() == Some(self.uid.as_str()) {
+            return;
+        }
+        let opts = BroadcastOptions {
+            nsp: nsp.to_owned(),
+            rooms: strings(&request["opts"]["rooms"]),
+            except: strings(&request["opts"]["except"]),
+        };
+        let has_opts = request["opts"].is_object();
+
+        match request["type"].as_u64() {
+            Some(ALL_ROOMS) => {
+                let response = json!({
+                    "requestId": request["requestId"],
+                    "rooms": self.node.rooms(nsp),
+                });
+                let channel = response_channel(&self.key, nsp);
+                let published = self
+                    .publisher
+                    .publish::<_, _, ()>(channel, response.to_string())
+                    .await;
+                if let Err(e) = published {
+                    trace!("Answering a request failed: {e}");
+                }
+            }
+            Some(REMOTE_JOIN) if has_opts => {
+                self.node.add_sockets(&opts, &strings(&request["rooms"]));
+            }
+            Some(REMOTE_LEAVE) if has_opts => {
+                self.node.del_sockets(&opts, &strings(&request["rooms"]));
+            }
+            Some(REMOTE_DISCONNECT) if has_opts => self.node.disconnect_sockets(&opts),
+            _ => trace!("Ignoring a request of type {}", request["type"]),
+        }
+    }
+
+    fn on_response(&self, message: &[u8]) {
+        let Some(response) = decode_json(message) else {
+            return;
+        };
+        let Some(request_id) = response["requestId"].as_str() else {
+            return;
+        };
+        let Ok(mut pending) = self.shared.pending.lock() else {
+            return;
+        };
+        let Some(request) = pending.get_mut(request_id) else {
+            return;
+        };
+        request.rooms.extend(strings(&response["rooms"]));
+        request.remaining = request.remaining.saturating_sub(1);
+        if request.remaining == 0 {
+            if let Some(done) = request.done.take() {
+                let _ = done.send(std::mem::take(&mut request.rooms));
+            }
+        }
+    }
+}
+
+fn request_channel(key: &str, nsp: &str) -> String {
+    format!("{key}-request#{nsp}#")
+}
+
+fn response_channel(key: &str, nsp: &str) -> String {
+    format!("{key}-response#{nsp}#")
+}
+
+fn adapter_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> Error {
+    Error::IncompleteAdapter(Box::new(error))
+}
+
+/// Encodes a broadcast as `[uid, packet, opts]` in msgpack.
+fn encode_broadcast(
+    uid: &str,
+    opts: &BroadcastOptions,
+    event: &Event,
+    payload: &Payload,
+) -> Vec<u8> {
+    let mut data = vec![MsgValue::from(event.as_str())];
+    match payload {
+        Payload::Binary(bytes) => data.push(MsgValue::Binary(bytes.to_vec())),
+        Payload::Text(values) => data.extend(values.iter().map(to_msgpack)),
+        #[allow(deprecated)]
+        Payload::String(string) => data.push(to_msgpack(&Payload::string_to_value(string.clone()))),
+    }
+    let rooms = |rooms: &[String]| {
+        MsgValue::Array(
+            rooms
+                .iter()
+                .map(|room| MsgValue::from(room.as_str()))
+                .collect(),
+        )
+    };
+    let message = MsgValue::Array(vec![
+        MsgValue::from(uid),
+        MsgValue::Map(vec![
+            (MsgValue::from("type"), MsgValue::from(EVENT)),
+            (MsgValue::from("data"), MsgValue::Array(data)),
+            (MsgValue::from("nsp"), MsgValue::from(opts.nsp.as_str())),
+        ]),
+        MsgValue::Map(vec![
+            (MsgValue::from("rooms"), rooms(&opts.rooms)),
+            (MsgValue::from("except"), rooms(&opts.except)),
+            (MsgValue::from("flags"), MsgValue::Map(Vec::new())),
+        ]),
+    ]);
+
+    let mut encoded = Vec::new();
+    // writing to a vector doesn't fail
+    let _ = rmpv::encode::write_value(&mut encoded, &message);
+    encoded
+}
+
+/// Decodes a broadcast into the uid of the node it came from, the sockets it's for
+/// and the event.
+fn decode_broadcast(mut message: &[u8]) -> Option<(String, BroadcastOptions, Event, Payload)> {
+    let message = rmpv::decode::read_value(&mut message).ok()?;
+    let [uid, packet, opts] = message.as_array()?.as_slice() else {
+        return None;
+    };
+    if !matches!(field(packet, "type")?.as_u64()?, EVENT | BINARY_EVENT) {
+        return None;
+    }
+    let nsp = field(packet, "nsp")
+        .and_then(MsgValue::as_str)
+        .unwrap_or("/");
+    let (event, args) = field(packet, "data")?.as_array()?.split_first()?;
+
+    // the crate passes a single binary argument on its own
+    let payload = match args.iter().find_map(MsgValue::as_slice) {
+        Some(binary) => Payload::Binary(Bytes::copy_from_slice(binary)),
+        None => Payload::Text(args.iter().map(to_json).collect()),
+    };
+    let rooms = |name| {
+        field(opts, name)
+            .and_then(MsgValue::as_array)
+            .map(|rooms| {
+                rooms
+                    .iter()
+                    .filter_map(|room| room.as_str().map(str::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let opts = BroadcastOptions {
+        nsp: nsp.to_owned(),
+        rooms: rooms("rooms"),
+        except: rooms("except"),
+    };
+    Some((
+        uid.as_str()?.to_owned(),
+        opts,
+        Event::from(event.as_str()?),
+        payload,
+    ))
+}
+
+/// Decodes a request or a response, which the javascript adapter sends in JSON or
+/// in msgpack.
+fn decode_json(message: &[u8]) -> Option<Value> {
+    if message.first() == Some(&b'{') {
+        serde_json::from_slice(message).ok()
+    } else {
+        let mut message = message;
+        Some(to_json(&rmpv::decode::read_value(&mut message).ok()?))
+    }
+}
+
+fn field<'a>(map: &'a MsgValue, name: &str) -> Option<&'a MsgValue> {
+    map.as_map()?
+        .iter()
+        .find(|(key, _)| key.as_str() == Some(name))
+        .map(|(_, value)| value)
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|value| value.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn to_msgpack(value: &Value) -> MsgValue {
+    match value {
+        Value::Null => MsgValue::Nil,
+        Value::Bool(bool) => MsgValue::Boolean(*bool),
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(unsigned), _) => MsgValue::from(unsigned),
+            (_, Some(signed)) => MsgValue::from(signed),
+            _ => MsgValue::F64(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(string) => MsgValue::from(string.as_str()),
+        Value::Array(values) => MsgValue::Array(values.iter().map(to_msgpack).collect()),
+        Value::Object(map) => MsgValue::Map(
+            map.iter()
+                .map(|(key, value)| (MsgValue::from(key.as_str()), to_msgpack(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_json(value: &MsgValue) -> Value {
+    match value {
+        MsgValue::Boolean(bool) => Value::Bool(*bool),
+        MsgValue::Integer(integer) => match (integer.as_u64(), integer.as_i64()) {
+            (Some(unsigned), _) => Value::from(unsigned),
+            (_, Some(signed)) => Value::from(signed),
+            _ => Value::Null,
+        },
+        MsgValue::F32(float) => Value::from(f64::from(*float)),
+        MsgValue::F64(float) => Value::from(*float),
+        MsgValue::String(string) => string.as_str().map_or(Value::Null, Value::from),
+        // nested binary data has no JSON form, so it's passed as its bytes
+        MsgValue::Binary(bytes) => Value::from(bytes.as_slice()),
+        MsgValue::Array(values) => Value::Array(values.iter().map(to_json).collect()),
+        MsgValue::Map(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = key.as_str().map_or_else(|| key.to_string(), str::to_owned);
+                    (key, to_json(value))
+                })
+                .collect(),
+        ),
+        // `undefined` and dates of the javascript encoder
+        MsgValue::Nil | MsgValue::Ext(..) => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::FutureExt;
+    use tokio::{
+        net::TcpListener,
+        sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    };
+
+    use super::*;
+    use crate::{
+        asynchronous::{Client as SocketClient, ClientBuilder},
+        server::{Server, ServerBuilder},
+    };
+
+    /// The redis for testing runs on port 6379.
+    const REDIS_URL: &str = "redis://127.0.0.1:6379";
+
+    fn fixstr(string: &str) -> Vec<u8> {
+        let mut encoded = vec![0xa0 | string.len() as u8];
+        encoded.extend_from_slice(string.as_bytes());
+        encoded
+    }
+
+    #[test]
+    fn encodes_broadcasts_like_notepack() {
+        let opts = BroadcastOptions {
+            nsp: "/".to_owned(),
+            rooms: vec!["lobby".to_owned()],
+            except: Vec::new(),
+        };
+        let payload = Payload::Text(vec![json!(1)]);
+        let encoded = encode_broadcast("uid1", &opts, &Event::from("chat"), &payload);
+
+        // ["uid1", {type: 2, data: ["chat", 1], nsp: "/"}, {rooms: ["lobby"], except: [], flags: {}}]
+        let expected = [
+            vec![0x93],
+            fixstr("uid1"),
+            vec![0x83],
+            fixstr("type"),
+            vec![0x02],
+            fixstr("data"),
+            vec![0x92],
+            fixstr("chat"),
+            vec![0x01],
+            fixstr("nsp"),
+            fixstr("/"),
+            vec![0x83],
+            fixstr("rooms"),
+            vec![0x91],
+            fixstr("lobby"),
+            fixstr("except"),
+            vec![0x90],
+            fixstr("flags"),
+            vec![0x80],
+        ]
+        .concat();
+        assert_eq!(encoded, expected);
+
+        let decoded = decode_broadcast(&encoded).unwrap();
+        assert_eq!(
+            decoded,
+            ("uid1".to_owned(), opts, Event::from("chat"), payload)
+        );
+    }
+
+    #[test]
+    fn decodes_binary_broadcasts() {
+        let opts = BroadcastOptions {
+            nsp: "/admin".to_owned(),
+            rooms: Vec::new(),
+            except: vec!["a".to_owned()],
+        };
+        let payload = Payload::Binary(Bytes::from_static(&[1, 2, 3]));
+        let encoded = encode_broadcast("uid1", &opts, &Event::from("file"), &payload);
+        let decoded = decode_broadcast(&encoded).unwrap();
+        assert_eq!(
+            decoded,
+            ("uid1".to_owned(), opts, Event::from("file"), payload)
+        );
+
+        assert!(decode_broadcast(b"\x93").is_none());
+        assert_eq!(
+            decode_json(br#"{"type":1,"requestId":"x"}"#),
+            Some(json!({"type": 1, "requestId": "x"}))
+        );
+    }
+
+    async fn node() -> (Server, String) {
+        let url = std::env::var("REDIS_SERVER").unwrap_or_else(|_| REDIS_URL.to_owned());
+        let client = redis::Client::open(url).unwrap();
+        let adapter = RedisAdapter::new(client)
+            .await
+            .unwrap()
+            .key(format!("test-{}", random_id()));
+        let server = ServerBuilder::new()
+            .on("join", |payload, socket| {
+                async move {
+                    if let Payload::Text(values) = payload {
+                        socket.join(values[0].as_str().unwrap_or_default());
+                    }
+                    let _ = socket.ack(json!(true)).await;
+                }
+                .boxed()
+            })
+            .adapter(adapter)
+            .build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+        (server, url)
+    }
+
+    async fn connect(url: &str) -> (SocketClient, UnboundedReceiver<Payload>) {
+        let (received, events) = unbounded_channel();
+        let builder = ClientBuilder::new(url).on("notice", move |payload, _| {
+            let _ = received.send(payload);
+            async {}.boxed()
+        });
+        let client = crate::test::connect(builder).await.unwrap();
+        (client, events)
+    }
+
+    #[tokio::test]
+    async fn broadcasts_across_nodes() {
+        let (first, first_url) = node().await;
+        let (second, second_url) = node().await;
+        let (_first_client, mut first_events) = connect(&first_url).await;
+        let (second_client, mut second_events) = connect(&second_url).await;
+
+        // wait until both nodes subscribed, which listing the rooms does
+        first.all_rooms("/").await.unwrap();
+        second.all_rooms("/").await.unwrap();
+
+        let (joined, mut acks) = unbounded_channel();
+        second_client
+            .emit_with_ack("join", "lobby", Duration::from_secs(5), move |_, _| {
+                let _ = joined.send(());
+                async {}.boxed()
+            })
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(5), acks.recv()).await.unwrap();
+
+        let rooms = first.all_rooms("/").await.unwrap();
+        assert!(rooms.contains(&"lobby".to_owned()));
+
+        // the first node only knows the lobby through the second one
+        first.to("lobby").emit("notice", json!(1)).await.unwrap();
+        let notice = timeout(Duration::from_secs(5), second_events.recv())
+            .await
+            .unwrap();
+        assert_eq!(notice, Some(Payload::Text(vec![json!(1)])));
+
+        // remote sockets join rooms
+        first.of("/").sockets_join("hall").await.unwrap();
+        first.to("hall").emit("notice", json!(2)).await.unwrap();
+        for events in [&mut first_events, &mut second_events] {
+            let notice = timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap();
+            assert_eq!(notice, Some(Payload::Text(vec![json!(2)])));
+        }
+
+        // local broadcasts stay on the node
+        second
+            .of("/")
+            .local()
+            .emit("notice", json!(3))
+            .await
+            .unwrap();
+        first.of("/").emit("notice", json!(4)).await.unwrap();
+        let notice = timeout(Duration::from_secs(5), first_events.recv())
+            .await
+            .unwrap();
+        assert_eq!(notice, Some(Payload::Text(vec![json!(4)])));
+    }
+}
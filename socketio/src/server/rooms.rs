@@ -0,0 +1,576 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::{future::Either, stream::FuturesUnordered, Stream};
+
+use crate::{error::Result, packet::Packet, Event, Payload};
+
+use super::{
+    adapter::{Adapter, BroadcastOptions},
+    engine::{encode, next_ack_id},
+    socket::ServerSocket,
+};
+
+/// The sockets of a namespace, with the rooms they joined.
+#[derive(Default)]
+struct Members {
+    sockets: HashMap<Arc<str>, ServerSocket>,
+    rooms: HashMap<String, HashSet<Arc<str>>>,
+    /// The rooms of each socket, to leave them all at once.
+    joined: HashMap<Arc<str>, HashSet<String>>,
+}
+
+/// The rooms of all namespaces of a server, shared by its connections, and the
+/// adapter carrying broadcasts to the other nodes of the server.
+pub(crate) struct Rooms {
+    namespaces: Mutex<HashMap<String, Members>>,
+    pub(crate) adapter: Option<Arc<dyn Adapter>>,
+}
+
+impl Rooms {
+    pub(crate) fn new(adapter: Option<Arc<dyn Adapter>>) -> Self {
+        Rooms {
+            namespaces: Mutex::default(),
+            adapter,
+        }
+    }
+
+    /// Adds a socket that connected to its namespace, in the room named by its id.
+    pub(crate) fn add(&self, socket: &ServerSocket) {
+        let Ok(mut namespaces) = self.namespaces.lock() else {
+            return;
+        };
+        let members = namespaces.entry(socket.nsp().to_owned()).or_default();
+        members
+            .sockets
+            .insert(socket.id.clone(), socket.with_ack(None));
+        members.join(&socket.id, socket.id());
+    }
+
+    /// Removes a socket that left its namespace from all of its rooms.
+    pub(crate) fn remove(&self, socket: &ServerSocket) {
+        let Ok(mut namespaces) = self.namespaces.lock() else {
+            return;
+        };
+        let Some(members) = namespaces.get_mut(socket.nsp()) else {
+            return;
+        };
+        members.sockets.remove(&socket.id);
+        for room in members.joined.remove(&socket.id).unwrap_or_default() {
+            members.forget(&socket.id, &room);
+        }
+        if members.sockets.is_empty() {
+            namespaces.remove(socket.nsp());
+        }
+    }
+
+    pub(crate) fn join(&self, socket: &ServerSocket, room: &str) {
+        let Ok(mut namespaces) = self.namespaces.lock() else {
+            return;
+        };
+        // a socket that already left its namespace can't join rooms anymore
+        if let Some(members) = namespaces.get_mut(socket.nsp()) {
+            if members.sockets.contains_key(&socket.id) {
+                members.join(&socket.id, room);
+            }
+        }
+    }
+
+    pub(crate) fn leave(&self, socket: &ServerSocket, room: &str) {
+        let Ok(mut namespaces) = self.namespaces.lock() else {
+            return;
+        };
+        if let Some(members) = namespaces.get_mut(socket.nsp()) {
+            members.leave(&socket.id, room);
+        }
+    }
+
+    /// The rooms a socket joined, including the one named by its id.
+    pub(crate) fn rooms_of(&self, socket: &ServerSocket) -> Vec<String> {
+        self.namespaces
+            .lock()
+            .ok()
+            .and_then(|namespaces| {
+                let joined = namespaces.get(socket.nsp())?.joined.get(&socket.id)?;
+                Some(joined.iter().cloned().collect())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The rooms of the namespace with at least one socket in them.
+    pub(crate) fn rooms(&self, nsp: &str) -> Vec<String> {
+        self.namespaces
+            .lock()
+            .ok()
+            .and_then(|namespaces| Some(namespaces.get(nsp)?.rooms.keys().cloned().collect()))
+            .unwrap_or_default()
+    }
+
+    /// The selected sockets of this node.
+    pub(crate) fn select(&self, opts: &BroadcastOptions) -> Vec<ServerSocket> {
+        let Ok(namespaces) = self.namespaces.lock() else {
+            return Vec::new();
+        };
+        let Some(members) = namespaces.get(&opts.nsp) else {
+            return Vec::new();
+        };
+        members
+            .select(opts)
+            .into_iter()
+            .filter_map(|id| members.sockets.get(&id).cloned())
+            .collect()
+    }
+
+    /// Emits an event to the selected sockets of this node. The packet is encoded
+    /// once for all of them. Sockets whose connection closed in the meantime are
+    /// skipped.
+    pub(crate) fn emit(
+        &self,
+        opts: &BroadcastOptions,
+        event: Event,
+        payload: Payload,
+    ) -> Result<()> {
+        let packet = Packet::new_from_payload(payload, event, &opts.nsp, None)?;
+        let encoded = encode(&packet);
+        for socket in self.select(opts) {
+            let _ = socket.connection.send_encoded(&encoded);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) {
+        let Ok(mut namespaces) = self.namespaces.lock() else {
+            return;
+        };
+        if let Some(members) = namespaces.get_mut(&opts.nsp) {
+            for id in members.select(opts) {
+                for room in rooms {
+                    members.join(&id, room);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn del_sockets(&self, opts: &BroadcastOptions, rooms: &[String]) {
+        let Ok(mut namespaces) = self.namespaces.lock() else {
+            return;
+        };
+        if let Some(members) = namespaces.get_mut(&opts.nsp) {
+            for id in members.select(opts) {
+                for room in rooms {
+                    members.leave(&id, room);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn disconnect_sockets(&self, opts: &BroadcastOptions) {
+        for socket in self.select(opts) {
+            let _ = socket.close();
+        }
+    }
+}
+
+impl Members {
+    /// The ids of the selected sockets.
+    fn select(&self, opts: &BroadcastOptions) -> Vec<Arc<str>> {
+        let excepted: HashSet<&Arc<str>> = opts
+            .except
+            .iter()
+            .filter_map(|room| self.rooms.get(room))
+            .flatten()
+            .collect();
+        let selected: HashSet<&Arc<str>> = if opts.rooms.is_empty() {
+            self.sockets.keys().collect()
+        } else {
+            opts.rooms
+                .iter()
+                .filter_map(|room| self.rooms.get(room))
+                .flatten()
+                .collect()
+        };
+        selected
+            .into_iter()
+            .filter(|id| !excepted.contains(id))
+            .cloned()
+            .collect()
+    }
+
+    fn join(&mut self, id: &Arc<str>, room: &str) {
+        self.rooms
+            .entry(room.to_owned())
+            .or_default()
+            .insert(id.clone());
+        self.joined
+            .entry(id.clone())
+            .or_default()
+            .insert(room.to_owned());
+    }
+
+    fn leave(&mut self, id: &Arc<str>, room: &str) {
+        if let Some(joined) = self.joined.get_mut(id) {
+            joined.remove(room);
+        }
+        self.forget(id, room);
+    }
+
+    /// Takes the socket out of the room, dropping the room once it's empty.
+    fn forget(&mut self, id: &Arc<str>, room: &str) {
+        if let Some(sockets) = self.rooms.get_mut(room) {
+            sockets.remove(id);
+            if sockets.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+}
+
+/// Emits events to or changes the rooms of a selection of the sockets of a
+/// namespace, created with [`Server::to`](super::Server::to),
+/// [`Server::of`](super::Server::of) or [`ServerSocket::to`]. Every socket is in the
+/// room named by its id, so `except(socket.id())` leaves out a single socket.
+///
+/// With an [`Adapter`], the selection includes the sockets connected to the other
+/// nodes of the server unless it's narrowed to this node with
+/// [`Broadcast::local`].
+#[derive(Clone)]
+pub struct Broadcast {
+    rooms: Arc<Rooms>,
+    opts: BroadcastOptions,
+    local: bool,
+}
+
+impl Broadcast {
+    pub(crate) fn new(rooms: Arc<Rooms>, nsp: String) -> Self {
+        Broadcast {
+            rooms,
+            opts: BroadcastOptions::new(nsp),
+            local: false,
+        }
+    }
+
+    /// Adds a room to send to. Sockets in several of the rooms get an event once.
+    pub fn to<T: Into<String>>(mut self, room: T) -> Self {
+        self.opts.rooms.push(room.into());
+        self
+    }
+
+    /// Leaves out the sockets in the room.
+    pub fn except<T: Into<String>>(mut self, room: T) -> Self {
+        self.opts.except.push(room.into());
+        self
+    }
+
+    /// Leaves out the sockets connected to the other nodes of the server.
+    pub fn local(mut self) -> Self {
+        self.local = true;
+        self
+    }
+
+    /// The selected sockets connected to this node.
+    pub fn sockets(&self) -> Vec<ServerSocket> {
+        self.rooms.select(&self.opts)
+    }
+
+    /// Sends an event to the selected sockets. The packet is encoded once for all of
+    /// them. Sockets whose connection closed in the meantime are skipped.
+    pub async fn emit<E, D>(&self, event: E, data: D) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let (event, payload) = (event.into(), data.into());
+        match self.adapter() {
+            Some(adapter) => {
+                self.rooms
+                    .emit(&self.opts, event.clone(), payload.clone())?;
+                adapter.broadcast(&self.opts, &event, &payload).await
+            }
+            None => self.rooms.emit(&self.opts, event, payload),
+        }
+    }
+
+    /// Sends an event to the selected sockets connected to this node, asking each of
+    /// them for an ack. The stream yields the answer of every socket as it arrives,
+    /// or [`Error::AckTimeout`](crate::Error::AckTimeout) for the sockets that
+    /// didn't answer in time, and ends once all sockets answered or timed out.
+    /// # Example
+    /// ```rust
+    /// use futures_util::StreamExt;
+    /// use rust_socketio::server::Server;
+    /// use std::time::Duration;
+    ///
+    /// async fn vote(server: Server) -> Result<(), rust_socketio::Error> {
+    ///     let mut answers = server
+    ///         .to("jury")
+    ///         .emit_with_ack("vote", "guilty?", Duration::from_secs(30))
+    ///         .await?;
+    ///     while let Some((socket, answer)) = answers.next().await {
+    ///         println!("{} answered {answer:?}", socket.id());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn emit_with_ack<E, D>(
+        &self,
+        event: E,
+        data: D,
+        timeout: Duration,
+    ) -> Result<impl Stream<Item = (ServerSocket, Result<Payload>)> + Send + 'static>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        // the sockets are asked with the same id, so the packet is encoded once
+        let id = next_ack_id();
+        let packet = Packet::new_from_payload(data.into(), event.into(), &self.opts.nsp, Some(id))?;
+        let encoded = encode(&packet);
+
+        let answers = FuturesUnordered::new();
+        for socket in self.rooms.select(&self.opts) {
+            let answered = socket.connection.expect_ack(&self.opts.nsp, id);
+            if let Err(e) = socket.connection.send_encoded(&encoded) {
+                socket.connection.forget_ack(id);
+                answers.push(Either::Left(async move { (socket, Err(e)) }));
+                continue;
+            }
+            answers.push(Either::Right(async move {
+                let answer = socket.wait_for_ack(id, answered, timeout).await;
+                (socket, answer)
+            }));
+        }
+        Ok(answers)
+    }
+
+    /// Adds the selected sockets to the room.
+    pub async fn sockets_join<T: Into<String>>(&self, room: T) -> Result<()> {
+        let rooms = [room.into()];
+        self.rooms.add_sockets(&self.opts, &rooms);
+        match self.adapter() {
+            Some(adapter) => adapter.add_sockets(&self.opts, &rooms).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the selected sockets out of the room.
+    pub async fn sockets_leave<T: Into<String>>(&self, room: T) -> Result<()> {
+        let rooms = [room.into()];
+        self.rooms.del_sockets(&self.opts, &rooms);
+        match self.adapter() {
+            Some(adapter) => adapter.del_sockets(&self.opts, &rooms).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Disconnects the selected sockets from the namespace.
+    pub async fn disconnect_sockets(&self) -> Result<()> {
+        self.rooms.disconnect_sockets(&self.opts);
+        match self.adapter() {
+            Some(adapter) => adapter.disconnect_sockets(&self.opts).await,
+            None => Ok(()),
+        }
+    }
+
+    fn adapter(&self) -> Option<&Arc<dyn Adapter>> {
+        self.rooms.adapter.as_ref().filter(|_| !self.local)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::{FutureExt, StreamExt};
+    use serde_json::json;
+    use tokio::{
+        net::TcpListener,
+        sync::mpsc::{unbounded_channel, UnboundedReceiver},
+        time::timeout,
+    };
+
+    use crate::{
+        asynchronous::{Client, ClientBuilder},
+        server::ServerBuilder,
+        Error, Event, Payload,
+    };
+
+    async fn connect(url: &str) -> (Client, UnboundedReceiver<Event>) {
+        let (received, events) = unbounded_channel();
+        let builder = ClientBuilder::new(url).on_any(move |event, _, _| {
+            let _ = received.send(event);
+            async {}.boxed()
+        });
+        let client = crate::test::connect(builder).await.unwrap();
+        (client, events)
+    }
+
+    async fn join(client: &Client, room: &str) {
+        let (joined, mut acks) = unbounded_channel();
+        client
+            .emit_with_ack("join", room, Duration::from_secs(5), move |_, _| {
+                let _ = joined.send(());
+                async {}.boxed()
+            })
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(5), acks.recv()).await.unwrap();
+    }
+
+    async fn next(events: &mut UnboundedReceiver<Event>) -> Event {
+        timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn broadcasts_to_rooms() {
+        let server = ServerBuilder::new()
+            .on("join", |payload, socket| {
+                async move {
+                    if let Payload::Text(values) = payload {
+                        socket.join(values[0].as_str().unwrap_or_default());
+                    }
+                    let _ = socket.ack(json!(socket.rooms().len())).await;
+                }
+                .boxed()
+            })
+            .on("shout", |payload, socket| {
+                async move {
+                    let _ = socket.to("lobby").emit("shout", payload).await;
+                }
+                .boxed()
+            })
+            .build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let (a, mut a_events) = connect(&url).await;
+        let (b, mut b_events) = connect(&url).await;
+        let (c, mut c_events) = connect(&url).await;
+        join(&a, "lobby").await;
+        join(&b, "lobby").await;
+        join(&b, "hall").await;
+        assert_eq!(server.to("lobby").sockets().len(), 2);
+        let b_socket = server.socket("/", &b.sid().unwrap()).unwrap();
+        let mut rooms = b_socket.rooms();
+        rooms.sort();
+        let mut expected = vec![
+            "hall".to_owned(),
+            "lobby".to_owned(),
+            b_socket.id().to_owned(),
+        ];
+        expected.sort();
+        assert_eq!(rooms, expected);
+
+        // the sender is left out
+        a.emit("shout", json!("hi")).await.unwrap();
+        assert_eq!(next(&mut b_events).await, Event::from("shout"));
+
+        // a socket in several rooms gets an event once
+        server
+            .to("lobby")
+            .to("hall")
+            .except(b_socket.id())
+            .emit("notice", json!(1))
+            .await
+            .unwrap();
+        server.to("hall").emit("notice", json!(2)).await.unwrap();
+        assert_eq!(next(&mut a_events).await, Event::from("notice"));
+        assert_eq!(next(&mut b_events).await, Event::from("notice"));
+
+        server.of("/").emit("all", json!(3)).await.unwrap();
+        for events in [&mut a_events, &mut b_events, &mut c_events] {
+            assert_eq!(next(events).await, Event::from("all"));
+        }
+
+        // disconnecting leaves all rooms
+        b.disconnect().await.unwrap();
+        timeout(Duration::from_secs(5), async {
+            while server.to("hall").sockets().len() == 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(server.to("lobby").sockets().len(), 1);
+        a.disconnect().await.unwrap();
+        c.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn collects_acks() {
+        let server = ServerBuilder::new().build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let answering = |answer: bool| {
+            let builder = ClientBuilder::new(url.clone()).on("ask", move |payload, client| {
+                async move {
+                    if answer {
+                        let _ = client.ack(payload).await;
+                    }
+                }
+                .boxed()
+            });
+            crate::test::connect(builder)
+        };
+        let first = answering(true).await.unwrap();
+        let second = answering(true).await.unwrap();
+        let silent = answering(false).await.unwrap();
+        timeout(Duration::from_secs(5), async {
+            while server.of("/").sockets().len() < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let socket = server.socket("/", &first.sid().unwrap()).unwrap();
+        let answer = socket
+            .emit_with_ack("ask", json!(1), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(answer, Payload::Text(vec![json!(1)]));
+
+        let answers: Vec<_> = server
+            .of("/")
+            .emit_with_ack("ask", json!(2), Duration::from_millis(500))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(answers.len(), 3);
+        for (socket, answer) in answers {
+            if Some(socket.id().to_owned()) == silent.sid() {
+                assert!(matches!(answer, Err(Error::AckTimeout())));
+            } else {
+                assert_eq!(answer.unwrap(), Payload::Text(vec![json!(2)]));
+            }
+        }
+
+        // disconnecting ends the wait
+        let socket = server.socket("/", &silent.sid().unwrap()).unwrap();
+        let asked = tokio::spawn(async move {
+            socket
+                .emit_with_ack("ask", json!(3), Duration::from_secs(30))
+                .await
+        });
+        silent.disconnect().await.unwrap();
+        let answer = timeout(Duration::from_secs(5), asked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(answer, Err(Error::StoppedEngineIoSocket)));
+
+        first.disconnect().await.unwrap();
+        second.disconnect().await.unwrap();
+    }
+}
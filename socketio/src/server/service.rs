@@ -0,0 +1,934 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    pin::pin,
+    sync::{Arc, Mutex},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::Body,
+    header::{self, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+    HeaderMap, Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use log::trace;
+use rust_engineio::{Packet as EnginePacket, PacketId as EnginePacketId};
+use serde_json::json;
+use tokio::{net::TcpListener, time::timeout};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::derive_accept_key,
+        protocol::{Role, WebSocketConfig},
+        Message,
+    },
+    WebSocketStream,
+};
+use url::form_urlencoded;
+
+use crate::error::Result;
+
+#[cfg(feature = "tower")]
+use super::layer::{SocketIoLayer, SocketIoService};
+use super::{
+    adapter::{Adapter, Node},
+    builder::Config,
+    engine::Connection,
+    rooms::{Broadcast, Rooms},
+    socket::ServerSocket,
+};
+
+/// Separates the packets of a polling payload.
+const SEPARATOR: u8 = b'\x1e';
+
+/// A socket.io server, built with a [`ServerBuilder`](super::ServerBuilder). Clones
+/// share the connections.
+#[derive(Clone)]
+pub struct Server {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    config: Arc<Config>,
+    connections: Mutex<HashMap<String, Arc<Connection>>>,
+    rooms: Arc<Rooms>,
+}
+
+/// How a websocket starts: upgrading a polling connection, or opening a new
+/// connection with the headers and the query of its request.
+enum Opening {
+    Upgrade(Arc<Connection>),
+    New(HeaderMap, HashMap<String, String>),
+}
+
+/// Why a request was rejected, with the codes of the reference implementation.
+#[derive(Copy, Clone, Debug)]
+enum Rejection {
+    UnknownTransport = 0,
+    UnknownSid = 1,
+    BadRequest = 3,
+    UnsupportedProtocolVersion = 5,
+}
+
+impl Server {
+    pub(crate) fn new(config: Config, adapter: Option<Arc<dyn Adapter>>) -> Self {
+        let rooms = Arc::new(Rooms::new(adapter.clone()));
+        if let Some(adapter) = adapter {
+            adapter.attach(Node::new(
+                &rooms,
+                config.namespaces.keys().cloned().collect(),
+            ));
+        }
+        Server {
+            shared: Arc::new(Shared {
+                config: Arc::new(config),
+                connections: Mutex::default(),
+                rooms,
+            }),
+        }
+    }
+
+    /// Accepts connections from the listener until accepting fails.
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, address) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                });
+                let connection = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .with_upgrades();
+                if let Err(e) = connection.await {
+                    trace!("Connection from {address} failed: {e}");
+                }
+            });
+        }
+    }
+
+    /// Answers an engine.io request, e.g. from the service of an existing `hyper`
+    /// server. Requests for another path are answered with `404 Not Found`.
+    /// Websockets are only accepted if the connection the request came over can be
+    /// upgraded.
+    pub async fn handle<B>(&self, request: Request<B>) -> Response<Full<Bytes>>
+    where
+        B: Body + Send + 'static,
+        B::Data: Send,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        if !self.serves(request.uri().path()) {
+            return response(StatusCode::NOT_FOUND, "");
+        }
+
+        let query: HashMap<String, String> = request
+            .uri()
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        if query.get("EIO").map(String::as_str) != Some("4") {
+            return reject(Rejection::UnsupportedProtocolVersion);
+        }
+        let sid = query.get("sid");
+
+        match query.get("transport").map(String::as_str) {
+            Some("polling") => match (request.method(), sid) {
+                (&Method::GET, None) => {
+                    let connection = self.open(request.headers().clone(), query);
+                    let handshake = connection.handshake(&["websocket"]);
+                    response(StatusCode::OK, Bytes::from(handshake))
+                }
+                (&Method::GET, Some(sid)) => match self.connection(sid) {
+                    Some(connection) if !connection.is_upgraded() => poll(&connection).await,
+                    Some(_) => reject(Rejection::BadRequest),
+                    None => reject(Rejection::UnknownSid),
+                },
+                (&Method::POST, Some(sid)) => match self.connection(sid) {
+                    Some(connection) => self.receive(&connection, request).await,
+                    None => reject(Rejection::UnknownSid),
+                },
+                _ => reject(Rejection::BadRequest),
+            },
+            Some("websocket") => {
+                let opening = match sid {
+                    Some(sid) => match self.connection(sid) {
+                        Some(connection) if !connection.is_upgraded() => {
+                            Opening::Upgrade(connection)
+                        }
+                        Some(_) => return reject(Rejection::BadRequest),
+                        None => return reject(Rejection::UnknownSid),
+                    },
+                    None => Opening::New(request.headers().clone(), query),
+                };
+                self.accept_websocket(request, opening)
+            }
+            _ => reject(Rejection::UnknownTransport),
+        }
+    }
+
+    /// A `tower` layer that answers the requests to the path of the server and
+    /// passes all others to the service it wraps, e.g. an `axum` router.
+    #[cfg(feature = "tower")]
+    pub fn layer(&self) -> SocketIoLayer {
+        SocketIoLayer::new(self.clone())
+    }
+
+    /// A `tower` service that answers the requests to the path of the server, e.g.
+    /// for `axum::Router::route_service`.
+    #[cfg(feature = "tower")]
+    pub fn service(&self) -> SocketIoService {
+        SocketIoService::new(self.clone())
+    }
+
+    /// The socket of the client with the given id connected to the namespace, if any.
+    pub fn socket(&self, nsp: &str, id: &str) -> Option<ServerSocket> {
+        self.sockets(nsp)
+            .into_iter()
+            .find(|socket| socket.id() == id)
+    }
+
+    /// The sockets connected to the namespace.
+    pub fn sockets(&self, nsp: &str) -> Vec<ServerSocket> {
+        self.connections()
+            .iter()
+            .filter_map(|connection| connection.socket(nsp))
+            .collect()
+    }
+
+    /// Selects the sockets of the default namespace in the room, to emit to them.
+    /// # Example
+    /// ```rust
+    /// # async fn notify(server: rust_socketio::server::Server) -> Result<(), rust_socketio::Error> {
+    /// server.to("lobby").to("hall").emit("notice", "closing soon").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to<T: Into<String>>(&self, room: T) -> Broadcast {
+        self.of("/").to(room)
+    }
+
+    /// Selects all sockets of the namespace, to emit to them or narrow them down to
+    /// rooms.
+    pub fn of<T: Into<String>>(&self, nsp: T) -> Broadcast {
+        Broadcast::new(self.shared.rooms.clone(), nsp.into())
+    }
+
+    /// The rooms of the namespace with at least one socket in them, on all nodes of
+    /// the server.
+    pub async fn all_rooms(&self, nsp: &str) -> Result<Vec<String>> {
+        let mut rooms = self.shared.rooms.rooms(nsp);
+        if let Some(adapter) = &self.shared.rooms.adapter {
+            for room in adapter.remote_rooms(nsp).await? {
+                if !rooms.contains(&room) {
+                    rooms.push(room);
+                }
+            }
+        }
+        Ok(rooms)
+    }
+
+    /// The sockets of all namespaces.
+    pub fn all_sockets(&self) -> Vec<ServerSocket> {
+        self.connections()
+            .iter()
+            .flat_map(|connection| connection.sockets())
+            .collect()
+    }
+
+    /// Whether requests to the path are answered by the server.
+    pub(crate) fn serves(&self, path: &str) -> bool {
+        path == self.shared.config.path || format!("{path}/") == self.shared.config.path
+    }
+
+    fn connections(&self) -> Vec<Arc<Connection>> {
+        match self.shared.connections.lock() {
+            Ok(connections) => connections.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn connection(&self, sid: &str) -> Option<Arc<Connection>> {
+        self.shared.connections.lock().ok()?.get(sid).cloned()
+    }
+
+    /// Registers a new connection and starts its heartbeat.
+    fn open(&self, headers: HeaderMap, query: HashMap<String, String>) -> Arc<Connection> {
+        let connection = Connection::new(
+            self.shared.config.clone(),
+            self.shared.rooms.clone(),
+            headers,
+            query,
+        );
+        if let Ok(mut connections) = self.shared.connections.lock() {
+            connections.insert(connection.sid.clone(), connection.clone());
+        }
+        trace!("Opened connection {}", connection.sid);
+
+        let server = self.clone();
+        let heartbeat = connection.clone();
+        tokio::spawn(async move {
+            let config = &server.shared.config;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(config.ping_interval) => (),
+                    _ = heartbeat.closed() => {
+                        // forgets a connection that closed itself, e.g. over a full queue
+                        server.close(&heartbeat);
+                        break;
+                    }
+                }
+                // register for the pong before it can arrive
+                let mut pong = pin!(heartbeat.pong.notified());
+                pong.as_mut().enable();
+                let ping = EnginePacket::new(EnginePacketId::Ping, Bytes::new());
+                if heartbeat.push(ping).is_err()
+                    || timeout(config.ping_timeout, pong).await.is_err()
+                {
+                    trace!("Connection {} timed out", heartbeat.sid);
+                    server.close(&heartbeat);
+                    break;
+                }
+            }
+        });
+        connection
+    }
+
+    /// Closes a connection and forgets it.
+    fn close(&self, connection: &Arc<Connection>) {
+        if let Ok(mut connections) = self.shared.connections.lock() {
+            connections.remove(&connection.sid);
+        }
+        if connection.close() {
+            trace!("Closed connection {}", connection.sid);
+        }
+    }
+
+    /// Handles the packets of a polling request.
+    async fn receive<B>(
+        &self,
+        connection: &Arc<Connection>,
+        request: Request<B>,
+    ) -> Response<Full<Bytes>>
+    where
+        B: Body,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let body = Limited::new(request.into_body(), self.shared.config.max_payload);
+        let Ok(body) = body.collect().await.map(|body| body.to_bytes()) else {
+            self.close(connection);
+            return response(StatusCode::PAYLOAD_TOO_LARGE, "");
+        };
+
+        for data in body.split(|&byte| byte == SEPARATOR) {
+            match EnginePacket::try_from(body.slice_ref(data)) {
+                Ok(packet) => {
+                    if !connection.handle(packet) {
+                        self.close(connection);
+                        break;
+                    }
+                }
+                Err(_) => {
+                    self.close(connection);
+                    return reject(Rejection::BadRequest);
+                }
+            }
+        }
+        response(StatusCode::OK, "ok")
+    }
+
+    /// Answers a websocket request with the switch of protocols and serves the
+    /// websocket once the connection was upgraded.
+    fn accept_websocket<B>(
+        &self,
+        mut request: Request<B>,
+        opening: Opening,
+    ) -> Response<Full<Bytes>> {
+        let is_upgrade = request
+            .headers()
+            .get(header::UPGRADE)
+            .and_then(|upgrade| upgrade.to_str().ok())
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+        let Some(key) = request.headers().get(header::SEC_WEBSOCKET_KEY) else {
+            return reject(Rejection::BadRequest);
+        };
+        if !is_upgrade {
+            return reject(Rejection::BadRequest);
+        }
+        let accept = derive_accept_key(key.as_bytes());
+
+        let server = self.clone();
+        let upgrade = hyper::upgrade::on(&mut request);
+        tokio::spawn(async move {
+            let upgraded = match upgrade.await {
+                Ok(upgraded) => upgraded,
+                Err(e) => {
+                    trace!("Websocket upgrade failed: {e}");
+                    return;
+                }
+            };
+            // a websocket message is held in memory like a polling request, so it's
+            // limited the same
+            let config = WebSocketConfig {
+                max_message_size: Some(server.shared.config.max_payload),
+                max_frame_size: Some(server.shared.config.max_payload),
+                ..Default::default()
+            };
+            let websocket = WebSocketStream::from_raw_socket(
+                TokioIo::new(upgraded),
+                Role::Server,
+                Some(config),
+            )
+            .await;
+            server.serve_websocket(websocket, opening).await;
+        });
+
+        let mut response = response(StatusCode::SWITCHING_PROTOCOLS, "");
+        let headers = response.headers_mut();
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        if let Ok(accept) = HeaderValue::from_str(&accept) {
+            headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+        }
+        response
+    }
+
+    async fn serve_websocket<S>(&self, websocket: WebSocketStream<S>, opening: Opening)
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let (mut sink, mut stream) = websocket.split();
+
+        let connection = match opening {
+            // a polling connection upgrades after probing the websocket
+            Opening::Upgrade(connection) => {
+                loop {
+                    match stream.next().await {
+                        Some(Ok(Message::Text(text))) if text == "2probe" => {
+                            if sink.send(Message::Text("3probe".to_owned())).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(Message::Text(text))) if text == "5" => break,
+                        Some(Ok(Message::Ping(_) | Message::Pong(_))) => (),
+                        _ => return,
+                    }
+                }
+                connection.upgrade();
+                trace!("Upgraded connection {}", connection.sid);
+                connection
+            }
+            Opening::New(headers, query) => {
+                let connection = self.open(headers, query);
+                let handshake = Bytes::from(connection.handshake(&[]));
+                let handshake = String::from_utf8_lossy(&handshake).into_owned();
+                if sink.send(Message::Text(handshake)).await.is_err() {
+                    self.close(&connection);
+                    return;
+                }
+                connection
+            }
+        };
+
+        let write = async {
+            while let Some(packet) = connection.next_queued().await {
+                let message = match packet.packet_id {
+                    EnginePacketId::MessageBinary => Message::Binary(packet.data.to_vec()),
+                    _ => Message::Text(String::from_utf8_lossy(&Bytes::from(packet)).into_owned()),
+                };
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        };
+        let read = async {
+            while let Some(Ok(message)) = stream.next().await {
+                let packet = match message {
+                    Message::Text(text) => match EnginePacket::try_from(Bytes::from(text)) {
+                        Ok(packet) => packet,
+                        Err(_) => break,
+                    },
+                    Message::Binary(data) => EnginePacket::new(EnginePacketId::MessageBinary, data),
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                if !connection.handle(packet) {
+                    break;
+                }
+            }
+            self.close(&connection);
+        };
+        tokio::join!(write, read);
+    }
+}
+
+/// Answers a poll with the queued packets.
+async fn poll(connection: &Connection) -> Response<Full<Bytes>> {
+    let mut payload = BytesMut::new();
+    for packet in connection.poll().await {
+        if !payload.is_empty() {
+            payload.put_u8(SEPARATOR);
+        }
+        payload.put(Bytes::from(packet));
+    }
+    response(StatusCode::OK, payload.freeze())
+}
+
+fn reject(rejection: Rejection) -> Response<Full<Bytes>> {
+    trace!("Rejecting a request: {rejection:?}");
+    let message = match rejection {
+        Rejection::UnknownTransport => "Transport unknown",
+        Rejection::UnknownSid => "Session ID unknown",
+        Rejection::BadRequest => "Bad request",
+        Rejection::UnsupportedProtocolVersion => "Unsupported protocol version",
+    };
+    let body = json!({"code": rejection as u8, "message": message});
+    let mut response = response(StatusCode::BAD_REQUEST, body.to_string());
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn response<T: Into<Bytes>>(status: StatusCode, body: T) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=UTF-8"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures_util::FutureExt;
+    use serde_json::Value;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    use super::*;
+    use crate::{
+        asynchronous::{Client, ClientBuilder},
+        server::{ConnectError, ServerBuilder},
+        Payload, TransportType,
+    };
+
+    /// Serves an echoing server on a free port, returning it with its url.
+    async fn echo_server() -> (Server, String) {
+        let server = ServerBuilder::new()
+            .on("echo", |payload, socket| {
+                async move {
+                    let _ = socket.emit("echo", payload).await;
+                }
+                .boxed()
+            })
+            .on("status", |_, socket| {
+                async move {
+                    let _ = socket.ack(json!("ok")).await;
+                }
+                .boxed()
+            })
+            .build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+        (server, url)
+    }
+
+    async fn connect(
+        url: &str,
+        transport_type: TransportType,
+    ) -> Result<(Client, UnboundedReceiver<Payload>)> {
+        let (echoed, echoes) = unbounded_channel();
+        let client = ClientBuilder::new(url)
+            .transport_type(transport_type)
+            .on("echo", move |payload, _| {
+                let _ = echoed.send(payload);
+                async {}.boxed()
+            })
+            .connect()
+            .await?;
+        Ok((client, echoes))
+    }
+
+    async fn assert_echoes(client: &Client, echoes: &mut UnboundedReceiver<Payload>) -> Result<()> {
+        client.emit("echo", json!({"token": 123})).await?;
+        let echo = timeout(Duration::from_secs(5), echoes.recv())
+            .await
+            .unwrap();
+        assert!(
+            matches!(echo, Some(Payload::Text(values)) if values == vec![json!({"token": 123})])
+        );
+
+        client.emit("echo", Bytes::from_static(&[1, 2, 3])).await?;
+        let echo = timeout(Duration::from_secs(5), echoes.recv())
+            .await
+            .unwrap();
+        assert!(
+            matches!(echo, Some(Payload::Binary(data)) if data == Bytes::from_static(&[1, 2, 3]))
+        );
+
+        let (acked, mut acks) = unbounded_channel();
+        client
+            .emit_with_ack(
+                "status",
+                json!(1),
+                Duration::from_secs(5),
+                move |payload, _| {
+                    let _ = acked.send(payload);
+                    async {}.boxed()
+                },
+            )
+            .await?;
+        let ack = timeout(Duration::from_secs(5), acks.recv()).await.unwrap();
+        // the client passes the arguments of an ack as one array
+        assert!(matches!(ack, Some(Payload::Text(values)) if values == vec![json!(["ok"])]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serves_websockets() -> Result<()> {
+        let (server, url) = echo_server().await;
+        let (client, mut echoes) = connect(&url, TransportType::Websocket).await?;
+        assert_echoes(&client, &mut echoes).await?;
+
+        let sockets = server.sockets("/");
+        assert_eq!(sockets.len(), 1);
+        assert_eq!(Some(sockets[0].id().to_owned()), client.sid());
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    /// Opens a websocket to the server by hand and connects it to the default
+    /// namespace, for sending what the client wouldn't.
+    async fn open_websocket(
+        address: std::net::SocketAddr,
+    ) -> WebSocketStream<tokio::net::TcpStream> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                b"GET /socket.io/?EIO=4&transport=websocket HTTP/1.1\r\n\
+                Host: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+                Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            response.push(stream.read_u8().await.unwrap());
+        }
+        assert!(response.starts_with(b"HTTP/1.1 101"));
+        let mut websocket = WebSocketStream::from_raw_socket(stream, Role::Client, None).await;
+
+        let handshake = timeout(Duration::from_secs(5), websocket.next()).await;
+        assert!(matches!(handshake, Ok(Some(Ok(Message::Text(text)))) if text.starts_with('0')));
+        websocket
+            .send(Message::Text("40".to_owned()))
+            .await
+            .unwrap();
+        let connected = timeout(Duration::from_secs(5), websocket.next()).await;
+        assert!(matches!(connected, Ok(Some(Ok(Message::Text(text)))) if text.starts_with("40")));
+        websocket
+    }
+
+    /// Waits for the server to close the websocket.
+    async fn assert_closed(websocket: &mut WebSocketStream<tokio::net::TcpStream>) {
+        let closed = timeout(Duration::from_secs(5), async {
+            while let Some(Ok(message)) = websocket.next().await {
+                if message.is_close() {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(closed.is_ok());
+    }
+
+    #[tokio::test]
+    async fn limits_the_size_of_websocket_messages() {
+        let server = ServerBuilder::new().max_payload(1_000).build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve(listener).await });
+
+        // the client refuses to send more than the server takes
+        let mut websocket = open_websocket(address).await;
+        let event = format!(r#"42["echo","{}"]"#, "x".repeat(2_000));
+        websocket.send(Message::Text(event)).await.unwrap();
+        assert_closed(&mut websocket).await;
+    }
+
+    #[tokio::test]
+    async fn limits_the_attachments_of_packets() {
+        type Limit = fn(ServerBuilder) -> ServerBuilder;
+        // each sends a packet of the attachments of the given size
+        let limits: [(Limit, &[usize]); 3] = [
+            (|builder| builder.max_attachments(1), &[10, 10]),
+            (|builder| builder.max_attachment_size(8), &[10]),
+            // the packet size defaults to the max payload
+            (|builder| builder.max_payload(1_000), &[600, 600]),
+        ];
+        for (limit, attachments) in limits {
+            let (received, mut files) = unbounded_channel();
+            let server = limit(ServerBuilder::new())
+                .on("file", move |payload, _| {
+                    let _ = received.send(payload);
+                    async {}.boxed()
+                })
+                .build();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move { server.serve(listener).await });
+            let mut websocket = open_websocket(address).await;
+
+            let send = |attachments: &[usize]| {
+                let placeholders: Vec<String> = (0..attachments.len())
+                    .map(|num| format!(r#"{{"_placeholder":true,"num":{num}}}"#))
+                    .collect();
+                let mut messages = vec![Message::Text(format!(
+                    r#"45{}-["file",{}]"#,
+                    attachments.len(),
+                    placeholders.join(",")
+                ))];
+                messages.extend(
+                    attachments
+                        .iter()
+                        .map(|size| Message::Binary(vec![0; *size])),
+                );
+                futures_util::stream::iter(messages.into_iter().map(Ok))
+            };
+            // a packet within the limits goes through
+            websocket.send_all(&mut send(&[5])).await.unwrap();
+            let file = timeout(Duration::from_secs(5), files.recv()).await.unwrap();
+            assert!(matches!(file, Some(Payload::Binary(data)) if data.len() == 5));
+
+            let _ = websocket.send_all(&mut send(attachments)).await;
+            assert_closed(&mut websocket).await;
+            assert!(files.try_recv().is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn closes_clients_not_reading() {
+        let server = ServerBuilder::new().max_queued_packets(4).build();
+        let request = |method: Method, uri: String, body: &'static str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Full::new(Bytes::from_static(body.as_bytes())))
+                .unwrap()
+        };
+
+        let uri = "/socket.io/?EIO=4&transport=polling".to_owned();
+        let response = server.handle(request(Method::GET, uri.clone(), "")).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let handshake: Value = serde_json::from_slice(&body[1..]).unwrap();
+        let uri = format!("{uri}&sid={}", handshake["sid"].as_str().unwrap());
+        let response = server.handle(request(Method::POST, uri, "40")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // the client never polls, its acceptance of the namespace is queued first
+        let socket = server.sockets("/").pop().unwrap();
+        for tick in 0..3 {
+            socket.emit("tick", json!(tick)).await.unwrap();
+        }
+        assert!(socket.emit("tick", json!(3)).await.is_err());
+        assert!(server.sockets("/").is_empty());
+        timeout(Duration::from_secs(5), async {
+            while !server.connections().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_polling_and_upgrades() -> Result<()> {
+        let (_, url) = echo_server().await;
+
+        let (client, mut echoes) = connect(&url, TransportType::Polling).await?;
+        assert_echoes(&client, &mut echoes).await?;
+        client.disconnect().await?;
+
+        let (client, mut echoes) = connect(&url, TransportType::Any).await?;
+        assert_echoes(&client, &mut echoes).await?;
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn calls_socket_handlers() -> Result<()> {
+        let (events, mut received) = unbounded_channel();
+        let (connected, disconnected) = (events.clone(), events);
+        let server = ServerBuilder::new()
+            .namespace("/admin")
+            .on_connect(move |socket| {
+                let _ = connected.send(("connect", socket.auth().cloned()));
+                async {}.boxed()
+            })
+            .on_disconnect(move |socket| {
+                let _ = disconnected.send(("disconnect", socket.auth().cloned()));
+                async {}.boxed()
+            })
+            .build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = ClientBuilder::new(url)
+            .namespace("/admin")
+            .auth(json!({"token": 1}))
+            .connect()
+            .await?;
+        let event = timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap();
+        assert_eq!(event, Some(("connect", Some(json!({"token": 1})))));
+        assert_eq!(server.sockets("/admin").len(), 1);
+        assert!(server.sockets("/").is_empty());
+
+        client.disconnect().await?;
+        let event = timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap();
+        assert_eq!(event, Some(("disconnect", Some(json!({"token": 1})))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn runs_middlewares() -> Result<()> {
+        let server = ServerBuilder::new()
+            .middleware(|handshake| {
+                async move {
+                    match handshake.headers.get("x-role") {
+                        Some(role) if role == "admin" => Ok(()),
+                        _ => Err(ConnectError::new("forbidden")),
+                    }
+                }
+                .boxed()
+            })
+            .middleware(|handshake| {
+                async move {
+                    let token = handshake.auth.as_ref().map(|auth| auth["token"].clone());
+                    if token == Some(json!(handshake.query["token"])) {
+                        Ok(())
+                    } else {
+                        Err(ConnectError::new("unauthorized").data(json!({"retry": false})))
+                    }
+                }
+                .boxed()
+            })
+            .build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let connect = |role: &'static str, token: &'static str| {
+            let (rejected, rejections) = unbounded_channel();
+            let client = crate::test::connect(
+                ClientBuilder::new(url.clone())
+                    .opening_header("x-role", role)
+                    .query("token", "1")
+                    .auth(json!({ "token": token }))
+                    .on_connect_error(move |error, _| {
+                        let _ = rejected.send(error);
+                        async {}.boxed()
+                    }),
+            );
+            async move { Ok::<_, crate::Error>((client.await?, rejections)) }
+        };
+
+        let (_, mut rejections) = connect("guest", "1").await?;
+        let error = timeout(Duration::from_secs(5), rejections.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            error,
+            Some(crate::Error::ConnectRejected { message, data: None }) if message == "forbidden"
+        ));
+
+        let (_, mut rejections) = connect("admin", "2").await?;
+        let error = timeout(Duration::from_secs(5), rejections.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            error,
+            Some(crate::Error::ConnectRejected { message, data: Some(data) })
+                if message == "unauthorized" && data == json!({"retry": false})
+        ));
+        assert!(server.sockets("/").is_empty());
+
+        let (client, _) = connect("admin", "1").await?;
+        timeout(Duration::from_secs(5), async {
+            while server.sockets("/").is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        client.disconnect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_requests() {
+        let server = ServerBuilder::new().build();
+        let request = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .body(Full::new(Bytes::new()))
+                .unwrap()
+        };
+        let code = |response: Response<Full<Bytes>>| async move {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<Value>(&body).unwrap()["code"].clone()
+        };
+
+        let response = server
+            .handle(request("/other/?EIO=4&transport=polling"))
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = server
+            .handle(request("/socket.io/?EIO=3&transport=polling"))
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(code(response).await, json!(5));
+
+        let response = server
+            .handle(request("/socket.io/?EIO=4&transport=carrier"))
+            .await;
+        assert_eq!(code(response).await, json!(0));
+
+        let response = server
+            .handle(request("/socket.io/?EIO=4&transport=polling&sid=unknown"))
+            .await;
+        assert_eq!(code(response).await, json!(1));
+
+        let response = server
+            .handle(request("/socket.io/?EIO=4&transport=polling"))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body[0], b'0');
+        assert_eq!(server.connections().len(), 1);
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use serde_json::Value;
+use tokio::{sync::oneshot, time::timeout};
+
+use crate::{
+    error::Result,
+    packet::{Packet, PacketId},
+    Error, Event, Payload,
+};
+
+use super::{
+    engine::{next_ack_id, Connection},
+    rooms::Broadcast,
+};
+
+/// A client connected to a namespace of a [`Server`](super::Server). Clones refer to
+/// the same socket.
+#[derive(Clone)]
+pub struct ServerSocket {
+    pub(crate) id: Arc<str>,
+    pub(crate) nsp: Arc<str>,
+    pub(crate) auth: Option<Arc<Value>>,
+    pub(crate) connection: Arc<Connection>,
+    /// The ack id of the event passed to a handler along with the socket.
+    pub(crate) ack_id: Option<i32>,
+}
+
+impl ServerSocket {
+    /// The id the socket was assigned when it connected, which the client knows as
+    /// its `sid`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The namespace the socket connected to.
+    pub fn nsp(&self) -> &str {
+        &self.nsp
+    }
+
+    /// The authentication payload the client connected with, if any.
+    pub fn auth(&self) -> Option<&Value> {
+        self.auth.as_deref()
+    }
+
+    /// Whether the socket is still connected to its namespace.
+    pub fn is_connected(&self) -> bool {
+        self.connection
+            .socket(&self.nsp)
+            .is_some_and(|socket| socket.id == self.id)
+    }
+
+    /// Sends an event to the client.
+    pub async fn emit<E, D>(&self, event: E, data: D) -> Result<()>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let packet = Packet::new_from_payload(data.into(), event.into(), &self.nsp, None)?;
+        self.connection.send(&packet)
+    }
+
+    /// Sends an event to the client and waits for it to answer with an ack. Fails
+    /// with [`Error::AckTimeout`] if it doesn't answer in time, or with
+    /// [`Error::StoppedEngineIoSocket`] if it disconnects before.
+    /// # Example
+    /// ```rust
+    /// use rust_socketio::server::ServerSocket;
+    /// use std::time::Duration;
+    ///
+    /// async fn confirm(socket: ServerSocket) {
+    ///     match socket.emit_with_ack("confirm", "delete?", Duration::from_secs(5)).await {
+    ///         Ok(answer) => println!("The client answered {answer:?}"),
+    ///         Err(e) => println!("No answer: {e}"),
+    ///     }
+    /// }
+    /// ```
+    pub async fn emit_with_ack<E, D>(&self, event: E, data: D, timeout: Duration) -> Result<Payload>
+    where
+        E: Into<Event>,
+        D: Into<Payload>,
+    {
+        let id = next_ack_id();
+        let packet = Packet::new_from_payload(data.into(), event.into(), &self.nsp, Some(id))?;
+        let answered = self.connection.expect_ack(&self.nsp, id);
+        if let Err(e) = self.connection.send(&packet) {
+            self.connection.forget_ack(id);
+            return Err(e);
+        }
+        self.wait_for_ack(id, answered, timeout).await
+    }
+
+    pub(crate) async fn wait_for_ack(
+        &self,
+        id: i32,
+        answered: oneshot::Receiver<Payload>,
+        wait: Duration,
+    ) -> Result<Payload> {
+        match timeout(wait, answered).await {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => Err(Error::StoppedEngineIoSocket),
+            Err(_) => {
+                self.connection.forget_ack(id);
+                Err(Error::AckTimeout())
+            }
+        }
+    }
+
+    /// Answers the event this socket was passed to a handler with. Does nothing if the
+    /// client didn't ask for an ack.
+    pub async fn ack<D: Into<Payload>>(&self, data: D) -> Result<()> {
+        let Some(id) = self.ack_id else {
+            return Ok(());
+        };
+        let packet = Packet::ack_from_payload(data.into(), Event::Message, &self.nsp, Some(id))?;
+        self.connection.send(&packet)
+    }
+
+    /// Disconnects the socket from its namespace. The connection stays open for the
+    /// other namespaces of the client.
+    pub async fn disconnect(&self) -> Result<()> {
+        self.close()
+    }
+
+    pub(crate) fn close(&self) -> Result<()> {
+        if self.connection.leave(&self.nsp) {
+            let packet = Packet::new(
+                PacketId::Disconnect,
+                self.nsp.to_string(),
+                None,
+                None,
+                0,
+                None,
+                None,
+            );
+            self.connection.send(&packet)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the socket to a room, which it leaves again when it disconnects.
+    pub fn join<T: AsRef<str>>(&self, room: T) {
+        self.connection.rooms.join(self, room.as_ref());
+    }
+
+    /// Takes the socket out of a room.
+    pub fn leave<T: AsRef<str>>(&self, room: T) {
+        self.connection.rooms.leave(self, room.as_ref());
+    }
+
+    /// The rooms the socket is in, including the one named by its id.
+    pub fn rooms(&self) -> Vec<String> {
+        self.connection.rooms.rooms_of(self)
+    }
+
+    /// Selects the other sockets of the namespace in the room, to emit to them. The
+    /// socket itself is left out.
+    pub fn to<T: Into<String>>(&self, room: T) -> Broadcast {
+        self.broadcast().to(room)
+    }
+
+    /// Selects all other sockets of the namespace.
+    pub fn broadcast(&self) -> Broadcast {
+        Broadcast::new(self.connection.rooms.clone(), self.nsp.to_string()).except(self.id())
+    }
+
+    /// Returns a socket answering the event with the given ack id.
+    pub(crate) fn with_ack(&self, ack_id: Option<i32>) -> Self {
+        ServerSocket {
+            ack_id,
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Debug for ServerSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerSocket")
+            .field("id", &self.id)
+            .field("nsp", &self.nsp)
+            .field("ack_id", &self.ack_id)
+            .finish()
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{Direction, Packet};
+
+/// A snapshot of the traffic of a client's connection, taken by `Client::stats`. The
+/// counts are kept over reconnects and include all namespaces sharing the connection.
+///
+/// With the `metrics` feature the counts, as well as the latency of acks and the time
+/// event handlers take, are also recorded through the [`metrics`](https://docs.rs/metrics)
+/// facade.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// The number of socket.io packets written to the server.
+    pub packets_sent: u64,
+    /// The number of socket.io packets read from the server.
+    pub packets_received: u64,
+    /// The encoded size of the written packets, attachments included.
+    pub bytes_sent: u64,
+    /// The encoded size of the read packets, attachments included.
+    pub bytes_received: u64,
+    /// The number of times the connection was re-established.
+    pub reconnects: u64,
+    /// The encoded size of the engine.io packets written to the server, heartbeats
+    /// included.
+    pub engine_bytes_sent: u64,
+    /// The encoded size of the engine.io packets read from the server, heartbeats
+    /// included.
+    pub engine_bytes_received: u64,
+}
+
+/// The traffic of one event on one namespace, taken by `Client::stats_by_event`. Only
+/// event packets are counted, acks are not. Like [`Stats`], the counts are kept over
+/// reconnects.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct EventStats {
+    /// The namespace the event was emitted on.
+    pub namespace: String,
+    /// The name of the event.
+    pub event: String,
+    /// The number of times the event was written to the server.
+    pub sent: u64,
+    /// The number of times the event was read from the server.
+    pub received: u64,
+    /// The encoded size of the written events, attachments included.
+    pub bytes_sent: u64,
+    /// The encoded size of the read events, attachments included.
+    pub bytes_received: u64,
+}
+
+impl EventStats {
+    /// The encoded size of the event in both directions.
+    pub fn bytes(&self) -> u64 {
+        self.bytes_sent + self.bytes_received
+    }
+}
+
+/// The counters behind [`Stats`], shared by the sockets a client connects over.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    reconnects: AtomicU64,
+    engine_bytes_sent: AtomicU64,
+    engine_bytes_received: AtomicU64,
+    /// The traffic per namespace and event name.
+    events: Mutex<HashMap<(String, String), EventStats>>,
+}
+
+impl Counters {
+    pub(crate) fn sent(&self, packet: &Packet, size: usize) {
+        self.event(Direction::Outbound, packet, size);
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(size as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::counter!("socketio_packets_sent_total").increment(1);
+            ::metrics::counter!("socketio_bytes_sent_total").increment(size as u64);
+        }
+    }
+
+    pub(crate) fn received(&self, packet: &Packet, size: usize) {
+        self.event(Direction::Inbound, packet, size);
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(size as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::counter!("socketio_packets_received_total").increment(1);
+            ::metrics::counter!("socketio_bytes_received_total").increment(size as u64);
+        }
+    }
+
+    pub(crate) fn reconnected(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("socketio_reconnects_total").increment(1);
+    }
+
+    pub(crate) fn engine_bytes(&self, direction: Direction, size: usize) {
+        match direction {
+            Direction::Inbound => {
+                self.engine_bytes_received
+                    .fetch_add(size as u64, Ordering::Relaxed);
+                #[cfg(feature = "metrics")]
+                ::metrics::counter!("socketio_engine_bytes_received_total").increment(size as u64);
+            }
+            Direction::Outbound => {
+                self.engine_bytes_sent
+                    .fetch_add(size as u64, Ordering::Relaxed);
+                #[cfg(feature = "metrics")]
+                ::metrics::counter!("socketio_engine_bytes_sent_total").increment(size as u64);
+            }
+        }
+    }
+
+    fn event(&self, direction: Direction, packet: &Packet, size: usize) {
+        let Some(event) = packet.event_name() else {
+            return;
+        };
+        let Ok(mut events) = self.events.lock() else {
+            return;
+        };
+        let stats = events
+            .entry((packet.nsp.clone(), event))
+            .or_insert_with_key(|(namespace, event)| EventStats {
+                namespace: namespace.clone(),
+                event: event.clone(),
+                ..Default::default()
+            });
+        match direction {
+            Direction::Inbound => {
+                stats.received += 1;
+                stats.bytes_received += size as u64;
+            }
+            Direction::Outbound => {
+                stats.sent += 1;
+                stats.bytes_sent += size as u64;
+            }
+        }
+    }
+
+    /// The traffic per event, the events with the most bytes first.
+    pub(crate) fn events(&self) -> Vec<EventStats> {
+        let mut events: Vec<EventStats> = match self.events.lock() {
+            Ok(events) => events.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        events.sort_by(|a, b| {
+            b.bytes()
+                .cmp(&a.bytes())
+                .then_with(|| (&a.namespace, &a.event).cmp(&(&b.namespace, &b.event)))
+        });
+        events
+    }
+
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            engine_bytes_sent: self.engine_bytes_sent.load(Ordering::Relaxed),
+            engine_bytes_received: self.engine_bytes_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records how long it took the server to acknowledge an emitted event. Does nothing
+/// without the `metrics` feature.
+#[inline]
+pub(crate) fn record_ack_latency(latency: Duration) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!("socketio_ack_latency_seconds").record(latency);
+    #[cfg(not(feature = "metrics"))]
+    let _ = latency;
+}
+
+/// Records how long the handlers of an event took, see [`record_ack_latency`].
+#[inline]
+pub(crate) fn record_handler_duration(duration: Duration) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!("socketio_handler_duration_seconds").record(duration);
+    #[cfg(not(feature = "metrics"))]
+    let _ = duration;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PacketId;
+
+    fn packet(packet_type: PacketId, nsp: &str, data: &str) -> Packet {
+        Packet::new(
+            packet_type,
+            nsp.to_owned(),
+            Some(data.to_owned()),
+            None,
+            0,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let counters = Counters::default();
+        let ack = packet(PacketId::Ack, "/", "[]");
+        counters.sent(&ack, 10);
+        counters.sent(&ack, 5);
+        counters.received(&ack, 7);
+        counters.reconnected();
+        counters.engine_bytes(Direction::Outbound, 12);
+        counters.engine_bytes(Direction::Inbound, 8);
+        counters.engine_bytes(Direction::Inbound, 1);
+
+        assert_eq!(
+            counters.snapshot(),
+            Stats {
+                packets_sent: 2,
+                packets_received: 1,
+                bytes_sent: 15,
+                bytes_received: 7,
+                reconnects: 1,
+                engine_bytes_sent: 12,
+                engine_bytes_received: 9,
+            }
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        let counters = Counters::default();
+        counters.sent(&packet(PacketId::Event, "/", r#"["chat","hi"]"#), 14);
+        counters.received(&packet(PacketId::Event, "/", r#"["chat","hello"]"#), 17);
+        counters.sent(&packet(PacketId::Event, "/admin", r#"["chat","hi"]"#), 21);
+        counters.received(&packet(PacketId::Event, "/", r#"["ping",1]"#), 11);
+        // acks aren't events
+        counters.received(&packet(PacketId::Ack, "/", r#"["chat"]"#), 9);
+
+        let events = counters.events();
+        let summary: Vec<_> = events
+            .iter()
+            .map(|stats| {
+                (
+                    stats.namespace.as_str(),
+                    stats.event.as_str(),
+                    stats.bytes(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [("/", "chat", 31), ("/admin", "chat", 21), ("/", "ping", 11)]
+        );
+        assert_eq!((events[0].sent, events[0].received), (1, 1));
+        assert_eq!(counters.snapshot().packets_received, 3);
+    }
+}
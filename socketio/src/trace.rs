@@ -0,0 +1,70 @@
+use crate::packet::Packet;
+
+/// Records a packet written to the server. `size` is the number of encoded bytes,
+/// attachments included. Does nothing without the `tracing` feature.
+#[inline]
+pub(crate) fn packet_sent(packet: &Packet, size: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        packet_type = ?packet.packet_type,
+        nsp = %packet.nsp,
+        event = packet.event_name().as_deref(),
+        ack_id = packet.id,
+        size,
+        "sent packet"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (packet, size);
+}
+
+/// Records a packet read from the server, see [`packet_sent`].
+#[inline]
+pub(crate) fn packet_received(packet: &Packet, size: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        packet_type = ?packet.packet_type,
+        nsp = %packet.nsp,
+        event = packet.event_name().as_deref(),
+        ack_id = packet.id,
+        size,
+        "received packet"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (packet, size);
+}
+
+/// The number of bytes of an encoded packet and its attachments.
+pub(crate) fn size(data: &[u8], packet: &Packet) -> usize {
+    data.len()
+        + packet
+            .attachments
+            .iter()
+            .flatten()
+            .map(|attachment| attachment.len())
+            .sum::<usize>()
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod test {
+    use super::*;
+    use crate::packet::PacketId;
+
+    fn packet(packet_type: PacketId, data: &str) -> Packet {
+        Packet::new(
+            packet_type,
+            "/".to_owned(),
+            Some(data.to_owned()),
+            None,
+            0,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_size() {
+        let mut packet = packet(PacketId::BinaryEvent, r#"["file"]"#);
+        packet.attachments = Some(vec![vec![0; 3].into(), vec![0; 4].into()]);
+        assert_eq!(size(b"51-[\"file\"]", &packet), 18);
+    }
+}